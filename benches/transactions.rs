@@ -0,0 +1,120 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use revault_tx::{
+    miniscript::bitcoin::{
+        secp256k1, util::bip32, Address, Amount, Network, OutPoint, Script, TxOut,
+    },
+    scripts::{
+        CpfpDescriptor, CsvValue, DepositDescriptor, DerivationIndex, EmergencyAddress,
+        UnvaultDescriptor,
+    },
+    transactions::{
+        tests_helpers::{get_participants_sets, satisfy_transaction_input},
+        transaction_chain, RevaultTransaction, SpendTransaction,
+    },
+    txins::{Sequence, UnvaultTxIn},
+    txouts::{SpendTxOut, UnvaultTxOut},
+};
+
+use std::convert::TryFrom;
+
+const N_STK: usize = 4;
+const N_MAN: usize = 3;
+const CSV: CsvValue = CsvValue::blocks(144);
+// The number of Unvault outputs spent by the batched Spend
+const N_SPEND_INPUTS: usize = 100;
+
+fn bench_transactions(c: &mut Criterion) {
+    let secp = secp256k1::Secp256k1::new();
+    let derivation_index = DerivationIndex::try_from(10).unwrap();
+    let child_number = bip32::ChildNumber::from(derivation_index);
+
+    let ((managers_priv, managers), (_, mancpfp), (_, stakeholders), (cosigners_priv, cosigners)) =
+        get_participants_sets(N_STK, N_MAN, true, &secp);
+    let deposit_descriptor = DepositDescriptor::new(stakeholders.clone()).unwrap();
+    let unvault_descriptor =
+        UnvaultDescriptor::new(stakeholders, managers, N_MAN, cosigners, CSV).unwrap();
+    let cpfp_descriptor = CpfpDescriptor::new(mancpfp).unwrap();
+    let emer_address =
+        EmergencyAddress::from(Address::p2wsh(&Script::new(), Network::Bitcoin)).unwrap();
+
+    c.bench_function("transaction_chain", |b| {
+        b.iter(|| {
+            transaction_chain(
+                OutPoint::default(),
+                Amount::from_sat(100_000_000),
+                &deposit_descriptor,
+                &unvault_descriptor,
+                &cpfp_descriptor,
+                derivation_index,
+                emer_address.clone(),
+                &secp,
+            )
+            .unwrap()
+        })
+    });
+
+    // A Spend batching many Unvault outputs
+    let der_unvault_descriptor = unvault_descriptor.derive(derivation_index, &secp);
+    let der_cpfp_descriptor = cpfp_descriptor.derive(derivation_index, &secp);
+    let unvault_txins: Vec<UnvaultTxIn> = (0..N_SPEND_INPUTS)
+        .map(|vout| {
+            UnvaultTxIn::new(
+                OutPoint {
+                    vout: vout as u32,
+                    ..OutPoint::default()
+                },
+                UnvaultTxOut::new(Amount::from_sat(100_000_000), &der_unvault_descriptor),
+                Sequence::Csv(CSV),
+            )
+        })
+        .collect();
+    let spend_txo = SpendTxOut::new(TxOut {
+        value: (N_SPEND_INPUTS as u64 - 1) * 100_000_000,
+        script_pubkey: Script::new_v0_wsh(&Default::default()),
+    });
+    let mut spend_tx = SpendTransaction::new(
+        unvault_txins,
+        vec![spend_txo],
+        None,
+        &der_cpfp_descriptor,
+        0,
+        false,
+    )
+    .unwrap();
+
+    c.bench_function("spend_all_sighashes", |b| {
+        b.iter(|| spend_tx.all_sighashes().unwrap())
+    });
+
+    let spenders_priv: Vec<bip32::ExtendedPrivKey> = managers_priv
+        .into_iter()
+        .chain(cosigners_priv.into_iter())
+        .collect();
+    for (i, input_sighash) in spend_tx.all_sighashes().unwrap().into_iter().enumerate() {
+        satisfy_transaction_input(
+            &secp,
+            &mut spend_tx,
+            i,
+            &input_sighash.sighash(),
+            &spenders_priv,
+            child_number,
+        )
+        .unwrap();
+    }
+
+    c.bench_function("spend_verify_signatures", |b| {
+        b.iter(|| spend_tx.verify_signatures(&secp).unwrap())
+    });
+
+    c.bench_function("spend_finalize", |b| {
+        b.iter_batched(
+            || spend_tx.clone(),
+            |mut tx| tx.finalize(&secp).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_transactions);
+criterion_main!(benches);
@@ -2,7 +2,10 @@
 use libfuzzer_sys::fuzz_target;
 
 use revault_tx::{
-    miniscript::bitcoin::secp256k1::{Signature, SECP256K1},
+    miniscript::bitcoin::{
+        secp256k1::{Signature, SECP256K1},
+        SigHashType,
+    },
     transactions::{EmergencyTransaction, RevaultPresignedTransaction, RevaultTransaction},
 };
 
@@ -31,7 +34,7 @@ fuzz_target!(|data: &[u8]| {
 
             // We can add a signature, it just is invalid
             assert!(tx
-                .add_sig(dummykey, dummy_sig, &SECP256K1)
+                .add_sig(dummykey, (dummy_sig, SigHashType::All), &SECP256K1)
                 .unwrap_err()
                 .to_string()
                 .contains("Invalid signature"));
@@ -43,7 +46,7 @@ fuzz_target!(|data: &[u8]| {
                 .to_string()
                 .contains("Missing witness_script"));
             assert!(tx
-                .add_sig(dummykey, dummy_sig, &SECP256K1)
+                .add_sig(dummykey, (dummy_sig, SigHashType::All), &SECP256K1)
                 .unwrap_err()
                 .to_string()
                 .contains("already finalized"));
@@ -2,7 +2,10 @@
 use libfuzzer_sys::fuzz_target;
 
 use revault_tx::{
-    miniscript::bitcoin::secp256k1::{Signature, SECP256K1},
+    miniscript::bitcoin::{
+        secp256k1::{Signature, SECP256K1},
+        SigHashType,
+    },
     transactions::{CpfpableTransaction, RevaultTransaction, SpendTransaction},
 };
 
@@ -34,7 +37,7 @@ fuzz_target!(|data: &[u8]| {
                 tx.signature_hash(i)
                     .expect("Must be in bound as it was parsed!");
                 assert!(tx
-                    .add_signature(i, dummykey, dummy_sig, &SECP256K1)
+                    .add_signature(i, dummykey, (dummy_sig, SigHashType::All), &SECP256K1)
                     .unwrap_err()
                     .to_string()
                     .contains("Invalid signature"));
@@ -46,7 +49,7 @@ fuzz_target!(|data: &[u8]| {
                     .to_string()
                     .contains("Missing witness_script"));
                 assert!(tx
-                    .add_signature(i, dummykey, dummy_sig, &SECP256K1)
+                    .add_signature(i, dummykey, (dummy_sig, SigHashType::All), &SECP256K1)
                     .unwrap_err()
                     .to_string()
                     .contains("already finalized"));
@@ -0,0 +1,31 @@
+#![no_main]
+use libfuzzer_sys::{
+    arbitrary::{Arbitrary, Unstructured},
+    fuzz_target,
+};
+
+use revault_tx::transactions::{
+    CancelTransaction, EmergencyTransaction, RevaultTransaction, SpendTransaction,
+    UnvaultEmergencyTransaction, UnvaultTransaction,
+};
+
+// Generate a transaction out of the fuzzer input and check it parses back from its PSBT
+macro_rules! roundtrip {
+    ($u:ident, $tx_type:ident) => {
+        if let Ok(tx) = $tx_type::arbitrary(&mut $u) {
+            let parsed_tx = $tx_type::from_psbt_serialized(&tx.as_psbt_serialized())
+                .expect("We created it, we must be able to parse it");
+            assert_eq!(tx, parsed_tx);
+        }
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+
+    roundtrip!(u, UnvaultTransaction);
+    roundtrip!(u, CancelTransaction);
+    roundtrip!(u, EmergencyTransaction);
+    roundtrip!(u, UnvaultEmergencyTransaction);
+    roundtrip!(u, SpendTransaction);
+});
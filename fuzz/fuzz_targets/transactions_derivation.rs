@@ -5,10 +5,11 @@ use libfuzzer_sys::arbitrary::Arbitrary;
 
 use revault_tx::{
     miniscript::bitcoin::{
-        blockdata::constants::max_money, hashes::Hash, secp256k1::SECP256K1, Network, OutPoint,
-        Txid,
+        blockdata::constants::max_money, hashes::Hash, secp256k1::SECP256K1, Amount, Network,
+        OutPoint, Txid,
     },
-    transactions::tests_helpers::{derive_transactions, seed_rng},
+    scripts::CsvValue,
+    transactions::tests_helpers::{derive_transactions, seed_rng, CosigningServers},
 };
 
 #[derive(Arbitrary, Debug)]
@@ -16,7 +17,7 @@ struct Config {
     n_stk: usize,
     n_man: usize,
     with_cosig_servers: bool,
-    csv: u32,
+    csv: u16,
     deposit_txid: [u8; 32],
     deposit_vout: u32,
     deposit_value: u64,
@@ -46,7 +47,7 @@ fuzz_target!(|config: Config| {
                     txid: Txid::from_slice(&txid).unwrap(),
                     vout,
                 },
-                value,
+                Amount::from_sat(value),
             )
         })
         .collect();
@@ -54,11 +55,15 @@ fuzz_target!(|config: Config| {
     derive_transactions(
         config.n_stk,
         config.n_man,
-        config.csv,
+        CsvValue::blocks(config.csv),
         deposit_prevout,
-        config.deposit_value,
+        Amount::from_sat(config.deposit_value),
         unvault_spends,
-        config.with_cosig_servers,
+        if config.with_cosig_servers {
+            CosigningServers::Xpubs
+        } else {
+            CosigningServers::Absent
+        },
         &SECP256K1,
     )
     .unwrap_or_else(|_| ());
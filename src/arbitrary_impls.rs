@@ -0,0 +1,341 @@
+//! Implementations of [arbitrary::Arbitrary] for the Revault descriptors, txins, txouts and
+//! transactions.
+//!
+//! They generate structurally valid objects out of the fuzzer input, so that downstream fuzzers
+//! and property tests don't need to reimplement the Revault logic.
+
+use crate::{
+    scripts::*,
+    transactions::{
+        transaction_chain, CancelTransaction, CpfpTransaction, DepositTransaction,
+        EmergencyTransaction, SpendTransaction, UnvaultEmergencyTransaction, UnvaultTransaction,
+    },
+    txins::*,
+    txouts::*,
+};
+
+use arbitrary::{Arbitrary, Error, Result, Unstructured};
+use miniscript::{
+    bitcoin::{
+        blockdata::constants::max_money, hashes::Hash, secp256k1, util::bip32, Address, Amount,
+        Network, OutPoint, Script, Transaction, TxIn, TxOut, Txid,
+    },
+    descriptor::{DescriptorPublicKey, DescriptorXKey, Wildcard},
+};
+
+use std::convert::TryFrom;
+
+// Keep the number of participants low for the generation to be fast
+const MAX_PARTICIPANTS: usize = 5;
+
+fn arbitrary_xpub(u: &mut Unstructured) -> Result<DescriptorPublicKey> {
+    let seed = <[u8; 32]>::arbitrary(u)?;
+    let xpriv = bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &seed)
+        .map_err(|_| Error::IncorrectFormat)?;
+    let secp = secp256k1::Secp256k1::signing_only();
+
+    Ok(DescriptorPublicKey::XPub(DescriptorXKey {
+        origin: None,
+        xkey: bip32::ExtendedPubKey::from_private(&secp, &xpriv),
+        derivation_path: bip32::DerivationPath::from(vec![]),
+        wildcard: Wildcard::Unhardened,
+    }))
+}
+
+fn arbitrary_xpubs(
+    u: &mut Unstructured,
+    min: usize,
+    max: usize,
+) -> Result<Vec<DescriptorPublicKey>> {
+    let n_keys = u.int_in_range(min..=max)?;
+    (0..n_keys).map(|_| arbitrary_xpub(u)).collect()
+}
+
+fn arbitrary_amount(u: &mut Unstructured, min: u64) -> Result<Amount> {
+    Ok(Amount::from_sat(
+        u.int_in_range(min..=max_money(Network::Bitcoin))?,
+    ))
+}
+
+fn arbitrary_outpoint(u: &mut Unstructured) -> Result<OutPoint> {
+    let txid = Txid::from_slice(&<[u8; 32]>::arbitrary(u)?).expect("32 bytes");
+    Ok(OutPoint::new(txid, u32::arbitrary(u)?))
+}
+
+impl<'a> Arbitrary<'a> for DerivationIndex {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let index: u32 = u.int_in_range(0..=(1 << 31) - 1)?;
+        Ok(DerivationIndex::try_from(index).expect("Non-hardened index"))
+    }
+}
+
+impl<'a> Arbitrary<'a> for DepositDescriptor {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let stakeholders = arbitrary_xpubs(u, 2, MAX_PARTICIPANTS)?;
+        DepositDescriptor::new(stakeholders).map_err(|_| Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for UnvaultDescriptor {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let stakeholders = arbitrary_xpubs(u, 2, MAX_PARTICIPANTS)?;
+        let managers = arbitrary_xpubs(u, 1, MAX_PARTICIPANTS)?;
+        let managers_threshold = u.int_in_range(1..=managers.len())?;
+        let cosigners = if bool::arbitrary(u)? {
+            arbitrary_xpubs(u, stakeholders.len(), stakeholders.len())?
+        } else {
+            vec![]
+        };
+        let csv = CsvValue::blocks(u.int_in_range(1..=u16::MAX)?);
+
+        UnvaultDescriptor::new(stakeholders, managers, managers_threshold, cosigners, csv)
+            .map_err(|_| Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for CpfpDescriptor {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let managers = arbitrary_xpubs(u, 1, MAX_PARTICIPANTS)?;
+        CpfpDescriptor::new(managers).map_err(|_| Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for DerivedDepositDescriptor {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let secp = secp256k1::Secp256k1::verification_only();
+        Ok(DepositDescriptor::arbitrary(u)?.derive(DerivationIndex::arbitrary(u)?, &secp))
+    }
+}
+
+impl<'a> Arbitrary<'a> for DerivedUnvaultDescriptor {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let secp = secp256k1::Secp256k1::verification_only();
+        Ok(UnvaultDescriptor::arbitrary(u)?.derive(DerivationIndex::arbitrary(u)?, &secp))
+    }
+}
+
+impl<'a> Arbitrary<'a> for DerivedCpfpDescriptor {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let secp = secp256k1::Secp256k1::verification_only();
+        Ok(CpfpDescriptor::arbitrary(u)?.derive(DerivationIndex::arbitrary(u)?, &secp))
+    }
+}
+
+impl<'a> Arbitrary<'a> for EmergencyAddress {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let script = Script::from(Vec::<u8>::arbitrary(u)?);
+        Ok(
+            EmergencyAddress::from(Address::p2wsh(&script, Network::Bitcoin))
+                .expect("It's a P2WSH"),
+        )
+    }
+}
+
+impl<'a> Arbitrary<'a> for DepositTxOut {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let value = arbitrary_amount(u, 0)?;
+        Ok(DepositTxOut::new(
+            value,
+            &DerivedDepositDescriptor::arbitrary(u)?,
+        ))
+    }
+}
+
+impl<'a> Arbitrary<'a> for UnvaultTxOut {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let value = arbitrary_amount(u, 0)?;
+        Ok(UnvaultTxOut::new(
+            value,
+            &DerivedUnvaultDescriptor::arbitrary(u)?,
+        ))
+    }
+}
+
+impl<'a> Arbitrary<'a> for CpfpTxOut {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let value = arbitrary_amount(u, 0)?;
+        Ok(CpfpTxOut::new(value, &DerivedCpfpDescriptor::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for EmergencyTxOut {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let address = EmergencyAddress::arbitrary(u)?;
+        Ok(EmergencyTxOut::new(address, arbitrary_amount(u, 0)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for SpendTxOut {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let value = arbitrary_amount(u, 0)?.as_sat();
+        let script_pubkey = Script::from(Vec::<u8>::arbitrary(u)?);
+        Ok(SpendTxOut::new(TxOut {
+            value,
+            script_pubkey,
+        }))
+    }
+}
+
+impl<'a> Arbitrary<'a> for DepositTxIn {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let outpoint = arbitrary_outpoint(u)?;
+        Ok(DepositTxIn::new(outpoint, DepositTxOut::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for UnvaultTxIn {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let outpoint = arbitrary_outpoint(u)?;
+        Ok(UnvaultTxIn::new_spend(
+            outpoint,
+            UnvaultTxOut::arbitrary(u)?,
+        ))
+    }
+}
+
+impl<'a> Arbitrary<'a> for CpfpTxIn {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let outpoint = arbitrary_outpoint(u)?;
+        Ok(CpfpTxIn::new(outpoint, CpfpTxOut::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for DepositTransaction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let funding_outpoint = arbitrary_outpoint(u)?;
+        let deposit_txo = DepositTxOut::arbitrary(u)?;
+
+        Ok(DepositTransaction(Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: funding_outpoint,
+                ..TxIn::default()
+            }],
+            output: vec![deposit_txo.into_txout()],
+        }))
+    }
+}
+
+// The presigned transactions of an arbitrary vault
+fn arbitrary_chain(
+    u: &mut Unstructured,
+) -> Result<(
+    UnvaultTransaction,
+    [CancelTransaction; 5],
+    EmergencyTransaction,
+    UnvaultEmergencyTransaction,
+)> {
+    let secp = secp256k1::Secp256k1::verification_only();
+    let deposit_outpoint = arbitrary_outpoint(u)?;
+    let deposit_value = arbitrary_amount(u, crate::transactions::DEPOSIT_MIN_SATS)?;
+    let deposit_descriptor = DepositDescriptor::arbitrary(u)?;
+    let unvault_descriptor = UnvaultDescriptor::arbitrary(u)?;
+    let cpfp_descriptor = CpfpDescriptor::arbitrary(u)?;
+    let derivation_index = DerivationIndex::arbitrary(u)?;
+    let emer_address = EmergencyAddress::arbitrary(u)?;
+
+    let (unvault_tx, cancel_batch, emer_tx, unemer_tx) = transaction_chain(
+        deposit_outpoint,
+        deposit_value,
+        &deposit_descriptor,
+        &unvault_descriptor,
+        &cpfp_descriptor,
+        derivation_index,
+        emer_address,
+        &secp,
+    )
+    .map_err(|_| Error::IncorrectFormat)?;
+
+    Ok((unvault_tx, cancel_batch.all_feerates(), emer_tx, unemer_tx))
+}
+
+impl<'a> Arbitrary<'a> for UnvaultTransaction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_chain(u).map(|(unvault_tx, _, _, _)| unvault_tx)
+    }
+}
+
+impl<'a> Arbitrary<'a> for CancelTransaction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let (_, cancel_txs, _, _) = arbitrary_chain(u)?;
+        let index = u.int_in_range(0..=cancel_txs.len() - 1)?;
+        Ok(cancel_txs[index].clone())
+    }
+}
+
+impl<'a> Arbitrary<'a> for EmergencyTransaction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_chain(u).map(|(_, _, emer_tx, _)| emer_tx)
+    }
+}
+
+impl<'a> Arbitrary<'a> for UnvaultEmergencyTransaction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_chain(u).map(|(_, _, _, unemer_tx)| unemer_tx)
+    }
+}
+
+impl<'a> Arbitrary<'a> for SpendTransaction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let unvault_descriptor = DerivedUnvaultDescriptor::arbitrary(u)?;
+        let cpfp_descriptor = CpfpDescriptor::arbitrary(u)?;
+        let cpfp_descriptor = cpfp_descriptor.derive(DerivationIndex::arbitrary(u)?, &secp);
+
+        let n_inputs = u.int_in_range(1..=MAX_PARTICIPANTS)?;
+        let mut unvault_txins = Vec::with_capacity(n_inputs);
+        for _ in 0..n_inputs {
+            unvault_txins.push(UnvaultTxIn::new_spend(
+                arbitrary_outpoint(u)?,
+                // Don't generate a total input value above the money supply
+                UnvaultTxOut::new(
+                    Amount::from_sat(
+                        u.int_in_range(0..=max_money(Network::Bitcoin) / n_inputs as u64)?,
+                    ),
+                    &unvault_descriptor,
+                ),
+            ));
+        }
+        let spend_txouts: Vec<SpendTxOut> = Arbitrary::arbitrary(u)?;
+        let change_txout = if bool::arbitrary(u)? {
+            Some(DepositTxOut::arbitrary(u)?)
+        } else {
+            None
+        };
+
+        SpendTransaction::new(
+            unvault_txins,
+            spend_txouts,
+            change_txout,
+            &cpfp_descriptor,
+            u32::arbitrary(u)?,
+            false,
+        )
+        .map_err(|_| Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for CpfpTransaction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let n_tbc = u.int_in_range(1..=MAX_PARTICIPANTS)?;
+        let to_be_cpfped = (0..n_tbc)
+            .map(|_| CpfpTxIn::arbitrary(u))
+            .collect::<Result<Vec<CpfpTxIn>>>()?;
+        // Don't generate overflowing feerates
+        let tbc_weight =
+            u.int_in_range(1_000..=crate::transactions::MAX_STANDARD_TX_WEIGHT as u64)?;
+        let tbc_fees = Amount::from_sat(u.int_in_range(0..=tbc_weight * 1_000)?);
+        let added_feerate = u.int_in_range(0..=1_000_000)?;
+        let available_utxos: Vec<CpfpTxIn> = Arbitrary::arbitrary(u)?;
+
+        CpfpTransaction::from_txins(
+            to_be_cpfped,
+            tbc_weight,
+            tbc_fees,
+            added_feerate,
+            available_utxos,
+        )
+        .map_err(|_| Error::IncorrectFormat)
+    }
+}
@@ -0,0 +1,157 @@
+//! # Uniform Resources encoding of Revault transactions
+//!
+//! Air-gapped signing devices exchange PSBTs as [BC-UR](https://github.com/BlockchainCommons/Research/blob/master/papers/bcr-2020-005-ur.md)
+//! `crypto-psbt` resources, split in fragments to be displayed as an animated QR code.
+
+use crate::{error::UrError, transactions::RevaultTransaction};
+
+/// The UR type of a PSBT
+pub const CRYPTO_PSBT_UR_TYPE: &str = "crypto-psbt";
+
+// CBOR major type 2 (byte string)
+const CBOR_BYTES: u8 = 0x40;
+
+// Encode these bytes as a CBOR byte string, the payload of a 'crypto-psbt' resource.
+fn cbor_bytes(data: &[u8]) -> Vec<u8> {
+    let len = data.len() as u64;
+    let mut cbor = Vec::with_capacity(data.len() + 9);
+    if len < 24 {
+        cbor.push(CBOR_BYTES | len as u8);
+    } else if len <= u8::MAX as u64 {
+        cbor.push(CBOR_BYTES | 24);
+        cbor.push(len as u8);
+    } else if len <= u16::MAX as u64 {
+        cbor.push(CBOR_BYTES | 25);
+        cbor.extend_from_slice(&(len as u16).to_be_bytes());
+    } else if len <= u32::MAX as u64 {
+        cbor.push(CBOR_BYTES | 26);
+        cbor.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        cbor.push(CBOR_BYTES | 27);
+        cbor.extend_from_slice(&len.to_be_bytes());
+    }
+    cbor.extend_from_slice(data);
+    cbor
+}
+
+// Get the content of a CBOR byte string, which must span the whole buffer.
+fn from_cbor_bytes(cbor: &[u8]) -> Result<&[u8], UrError> {
+    let (header, rest) = cbor.split_first().ok_or(UrError::InvalidCbor)?;
+    if header & 0xe0 != CBOR_BYTES {
+        return Err(UrError::InvalidCbor);
+    }
+
+    let (len, data) = match header & 0x1f {
+        len @ 0..=23 => (len as u64, rest),
+        additional @ 24..=27 => {
+            let size = 1 << (additional - 24);
+            if rest.len() < size {
+                return Err(UrError::InvalidCbor);
+            }
+            let (len_bytes, data) = rest.split_at(size);
+            let len = len_bytes
+                .iter()
+                .fold(0u64, |len, byte| (len << 8) | *byte as u64);
+            (len, data)
+        }
+        // Indefinite lengths and reserved values
+        _ => return Err(UrError::InvalidCbor),
+    };
+    if data.len() as u64 != len {
+        return Err(UrError::InvalidCbor);
+    }
+
+    Ok(data)
+}
+
+/// Encodes a Revault transaction as the parts of a `crypto-psbt` resource, to be displayed as an
+/// animated QR code.
+pub struct PsbtUrEncoder(ur::Encoder);
+
+impl PsbtUrEncoder {
+    /// Split this transaction in fragments of at most `max_fragment_len` bytes.
+    pub fn new(tx: &impl RevaultTransaction, max_fragment_len: usize) -> Result<Self, UrError> {
+        let payload = cbor_bytes(&tx.as_psbt_serialized());
+        ur::Encoder::new(&payload, max_fragment_len, CRYPTO_PSBT_UR_TYPE)
+            .map(PsbtUrEncoder)
+            .map_err(|e| UrError::Ur(e.to_string()))
+    }
+
+    /// The number of fragments the transaction was split in. Once they were all displayed, the
+    /// next parts are fountain-encoded mixes of them which help the receiver to recover from the
+    /// parts it missed.
+    pub fn fragment_count(&self) -> usize {
+        self.0.fragment_count()
+    }
+
+    /// Get the next part to display
+    pub fn next_part(&mut self) -> Result<String, UrError> {
+        self.0.next_part().map_err(|e| UrError::Ur(e.to_string()))
+    }
+}
+
+/// Gathers the parts of a `crypto-psbt` resource scanned from an animated QR code, until it can
+/// be decoded as a Revault transaction.
+#[derive(Default)]
+pub struct PsbtUrDecoder(ur::Decoder);
+
+impl PsbtUrDecoder {
+    /// Add a scanned part. Parts of a resource which is not a `crypto-psbt` are refused.
+    pub fn receive(&mut self, part: &str) -> Result<(), UrError> {
+        // QR codes use the uppercase alphanumeric mode
+        let prefix = format!("ur:{}/", CRYPTO_PSBT_UR_TYPE);
+        if !part.to_lowercase().starts_with(&prefix) {
+            return Err(UrError::WrongType(part.to_string()));
+        }
+
+        self.0
+            .receive(&part.to_lowercase())
+            .map_err(|e| UrError::Ur(e.to_string()))
+    }
+
+    /// Whether enough parts were received to decode the transaction
+    pub fn is_complete(&self) -> bool {
+        self.0.complete()
+    }
+
+    /// Decode the transaction once all the parts were received.
+    pub fn transaction<T: RevaultTransaction>(&self) -> Result<T, UrError> {
+        let payload = self
+            .0
+            .message()
+            .map_err(|e| UrError::Ur(e.to_string()))?
+            .ok_or(UrError::Incomplete)?;
+        let raw_psbt = from_cbor_bytes(&payload)?;
+        T::from_psbt_serialized(raw_psbt).map_err(UrError::Transaction)
+    }
+}
+
+/// Encode this transaction as a single-part `crypto-psbt` resource, for small transactions fitting
+/// in a static QR code.
+pub fn to_ur(tx: &impl RevaultTransaction) -> String {
+    ur::encode(&cbor_bytes(&tx.as_psbt_serialized()), CRYPTO_PSBT_UR_TYPE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cbor_bytes, from_cbor_bytes};
+
+    #[test]
+    fn cbor_byte_strings() {
+        for len in &[0, 1, 23, 24, 255, 256, 65_535, 65_536] {
+            let data = vec![0x42; *len];
+            let cbor = cbor_bytes(&data);
+            assert_eq!(from_cbor_bytes(&cbor).unwrap(), &data[..]);
+        }
+
+        assert_eq!(cbor_bytes(&[0x01, 0x02]), vec![0x42, 0x01, 0x02]);
+        assert_eq!(cbor_bytes(&[0; 24])[..2], [0x58, 24]);
+
+        // Not a byte string, truncated, trailing data
+        assert!(from_cbor_bytes(&[0x62, 0x01, 0x02]).is_err());
+        assert!(from_cbor_bytes(&[0x43, 0x01, 0x02]).is_err());
+        assert!(from_cbor_bytes(&[0x41, 0x01, 0x02]).is_err());
+        assert!(from_cbor_bytes(&[0x59, 0x01]).is_err());
+        assert!(from_cbor_bytes(&[]).is_err());
+    }
+}
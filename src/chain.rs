@@ -0,0 +1,260 @@
+//! # Revault vault state
+//!
+//! Track the completeness of the chain of presigned transactions of a vault as the stakeholders'
+//! signatures are exchanged.
+
+use crate::{
+    error::VaultStateError,
+    scripts::DerivedDepositDescriptor,
+    transactions::{
+        CancelTransaction, CancelTransactionsBatch, EmergencyTransaction, InputSigHash,
+        RevaultTransaction, SignatureInsertion, UnvaultEmergencyTransaction, UnvaultTransaction,
+    },
+};
+
+use miniscript::bitcoin::{
+    secp256k1, util::psbt::PartiallySignedTransaction as Psbt, PublicKey, SigHashType,
+};
+
+/// One of the presigned transactions of a vault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresignedTx {
+    /// The Unvault transaction
+    Unvault,
+    /// The Cancel transaction at this index in the batch, ordered by ascending feerate
+    Cancel(usize),
+    /// The Emergency transaction
+    Emergency,
+    /// The Unvault Emergency transaction
+    UnvaultEmergency,
+}
+
+/// The status of a vault, as far as its presigned transactions are concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VaultStatus {
+    /// Some signatures of the revocation transactions are still missing
+    Funded,
+    /// All the revocation transactions are signed, but not the Unvault
+    Secured,
+    /// All the presigned transactions are signed, the vault may be spent by the managers
+    Active,
+    /// The Unvault transaction was broadcast
+    Unvaulting,
+}
+
+/// The chain of presigned transactions of a deposit.
+///
+/// Signatures for the Unvault transaction are only accepted once all the revocation transactions
+/// (Cancel, Emergency and Unvault Emergency) are signed by all the stakeholders, as sharing them
+/// before would allow the managers to unvault without the stakeholders being able to revault.
+#[derive(Debug, Clone)]
+pub struct VaultState {
+    unvault_tx: UnvaultTransaction,
+    cancel_txs: Vec<CancelTransaction>,
+    emergency_tx: EmergencyTransaction,
+    unvault_emergency_tx: UnvaultEmergencyTransaction,
+    stakeholders: Vec<PublicKey>,
+    unvaulting: bool,
+}
+
+impl VaultState {
+    /// Track the presigned transactions of the deposit using this `deposit_descriptor`.
+    pub fn new(
+        unvault_tx: UnvaultTransaction,
+        cancel_batch: CancelTransactionsBatch,
+        emergency_tx: EmergencyTransaction,
+        unvault_emergency_tx: UnvaultEmergencyTransaction,
+        deposit_descriptor: &DerivedDepositDescriptor,
+    ) -> VaultState {
+        VaultState {
+            unvault_tx,
+            cancel_txs: cancel_batch.all_feerates().to_vec(),
+            emergency_tx,
+            unvault_emergency_tx,
+            stakeholders: deposit_descriptor
+                .keys()
+                .into_iter()
+                .map(|k| k.key)
+                .collect(),
+            unvaulting: false,
+        }
+    }
+
+    /// Get a reference to the Unvault transaction
+    pub fn unvault_tx(&self) -> &UnvaultTransaction {
+        &self.unvault_tx
+    }
+
+    /// Get the Cancel transactions, ordered by ascending feerate
+    pub fn cancel_txs(&self) -> &[CancelTransaction] {
+        &self.cancel_txs
+    }
+
+    /// Get a reference to the Emergency transaction
+    pub fn emergency_tx(&self) -> &EmergencyTransaction {
+        &self.emergency_tx
+    }
+
+    /// Get a reference to the Unvault Emergency transaction
+    pub fn unvault_emergency_tx(&self) -> &UnvaultEmergencyTransaction {
+        &self.unvault_emergency_tx
+    }
+
+    // Get the PSBT of one of the presigned transactions
+    fn psbt(&self, tx: PresignedTx) -> Result<&Psbt, VaultStateError> {
+        Ok(match tx {
+            PresignedTx::Unvault => self.unvault_tx.psbt(),
+            PresignedTx::Cancel(i) => self
+                .cancel_txs
+                .get(i)
+                .ok_or(VaultStateError::UnknownCancel(i))?
+                .psbt(),
+            PresignedTx::Emergency => self.emergency_tx.psbt(),
+            PresignedTx::UnvaultEmergency => self.unvault_emergency_tx.psbt(),
+        })
+    }
+
+    // All the revocation transactions
+    fn revocation_txs(&self) -> impl Iterator<Item = PresignedTx> {
+        (0..self.cancel_txs.len())
+            .map(PresignedTx::Cancel)
+            .chain(vec![PresignedTx::Emergency, PresignedTx::UnvaultEmergency])
+    }
+
+    /// Check and add a stakeholder's signature to one of the presigned transactions.
+    ///
+    /// Returns whether a previous signature for this key was replaced.
+    pub fn add_signature<C: secp256k1::Verification>(
+        &mut self,
+        tx: PresignedTx,
+        pubkey: secp256k1::PublicKey,
+        signature: (secp256k1::Signature, SigHashType),
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<SignatureInsertion, VaultStateError> {
+        if tx == PresignedTx::Unvault && !self.is_secured() {
+            return Err(VaultStateError::NotSecured);
+        }
+
+        let res = match tx {
+            PresignedTx::Unvault => self.unvault_tx.add_signature(0, pubkey, signature, secp),
+            PresignedTx::Cancel(i) => self
+                .cancel_txs
+                .get_mut(i)
+                .ok_or(VaultStateError::UnknownCancel(i))?
+                .add_signature(0, pubkey, signature, secp),
+            PresignedTx::Emergency => self.emergency_tx.add_signature(0, pubkey, signature, secp),
+            PresignedTx::UnvaultEmergency => self
+                .unvault_emergency_tx
+                .add_signature(0, pubkey, signature, secp),
+        };
+
+        res.map_err(VaultStateError::InputSatisfaction)
+    }
+
+    /// Get the stakeholders' keys whose signature is still missing for this transaction.
+    pub fn missing_signatures(&self, tx: PresignedTx) -> Result<Vec<PublicKey>, VaultStateError> {
+        let psbtin = &self.psbt(tx)?.inputs[0];
+        // A finalized input doesn't have partial signatures anymore
+        if psbtin.final_script_witness.is_some() {
+            return Ok(vec![]);
+        }
+
+        Ok(self
+            .stakeholders
+            .iter()
+            .filter(|key| !psbtin.partial_sigs.contains_key(*key))
+            .copied()
+            .collect())
+    }
+
+    /// Get the sighash of every presigned transaction of this vault, along with the derivation
+    /// paths of the keys expected to sign it. The revocation transactions come first, in the
+    /// order their signatures must be shared, then the Unvault transaction.
+    ///
+    /// Will error if one of the transactions was already finalized.
+    pub fn all_sighashes(&self) -> Result<Vec<(PresignedTx, InputSigHash)>, VaultStateError> {
+        self.revocation_txs()
+            .chain(std::iter::once(PresignedTx::Unvault))
+            .map(|tx| {
+                let sighashes = match tx {
+                    PresignedTx::Unvault => self.unvault_tx.all_sighashes(),
+                    PresignedTx::Cancel(i) => self.cancel_txs[i].all_sighashes(),
+                    PresignedTx::Emergency => self.emergency_tx.all_sighashes(),
+                    PresignedTx::UnvaultEmergency => self.unvault_emergency_tx.all_sighashes(),
+                }
+                .map_err(VaultStateError::InputSatisfaction)?;
+                // Presigned transactions always have a single input
+                let sighash = sighashes
+                    .into_iter()
+                    .next()
+                    .expect("Presigned transactions always have an input");
+                Ok((tx, sighash))
+            })
+            .collect()
+    }
+
+    /// Get all the presigned transactions which are still missing signatures, along with the keys
+    /// of the stakeholders whose signature is missing.
+    pub fn missing(&self) -> Vec<(PresignedTx, Vec<PublicKey>)> {
+        self.revocation_txs()
+            .chain(std::iter::once(PresignedTx::Unvault))
+            .filter_map(|tx| {
+                let missing = self
+                    .missing_signatures(tx)
+                    .expect("We only iterate over existing transactions");
+                if missing.is_empty() {
+                    None
+                } else {
+                    Some((tx, missing))
+                }
+            })
+            .collect()
+    }
+
+    /// Whether all the revocation transactions are signed by all the stakeholders.
+    pub fn is_secured(&self) -> bool {
+        self.revocation_txs().all(|tx| {
+            self.missing_signatures(tx)
+                .expect("We only iterate over existing transactions")
+                .is_empty()
+        })
+    }
+
+    /// Whether all the presigned transactions, including the Unvault, are signed by all the
+    /// stakeholders.
+    pub fn is_active(&self) -> bool {
+        self.is_secured()
+            && self
+                .missing_signatures(PresignedTx::Unvault)
+                .expect("The Unvault always exists")
+                .is_empty()
+    }
+
+    /// Record that the Unvault transaction was broadcast.
+    pub fn mark_unvaulting(&mut self) -> Result<(), VaultStateError> {
+        if !self.is_active() {
+            return Err(VaultStateError::NotActive);
+        }
+        self.unvaulting = true;
+
+        Ok(())
+    }
+
+    /// Whether the Unvault transaction was broadcast.
+    pub fn is_unvaulting(&self) -> bool {
+        self.unvaulting
+    }
+
+    /// Get the current status of the vault.
+    pub fn status(&self) -> VaultStatus {
+        if self.unvaulting {
+            VaultStatus::Unvaulting
+        } else if self.is_active() {
+            VaultStatus::Active
+        } else if self.is_secured() {
+            VaultStatus::Secured
+        } else {
+            VaultStatus::Funded
+        }
+    }
+}
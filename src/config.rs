@@ -0,0 +1,269 @@
+//! # Revault deployment configuration
+//!
+//! The participants' keys and parameters of a Revault deployment, as found in its configuration
+//! file, out of which all the descriptors are created.
+
+use crate::{
+    error::ScriptCreationError,
+    scripts::{CpfpDescriptor, CsvValue, DepositDescriptor, EmergencyAddress, UnvaultDescriptor},
+};
+
+use miniscript::DescriptorPublicKey;
+
+#[cfg(feature = "use-serde")]
+use {
+    serde::de::{self, Deserialize, Deserializer},
+    std::{fmt, str::FromStr},
+};
+
+/// The configuration of the Scripts of a Revault deployment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptsConfig {
+    /// The stakeholders' xpubs
+    pub stakeholders: Vec<DescriptorPublicKey>,
+    /// The managers' xpubs
+    pub managers: Vec<DescriptorPublicKey>,
+    /// The number of managers required to sign a Spend
+    pub managers_threshold: usize,
+    /// The Cosigning Servers' keys, one per stakeholder (or none)
+    pub cosigners: Vec<DescriptorPublicKey>,
+    /// The managers' xpubs used to fee-bump the Unvault and Spend transactions
+    pub cpfp_keys: Vec<DescriptorPublicKey>,
+    /// The relative timelock of the Unvault output
+    pub csv: CsvValue,
+    /// Where the funds are sent in case of an emergency
+    pub emergency_address: EmergencyAddress,
+}
+
+/// All the descriptors of a Revault deployment, as created by [ScriptsConfig::into_descriptors].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Descriptors {
+    /// The descriptor of the deposit outputs
+    pub deposit: DepositDescriptor,
+    /// The descriptor of the Unvault outputs
+    pub unvault: UnvaultDescriptor,
+    /// The descriptor of the CPFP outputs of the Unvault and Spend transactions
+    pub cpfp: CpfpDescriptor,
+    /// Where the Emergency transactions pay
+    pub emergency_address: EmergencyAddress,
+}
+
+impl ScriptsConfig {
+    /// Create and check all the descriptors of this deployment.
+    ///
+    /// # Errors
+    /// - Same as [DepositDescriptor::new], [UnvaultDescriptor::new] and [CpfpDescriptor::new].
+    pub fn into_descriptors(self) -> Result<Descriptors, ScriptCreationError> {
+        let deposit = DepositDescriptor::new(self.stakeholders.clone())?;
+        let unvault = UnvaultDescriptor::new(
+            self.stakeholders,
+            self.managers,
+            self.managers_threshold,
+            self.cosigners,
+            self.csv,
+        )?;
+        let cpfp = CpfpDescriptor::new(self.cpfp_keys)?;
+
+        Ok(Descriptors {
+            deposit,
+            unvault,
+            cpfp,
+            emergency_address: self.emergency_address,
+        })
+    }
+}
+
+// FIXME: the windows CI build is preventing us from using the 'use-serde' feature of
+// rust-bitcoin, hence the manual implementation.
+
+#[cfg(feature = "use-serde")]
+const SCRIPTS_CONFIG_FIELDS: &[&str] = &[
+    "stakeholders",
+    "managers",
+    "managers_threshold",
+    "cosigners",
+    "cpfp_keys",
+    "csv",
+    "emergency_address",
+];
+
+#[cfg(feature = "use-serde")]
+fn parse_keys<E: de::Error>(keys: Vec<String>) -> Result<Vec<DescriptorPublicKey>, E> {
+    keys.iter()
+        .map(|k| DescriptorPublicKey::from_str(k))
+        .collect::<Result<Vec<DescriptorPublicKey>, _>>()
+        .map_err(de::Error::custom)
+}
+
+#[cfg(feature = "use-serde")]
+impl<'de> Deserialize<'de> for ScriptsConfig {
+    fn deserialize<D>(deserializer: D) -> Result<ScriptsConfig, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ScriptsConfigVisitor;
+
+        impl<'de> de::Visitor<'de> for ScriptsConfigVisitor {
+            type Value = ScriptsConfig;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a Revault scripts configuration")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<ScriptsConfig, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut stakeholders = None;
+                let mut managers = None;
+                let mut managers_threshold = None;
+                let mut cosigners = None;
+                let mut cpfp_keys = None;
+                let mut csv = None;
+                let mut emergency_address = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "stakeholders" => stakeholders = Some(parse_keys(map.next_value()?)?),
+                        "managers" => managers = Some(parse_keys(map.next_value()?)?),
+                        "managers_threshold" => managers_threshold = Some(map.next_value()?),
+                        "cosigners" => cosigners = Some(parse_keys(map.next_value()?)?),
+                        "cpfp_keys" => cpfp_keys = Some(parse_keys(map.next_value()?)?),
+                        "csv" => {
+                            let csv_value = CsvValue::from_bip68(map.next_value()?)
+                                .map_err(de::Error::custom)?;
+                            csv = Some(csv_value);
+                        }
+                        "emergency_address" => emergency_address = Some(map.next_value()?),
+                        _ => return Err(de::Error::unknown_field(&key, SCRIPTS_CONFIG_FIELDS)),
+                    }
+                }
+
+                Ok(ScriptsConfig {
+                    stakeholders: stakeholders
+                        .ok_or_else(|| de::Error::missing_field("stakeholders"))?,
+                    managers: managers.ok_or_else(|| de::Error::missing_field("managers"))?,
+                    managers_threshold: managers_threshold
+                        .ok_or_else(|| de::Error::missing_field("managers_threshold"))?,
+                    // There may not be any Cosigning Server
+                    cosigners: cosigners.unwrap_or_default(),
+                    cpfp_keys: cpfp_keys.ok_or_else(|| de::Error::missing_field("cpfp_keys"))?,
+                    csv: csv.ok_or_else(|| de::Error::missing_field("csv"))?,
+                    emergency_address: emergency_address
+                        .ok_or_else(|| de::Error::missing_field("emergency_address"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct(
+            "ScriptsConfig",
+            SCRIPTS_CONFIG_FIELDS,
+            ScriptsConfigVisitor,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScriptsConfig;
+    use crate::{
+        error::ScriptCreationError,
+        scripts::{
+            CpfpDescriptor, CsvValue, DepositDescriptor, EmergencyAddress, UnvaultDescriptor,
+        },
+    };
+
+    use miniscript::{
+        bitcoin::{Address, Network, Script},
+        DescriptorPublicKey,
+    };
+
+    use std::str::FromStr;
+
+    const STAKEHOLDERS: [&str; 2] = [
+        "xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*",
+        "xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*",
+    ];
+    const MANAGER: &str = "xpub6Br1DUfrzxTVGo1sanuKDCUmSxDfLRrxLQBqpMqygkQLkQWodoyvvGtUV8Rp3r6d6BNYvedBSU8c7whhn2U8haRVxsWwuQiZ9LoFp7jXPQA/*";
+
+    fn keys(keys: &[&str]) -> Vec<DescriptorPublicKey> {
+        keys.iter()
+            .map(|k| DescriptorPublicKey::from_str(k).unwrap())
+            .collect()
+    }
+
+    fn config() -> ScriptsConfig {
+        ScriptsConfig {
+            stakeholders: keys(&STAKEHOLDERS),
+            managers: keys(&[MANAGER]),
+            managers_threshold: 1,
+            cosigners: vec![],
+            cpfp_keys: keys(&[MANAGER]),
+            csv: CsvValue::blocks(144),
+            emergency_address: EmergencyAddress::from(Address::p2wsh(
+                &Script::new(),
+                Network::Bitcoin,
+            ))
+            .unwrap(),
+        }
+    }
+
+    #[test]
+    fn scripts_config_descriptors() {
+        let descriptors = config().into_descriptors().unwrap();
+        assert_eq!(
+            descriptors.deposit,
+            DepositDescriptor::new(keys(&STAKEHOLDERS)).unwrap()
+        );
+        assert_eq!(
+            descriptors.unvault,
+            UnvaultDescriptor::new(
+                keys(&STAKEHOLDERS),
+                keys(&[MANAGER]),
+                1,
+                vec![],
+                CsvValue::blocks(144)
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            descriptors.cpfp,
+            CpfpDescriptor::new(keys(&[MANAGER])).unwrap()
+        );
+
+        let mut bad_config = config();
+        bad_config.managers_threshold = 2;
+        assert_eq!(
+            bad_config.into_descriptors(),
+            Err(ScriptCreationError::BadParameters)
+        );
+    }
+
+    #[cfg(feature = "use-serde")]
+    #[test]
+    fn serde_scripts_config() {
+        let config_json = format!(
+            "{{\"stakeholders\": [\"{}\", \"{}\"], \"managers\": [\"{}\"], \
+             \"managers_threshold\": 1, \"cpfp_keys\": [\"{}\"], \"csv\": 144, \
+             \"emergency_address\": \"{}\"}}",
+            STAKEHOLDERS[0],
+            STAKEHOLDERS[1],
+            MANAGER,
+            MANAGER,
+            config().emergency_address
+        );
+        assert_eq!(
+            serde_json::from_str::<ScriptsConfig>(&config_json).unwrap(),
+            config()
+        );
+
+        // The CSV must be a valid relative timelock, not a disabled one
+        serde_json::from_str::<ScriptsConfig>(
+            &config_json.replace("\"csv\": 144", "\"csv\": 2147483792"),
+        )
+        .unwrap_err();
+        // Unknown fields are refused
+        serde_json::from_str::<ScriptsConfig>(&config_json.replace("cpfp_keys", "cpfp"))
+            .unwrap_err();
+    }
+}
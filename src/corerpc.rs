@@ -0,0 +1,93 @@
+//! # bitcoind RPC interface helpers
+//!
+//! Conversions from the results of the bitcoind wallet RPC commands, as exposed by the
+//! `bitcoincore_rpc` crate, for a Revault transaction to round-trip through a Bitcoin Core wallet.
+//! The other direction is already covered by [RevaultTransaction::as_psbt_string] for the PSBT
+//! commands and by [RevaultTransaction::hex] for `sendrawtransaction`.
+//!
+//! It also creates the `importdescriptors` request to watch the outputs of a deployment with a
+//! watch-only descriptor wallet.
+
+use crate::{
+    config::Descriptors, error::CoreRpcError, scripts::DerivationIndex,
+    transactions::RevaultTransaction,
+};
+
+use bitcoincore_rpc_json::{FinalizePsbtResult, WalletProcessPsbtResult};
+use miniscript::bitcoin::{consensus::encode, hashes::hex::FromHex, Transaction};
+
+/// Parse the PSBT returned by `walletprocesspsbt` as a Revault transaction of this type.
+///
+/// Returns whether the wallet considers the transaction complete along with it.
+pub fn from_wallet_process_psbt<T: RevaultTransaction>(
+    res: &WalletProcessPsbtResult,
+) -> Result<(T, bool), CoreRpcError> {
+    let tx = T::from_psbt_str(&res.psbt).map_err(CoreRpcError::Psbt)?;
+    Ok((tx, res.complete))
+}
+
+/// Get the network transaction out of the result of `finalizepsbt`, checking it is the
+/// finalized version of `tx`.
+pub fn finalized_transaction(
+    tx: &impl RevaultTransaction,
+    res: &FinalizePsbtResult,
+) -> Result<Transaction, CoreRpcError> {
+    let raw_tx = match (&res.hex, res.complete) {
+        (Some(raw_tx), true) => raw_tx,
+        _ => return Err(CoreRpcError::Incomplete),
+    };
+    let final_tx: Transaction =
+        encode::deserialize(raw_tx).map_err(|e| CoreRpcError::Encode(e.to_string()))?;
+
+    if final_tx.txid() != tx.txid() {
+        return Err(CoreRpcError::TxidMismatch(tx.txid(), final_tx.txid()));
+    }
+
+    Ok(final_tx)
+}
+
+/// Parse a hex-encoded network-serialized transaction, as returned by `getrawtransaction`.
+pub fn transaction_from_hex(raw_hex: &str) -> Result<Transaction, CoreRpcError> {
+    let raw_tx = Vec::<u8>::from_hex(raw_hex).map_err(|e| CoreRpcError::Encode(e.to_string()))?;
+    encode::deserialize(&raw_tx).map_err(|e| CoreRpcError::Encode(e.to_string()))
+}
+
+/// Get the requests for the `importdescriptors` command of a watch-only descriptor wallet to
+/// watch the deposit, Unvault and CPFP outputs derived at an index from `start` (included) to
+/// `end` (excluded).
+///
+/// Only the deposit descriptor is imported as receiving (non-internal), as the Unvault and CPFP
+/// outputs are only ever created by the wallet's own transactions. The `timestamp` is the time
+/// from which to rescan the chain, `None` for "now" in a fresh wallet without any deposit yet.
+/// There are no requests for an empty range.
+pub fn import_descriptors_requests(
+    descriptors: &Descriptors,
+    start: DerivationIndex,
+    end: DerivationIndex,
+    timestamp: Option<u64>,
+) -> Vec<serde_json::Value> {
+    let (start, end) = (u32::from(start), u32::from(end));
+    if start >= end {
+        return vec![];
+    }
+
+    let timestamp = match timestamp {
+        Some(timestamp) => serde_json::json!(timestamp),
+        None => serde_json::json!("now"),
+    };
+    let request = |desc: String, internal: bool| {
+        serde_json::json!({
+            "desc": desc,
+            "range": [start, end - 1],
+            "timestamp": timestamp,
+            "internal": internal,
+        })
+    };
+
+    // The Display implementation of the descriptors includes the checksum
+    vec![
+        request(descriptors.deposit.to_string(), false),
+        request(descriptors.unvault.to_string(), true),
+        request(descriptors.cpfp.to_string(), true),
+    ]
+}
@@ -0,0 +1,272 @@
+//! # Revault cosigning servers messages
+//!
+//! The messages exchanged between the managers' wallet and the cosigning servers when requesting
+//! the signature of a [Spend](crate::transactions::SpendTransaction).
+
+use crate::{
+    error::CosigningError,
+    transactions::{RevaultTransaction, SpendTransaction},
+};
+
+use miniscript::bitcoin::{secp256k1, OutPoint, PublicKey, SigHashType};
+
+#[cfg(feature = "use-serde")]
+use {
+    serde::de::{self, Deserialize, Deserializer},
+    serde::ser::{Serialize, SerializeStruct, Serializer},
+    std::{fmt, str::FromStr},
+};
+
+use std::collections::HashSet;
+
+/// A request for a cosigning server to sign a Spend transaction.
+///
+/// A cosigning server must never sign twice for the same deposit, hence the outpoints of the
+/// deposits whose Unvault outputs are being spent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignRequest {
+    spend_tx: SpendTransaction,
+    deposit_outpoints: Vec<OutPoint>,
+}
+
+impl SignRequest {
+    /// Create a request for signing this Spend transaction. The deposit outpoints must be
+    /// ordered as the Spend transaction inputs.
+    ///
+    /// Will error if there isn't exactly one (unique) deposit outpoint per Spend input.
+    pub fn new(
+        spend_tx: SpendTransaction,
+        deposit_outpoints: Vec<OutPoint>,
+    ) -> Result<SignRequest, CosigningError> {
+        let req = SignRequest {
+            spend_tx,
+            deposit_outpoints,
+        };
+        req.validate()?;
+
+        Ok(req)
+    }
+
+    /// Check there is exactly one (unique) deposit outpoint per Spend input.
+    pub fn validate(&self) -> Result<(), CosigningError> {
+        let n_inputs = self.spend_tx.tx().input.len();
+        if self.deposit_outpoints.len() != n_inputs {
+            return Err(CosigningError::DepositCountMismatch(
+                n_inputs,
+                self.deposit_outpoints.len(),
+            ));
+        }
+
+        let mut seen = HashSet::with_capacity(n_inputs);
+        for outpoint in self.deposit_outpoints.iter() {
+            if !seen.insert(outpoint) {
+                return Err(CosigningError::DuplicatedDeposit(*outpoint));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get a reference to the Spend transaction to be signed
+    pub fn spend_tx(&self) -> &SpendTransaction {
+        &self.spend_tx
+    }
+
+    /// Get the outpoints of the deposits spent by the Spend transaction, in inputs order
+    pub fn deposit_outpoints(&self) -> &[OutPoint] {
+        &self.deposit_outpoints
+    }
+
+    /// Move out the Spend transaction and the deposit outpoints
+    pub fn into_parts(self) -> (SpendTransaction, Vec<OutPoint>) {
+        (self.spend_tx, self.deposit_outpoints)
+    }
+}
+
+/// The signatures of a cosigning server for each input of a Spend transaction, in inputs order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignResult {
+    signatures: Vec<(PublicKey, secp256k1::Signature)>,
+}
+
+impl SignResult {
+    /// Create a response out of the cosigning server's signatures, in the Spend inputs order
+    pub fn new(signatures: Vec<(PublicKey, secp256k1::Signature)>) -> SignResult {
+        SignResult { signatures }
+    }
+
+    /// Get the cosigning server's signatures, in the Spend inputs order
+    pub fn signatures(&self) -> &[(PublicKey, secp256k1::Signature)] {
+        &self.signatures
+    }
+
+    /// Check and add the signatures to this Spend transaction.
+    ///
+    /// Will error if there isn't exactly one signature per Spend input or if one of them is
+    /// invalid. The Spend transaction is left untouched on error.
+    pub fn apply<C: secp256k1::Verification>(
+        &self,
+        spend_tx: &mut SpendTransaction,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), CosigningError> {
+        let n_inputs = spend_tx.tx().input.len();
+        if self.signatures.len() != n_inputs {
+            return Err(CosigningError::SignatureCountMismatch(
+                n_inputs,
+                self.signatures.len(),
+            ));
+        }
+
+        let mut signed_tx = spend_tx.clone();
+        for (i, (pubkey, sig)) in self.signatures.iter().enumerate() {
+            signed_tx
+                .add_signature(i, pubkey.key, (*sig, SigHashType::All), secp)
+                .map_err(CosigningError::InvalidSignature)?;
+        }
+        *spend_tx = signed_tx;
+
+        Ok(())
+    }
+}
+
+// FIXME: the windows CI build is preventing us from using the 'use-serde' feature of
+// rust-bitcoin, hence the manual implementations.
+
+#[cfg(feature = "use-serde")]
+const SIGN_REQUEST_FIELDS: &[&str] = &["spend_tx", "deposit_outpoints"];
+
+#[cfg(feature = "use-serde")]
+impl Serialize for SignRequest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let outpoints: Vec<String> = self
+            .deposit_outpoints
+            .iter()
+            .map(|o| o.to_string())
+            .collect();
+
+        let mut state = serializer.serialize_struct("SignRequest", SIGN_REQUEST_FIELDS.len())?;
+        state.serialize_field("spend_tx", &self.spend_tx)?;
+        state.serialize_field("deposit_outpoints", &outpoints)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "use-serde")]
+impl<'de> Deserialize<'de> for SignRequest {
+    fn deserialize<D>(deserializer: D) -> Result<SignRequest, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SignRequestVisitor;
+
+        impl<'de> de::Visitor<'de> for SignRequestVisitor {
+            type Value = SignRequest;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a cosigning server sign request")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<SignRequest, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut spend_tx = None;
+                let mut deposit_outpoints = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "spend_tx" => spend_tx = Some(map.next_value()?),
+                        "deposit_outpoints" => {
+                            let outpoints = map
+                                .next_value::<Vec<String>>()?
+                                .iter()
+                                .map(|o| OutPoint::from_str(o))
+                                .collect::<Result<Vec<OutPoint>, _>>()
+                                .map_err(de::Error::custom)?;
+                            deposit_outpoints = Some(outpoints);
+                        }
+                        _ => return Err(de::Error::unknown_field(&key, SIGN_REQUEST_FIELDS)),
+                    }
+                }
+
+                SignRequest::new(
+                    spend_tx.ok_or_else(|| de::Error::missing_field("spend_tx"))?,
+                    deposit_outpoints
+                        .ok_or_else(|| de::Error::missing_field("deposit_outpoints"))?,
+                )
+                .map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_struct("SignRequest", SIGN_REQUEST_FIELDS, SignRequestVisitor)
+    }
+}
+
+#[cfg(feature = "use-serde")]
+impl Serialize for SignResult {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let signatures: Vec<(String, String)> = self
+            .signatures
+            .iter()
+            .map(|(pubkey, sig)| (pubkey.to_string(), sig.to_string()))
+            .collect();
+
+        let mut state = serializer.serialize_struct("SignResult", 1)?;
+        state.serialize_field("signatures", &signatures)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "use-serde")]
+impl<'de> Deserialize<'de> for SignResult {
+    fn deserialize<D>(deserializer: D) -> Result<SignResult, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SignResultVisitor;
+
+        impl<'de> de::Visitor<'de> for SignResultVisitor {
+            type Value = SignResult;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a cosigning server sign result")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<SignResult, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut signatures = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "signatures" => {
+                            let mut sigs = Vec::new();
+                            for (pubkey, sig) in map.next_value::<Vec<(String, String)>>()? {
+                                sigs.push((
+                                    PublicKey::from_str(&pubkey).map_err(de::Error::custom)?,
+                                    secp256k1::Signature::from_str(&sig)
+                                        .map_err(de::Error::custom)?,
+                                ));
+                            }
+                            signatures = Some(sigs);
+                        }
+                        _ => return Err(de::Error::unknown_field(&key, &["signatures"])),
+                    }
+                }
+
+                Ok(SignResult {
+                    signatures: signatures.ok_or_else(|| de::Error::missing_field("signatures"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct("SignResult", &["signatures"], SignResultVisitor)
+    }
+}
@@ -2,12 +2,12 @@
 
 use crate::transactions::INSANE_FEES;
 
+#[cfg(not(target_arch = "wasm32"))]
 use bitcoinconsensus::Error as LibConsensusError;
 use miniscript::{
     bitcoin::{
-        consensus::encode::Error as EncodeError,
-        secp256k1,
-        util::psbt::{Input as PsbtInput, Output as PsbtOutput},
+        consensus::encode::Error as EncodeError, secp256k1,
+        util::psbt::PartiallySignedTransaction as Psbt, Amount, Network, OutPoint, SigHashType,
     },
     policy::compiler::CompilerError,
 };
@@ -23,13 +23,48 @@ pub enum ScriptCreationError {
     NonWildcardKeys,
     /// No xpub were present in a generalist descriptor
     NoXpub,
-    /// Invalid derived public key, must always be of the form '[fingerprint/index]<66 hex chars>'
+    /// Invalid derived public key, must always be of the form '[fingerprint/path/index]<66 hex chars>'
     DerivedKeyParsing,
     /// Miniscript policy compilation error
     PolicyCompilation(CompilerError),
     /// Miniscript general error, currently only for sanity checks in descriptor
     /// constructors
     MiniscriptError(miniscript::Error),
+    /// At least one of the xpubs has no origin information
+    MissingKeyOrigin,
+    /// Our descriptors can't be derived at hardened indexes
+    HardenedDerivationIndex,
+    /// The descriptor is not of the form expected by the Revault scripts templates
+    TemplateMismatch,
+    /// More stakeholders than [crate::scripts::MAX_STAKEHOLDERS] (actual number)
+    TooManyStakeholders(usize),
+    /// More managers than [crate::scripts::MAX_MANAGERS] (actual number)
+    TooManyManagers(usize),
+    /// More Cosigning Servers than [crate::scripts::MAX_COSIGNERS] (actual number)
+    TooManyCosigners(usize),
+    /// The same public key is used more than once in the descriptor
+    DuplicateKey,
+    /// The witness script is larger than the standardness limit (actual size)
+    ScriptTooLarge(usize),
+    /// The witness script contains more than the consensus-allowed number of non-push opcodes
+    TooManyOps,
+    /// Satisfying the witness script needs more than the standardness limit of witness
+    /// stack elements (actual number)
+    TooManyWitnessElements(usize),
+    /// The descriptor string has no checksum
+    MissingChecksum,
+    /// The descriptor string contains a character outside of the BIP380 character set
+    InvalidDescriptorCharacter(char),
+    /// The descriptor checksum doesn't match its content (expected checksum)
+    InvalidChecksum(String),
+    /// The relative timelock is not a valid CSV value (actual value)
+    InvalidCsv(u32),
+    /// A spending path of the Script doesn't require any signature
+    SiglessBranch,
+    /// The satisfaction of the Script could be malleated by a third party
+    MalleableScript,
+    /// A spending path of the Script combines height-based and time-based timelocks
+    MixedTimelocks,
 }
 
 impl fmt::Display for ScriptCreationError {
@@ -40,7 +75,60 @@ impl fmt::Display for ScriptCreationError {
             Self::MiniscriptError(e) => write!(f, "Miniscript error: '{}'", e),
             Self::NonWildcardKeys => write!(f, "Not all xpubs were wildcard"),
             Self::NoXpub => write!(f, "No xpub present in generalist descriptor"),
-            Self::DerivedKeyParsing => write!(f, "Invalid derived public key, must always be of the form '[fingerprint/index]<66 hex chars>'"),
+            Self::DerivedKeyParsing => write!(f, "Invalid derived public key, must always be of the form '[fingerprint/path/index]<66 hex chars>'"),
+            Self::MissingKeyOrigin => write!(f, "Not all xpubs have an origin"),
+            Self::HardenedDerivationIndex => write!(f, "Hardened derivation index"),
+            Self::TemplateMismatch => write!(f, "Descriptor doesn't match the Revault template"),
+            Self::TooManyStakeholders(n) => write!(
+                f,
+                "Too many stakeholders: {} (maximum is {})",
+                n,
+                crate::scripts::MAX_STAKEHOLDERS
+            ),
+            Self::TooManyManagers(n) => write!(
+                f,
+                "Too many managers: {} (maximum is {})",
+                n,
+                crate::scripts::MAX_MANAGERS
+            ),
+            Self::TooManyCosigners(n) => write!(
+                f,
+                "Too many Cosigning Servers: {} (maximum is {})",
+                n,
+                crate::scripts::MAX_COSIGNERS
+            ),
+            Self::DuplicateKey => write!(f, "A public key is used more than once"),
+            Self::ScriptTooLarge(size) => write!(
+                f,
+                "Witness script is too large: {} bytes (standardness limit is {})",
+                size,
+                crate::scripts::MAX_STANDARD_P2WSH_SCRIPT_SIZE
+            ),
+            Self::TooManyOps => write!(f, "Witness script has too many non-push opcodes"),
+            Self::TooManyWitnessElements(n) => write!(
+                f,
+                "Satisfaction needs too many witness elements: {} (standardness limit is {})",
+                n,
+                crate::scripts::MAX_STANDARD_P2WSH_STACK_ITEMS
+            ),
+            Self::MissingChecksum => write!(f, "Descriptor has no checksum"),
+            Self::InvalidDescriptorCharacter(c) => {
+                write!(f, "Invalid character in descriptor: '{}'", c)
+            }
+            Self::InvalidChecksum(expected) => {
+                write!(f, "Invalid descriptor checksum, expected '{}'", expected)
+            }
+            Self::InvalidCsv(csv) => write!(
+                f,
+                "Invalid CSV value: {} (must be an enabled and minimal BIP68 relative timelock)",
+                csv
+            ),
+            Self::SiglessBranch => write!(f, "Script can be spent without any signature"),
+            Self::MalleableScript => write!(f, "Script satisfaction is malleable"),
+            Self::MixedTimelocks => write!(
+                f,
+                "Script combines height-based and time-based timelocks in the same spending path"
+            ),
         }
     }
 }
@@ -64,18 +152,47 @@ impl PartialEq for ScriptCreationError {
     }
 }
 
-impl error::Error for ScriptCreationError {}
+impl error::Error for ScriptCreationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::PolicyCompilation(e) => Some(e),
+            Self::MiniscriptError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 /// Error when creating a Revault Bitcoin transaction output
 #[derive(PartialEq, Debug)]
 pub enum TxoutCreationError {
+    /// The scriptPubKey is not of a standard type
     InvalidScriptPubkeyType,
+    /// The address is not valid for the expected network (expected, actual)
+    NetworkMismatch(Network, Network),
+    /// The output value is below the dust threshold of its scriptPubKey
+    Dust,
+    /// The output value would leave less than this fee to the miners
+    InsufficientFee(Amount),
+    /// The output does not pay to the given descriptor
+    DescriptorMismatch,
 }
 
 impl fmt::Display for TxoutCreationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::InvalidScriptPubkeyType => write!(f, "Invalid ScriptPubKey type"),
+            Self::NetworkMismatch(expected, actual) => write!(
+                f,
+                "Address is for network '{}' but we expected '{}'",
+                actual, expected
+            ),
+            Self::Dust => write!(f, "Output value is below the dust threshold"),
+            Self::InsufficientFee(min_fee) => write!(
+                f,
+                "Output value would leave less than the minimum fee of {} to the miners",
+                min_fee
+            ),
+            Self::DescriptorMismatch => write!(f, "Output does not pay to the given descriptor"),
         }
     }
 }
@@ -103,6 +220,14 @@ pub enum TransactionCreationError {
     InsufficientFunds,
     /// Would result in negative fees or create a too small output
     FeerateTooHigh,
+    /// Trying to migrate a deposit to the descriptor it is already under
+    SameDescriptor,
+    /// The transaction does not pay to the deposit descriptor
+    NoDepositTxOut,
+    /// Trying to create a transaction without any input
+    NoInput,
+    /// Trying to create a transaction without any output besides the CPFP one
+    NoOutput,
 }
 
 impl fmt::Display for TransactionCreationError {
@@ -135,6 +260,19 @@ impl fmt::Display for TransactionCreationError {
                 "Feerate too high, can't afford without creating a too small \
                  output or having negative fees"
             ),
+            Self::SameDescriptor => write!(
+                f,
+                "Trying to migrate a deposit to the descriptor it is already under"
+            ),
+            Self::NoDepositTxOut => write!(
+                f,
+                "No output of the transaction pays to the deposit descriptor"
+            ),
+            Self::NoInput => write!(f, "Trying to create a transaction without any input"),
+            Self::NoOutput => write!(
+                f,
+                "Trying to create a transaction without any destination or change output"
+            ),
         }
     }
 }
@@ -156,6 +294,18 @@ pub enum InputSatisfactionError {
         secp256k1::PublicKey,
         secp256k1::Message,
     ),
+    /// A partial signature is not DER-encoded or not of the expected SIGHASH type
+    InvalidRawSignature(Vec<u8>),
+    /// The witness_script of the PSBT input is not a satisfiable Miniscript
+    NotMiniscript,
+    /// Trying to add a signature with a sighash type other than ALL
+    InvalidSighashType(SigHashType),
+    /// Trying to add a signature for a key which is not part of the input's witness script
+    UnknownKey(secp256k1::PublicKey),
+    /// Trying to add a preimage for none of the hashlocks of the input's witness script
+    UnknownPreimage,
+    /// The input does not hold enough valid signatures to be finalized (number missing)
+    MissingSignatures(usize),
 }
 
 impl fmt::Display for InputSatisfactionError {
@@ -172,12 +322,105 @@ impl fmt::Display for InputSatisfactionError {
                 "Invalid signature '{:x?}' for key '{:x?}' and sighash '{:x?}'",
                 &sig, &pk, &hash
             ),
+            Self::InvalidRawSignature(sig) => write!(
+                f,
+                "Invalid raw signature '{:x?}': not DER-encoded or not SIGHASH_ALL",
+                &sig
+            ),
+            Self::NotMiniscript => write!(
+                f,
+                "The witness_script of the PSBT input is not a satisfiable Miniscript"
+            ),
+            Self::InvalidSighashType(sighash_type) => write!(
+                f,
+                "Invalid sighash type '{:?}', only SIGHASH_ALL is accepted",
+                sighash_type
+            ),
+            Self::UnknownKey(pk) => write!(
+                f,
+                "Public key '{}' is not part of the input's witness script",
+                pk
+            ),
+            Self::UnknownPreimage => write!(
+                f,
+                "Preimage does not match any hashlock of the input's witness script"
+            ),
+            Self::MissingSignatures(missing) => write!(
+                f,
+                "Missing {} valid signature(s) to satisfy the input's witness script",
+                missing
+            ),
         }
     }
 }
 
 impl error::Error for InputSatisfactionError {}
 
+/// Error when combining two versions of a Revault transaction (PSBT Combiner role)
+#[derive(PartialEq, Eq, Debug)]
+pub enum PsbtCombinationError {
+    /// The two PSBTs are not for the same unsigned transaction
+    DifferentTransactions,
+    /// The PSBT inputs at this index spend a different previous txout
+    ConflictingWitnessUtxo(usize),
+    /// The PSBT inputs at this index have a different witness script
+    ConflictingWitnessScript(usize),
+    /// The PSBT inputs at this index have a different sighash type
+    ConflictingSighashType(usize),
+    /// The PSBT inputs at this index have different derivation paths
+    ConflictingDerivationPaths(usize),
+    /// A partial signature for the PSBT input at this index is invalid
+    InvalidSignature(usize),
+    /// The PSBT inputs at this index hold different signatures for the same key
+    ConflictingSignature(usize),
+    /// A partial signature for the PSBT input at this index is for a key not in its witness script
+    UnknownKey(usize),
+    /// The other PSBT is finalized but its final witnesses don't satisfy the inputs
+    InvalidFinalWitness,
+}
+
+impl fmt::Display for PsbtCombinationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DifferentTransactions => {
+                write!(f, "The PSBTs are not for the same unsigned transaction")
+            }
+            Self::ConflictingWitnessUtxo(i) => {
+                write!(f, "Conflicting witness_utxo for input at index '{}'", i)
+            }
+            Self::ConflictingWitnessScript(i) => {
+                write!(f, "Conflicting witness_script for input at index '{}'", i)
+            }
+            Self::ConflictingSighashType(i) => {
+                write!(f, "Conflicting sighash_type for input at index '{}'", i)
+            }
+            Self::ConflictingDerivationPaths(i) => {
+                write!(f, "Conflicting bip32_derivation for input at index '{}'", i)
+            }
+            Self::InvalidSignature(i) => {
+                write!(f, "Invalid partial signature for input at index '{}'", i)
+            }
+            Self::ConflictingSignature(i) => {
+                write!(
+                    f,
+                    "Conflicting partial signatures for input at index '{}'",
+                    i
+                )
+            }
+            Self::UnknownKey(i) => write!(
+                f,
+                "Partial signature by a key not in the witness script for input at index '{}'",
+                i
+            ),
+            Self::InvalidFinalWitness => {
+                write!(f, "Invalid final witness in the finalized PSBT")
+            }
+        }
+    }
+}
+
+impl error::Error for PsbtCombinationError {}
+
 /// Error when validating a correctly serialized PSBT representing a Revault transaction
 #[derive(PartialEq, Debug)]
 pub enum PsbtValidationError {
@@ -188,15 +431,16 @@ pub enum PsbtValidationError {
     InvalidOutputCount(usize),
     DuplicatedInput,
     MissingRevocationInput,
-    MissingWitnessUtxo(PsbtInput),
-    MissingInWitnessScript(PsbtInput),
-    InvalidInWitnessScript(PsbtInput),
-    MissingOutWitnessScript(PsbtOutput),
-    InvalidOutWitnessScript(PsbtOutput),
-    InvalidInputField(PsbtInput),
-    InvalidOutputField(PsbtOutput),
-    InvalidPrevoutType(PsbtInput),
+    MissingWitnessUtxo(usize),
+    MissingInWitnessScript(usize),
+    InvalidInWitnessScript(usize),
+    MissingOutWitnessScript(usize),
+    InvalidOutWitnessScript(usize),
+    InvalidInputField(usize, &'static str),
+    InvalidOutputField(usize, &'static str),
+    InvalidPrevoutType(usize),
     InvalidCountOuputWithDerivations(usize),
+    InvalidSequence(u32),
     PartiallyFinalized,
     InsaneAmounts,
     TransactionTooLarge,
@@ -222,31 +466,30 @@ impl fmt::Display for PsbtValidationError {
             Self::MissingRevocationInput => {
                 write!(f, "Missing P2WSH input for revocation transaction")
             }
-            Self::MissingWitnessUtxo(i) => write!(f, "Missing witness utxo for input '{:#?}'", i),
-            Self::MissingInWitnessScript(i) => {
-                write!(f, "Missing witness script for input '{:#?}'", i)
-            }
-            Self::InvalidInWitnessScript(i) => {
-                write!(f, "Invalid witness script for input '{:#?}'", i)
-            }
+            Self::MissingWitnessUtxo(i) => write!(f, "Missing witness utxo for input #{}", i),
+            Self::MissingInWitnessScript(i) => write!(f, "Missing witness script for input #{}", i),
+            Self::InvalidInWitnessScript(i) => write!(f, "Invalid witness script for input #{}", i),
             Self::MissingOutWitnessScript(o) => {
-                write!(f, "Missing witness script for output '{:#?}'", o)
+                write!(f, "Missing witness script for output #{}", o)
             }
             Self::InvalidOutWitnessScript(o) => {
-                write!(f, "Invalid witness script for output '{:#?}'", o)
+                write!(f, "Invalid witness script for output #{}", o)
+            }
+            Self::InvalidInputField(i, reason) => {
+                write!(f, "Invalid field in input #{}: {}", i, reason)
+            }
+            Self::InvalidOutputField(o, reason) => {
+                write!(f, "Invalid field in output #{}: {}", o, reason)
+            }
+            Self::InvalidPrevoutType(i) => {
+                write!(f, "Input #{} refers to an output of invalid type", i)
             }
-            Self::InvalidInputField(i) => write!(f, "Invalid field in input: '{:#?}'", i),
-            Self::InvalidOutputField(o) => write!(f, "Invalid field in output: '{:#?}'", o),
-            Self::InvalidPrevoutType(i) => write!(
-                f,
-                "This input refers to an output of invalid type: '{:#?}'",
-                i
-            ),
             Self::InvalidCountOuputWithDerivations(c) => write!(
                 f,
                 "Incorrect number of PSBT outputs with bip32 derivation set: {}.",
                 c
             ),
+            Self::InvalidSequence(s) => write!(f, "Invalid input nSequence: '{}'", s),
             Self::PartiallyFinalized => write!(f, "PSBT contains both final and non-final inputs"),
             Self::InsaneAmounts => write!(
                 f,
@@ -260,6 +503,42 @@ impl fmt::Display for PsbtValidationError {
     }
 }
 
+impl PsbtValidationError {
+    /// The index of the PSBT input this error is about, if any
+    pub fn input_index(&self) -> Option<usize> {
+        match self {
+            Self::MissingWitnessUtxo(i)
+            | Self::MissingInWitnessScript(i)
+            | Self::InvalidInWitnessScript(i)
+            | Self::InvalidInputField(i, _)
+            | Self::InvalidPrevoutType(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// The index of the PSBT output this error is about, if any
+    pub fn output_index(&self) -> Option<usize> {
+        match self {
+            Self::MissingOutWitnessScript(o)
+            | Self::InvalidOutWitnessScript(o)
+            | Self::InvalidOutputField(o, _) => Some(*o),
+            _ => None,
+        }
+    }
+
+    /// Render this error along with the content of the offending input or output of `psbt`, for
+    /// debugging purposes. Beware it may contain signatures.
+    pub fn verbose(&self, psbt: &Psbt) -> String {
+        if let Some(input) = self.input_index().and_then(|i| psbt.inputs.get(i)) {
+            format!("{}: '{:#?}'", self, input)
+        } else if let Some(output) = self.output_index().and_then(|o| psbt.outputs.get(o)) {
+            format!("{}: '{:#?}'", self, output)
+        } else {
+            self.to_string()
+        }
+    }
+}
+
 impl error::Error for PsbtValidationError {}
 
 /// Error when working with serialized Revault transactions
@@ -302,10 +581,282 @@ impl From<PsbtValidationError> for TransactionSerialisationError {
     }
 }
 
-impl error::Error for TransactionSerialisationError {}
+impl error::Error for TransactionSerialisationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Base64Decode(e) => Some(e),
+            Self::Validation(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Error when verifying the internal consistency of a
+/// [WatchtowerBundle](crate::watchtower::WatchtowerBundle)
+#[derive(PartialEq, Debug)]
+pub enum WatchtowerBundleError {
+    /// The bundle does not contain any Cancel transaction
+    MissingCancel,
+    /// The Emergency transaction does not spend the deposit outpoint
+    EmergencyOutpointMismatch(OutPoint),
+    /// The Cancel and Unvault Emergency transactions don't all spend the same Unvault outpoint
+    UnvaultOutpointMismatch(OutPoint),
+    /// The Emergency and Unvault Emergency transactions don't pay to the same Emergency Script
+    EmergencyScriptMismatch,
+    /// A key of a transaction input was not derived at the derivation index of the bundle
+    DerivationIndexMismatch,
+    /// A partial signature of one of the transactions is invalid
+    InvalidSignature(InputSatisfactionError),
+}
+
+impl fmt::Display for WatchtowerBundleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingCancel => write!(f, "No Cancel transaction in the bundle"),
+            Self::EmergencyOutpointMismatch(outpoint) => write!(
+                f,
+                "Emergency transaction spends '{}' instead of the deposit outpoint",
+                outpoint
+            ),
+            Self::UnvaultOutpointMismatch(outpoint) => write!(
+                f,
+                "Transaction spends '{}' instead of the Unvault outpoint",
+                outpoint
+            ),
+            Self::EmergencyScriptMismatch => write!(
+                f,
+                "The Emergency transactions don't pay to the same Emergency Script"
+            ),
+            Self::DerivationIndexMismatch => write!(
+                f,
+                "Transaction input keys were not derived at the bundle's derivation index"
+            ),
+            Self::InvalidSignature(e) => write!(f, "Invalid signature: '{}'", e),
+        }
+    }
+}
+
+impl error::Error for WatchtowerBundleError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::InvalidSignature(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Error when creating or processing the messages exchanged with a cosigning server
+#[derive(PartialEq, Debug)]
+pub enum CosigningError {
+    /// There isn't one deposit outpoint per Spend input (expected, actual)
+    DepositCountMismatch(usize, usize),
+    /// The same deposit outpoint is present twice in a request
+    DuplicatedDeposit(OutPoint),
+    /// There isn't one signature per Spend input (expected, actual)
+    SignatureCountMismatch(usize, usize),
+    /// One of the returned signatures is invalid
+    InvalidSignature(InputSatisfactionError),
+}
+
+impl fmt::Display for CosigningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DepositCountMismatch(expected, actual) => write!(
+                f,
+                "Got {} deposit outpoints for {} Spend inputs",
+                actual, expected
+            ),
+            Self::DuplicatedDeposit(outpoint) => {
+                write!(f, "Deposit outpoint '{}' is present twice", outpoint)
+            }
+            Self::SignatureCountMismatch(expected, actual) => {
+                write!(f, "Got {} signatures for {} Spend inputs", actual, expected)
+            }
+            Self::InvalidSignature(e) => write!(f, "Invalid signature: '{}'", e),
+        }
+    }
+}
+
+impl error::Error for CosigningError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::InvalidSignature(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Error when encoding or decoding a Revault transaction as a BC-UR resource
+#[cfg(feature = "bc-ur")]
+#[derive(PartialEq, Debug)]
+pub enum UrError {
+    /// Error from the UR encoder or decoder
+    Ur(String),
+    /// The part is not from a 'crypto-psbt' resource
+    WrongType(String),
+    /// Not all the parts of the resource were received
+    Incomplete,
+    /// The payload of the resource is not a CBOR byte string
+    InvalidCbor,
+    /// The PSBT is not a valid Revault transaction
+    Transaction(TransactionSerialisationError),
+}
+
+#[cfg(feature = "bc-ur")]
+impl fmt::Display for UrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Ur(s) => write!(f, "UR error: '{}'", s),
+            Self::WrongType(part) => write!(f, "Not a 'crypto-psbt' UR part: '{}'", part),
+            Self::Incomplete => write!(f, "Not all the UR parts were received"),
+            Self::InvalidCbor => write!(f, "UR payload is not a CBOR byte string"),
+            Self::Transaction(e) => write!(f, "Invalid transaction: '{}'", e),
+        }
+    }
+}
+
+#[cfg(feature = "bc-ur")]
+impl error::Error for UrError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Transaction(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Error when merging the signatures from HWI's output into a Revault transaction
+#[cfg(feature = "hwi")]
+#[derive(PartialEq, Debug)]
+pub enum HwiError {
+    /// The output is not valid JSON
+    Json(String),
+    /// HWI reported an error
+    Hwi(String),
+    /// The output is neither a PSBT nor a map of signatures
+    UnexpectedOutput(String),
+    /// The returned PSBT is not a valid Revault transaction
+    Psbt(TransactionSerialisationError),
+    /// The returned PSBT could not be combined with the transaction
+    Combination(PsbtCombinationError),
+    /// A public key of the signatures map is not valid
+    InvalidKey(String),
+    /// A signature of the signatures map is not a DER-encoded signature with its sighash type
+    InvalidSignature(String),
+    /// No input of the transaction is to be signed by this key
+    UnknownKey(miniscript::bitcoin::PublicKey),
+    /// A signature could not be added to the input(s) of its key
+    Satisfaction(InputSatisfactionError),
+}
+
+#[cfg(feature = "hwi")]
+impl fmt::Display for HwiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Json(e) => write!(f, "Invalid JSON: '{}'", e),
+            Self::Hwi(e) => write!(f, "HWI error: '{}'", e),
+            Self::UnexpectedOutput(output) => write!(f, "Unexpected HWI output: '{}'", output),
+            Self::Psbt(e) => write!(f, "Invalid PSBT: '{}'", e),
+            Self::Combination(e) => write!(f, "PSBT combination error: '{}'", e),
+            Self::InvalidKey(key) => write!(f, "Invalid public key: '{}'", key),
+            Self::InvalidSignature(sig) => write!(f, "Invalid raw signature: '{}'", sig),
+            Self::UnknownKey(key) => write!(f, "No input to be signed by key '{}'", key),
+            Self::Satisfaction(e) => write!(f, "Input satisfaction error: '{}'", e),
+        }
+    }
+}
+
+#[cfg(feature = "hwi")]
+impl error::Error for HwiError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Psbt(e) => Some(e),
+            Self::Combination(e) => Some(e),
+            Self::Satisfaction(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Error when converting the results of the bitcoind RPC commands
+#[cfg(feature = "core-rpc")]
+#[derive(PartialEq, Debug)]
+pub enum CoreRpcError {
+    /// The returned PSBT is not a valid Revault transaction
+    Psbt(TransactionSerialisationError),
+    /// The returned transaction could not be deserialized
+    Encode(String),
+    /// bitcoind could not finalize the transaction
+    Incomplete,
+    /// The finalized transaction is not the expected one (expected, actual)
+    TxidMismatch(miniscript::bitcoin::Txid, miniscript::bitcoin::Txid),
+}
+
+#[cfg(feature = "core-rpc")]
+impl fmt::Display for CoreRpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Psbt(e) => write!(f, "Invalid PSBT: '{}'", e),
+            Self::Encode(e) => write!(f, "Invalid transaction: '{}'", e),
+            Self::Incomplete => write!(f, "bitcoind could not finalize the transaction"),
+            Self::TxidMismatch(expected, actual) => write!(
+                f,
+                "Finalized transaction '{}' is not the expected '{}'",
+                actual, expected
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "core-rpc")]
+impl error::Error for CoreRpcError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Psbt(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Error when updating a [VaultState](crate::chain::VaultState)
+#[derive(PartialEq, Debug)]
+pub enum VaultStateError {
+    /// There is no Cancel transaction at this index
+    UnknownCancel(usize),
+    /// Trying to add an Unvault signature while some revocation signatures are missing
+    NotSecured,
+    /// Trying to unvault while some signatures are missing
+    NotActive,
+    /// The signature could not be added, or the sighash could not be computed
+    InputSatisfaction(InputSatisfactionError),
+}
+
+impl fmt::Display for VaultStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownCancel(i) => write!(f, "No Cancel transaction at index '{}'", i),
+            Self::NotSecured => write!(
+                f,
+                "Not all revocation transactions are signed, refusing to add an Unvault signature"
+            ),
+            Self::NotActive => write!(f, "Not all presigned transactions are signed"),
+            Self::InputSatisfaction(e) => write!(f, "Input satisfaction error: '{}'", e),
+        }
+    }
+}
+
+impl error::Error for VaultStateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::InputSatisfaction(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 /// An error specific to the management of Revault transactions and scripts.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Error when creating a Revault Bitcoin Script
     ScriptCreation(ScriptCreationError),
@@ -315,10 +866,15 @@ pub enum Error {
     TransactionCreation(TransactionCreationError),
     /// Satisfaction (PSBT signer role) of a Revault transaction input failed.
     InputSatisfaction(InputSatisfactionError),
-    // FIXME: have upstream(s) derive PartialEq on Errors?
+    /// Combination (PSBT combiner role) of two versions of a Revault transaction failed.
+    PsbtCombination(PsbtCombinationError),
     /// Completion (PSBT finalizer role) of the Revault transaction failed.
-    TransactionFinalisation(String),
+    TransactionFinalisation(miniscript::psbt::Error),
+    /// Completion (PSBT finalizer role) of the Revault transaction failed because of these
+    /// inputs, along with the reason for each of them.
+    InputsFinalisation(Vec<(usize, InputSatisfactionError)>),
     /// The verification of the PSBT input against libbitcoinconsensus failed.
+    #[cfg(not(target_arch = "wasm32"))]
     TransactionVerification(LibConsensusError),
     /// Error when working with serialized Revault transactions
     TransactionSerialisation(TransactionSerialisationError),
@@ -348,6 +904,13 @@ impl From<InputSatisfactionError> for Error {
     }
 }
 
+impl From<PsbtCombinationError> for Error {
+    fn from(e: PsbtCombinationError) -> Self {
+        Self::PsbtCombination(e)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 impl From<LibConsensusError> for Error {
     fn from(e: LibConsensusError) -> Self {
         Self::TransactionVerification(e)
@@ -360,6 +923,30 @@ impl From<TransactionSerialisationError> for Error {
     }
 }
 
+impl From<miniscript::psbt::Error> for Error {
+    fn from(e: miniscript::psbt::Error) -> Self {
+        Self::TransactionFinalisation(e)
+    }
+}
+
+impl From<miniscript::Error> for Error {
+    fn from(e: miniscript::Error) -> Self {
+        Self::ScriptCreation(e.into())
+    }
+}
+
+impl From<PsbtValidationError> for Error {
+    fn from(e: PsbtValidationError) -> Self {
+        Self::TransactionSerialisation(e.into())
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(e: EncodeError) -> Self {
+        Self::TransactionSerialisation(e.into())
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -373,12 +960,23 @@ impl fmt::Display for Error {
             Error::InputSatisfaction(ref e) => {
                 write!(f, "Revault input satisfaction error: '{}'", e)
             }
+            Error::PsbtCombination(ref e) => {
+                write!(f, "Revault PSBT combination error: '{}'", e)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
             Error::TransactionVerification(ref e) => {
                 write!(f, "Revault transaction verification error: '{:?}'", e)
             }
             Error::TransactionFinalisation(ref e) => {
                 write!(f, "Revault transaction finalisation error: '{}'", e)
             }
+            Error::InputsFinalisation(ref failures) => {
+                write!(f, "Revault transaction finalisation error:")?;
+                for (i, e) in failures {
+                    write!(f, " input #{}: '{}'", i, e)?;
+                }
+                Ok(())
+            }
             Error::TransactionSerialisation(ref e) => {
                 write!(f, "Revault transaction serialisation error: '{}'", e)
             }
@@ -386,4 +984,21 @@ impl fmt::Display for Error {
     }
 }
 
-impl error::Error for Error {}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::ScriptCreation(e) => Some(e),
+            Self::TxoutCreation(e) => Some(e),
+            Self::TransactionCreation(e) => Some(e),
+            Self::InputSatisfaction(e) => Some(e),
+            Self::PsbtCombination(e) => Some(e),
+            Self::TransactionFinalisation(e) => Some(e),
+            // There may be several of them
+            Self::InputsFinalisation(_) => None,
+            // libbitcoinconsensus' error doesn't implement std's Error
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::TransactionVerification(_) => None,
+            Self::TransactionSerialisation(e) => Some(e),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! # HWI signatures import
+//!
+//! Merge the signatures returned by [HWI](https://github.com/bitcoin-core/HWI)'s `signtx` command
+//! into a Revault transaction.
+
+use crate::{error::HwiError, transactions::RevaultTransaction};
+
+use miniscript::bitcoin::{hashes::hex::FromHex, secp256k1, PublicKey, SigHashType};
+
+use std::str::FromStr;
+
+// Parse a hex-encoded DER signature with its sighash type appended
+fn parse_raw_sig(sig_hex: &str) -> Result<(secp256k1::Signature, SigHashType), HwiError> {
+    let rawsig = Vec::<u8>::from_hex(sig_hex)
+        .map_err(|_| HwiError::InvalidSignature(sig_hex.to_string()))?;
+    let (sighash_type, der_sig) = rawsig
+        .split_last()
+        .ok_or_else(|| HwiError::InvalidSignature(sig_hex.to_string()))?;
+    let sighash_type = SigHashType::from_u32_standard(*sighash_type as u32)
+        .map_err(|_| HwiError::InvalidSignature(sig_hex.to_string()))?;
+    let sig = secp256k1::Signature::from_der(der_sig)
+        .map_err(|_| HwiError::InvalidSignature(sig_hex.to_string()))?;
+
+    Ok((sig, sighash_type))
+}
+
+// Add this signature to the (first) input it is valid for
+fn add_hwi_signature<C: secp256k1::Verification>(
+    tx: &mut impl RevaultTransaction,
+    pubkey: &PublicKey,
+    signature: (secp256k1::Signature, SigHashType),
+    secp: &secp256k1::Secp256k1<C>,
+) -> Result<(), HwiError> {
+    let candidates: Vec<usize> = tx
+        .psbt()
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, psbtin)| {
+            psbtin.final_script_witness.is_none() && psbtin.bip32_derivation.contains_key(pubkey)
+        })
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut last_error = None;
+    for i in candidates {
+        match tx.add_signature(i, pubkey.key, signature, secp) {
+            Ok(_) => return Ok(()),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error
+        .map(HwiError::Satisfaction)
+        .unwrap_or(HwiError::UnknownKey(*pubkey)))
+}
+
+/// Merge the signatures from the JSON output of HWI's `signtx` command into this transaction.
+///
+/// The output is either the signed PSBT (`{"psbt": "<base64>", "signed": true}`), in which case
+/// it is combined with this transaction, or a map of hex-encoded public keys to hex-encoded
+/// signatures, in which case each signature is added to the input it is valid for.
+/// All the signatures are checked, and this transaction is not modified on error.
+pub fn merge_hwi_signatures<T: RevaultTransaction, C: secp256k1::Verification>(
+    tx: &mut T,
+    hwi_output: &str,
+    secp: &secp256k1::Secp256k1<C>,
+) -> Result<(), HwiError> {
+    let output: serde_json::Value =
+        serde_json::from_str(hwi_output).map_err(|e| HwiError::Json(e.to_string()))?;
+    let output = output
+        .as_object()
+        .ok_or_else(|| HwiError::UnexpectedOutput(hwi_output.to_string()))?;
+
+    // HWI reports failures as '{"error": "<message>", "code": <code>}'
+    if let Some(error) = output.get("error") {
+        return Err(HwiError::Hwi(error.to_string()));
+    }
+
+    if let Some(psbt) = output.get("psbt") {
+        let psbt = psbt
+            .as_str()
+            .ok_or_else(|| HwiError::UnexpectedOutput(hwi_output.to_string()))?;
+        let signed_tx = T::from_psbt_str(psbt).map_err(HwiError::Psbt)?;
+        return tx.combine(signed_tx, secp).map_err(HwiError::Combination);
+    }
+
+    let mut signed_tx = tx.clone();
+    for (pubkey, sig) in output.iter() {
+        let pubkey =
+            PublicKey::from_str(pubkey).map_err(|_| HwiError::InvalidKey(pubkey.to_string()))?;
+        let sig = sig
+            .as_str()
+            .ok_or_else(|| HwiError::UnexpectedOutput(hwi_output.to_string()))?;
+        add_hwi_signature(&mut signed_tx, &pubkey, parse_raw_sig(sig)?, secp)?;
+    }
+    *tx = signed_tx;
+
+    Ok(())
+}
@@ -12,8 +12,31 @@ pub use error::Error;
 
 pub mod scripts;
 
+pub mod config;
+
 pub mod txins;
 
 pub mod txouts;
 
 pub mod transactions;
+
+pub mod chain;
+
+pub mod watchtower;
+
+pub mod cosigning;
+
+#[cfg(feature = "bc-ur")]
+pub mod bcur;
+
+#[cfg(feature = "hwi")]
+pub mod hwi;
+
+#[cfg(feature = "core-rpc")]
+pub mod corerpc;
+
+#[cfg(feature = "test-vectors")]
+pub mod test_vectors;
+
+#[cfg(feature = "fuzz")]
+mod arbitrary_impls;
@@ -6,7 +6,10 @@
 //! We use [miniscript](http://bitcoin.sipa.be/miniscript/) in order to "safely" compile,
 //! derive, and satisfy Scripts depending on the setup configuration (ie the number of
 //! stakeholders, the number of fund managers, and the relative timelock) for all script
-//! but the (unknown) Emergency one.
+//! but the (unknown) Emergency one. For deployments using a plain N-of-N of the stakeholders'
+//! emergency keys as Emergency Script, an [EmergencyDescriptor] is provided as well. It may also
+//! be created from any P2WSH Miniscript descriptor, otherwise only the [EmergencyAddress] is
+//! known.
 //!
 //! **NOTE**: the compilation functions are not safe to reuse after initial set up, as the
 //! returned descriptors are non-deterministically compiled from an abstract policy.
@@ -19,9 +22,9 @@ use miniscript::{
         hashes::{hash160, Hash},
         secp256k1,
         util::bip32,
-        Address, PublicKey,
+        Address, Amount, Network, PublicKey, Script,
     },
-    descriptor::{DescriptorPublicKey, DescriptorTrait, Wildcard, WshInner},
+    descriptor::{DescriptorPublicKey, DescriptorTrait, DescriptorXKey, Wildcard, WshInner},
     miniscript::{
         iter::PkPkh,
         limits::{SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_TYPE_FLAG},
@@ -32,6 +35,9 @@ use miniscript::{
 };
 
 use std::{
+    cmp,
+    collections::HashSet,
+    convert::TryFrom,
     fmt::{self, Display},
     io::Write,
     str::FromStr,
@@ -50,26 +56,37 @@ pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x00_00_ff_ff;
 // Further, these bounds simplify the task of testing most deployment parameters.
 pub const MAX_STAKEHOLDERS: usize = 20;
 pub const MAX_MANAGERS: usize = 20;
+/// There is always as many Cosigning Servers as there are stakeholders.
+pub const MAX_COSIGNERS: usize = MAX_STAKEHOLDERS;
+
+/// Maximum size of a P2WSH witness script for the transaction spending it to be standard.
+pub const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+
+/// Maximum number of witness stack elements (not counting the witness script) for the
+/// transaction spending a P2WSH to be standard.
+pub const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
 
 /// A public key used in derived descriptors
 #[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
 pub struct DerivedPublicKey {
-    /// Fingerprint of the master xpub and the derivation index used. We don't use a path
-    /// since we never derive at more than one depth.
-    pub origin: (bip32::Fingerprint, bip32::ChildNumber),
+    /// Fingerprint of the master xpub and the full derivation path from it, including the
+    /// origin path of the xpub (if any). The last step is always the derivation index used.
+    pub origin: (bip32::Fingerprint, bip32::DerivationPath),
     /// The actual key
     pub key: PublicKey,
 }
 
 impl fmt::Display for DerivedPublicKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let (fingerprint, deriv_index) = &self.origin;
+        let (fingerprint, deriv_path) = &self.origin;
 
         write!(f, "[")?;
         for byte in fingerprint.as_bytes().iter() {
             write!(f, "{:02x}", byte)?;
         }
-        write!(f, "/{}", deriv_index)?;
+        for child in deriv_path.as_ref() {
+            write!(f, "/{}", child)?;
+        }
         write!(f, "]{}", self.key)
     }
 }
@@ -79,7 +96,7 @@ impl FromStr for DerivedPublicKey {
 
     fn from_str(s: &str) -> Result<DerivedPublicKey, Self::Err> {
         // The key is always of the form:
-        // [ fingerprint / index ]<key>
+        // [ fingerprint / path / index ]<key>
 
         // 1 + 8 + 1 + 1 + 1 + 66 minimum
         if s.len() < 78 {
@@ -101,15 +118,21 @@ impl FromStr for DerivedPublicKey {
         let fg_deriv = parts.next().ok_or(ScriptCreationError::DerivedKeyParsing)?;
         let key_str = parts.next().ok_or(ScriptCreationError::DerivedKeyParsing)?;
 
-        if fg_deriv.len() < 10 {
+        let mut fg_deriv = fg_deriv.split('/');
+        let fingerprint_str = fg_deriv.next().expect("Split always yields an item");
+        if fingerprint_str.len() != 8 {
             return Err(ScriptCreationError::DerivedKeyParsing);
         }
-        let fingerprint = bip32::Fingerprint::from_str(&fg_deriv[..8])
+        let fingerprint = bip32::Fingerprint::from_str(fingerprint_str)
             .map_err(|_| ScriptCreationError::DerivedKeyParsing)?;
-        let deriv_index = bip32::ChildNumber::from_str(&fg_deriv[9..])
+        let deriv_path = fg_deriv
+            .map(bip32::ChildNumber::from_str)
+            .collect::<Result<Vec<bip32::ChildNumber>, _>>()
             .map_err(|_| ScriptCreationError::DerivedKeyParsing)?;
-        if deriv_index.is_hardened() {
-            return Err(ScriptCreationError::DerivedKeyParsing);
+        // There must be a derivation index, and it's never hardened
+        match deriv_path.last() {
+            Some(index) if index.is_normal() => {}
+            _ => return Err(ScriptCreationError::DerivedKeyParsing),
         }
 
         let key =
@@ -117,7 +140,7 @@ impl FromStr for DerivedPublicKey {
 
         Ok(DerivedPublicKey {
             key,
-            origin: (fingerprint, deriv_index),
+            origin: (fingerprint, deriv_path.into()),
         })
     }
 }
@@ -149,17 +172,368 @@ impl ToPublicKey for DerivedPublicKey {
     }
 }
 
+/// A non-hardened BIP32 derivation index.
+///
+/// Our descriptors only contain xpubs, which can't be derived at hardened indexes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DerivationIndex(u32);
+
+impl TryFrom<u32> for DerivationIndex {
+    type Error = ScriptCreationError;
+
+    fn try_from(index: u32) -> Result<DerivationIndex, Self::Error> {
+        bip32::ChildNumber::from_normal_idx(index)
+            .map(|_| DerivationIndex(index))
+            .map_err(|_| ScriptCreationError::HardenedDerivationIndex)
+    }
+}
+
+impl TryFrom<bip32::ChildNumber> for DerivationIndex {
+    type Error = ScriptCreationError;
+
+    fn try_from(child_number: bip32::ChildNumber) -> Result<DerivationIndex, Self::Error> {
+        match child_number {
+            bip32::ChildNumber::Normal { index } => Ok(DerivationIndex(index)),
+            bip32::ChildNumber::Hardened { .. } => {
+                Err(ScriptCreationError::HardenedDerivationIndex)
+            }
+        }
+    }
+}
+
+impl From<DerivationIndex> for bip32::ChildNumber {
+    fn from(index: DerivationIndex) -> bip32::ChildNumber {
+        bip32::ChildNumber::Normal { index: index.0 }
+    }
+}
+
+impl From<DerivationIndex> for u32 {
+    fn from(index: DerivationIndex) -> u32 {
+        index.0
+    }
+}
+
+impl fmt::Display for DerivationIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A relative timelock, as enforced by the CSV fragments of our descriptors and the nSequence
+/// of the inputs spending them.
+///
+/// It is always enabled and minimal (all the bits without consensus meaning are unset), so it
+/// can safely be used as both a Script CSV value and an nSequence. It is in number of blocks
+/// unless explicitly created as a time-based timelock, in units of 512 seconds (see BIP68).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CsvValue(u32);
+
+impl CsvValue {
+    /// A relative timelock of this number of blocks
+    pub const fn blocks(n_blocks: u16) -> CsvValue {
+        CsvValue(n_blocks as u32)
+    }
+
+    /// A relative timelock of this number of 512 seconds intervals
+    pub const fn time_intervals(n_intervals: u16) -> CsvValue {
+        CsvValue(SEQUENCE_LOCKTIME_TYPE_FLAG | n_intervals as u32)
+    }
+
+    /// Parse a BIP68 relative timelock, which may be either in blocks or time-based. Use
+    /// [CsvValue::try_from] to only accept timelocks in blocks.
+    pub fn from_bip68(csv_value: u32) -> Result<CsvValue, ScriptCreationError> {
+        // We require the locktime to:
+        //  - not be disabled
+        //  - be 'clean' / minimal, ie all bits without consensus meaning should be 0
+        let value_mask = SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK;
+        if (csv_value & SEQUENCE_LOCKTIME_DISABLE_FLAG) != 0
+            || (csv_value & value_mask) != csv_value
+        {
+            return Err(ScriptCreationError::InvalidCsv(csv_value));
+        }
+
+        Ok(CsvValue(csv_value))
+    }
+
+    /// Whether this timelock is in units of 512 seconds rather than in number of blocks
+    pub fn is_time_based(&self) -> bool {
+        self.0 & SEQUENCE_LOCKTIME_TYPE_FLAG != 0
+    }
+
+    /// The number of blocks, or of 512 seconds intervals for a time-based timelock
+    pub fn value(&self) -> u16 {
+        (self.0 & SEQUENCE_LOCKTIME_MASK) as u16
+    }
+
+    /// The nSequence of an input spending an output locked by this CSV
+    pub fn sequence(&self) -> u32 {
+        self.0
+    }
+}
+
+impl TryFrom<u32> for CsvValue {
+    type Error = ScriptCreationError;
+
+    /// Only accepts timelocks in number of blocks, see [CsvValue::from_bip68] for time-based
+    /// ones.
+    fn try_from(csv_value: u32) -> Result<CsvValue, Self::Error> {
+        let csv = CsvValue::from_bip68(csv_value)?;
+        if csv.is_time_based() {
+            return Err(ScriptCreationError::InvalidCsv(csv_value));
+        }
+
+        Ok(csv)
+    }
+}
+
+impl From<CsvValue> for u32 {
+    fn from(csv_value: CsvValue) -> u32 {
+        csv_value.0
+    }
+}
+
+impl fmt::Display for CsvValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_time_based() {
+            write!(f, "{} seconds", self.value() as u32 * 512)
+        } else {
+            write!(f, "{} blocks", self.value())
+        }
+    }
+}
+
+// The BIP380 descriptor and checksum character sets
+const DESC_INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const DESC_CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn desc_polymod(c: u64, val: u64) -> u64 {
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7_ffff_ffff) << 5) ^ val;
+    if c0 & 1 != 0 {
+        c ^= 0xf5_dee5_1989;
+    }
+    if c0 & 2 != 0 {
+        c ^= 0xa9_fdca_3312;
+    }
+    if c0 & 4 != 0 {
+        c ^= 0x1b_ab10_e32d;
+    }
+    if c0 & 8 != 0 {
+        c ^= 0x37_06b1_677a;
+    }
+    if c0 & 16 != 0 {
+        c ^= 0x64_4d62_6ffd;
+    }
+    c
+}
+
+/// Compute the BIP380 checksum of a descriptor string, which must not contain a checksum.
+pub fn descriptor_checksum(desc: &str) -> Result<String, ScriptCreationError> {
+    let (mut c, mut cls, mut clscount) = (1, 0, 0);
+    for ch in desc.chars() {
+        let pos = DESC_INPUT_CHARSET
+            .find(ch)
+            .ok_or(ScriptCreationError::InvalidDescriptorCharacter(ch))? as u64;
+        c = desc_polymod(c, pos & 31);
+        cls = cls * 3 + (pos >> 5);
+        clscount += 1;
+        if clscount == 3 {
+            c = desc_polymod(c, cls);
+            cls = 0;
+            clscount = 0;
+        }
+    }
+    if clscount > 0 {
+        c = desc_polymod(c, cls);
+    }
+    for _ in 0..8 {
+        c = desc_polymod(c, 0);
+    }
+    c ^= 1;
+
+    Ok((0..8)
+        .map(|j| DESC_CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char)
+        .collect())
+}
+
+/// Verify the BIP380 checksum of a `<descriptor>#<checksum>` string, as exchanged between
+/// participants.
+pub fn verify_descriptor_checksum(desc: &str) -> Result<(), ScriptCreationError> {
+    let mut parts = desc.rsplitn(2, '#');
+    let (checksum, desc) = match (parts.next(), parts.next()) {
+        (Some(checksum), Some(desc)) => (checksum, desc),
+        _ => return Err(ScriptCreationError::MissingChecksum),
+    };
+
+    let expected = descriptor_checksum(desc)?;
+    if checksum != expected {
+        return Err(ScriptCreationError::InvalidChecksum(expected));
+    }
+
+    Ok(())
+}
+
+// Get the checksum of a descriptor out of its string representation
+fn displayed_checksum(desc: &impl fmt::Display) -> String {
+    desc.to_string()
+        .split('#')
+        .nth(1)
+        .expect("Descriptors are always displayed with their checksum")
+        .to_string()
+}
+
+/// A key which can be sorted in a deterministic order, independent of how it was configured.
+pub trait SortableKey {
+    /// The bytes this key is lexicographically sorted by
+    fn sorting_key(&self) -> Vec<u8>;
+}
+
+impl SortableKey for DerivedPublicKey {
+    /// As per BIP67, the compressed serialization of the key.
+    fn sorting_key(&self) -> Vec<u8> {
+        self.key.to_bytes()
+    }
+}
+
+impl SortableKey for PublicKey {
+    /// As per BIP67, the compressed serialization of the key.
+    fn sorting_key(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
+
+impl SortableKey for DescriptorPublicKey {
+    /// The order of the keys derived from xpubs changes with the derivation index, so these are
+    /// sorted by their string representation instead.
+    fn sorting_key(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+/// Sort the keys of a participants set before creating a descriptor, for independently-configured
+/// participants to produce identical descriptors regardless of the order they listed the keys in.
+///
+/// Concrete keys are sorted according to BIP67, the xpubs of generalistic descriptors by their
+/// string representation.
+pub fn sort_keys<K: SortableKey>(keys: &mut [K]) {
+    keys.sort_by_cached_key(|k| k.sorting_key());
+}
+
+// Replace each key of this descriptor by a raw key without deriving it (and therefore without
+// needing a libsecp256k1 context). The resulting descriptor must only be used for size
+// computations, which don't depend on the keys: all compressed public keys have the same size.
+fn raw_keys_descriptor(desc: &Descriptor<DescriptorPublicKey>) -> Descriptor<PublicKey> {
+    desc.translate_pk2_infallible(|xpk| match xpk {
+        DescriptorPublicKey::XPub(xpub) => xpub.xkey.public_key,
+        DescriptorPublicKey::SinglePub(single) => single.key,
+    })
+}
+
+// Check the witness script of a P2WSH descriptor against consensus and standardness limits
+fn check_resource_limits<Pk: MiniscriptKey + ToPublicKey>(
+    desc: &Descriptor<Pk>,
+) -> Result<(), ScriptCreationError> {
+    let ms = match desc {
+        Descriptor::Wsh(ref wsh) => match wsh.as_inner() {
+            WshInner::Ms(ms) => ms,
+            WshInner::SortedMulti(_) => {
+                unreachable!("None of our descriptors is a sorted multi")
+            }
+        },
+        _ => unreachable!("All our descriptors are always P2WSH"),
+    };
+
+    let script_size = ms.script_size();
+    if script_size > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+        return Err(ScriptCreationError::ScriptTooLarge(script_size));
+    }
+
+    if !ms.within_resource_limits() {
+        return Err(ScriptCreationError::TooManyOps);
+    }
+
+    // This includes the witness script itself
+    let witness_elements = ms.max_satisfaction_witness_elements()?;
+    if witness_elements > MAX_STANDARD_P2WSH_STACK_ITEMS + 1 {
+        return Err(ScriptCreationError::TooManyWitnessElements(
+            witness_elements - 1,
+        ));
+    }
+
+    Ok(())
+}
+
+// Check the Miniscript of a P2WSH descriptor is safe to presign transactions for: all its
+// spending paths require a signature, the satisfaction can't be malleated by a third party, and
+// no spending path mixes height-based and time-based timelocks.
+fn check_miniscript_analysis<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Result<(), ScriptCreationError> {
+    let ms = wsh_miniscript(desc).expect("All our descriptors are always P2WSH Miniscripts");
+
+    if !ms.requires_sig() {
+        return Err(ScriptCreationError::SiglessBranch);
+    }
+    if !ms.is_non_malleable() {
+        return Err(ScriptCreationError::MalleableScript);
+    }
+    if ms.has_mixed_timelocks() {
+        return Err(ScriptCreationError::MixedTimelocks);
+    }
+
+    Ok(())
+}
+
+// Infer the derivation index of a derived descriptor out of the derivation paths of its keys. All
+// the xpubs of our descriptors are derived at the same index, and the raw keys are given a `0`
+// index. Therefore the highest unhardened index is the one we are looking for.
+fn infer_derivation_index(desc: &Descriptor<DerivedPublicKey>) -> DerivationIndex {
+    let ms = match wsh_miniscript(desc) {
+        Some(ms) => ms,
+        None => return DerivationIndex(0),
+    };
+
+    // For DerivedPublicKey, Pk::Hash == Self.
+    ms.iter_pk_pkh()
+        .filter_map(|pkpkh| {
+            let key = match pkpkh {
+                PkPkh::PlainPubkey(pk) => pk,
+                PkPkh::HashedPubkey(pkh) => pkh,
+            };
+            match key.origin.1.as_ref().last() {
+                Some(bip32::ChildNumber::Normal { index }) => Some(*index),
+                _ => None,
+            }
+        })
+        .max()
+        .map(DerivationIndex)
+        .unwrap_or_default()
+}
+
+// Check a set of public keys doesn't contain any duplicate
+fn check_duplicate_keys(keys: impl Iterator<Item = PublicKey>) -> Result<(), ScriptCreationError> {
+    let mut seen = HashSet::new();
+    for key in keys {
+        if !seen.insert(key) {
+            return Err(ScriptCreationError::DuplicateKey);
+        }
+    }
+
+    Ok(())
+}
+
 // These are useful to create TxOuts out of the right Script descriptor
 
 macro_rules! impl_descriptor_newtype {
-    ($struct_name:ident, $derived_struct_name:ident, $doc_comment:meta, $der_doc_comment:meta) => {
+    ($struct_name:ident, $derived_struct_name:ident, $roles_fn:ident, $doc_comment:meta, $der_doc_comment:meta) => {
         #[$doc_comment]
         #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
         pub struct $struct_name(Descriptor<DescriptorPublicKey>);
 
         #[$der_doc_comment]
-        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-        pub struct $derived_struct_name(Descriptor<DerivedPublicKey>);
+        #[derive(Debug, Clone)]
+        pub struct $derived_struct_name(Descriptor<DerivedPublicKey>, DerivationIndex);
 
         impl $struct_name {
             pub fn inner(&self) -> &Descriptor<DescriptorPublicKey> {
@@ -170,31 +544,123 @@ macro_rules! impl_descriptor_newtype {
                 self.0
             }
 
-            /// Derives all wildcard keys in the descriptor using the supplied `child_number`
+            /// Derives all wildcard keys in the descriptor using the supplied `index`
             pub fn derive<C: secp256k1::Verification>(
                 &self,
-                child_number: bip32::ChildNumber,
+                index: DerivationIndex,
                 secp: &secp256k1::Secp256k1<C>,
             ) -> $derived_struct_name {
-                $derived_struct_name(
+                let child_number = bip32::ChildNumber::from(index);
+                let desc = self
+                    .0
+                    .derive(child_number.into())
+                    .translate_pk2(|xpk| {
+                        xpk.derive_public_key(secp).map(|key| {
+                            // FIXME: rust-miniscript will panic if we call
+                            // xpk.master_fingerprint() on a key without origin
+                            let origin = match xpk {
+                                // The wildcard was replaced by the child number at the end
+                                // of the derivation path. Record the full path from the
+                                // master xpub for signing devices to recognize the key.
+                                DescriptorPublicKey::XPub(xpub) => {
+                                    let full_path = xpub
+                                        .origin
+                                        .as_ref()
+                                        .map(|(_, path)| path.extend(&xpub.derivation_path))
+                                        .unwrap_or_else(|| xpub.derivation_path.clone());
+                                    (xpk.master_fingerprint(), full_path)
+                                }
+                                _ => (
+                                    bip32::Fingerprint::from(&[0, 0, 0, 0][..]),
+                                    bip32::DerivationPath::from(vec![0.into()]),
+                                ),
+                            };
+
+                            DerivedPublicKey { key, origin }
+                        })
+                    })
+                    .expect("All pubkeys are derived, no wildcard.");
+
+                $derived_struct_name(desc, index)
+            }
+
+            /// Derives all wildcard keys in the descriptor at each index from `start` (included) to
+            /// `end` (excluded).
+            ///
+            /// This is equivalent to calling [Self::derive] for each index, but the non-wildcard
+            /// part of the derivation path of each xpub is only derived once for the whole range.
+            pub fn derive_range<C: secp256k1::Verification>(
+                &self,
+                start: DerivationIndex,
+                end: DerivationIndex,
+                secp: &secp256k1::Secp256k1<C>,
+            ) -> Vec<$derived_struct_name> {
+                // Replace each xpub by its child at the end of its derivation path, keeping the
+                // same master fingerprint.
+                let shortcut = $struct_name(
                     self.0
-                        .derive(child_number.into())
-                        .translate_pk2(|xpk| {
-                            xpk.derive_public_key(secp).map(|key| {
-                                // FIXME: rust-miniscript will panic if we call
-                                // xpk.master_fingerprint() on a key without origin
-                                let origin = match xpk {
-                                    DescriptorPublicKey::XPub(..) => {
-                                        (xpk.master_fingerprint(), child_number)
-                                    }
-                                    _ => (bip32::Fingerprint::from(&[0, 0, 0, 0][..]), 0.into()),
-                                };
-
-                                DerivedPublicKey { key, origin }
-                            })
+                        .translate_pk2(|xpk| match xpk {
+                            DescriptorPublicKey::XPub(xpub)
+                                if !xpub.derivation_path.as_ref().is_empty() =>
+                            {
+                                let master_fingerprint = xpk.master_fingerprint();
+                                let origin_path = xpub
+                                    .origin
+                                    .as_ref()
+                                    .map(|(_, path)| path.clone())
+                                    .unwrap_or_else(|| bip32::DerivationPath::from(vec![]));
+                                xpub.xkey
+                                    .derive_pub(secp, &xpub.derivation_path)
+                                    .map(|xkey| {
+                                        DescriptorPublicKey::XPub(DescriptorXKey {
+                                            origin: Some((
+                                                master_fingerprint,
+                                                origin_path.extend(&xpub.derivation_path),
+                                            )),
+                                            xkey,
+                                            derivation_path: bip32::DerivationPath::from(vec![]),
+                                            wildcard: xpub.wildcard,
+                                        })
+                                    })
+                            }
+                            _ => Ok(xpk.clone()),
                         })
-                        .expect("All pubkeys are derived, no wildcard."),
-                )
+                        .expect("Xpubs in descriptors can't have hardened derivation steps"),
+                );
+
+                (start.0..end.0)
+                    .map(|index| shortcut.derive(DerivationIndex(index), secp))
+                    .collect()
+            }
+
+            /// Get the P2WSH address of this descriptor derived at `index`, on the given network
+            pub fn address<C: secp256k1::Verification>(
+                &self,
+                index: DerivationIndex,
+                network: Network,
+                secp: &secp256k1::Secp256k1<C>,
+            ) -> Address {
+                self.derive(index, secp).address(network)
+            }
+
+            /// Get the BIP380 checksum of this descriptor, which is always part of its string
+            /// representation.
+            pub fn checksum(&self) -> String {
+                displayed_checksum(&self.0)
+            }
+
+            /// Get the different ways an output of this descriptor can be spent: which
+            /// participants must sign, and after which relative timelock (if any).
+            pub fn spending_paths(&self) -> Vec<SpendingPath<DescriptorPublicKey>> {
+                descriptor_spending_paths(&self.0, &$roles_fn(&self.0))
+            }
+
+            /// Get the maximum size, in weight units, a satisfaction for an output of this
+            /// descriptor would cost. It doesn't depend on the derivation index.
+            pub fn max_sat_weight(&self) -> usize {
+                raw_keys_descriptor(&self.0)
+                    .max_satisfaction_weight()
+                    .expect("It's a sane Script, derived from a Miniscript")
             }
 
             /// Get all the xpubs used in this descriptor.
@@ -217,9 +683,73 @@ macro_rules! impl_descriptor_newtype {
                     })
                     .collect()
             }
+
+            /// Check all the keys of this descriptor are wildcard xpubs with origin information.
+            ///
+            /// This is stricter than the constructors, which accept raw keys (for instance for
+            /// the Cosigning Servers) and xpubs without origin. Bare keys can't be derived per
+            /// vault, and keys without origin can't be recognized by signing devices or
+            /// watchtowers.
+            pub fn strict_check(&self) -> Result<(), ScriptCreationError> {
+                let xpubs = self.xpubs();
+                check_deriveable(xpubs.iter())?;
+
+                for xpub in xpubs.iter() {
+                    if let DescriptorPublicKey::XPub(xpub) = xpub {
+                        if xpub.origin.is_none() {
+                            return Err(ScriptCreationError::MissingKeyOrigin);
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+
+            /// Check this descriptor doesn't use the same public key twice, even under different
+            /// derivation paths, that it is safe and non-malleable, and that its witness script
+            /// and satisfaction are within the consensus and standardness limits.
+            pub fn sanity_check(&self) -> Result<(), ScriptCreationError> {
+                check_duplicate_keys(self.xpubs().into_iter().map(|xpk| match xpk {
+                    DescriptorPublicKey::XPub(xpub) => xpub.xkey.public_key,
+                    DescriptorPublicKey::SinglePub(single) => single.key,
+                }))?;
+                check_miniscript_analysis(&self.0)?;
+
+                // The limits can only be checked on raw keys, but they don't depend on which.
+                check_resource_limits(&raw_keys_descriptor(&self.0))
+            }
+        }
+
+        // The derivation index is only informational, two descriptors with the same Script are
+        // the same descriptor.
+        impl PartialEq for $derived_struct_name {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+
+        impl Eq for $derived_struct_name {}
+
+        impl PartialOrd for $derived_struct_name {
+            fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $derived_struct_name {
+            fn cmp(&self, other: &Self) -> cmp::Ordering {
+                self.0.cmp(&other.0)
+            }
         }
 
         impl $derived_struct_name {
+            // For a descriptor which wasn't derived from a generalistic one, we can only infer the
+            // index from the derivation paths of its keys.
+            fn from_inner(desc: Descriptor<DerivedPublicKey>) -> $derived_struct_name {
+                let index = infer_derivation_index(&desc);
+                $derived_struct_name(desc, index)
+            }
+
             pub fn inner(&self) -> &Descriptor<DerivedPublicKey> {
                 &self.0
             }
@@ -228,6 +758,22 @@ macro_rules! impl_descriptor_newtype {
                 self.0
             }
 
+            /// Get the P2WSH address of this descriptor on the given network
+            pub fn address(&self, network: Network) -> Address {
+                self.0
+                    .address(network)
+                    .expect("All our descriptors are always P2WSH")
+            }
+
+            /// Get the derivation index this descriptor was derived at.
+            ///
+            /// For a descriptor created from derived keys or parsed from a string, rather than
+            /// derived from a generalistic descriptor, this is the highest unhardened index the
+            /// derivation path of one of its keys ends with (or `0`).
+            pub fn derivation_index(&self) -> DerivationIndex {
+                self.1
+            }
+
             /// Get all the keys and key source used in this derived descriptor
             pub fn keys(&self) -> Vec<DerivedPublicKey> {
                 let ms = match self.0 {
@@ -248,6 +794,35 @@ macro_rules! impl_descriptor_newtype {
                     })
                     .collect()
             }
+
+            /// Get the BIP380 checksum of this descriptor, which is always part of its string
+            /// representation.
+            pub fn checksum(&self) -> String {
+                displayed_checksum(&self.0)
+            }
+
+            /// Get the different ways this output can be spent: which participants must sign,
+            /// and after which relative timelock (if any).
+            pub fn spending_paths(&self) -> Vec<SpendingPath<DerivedPublicKey>> {
+                descriptor_spending_paths(&self.0, &$roles_fn(&self.0))
+            }
+
+            /// Get the maximum size, in weight units, a satisfaction for an output of this
+            /// descriptor would cost.
+            pub fn max_sat_weight(&self) -> usize {
+                self.0
+                    .max_satisfaction_weight()
+                    .expect("It's a sane Script, derived from a Miniscript")
+            }
+
+            /// Check this descriptor doesn't use the same public key twice, that it is safe and
+            /// non-malleable, and that its witness script and satisfaction are within the
+            /// consensus and standardness limits.
+            pub fn sanity_check(&self) -> Result<(), ScriptCreationError> {
+                check_duplicate_keys(self.keys().into_iter().map(|k| k.key))?;
+                check_miniscript_analysis(&self.0)?;
+                check_resource_limits(&self.0)
+            }
         }
     };
 }
@@ -255,6 +830,7 @@ macro_rules! impl_descriptor_newtype {
 impl_descriptor_newtype!(
     DepositDescriptor,
     DerivedDepositDescriptor,
+    deposit_descriptor_roles,
     doc = "A **generalistic** (with wildcard xpubs) deposit Miniscript descriptor.",
     doc = "A **concrete** (with raw public keys) deposit Miniscript descriptor. "
 );
@@ -262,6 +838,7 @@ impl_descriptor_newtype!(
 impl_descriptor_newtype!(
     UnvaultDescriptor,
     DerivedUnvaultDescriptor,
+    unvault_descriptor_roles,
     doc = "A **generalistic** (with wildcard xpubs) Unvault miniscript descriptor.",
     doc = "A **concrete** (with raw public keys) Unvault miniscript descriptor."
 );
@@ -269,13 +846,28 @@ impl_descriptor_newtype!(
 impl_descriptor_newtype!(
     CpfpDescriptor,
     DerivedCpfpDescriptor,
+    cpfp_descriptor_roles,
     doc = "A **generalistic** (with wildcard xpubs) CPFP miniscript descriptor.",
     doc = "A **concrete** (with raw public keys) CPFP miniscript descriptor."
 );
 
 macro_rules! deposit_desc_checks {
     ($stakeholders:ident) => {
-        if $stakeholders.len() < 2 || $stakeholders.len() > MAX_STAKEHOLDERS {
+        if $stakeholders.len() > MAX_STAKEHOLDERS {
+            return Err(ScriptCreationError::TooManyStakeholders(
+                $stakeholders.len(),
+            ));
+        }
+
+        if $stakeholders.len() < 2 {
+            return Err(ScriptCreationError::BadParameters);
+        }
+    };
+}
+
+macro_rules! deposit_threshold_checks {
+    ($stakeholders:ident, $threshold:ident) => {
+        if $threshold == 0 || $threshold > $stakeholders.len() {
             return Err(ScriptCreationError::BadParameters);
         }
     };
@@ -283,12 +875,16 @@ macro_rules! deposit_desc_checks {
 
 macro_rules! deposit_desc {
     ($stakeholders:ident) => {{
+        let threshold = $stakeholders.len();
+        deposit_desc!($stakeholders, threshold)
+    }};
+    ($stakeholders:ident, $threshold:ident) => {{
         let pubkeys = $stakeholders
             .into_iter()
             .map(Policy::Key)
             .collect::<Vec<Policy<_>>>();
 
-        let policy = Policy::Threshold(pubkeys.len(), pubkeys);
+        let policy = Policy::Threshold($threshold, pubkeys);
 
         // This handles the non-safe or malleable cases.
         let ms = policy.compile::<Segwitv0>()?;
@@ -296,8 +892,42 @@ macro_rules! deposit_desc {
     }};
 }
 
+macro_rules! deposit_recovery_checks {
+    ($recovery_keys:ident, $recovery_threshold:ident) => {
+        if $recovery_keys.is_empty() || $recovery_keys.len() > MAX_STAKEHOLDERS {
+            return Err(ScriptCreationError::BadParameters);
+        }
+
+        if $recovery_threshold == 0 || $recovery_threshold > $recovery_keys.len() {
+            return Err(ScriptCreationError::BadParameters);
+        }
+    };
+}
+
+macro_rules! deposit_recovery_desc {
+    ($stakeholders:ident, $recovery_keys:ident, $recovery_threshold:ident, $recovery_csv:ident) => {{
+        let mut pubkeys = $stakeholders
+            .into_iter()
+            .map(Policy::Key)
+            .collect::<Vec<Policy<_>>>();
+        let stakeholders_thres = Policy::Threshold(pubkeys.len(), pubkeys);
+
+        pubkeys = $recovery_keys
+            .into_iter()
+            .map(Policy::Key)
+            .collect::<Vec<Policy<_>>>();
+        let recovery_thres = Policy::Threshold($recovery_threshold, pubkeys);
+        let recovery_path = Policy::And(vec![recovery_thres, Policy::Older($recovery_csv.into())]);
+
+        // The recovery path is only expected to be used in case of disaster
+        let policy = Policy::Or(vec![(9, stakeholders_thres), (1, recovery_path)]);
+        let ms = policy.compile::<Segwitv0>()?;
+        Descriptor::new_wsh(ms)?
+    }};
+}
+
 macro_rules! unvault_desc_checks {
-    ($stakeholders:ident,$managers:ident, $managers_threshold:ident, $cosigners:ident, $csv_value:ident) => {
+    ($stakeholders:ident,$managers:ident, $managers_threshold:ident, $cosigners:ident) => {
         if $stakeholders.is_empty()
             || $managers.is_empty()
             || !$cosigners.is_empty() && $cosigners.len() != $stakeholders.len()
@@ -309,17 +939,24 @@ macro_rules! unvault_desc_checks {
             return Err(ScriptCreationError::BadParameters);
         }
 
-        if $stakeholders.len() > MAX_STAKEHOLDERS || $managers.len() > MAX_MANAGERS {
-            return Err(ScriptCreationError::BadParameters);
+        if $stakeholders.len() > MAX_STAKEHOLDERS {
+            return Err(ScriptCreationError::TooManyStakeholders(
+                $stakeholders.len(),
+            ));
+        }
+        if $managers.len() > MAX_MANAGERS {
+            return Err(ScriptCreationError::TooManyManagers($managers.len()));
         }
+        if $cosigners.len() > MAX_COSIGNERS {
+            return Err(ScriptCreationError::TooManyCosigners($cosigners.len()));
+        }
+    };
+}
 
-        // We require the locktime to:
-        //  - not be disabled
-        //  - be in number of blocks
-        //  - be 'clean' / minimal, ie all bits without consensus meaning should be 0
-        if ($csv_value & SEQUENCE_LOCKTIME_DISABLE_FLAG) != 0
-            || ($csv_value & SEQUENCE_LOCKTIME_TYPE_FLAG) != 0
-            || ($csv_value & SEQUENCE_LOCKTIME_MASK) != $csv_value
+macro_rules! cosigners_threshold_checks {
+    ($cosigners:ident, $cosigners_threshold:ident) => {
+        if $cosigners_threshold > $cosigners.len()
+            || !$cosigners.is_empty() && $cosigners_threshold == 0
         {
             return Err(ScriptCreationError::BadParameters);
         }
@@ -328,6 +965,17 @@ macro_rules! unvault_desc_checks {
 
 macro_rules! unvault_desc {
     ($stakeholders:ident, $managers:ident, $managers_threshold:ident, $cosigners:ident, $csv_value:ident) => {{
+        let cosigners_threshold = $cosigners.len();
+        unvault_desc!(
+            $stakeholders,
+            $managers,
+            $managers_threshold,
+            $cosigners,
+            cosigners_threshold,
+            $csv_value
+        )
+    }};
+    ($stakeholders:ident, $managers:ident, $managers_threshold:ident, $cosigners:ident, $cosigners_threshold:ident, $csv_value:ident) => {{
         let mut pubkeys = $managers
             .into_iter()
             .map(Policy::Key)
@@ -341,14 +989,15 @@ macro_rules! unvault_desc {
         let stakeholders_thres = Policy::Threshold(pubkeys.len(), pubkeys);
 
         let managers_path = if $cosigners.is_empty() {
-            Policy::And(vec![spenders_thres, Policy::Older($csv_value)])
+            Policy::And(vec![spenders_thres, Policy::Older($csv_value.into())])
         } else {
             pubkeys = $cosigners
                 .into_iter()
                 .map(Policy::Key)
                 .collect::<Vec<Policy<_>>>();
-            let cosigners_thres = Policy::Threshold(pubkeys.len(), pubkeys);
-            let cosigners_and_csv = Policy::And(vec![cosigners_thres, Policy::Older($csv_value)]);
+            let cosigners_thres = Policy::Threshold($cosigners_threshold, pubkeys);
+            let cosigners_and_csv =
+                Policy::And(vec![cosigners_thres, Policy::Older($csv_value.into())]);
 
             Policy::And(vec![spenders_thres, cosigners_and_csv])
         };
@@ -359,18 +1008,87 @@ macro_rules! unvault_desc {
     }};
 }
 
-// Check all xpubs contain a wildcard
-fn check_deriveable<'a>(
-    keys: impl Iterator<Item = &'a DescriptorPublicKey>,
-) -> Result<(), ScriptCreationError> {
-    for key in keys {
-        match key {
-            DescriptorPublicKey::XPub(xpub) => {
-                if matches!(xpub.wildcard, Wildcard::None) {
-                    return Err(ScriptCreationError::NonWildcardKeys);
-                }
-            }
-            DescriptorPublicKey::SinglePub(_) => {
+// Parse and check the (policy, CSV) spending tiers of an Unvault descriptor, sorted by increasing
+// CSV.
+macro_rules! unvault_spend_tiers {
+    ($spend_tiers:ident, $key_type:ty) => {{
+        if $spend_tiers.is_empty() {
+            return Err(ScriptCreationError::BadParameters);
+        }
+
+        let mut tiers = Vec::with_capacity($spend_tiers.len());
+        for (spend_policy, csv_value) in $spend_tiers.iter() {
+            let csv_value = *csv_value;
+            let spend_policy = Policy::<$key_type>::from_str(spend_policy)?;
+            check_spend_policy(&spend_policy)?;
+            tiers.push((spend_policy, csv_value));
+        }
+
+        // Two tiers with the same timelock should be a single policy, and the tiers can't be
+        // ordered if their timelocks are not in the same unit.
+        tiers.sort_by_key(|(_, csv_value)| *csv_value);
+        if tiers
+            .windows(2)
+            .any(|w| w[0].1 == w[1].1 || w[0].1.is_time_based() != w[1].1.is_time_based())
+        {
+            return Err(ScriptCreationError::BadParameters);
+        }
+
+        tiers
+    }};
+}
+
+macro_rules! unvault_custom_desc {
+    ($stakeholders:ident, $spend_tiers:ident) => {{
+        let pubkeys = $stakeholders
+            .into_iter()
+            .map(Policy::Key)
+            .collect::<Vec<Policy<_>>>();
+        let stakeholders_thres = Policy::Threshold(pubkeys.len(), pubkeys);
+
+        // The tiers are sorted by increasing CSV, the shortest one is expected to be the most
+        // used.
+        let managers_path = $spend_tiers
+            .into_iter()
+            .rev()
+            .fold(None, |longer_tiers, (spend_policy, csv_value)| {
+                let tier = Policy::And(vec![spend_policy, Policy::Older(csv_value.into())]);
+                Some(match longer_tiers {
+                    Some(longer_tiers) => Policy::Or(vec![(9, tier), (1, longer_tiers)]),
+                    None => tier,
+                })
+            })
+            .expect("There is always at least one spending tier");
+
+        let policy = Policy::Or(vec![(1, stakeholders_thres), (9, managers_path)]);
+        let ms = policy.compile::<Segwitv0>()?;
+        Descriptor::new_wsh(ms)?
+    }};
+}
+
+// Check a custom spending policy for the Unvault descriptor doesn't contain any timelock
+// (the CSV is added by us), and uses at least a key.
+fn check_spend_policy<Pk: MiniscriptKey>(policy: &Policy<Pk>) -> Result<(), ScriptCreationError> {
+    let semantic = policy.lift()?;
+    if contains_timelock(&semantic) || semantic.n_keys() == 0 {
+        return Err(ScriptCreationError::BadParameters);
+    }
+
+    Ok(())
+}
+
+// Check all xpubs contain a wildcard
+fn check_deriveable<'a>(
+    keys: impl Iterator<Item = &'a DescriptorPublicKey>,
+) -> Result<(), ScriptCreationError> {
+    for key in keys {
+        match key {
+            DescriptorPublicKey::XPub(xpub) => {
+                if matches!(xpub.wildcard, Wildcard::None) {
+                    return Err(ScriptCreationError::NonWildcardKeys);
+                }
+            }
+            DescriptorPublicKey::SinglePub(_) => {
                 return Err(ScriptCreationError::NonWildcardKeys);
             }
         }
@@ -387,7 +1105,7 @@ impl DepositDescriptor {
     /// # Examples
     /// ```rust
     /// use revault_tx::{scripts, miniscript::{bitcoin::{self, secp256k1, util::bip32}, DescriptorPublicKey, DescriptorTrait}};
-    /// use std::str::FromStr;
+    /// use std::{convert::TryFrom, str::FromStr};
     ///
     /// let first_stakeholder = DescriptorPublicKey::from_str("xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*").unwrap();
     /// let second_stakeholder = DescriptorPublicKey::from_str("xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*").unwrap();
@@ -400,7 +1118,7 @@ impl DepositDescriptor {
     /// assert_eq!(deposit_descriptor, scripts::DepositDescriptor::from_str(&desc_str).unwrap());
     ///
     /// let secp = secp256k1::Secp256k1::verification_only();
-    /// println!("Tenth child witness script: {}", deposit_descriptor.derive(bip32::ChildNumber::from(10), &secp).inner().explicit_script());
+    /// println!("Tenth child witness script: {}", deposit_descriptor.derive(scripts::DerivationIndex::try_from(10).unwrap(), &secp).inner().explicit_script());
     /// ```
     ///
     /// # Errors
@@ -414,7 +1132,211 @@ impl DepositDescriptor {
         deposit_desc_checks!(stakeholders);
         check_deriveable(stakeholders.iter())?;
 
-        Ok(DepositDescriptor(deposit_desc!(stakeholders)))
+        let desc = DepositDescriptor(deposit_desc!(stakeholders));
+        desc.sanity_check()?;
+
+        Ok(desc)
+    }
+
+    /// Get the xpub miniscript descriptor for deposit outputs, with a `threshold`-of-N policy
+    /// between the stakeholders instead of the default N-of-N.
+    ///
+    /// **WARNING**: the Revault security model assumes that **all** the stakeholders must sign
+    /// to move the deposited funds out of the Unvault / Cancel / Emergency flow. With a
+    /// `threshold` lower than the number of stakeholders, a subset of them can collude to
+    /// spend a deposit without ever going through the Unvault. Only use this if you know what
+    /// you are doing.
+    ///
+    /// # Errors
+    /// - If the given `DescriptorPublickKey`s are not wildcards (can be derived from).
+    /// - If the given vector contains less than 2 public keys.
+    /// - If the `threshold` is `0` or larger than the number of stakeholders.
+    /// - If the policy compilation to miniscript failed, which should not happen (tm) and would be a
+    ///   bug.
+    pub fn with_threshold(
+        stakeholders: Vec<DescriptorPublicKey>,
+        threshold: usize,
+    ) -> Result<DepositDescriptor, ScriptCreationError> {
+        deposit_desc_checks!(stakeholders);
+        deposit_threshold_checks!(stakeholders, threshold);
+        check_deriveable(stakeholders.iter())?;
+
+        let desc = DepositDescriptor(deposit_desc!(stakeholders, threshold));
+        desc.sanity_check()?;
+
+        Ok(desc)
+    }
+
+    /// Get the xpub miniscript descriptor for deposit outputs, with a recovery path allowing
+    /// `recovery_threshold` of the `recovery_keys` to spend the deposit after `recovery_csv`
+    /// blocks.
+    ///
+    /// This protects the deposited funds against the permanent loss of a stakeholder's key. The
+    /// recovery keys may be a subset of the stakeholders' keys under a different derivation
+    /// path, or a dedicated set of keys.
+    ///
+    /// **WARNING**: the Unvault and Cancel transactions must always be broadcast before
+    /// `recovery_csv` blocks after the deposit confirmed, otherwise the recovery keys can
+    /// spend the deposit. The relative timelock should therefore be much larger than the
+    /// expected lifetime of a vault.
+    ///
+    /// # Errors
+    /// - If the given `DescriptorPublickKey`s are not wildcards (can be derived from).
+    /// - If there are less than 2 stakeholders, or no recovery key.
+    /// - If the `recovery_threshold` is `0` or larger than the number of recovery keys.
+    /// - If the policy compilation to miniscript failed (eg if a key is used twice)
+    pub fn with_recovery(
+        stakeholders: Vec<DescriptorPublicKey>,
+        recovery_keys: Vec<DescriptorPublicKey>,
+        recovery_threshold: usize,
+        recovery_csv: CsvValue,
+    ) -> Result<DepositDescriptor, ScriptCreationError> {
+        deposit_desc_checks!(stakeholders);
+        deposit_recovery_checks!(recovery_keys, recovery_threshold);
+        check_deriveable(stakeholders.iter().chain(recovery_keys.iter()))?;
+
+        let desc = DepositDescriptor(deposit_recovery_desc!(
+            stakeholders,
+            recovery_keys,
+            recovery_threshold,
+            recovery_csv
+        ));
+        desc.sanity_check()?;
+
+        Ok(desc)
+    }
+
+    /// Get the xpub miniscript descriptor for deposit outputs during a rotation of the
+    /// stakeholders' keys.
+    ///
+    /// The deposit can be spent by all the `new_stakeholders`, or by all the
+    /// `old_stakeholders` after `rotation_csv` blocks. This allows to rotate keys without
+    /// breaking the in-flight vaults, which can be moved by the old setup if a new key is lost
+    /// during the transition.
+    ///
+    /// # Errors
+    /// - Same as [DepositDescriptor::with_recovery].
+    pub fn rotation(
+        new_stakeholders: Vec<DescriptorPublicKey>,
+        old_stakeholders: Vec<DescriptorPublicKey>,
+        rotation_csv: CsvValue,
+    ) -> Result<DepositDescriptor, ScriptCreationError> {
+        let threshold = old_stakeholders.len();
+        DepositDescriptor::with_recovery(
+            new_stakeholders,
+            old_stakeholders,
+            threshold,
+            rotation_csv,
+        )
+    }
+
+    /// Get the scriptPubKeys of the deposit outputs at each index from `start` (included) to
+    /// `end` (excluded), for instance to watch the chain for new deposits.
+    pub fn script_pubkeys<C: secp256k1::Verification>(
+        &self,
+        start: DerivationIndex,
+        end: DerivationIndex,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Vec<Script> {
+        self.derive_range(start, end, secp)
+            .into_iter()
+            .map(|desc| desc.inner().script_pubkey())
+            .collect()
+    }
+}
+
+/// Find which epoch a deposit `script_pubkey` belongs to, given the deposit descriptors of all
+/// the successive setups (epochs) and the derivation index of the deposit.
+///
+/// Returns the position of the matching descriptor in `epochs`, if any.
+pub fn deposit_epoch<C: secp256k1::Verification>(
+    epochs: &[DepositDescriptor],
+    script_pubkey: &Script,
+    index: DerivationIndex,
+    secp: &secp256k1::Secp256k1<C>,
+) -> Option<usize> {
+    epochs
+        .iter()
+        .position(|desc| &desc.derive(index, secp).inner().script_pubkey() == script_pubkey)
+}
+
+// The first hardened derivation index, which we never derive at
+const HARDENED_INDEX: u32 = 1 << 31;
+
+/// An iterator over the deposit and Unvault scriptPubKeys of a deployment at each derivation
+/// index, up to `gap_limit` indexes past the last one a deposit was found at.
+///
+/// This is for light watchtowers, which don't run a descriptor wallet, to know which
+/// scriptPubKeys to watch. As the iterator is borrowed while iterating, use it in a `while let`
+/// loop to [ScriptPubKeysIter::mark_used] the indexes deposits are found at, which extends the
+/// watched range.
+pub struct ScriptPubKeysIter<'a, C: secp256k1::Verification> {
+    deposit_descriptor: &'a DepositDescriptor,
+    unvault_descriptor: &'a UnvaultDescriptor,
+    secp: &'a secp256k1::Secp256k1<C>,
+    start: u32,
+    next_index: u32,
+    last_used: Option<u32>,
+    gap_limit: u32,
+}
+
+impl<'a, C: secp256k1::Verification> ScriptPubKeysIter<'a, C> {
+    /// Iterate over the scriptPubKeys from derivation index `start`, up to `gap_limit` indexes
+    /// past the last one marked as used (or past `start` if none is).
+    pub fn new(
+        deposit_descriptor: &'a DepositDescriptor,
+        unvault_descriptor: &'a UnvaultDescriptor,
+        start: DerivationIndex,
+        gap_limit: u32,
+        secp: &'a secp256k1::Secp256k1<C>,
+    ) -> ScriptPubKeysIter<'a, C> {
+        ScriptPubKeysIter {
+            deposit_descriptor,
+            unvault_descriptor,
+            secp,
+            start: start.0,
+            next_index: start.0,
+            last_used: None,
+            gap_limit,
+        }
+    }
+
+    /// Mark a deposit was found at this derivation index. The watched range is extended to
+    /// `gap_limit` indexes past it, if it was the last used index.
+    pub fn mark_used(&mut self, index: DerivationIndex) {
+        if self.last_used.map(|last| index.0 > last).unwrap_or(true) {
+            self.last_used = Some(index.0);
+        }
+    }
+
+    // The first derivation index past the watched range
+    fn end(&self) -> u32 {
+        let first_unused = self.last_used.map(|last| last + 1).unwrap_or(self.start);
+        cmp::min(first_unused.saturating_add(self.gap_limit), HARDENED_INDEX)
+    }
+}
+
+impl<'a, C: secp256k1::Verification> Iterator for ScriptPubKeysIter<'a, C> {
+    type Item = (DerivationIndex, Script, Script);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_index >= self.end() {
+            return None;
+        }
+
+        let index = DerivationIndex(self.next_index);
+        self.next_index += 1;
+        Some((
+            index,
+            self.deposit_descriptor
+                .derive(index, self.secp)
+                .inner()
+                .script_pubkey(),
+            self.unvault_descriptor
+                .derive(index, self.secp)
+                .inner()
+                .script_pubkey(),
+        ))
     }
 }
 
@@ -434,6 +1356,8 @@ impl FromStr for DepositDescriptor {
             return Err(ScriptCreationError::NonWildcardKeys);
         }
 
+        check_deposit_template(&desc)?;
+
         Ok(DepositDescriptor(desc))
     }
 }
@@ -468,7 +1392,64 @@ impl DerivedDepositDescriptor {
     ) -> Result<DerivedDepositDescriptor, ScriptCreationError> {
         deposit_desc_checks!(stakeholders);
 
-        Ok(DerivedDepositDescriptor(deposit_desc!(stakeholders)))
+        let desc = DerivedDepositDescriptor::from_inner(deposit_desc!(stakeholders));
+        desc.sanity_check()?;
+
+        Ok(desc)
+    }
+
+    /// Get the derived miniscript descriptor for deposit outputs, with a `threshold`-of-N
+    /// policy between the stakeholders instead of the default N-of-N.
+    ///
+    /// **WARNING**: see [DepositDescriptor::with_threshold], a deposit that can be spent by a
+    /// subset of the stakeholders weakens the Revault security model.
+    ///
+    /// # Errors
+    /// - If the given vector contains less than 2 public keys.
+    /// - If the `threshold` is `0` or larger than the number of stakeholders.
+    /// - If the policy compilation to miniscript failed, which should not happen (tm) and would be a
+    ///   bug.
+    pub fn with_threshold(
+        stakeholders: Vec<DerivedPublicKey>,
+        threshold: usize,
+    ) -> Result<DerivedDepositDescriptor, ScriptCreationError> {
+        deposit_desc_checks!(stakeholders);
+        deposit_threshold_checks!(stakeholders, threshold);
+
+        let desc = DerivedDepositDescriptor::from_inner(deposit_desc!(stakeholders, threshold));
+        desc.sanity_check()?;
+
+        Ok(desc)
+    }
+
+    /// Get the derived miniscript descriptor for deposit outputs, with a recovery path
+    /// allowing `recovery_threshold` of the `recovery_keys` to spend the deposit after
+    /// `recovery_csv` blocks.
+    ///
+    /// **WARNING**: see [DepositDescriptor::with_recovery].
+    ///
+    /// # Errors
+    /// - If there are less than 2 stakeholders, or no recovery key.
+    /// - If the `recovery_threshold` is `0` or larger than the number of recovery keys.
+    /// - If the policy compilation to miniscript failed (eg if a key is used twice)
+    pub fn with_recovery(
+        stakeholders: Vec<DerivedPublicKey>,
+        recovery_keys: Vec<DerivedPublicKey>,
+        recovery_threshold: usize,
+        recovery_csv: CsvValue,
+    ) -> Result<DerivedDepositDescriptor, ScriptCreationError> {
+        deposit_desc_checks!(stakeholders);
+        deposit_recovery_checks!(recovery_keys, recovery_threshold);
+
+        let desc = DerivedDepositDescriptor::from_inner(deposit_recovery_desc!(
+            stakeholders,
+            recovery_keys,
+            recovery_threshold,
+            recovery_csv
+        ));
+        desc.sanity_check()?;
+
+        Ok(desc)
     }
 }
 
@@ -484,11 +1465,14 @@ impl FromStr for DerivedDepositDescriptor {
     fn from_str(s: &str) -> Result<DerivedDepositDescriptor, Self::Err> {
         let desc: Descriptor<DerivedPublicKey> = FromStr::from_str(s)?;
 
-        Ok(DerivedDepositDescriptor(desc))
+        check_deposit_template(&desc)?;
+
+        Ok(DerivedDepositDescriptor::from_inner(desc))
     }
 }
 
-fn unvault_descriptor_csv<Pk: MiniscriptKey>(desc: &Descriptor<Pk>) -> u32 {
+// The CSVs of all the spending tiers of an Unvault descriptor, from the shortest to the longest
+fn unvault_descriptor_csvs<Pk: MiniscriptKey>(desc: &Descriptor<Pk>) -> Vec<CsvValue> {
     let ms = match desc {
         Descriptor::Wsh(ref wsh) => match wsh.as_inner() {
             WshInner::Ms(ms) => ms,
@@ -497,113 +1481,827 @@ fn unvault_descriptor_csv<Pk: MiniscriptKey>(desc: &Descriptor<Pk>) -> u32 {
         _ => unreachable!("Unvault descriptor is always a P2WSH"),
     };
 
-    let csv_frag = ms
+    // The CSV values were checked at construction
+    let mut csvs: Vec<CsvValue> = ms
         .iter()
-        .find(|ms| matches!(ms.node, Terminal::Older(_)))
-        .expect("Unvault Miniscript always contains a CSV fragment");
-    match csv_frag.node {
-        Terminal::Older(csv_value) => csv_value,
-        _ => unreachable!("Just matched."),
+        .filter_map(|ms| match ms.node {
+            Terminal::Older(csv_value) => Some(CsvValue(csv_value)),
+            _ => None,
+        })
+        .collect();
+    csvs.sort_unstable();
+    csvs.dedup();
+    assert!(
+        !csvs.is_empty(),
+        "Unvault Miniscript always contains a CSV fragment"
+    );
+
+    csvs
+}
+
+fn unvault_descriptor_csv<Pk: MiniscriptKey>(desc: &Descriptor<Pk>) -> CsvValue {
+    unvault_descriptor_csvs(desc)[0]
+}
+
+// Get rid of the trivial branches introduced by some fragments (eg 'l:' or 't:') when lifted
+fn strip_trivial<Pk: MiniscriptKey>(policy: &SemanticPolicy<Pk>) -> &SemanticPolicy<Pk> {
+    match policy {
+        SemanticPolicy::Threshold(k, ref subs) if *k == 1 || *k == subs.len() => {
+            let mut relevant_subs = subs.iter().filter(|sub| match sub {
+                SemanticPolicy::Unsatisfiable => *k != 1,
+                SemanticPolicy::Trivial => *k != subs.len(),
+                _ => true,
+            });
+            match (relevant_subs.next(), relevant_subs.next()) {
+                (Some(sub), None) => strip_trivial(sub),
+                _ => policy,
+            }
+        }
+        _ => policy,
     }
 }
 
-fn unvault_descriptor_managers_threshold<Pk: MiniscriptKey>(
-    desc: &Descriptor<Pk>,
-) -> Option<usize> {
-    let ms = match &desc {
+fn contains_csv<Pk: MiniscriptKey>(policy: &SemanticPolicy<Pk>) -> bool {
+    match policy {
+        SemanticPolicy::Older(..) => true,
+        SemanticPolicy::Threshold(_, ref subs) => subs.iter().any(contains_csv),
+        _ => false,
+    }
+}
+
+fn contains_timelock<Pk: MiniscriptKey>(policy: &SemanticPolicy<Pk>) -> bool {
+    match policy {
+        SemanticPolicy::Older(..) | SemanticPolicy::After(..) => true,
+        SemanticPolicy::Threshold(_, ref subs) => subs.iter().any(contains_timelock),
+        _ => false,
+    }
+}
+
+fn threshold_of<Pk: MiniscriptKey>(policy: SemanticPolicy<Pk>) -> Option<usize> {
+    match policy.normalized() {
+        SemanticPolicy::Threshold(k, ref subs) if k < subs.len() => Some(k),
+        _ => None,
+    }
+}
+
+// The semantic policies of the participant sets that can spend an Unvault output after a given
+// relative timelock
+struct UnvaultSpendTier<Pk: MiniscriptKey> {
+    managers: SemanticPolicy<Pk>,
+    cosigners: Option<SemanticPolicy<Pk>>,
+    csv: u32,
+}
+
+// The semantic policies of each participant set of an Unvault descriptor
+struct UnvaultParticipants<Pk: MiniscriptKey> {
+    stakeholders: SemanticPolicy<Pk>,
+    // Sorted by increasing CSV
+    tiers: Vec<UnvaultSpendTier<Pk>>,
+}
+
+fn wsh_miniscript<Pk: MiniscriptKey>(desc: &Descriptor<Pk>) -> Option<&Miniscript<Pk, Segwitv0>> {
+    match desc {
         Descriptor::Wsh(ref wsh) => match wsh.as_inner() {
-            WshInner::Ms(ms) => ms,
-            WshInner::SortedMulti(_) => unreachable!("Unvault descriptor is not a sorted multi"),
+            WshInner::Ms(ms) => Some(ms),
+            WshInner::SortedMulti(_) => None,
         },
-        _ => unreachable!("Unvault descriptor is always a P2WSH"),
-    };
+        _ => None,
+    }
+}
 
-    let policy = ms
-        .lift()
-        .expect("Lifting can't fail on a Miniscript")
-        .normalized();
+// Lift a Miniscript to a semantic policy, keeping the structure of its timelocked branches.
+// Miniscript's lifting normalizes the policy at each node, which merges the thresholds of the
+// participants into the 'and's they are part of (eg 'and(thresh(2,A,B),older(42))' becomes
+// 'thresh(3,A,B,older(42))') so we could not tell the participants sets apart anymore. Only the
+// branches without a timelock, ie the participants sets themselves, are normalized.
+fn lift_structure<Pk: MiniscriptKey>(ms: &Miniscript<Pk, Segwitv0>) -> SemanticPolicy<Pk> {
+    let policy = match ms.node {
+        Terminal::PkK(ref pk) => SemanticPolicy::KeyHash(pk.to_pubkeyhash()),
+        Terminal::PkH(ref pkh) => SemanticPolicy::KeyHash(pkh.clone()),
+        Terminal::After(t) => SemanticPolicy::After(t),
+        Terminal::Older(t) => SemanticPolicy::Older(t),
+        Terminal::Sha256(h) => SemanticPolicy::Sha256(h),
+        Terminal::Hash256(h) => SemanticPolicy::Hash256(h),
+        Terminal::Ripemd160(h) => SemanticPolicy::Ripemd160(h),
+        Terminal::Hash160(h) => SemanticPolicy::Hash160(h),
+        Terminal::True => SemanticPolicy::Trivial,
+        Terminal::False => SemanticPolicy::Unsatisfiable,
+        Terminal::Alt(ref sub)
+        | Terminal::Swap(ref sub)
+        | Terminal::Check(ref sub)
+        | Terminal::DupIf(ref sub)
+        | Terminal::Verify(ref sub)
+        | Terminal::NonZero(ref sub)
+        | Terminal::ZeroNotEqual(ref sub) => return lift_structure(sub),
+        Terminal::AndV(ref left, ref right) | Terminal::AndB(ref left, ref right) => {
+            SemanticPolicy::Threshold(2, vec![lift_structure(left), lift_structure(right)])
+        }
+        Terminal::AndOr(ref a, ref b, ref c) => SemanticPolicy::Threshold(
+            1,
+            vec![
+                SemanticPolicy::Threshold(2, vec![lift_structure(a), lift_structure(b)]),
+                lift_structure(c),
+            ],
+        ),
+        Terminal::OrB(ref left, ref right)
+        | Terminal::OrD(ref left, ref right)
+        | Terminal::OrC(ref left, ref right)
+        | Terminal::OrI(ref left, ref right) => {
+            SemanticPolicy::Threshold(1, vec![lift_structure(left), lift_structure(right)])
+        }
+        Terminal::Thresh(k, ref subs) => {
+            SemanticPolicy::Threshold(k, subs.iter().map(|sub| lift_structure(sub)).collect())
+        }
+        Terminal::Multi(k, ref keys) => SemanticPolicy::Threshold(
+            k,
+            keys.iter()
+                .map(|pk| SemanticPolicy::KeyHash(pk.to_pubkeyhash()))
+                .collect(),
+        ),
+    };
 
-    // The Unvault descriptor is always of the form 'or(mans_branch, stks_branch)'
-    match policy {
-        SemanticPolicy::Threshold(1, ref subs) => {
-            assert_eq!(subs.len(), 2);
+    if contains_timelock(&policy) {
+        policy
+    } else {
+        policy.normalized()
+    }
+}
 
-            // The 'mans_branch' can be identified as the one containing the CSV. It is always an
-            // 'and()' of CSV + Managers (the latter being potentially a thresh) and optionally
-            // along with Cosigning Servers
+// Get the alternative branches of the top-level 'or's of this policy
+fn or_branches<'a, Pk: MiniscriptKey>(
+    policy: &'a SemanticPolicy<Pk>,
+    branches: &mut Vec<&'a SemanticPolicy<Pk>>,
+) {
+    match strip_trivial(policy) {
+        SemanticPolicy::Threshold(1, ref subs) if subs.len() > 1 => {
             for sub in subs {
-                match sub {
-                    SemanticPolicy::Threshold(k, ref subs)
-                        if k == &subs.len()
-                            && subs
-                                .iter()
-                                .any(|sub| matches!(sub, SemanticPolicy::Older(..))) =>
-                    {
-                        // Now, the mans are either a Threshold or directly pks (in the case the
-                        // thresh is an 'and()', pks are flattened in the upper 'and()').
-                        return subs.iter().find_map(|sub| match sub {
-                            SemanticPolicy::Threshold(k, _) => Some(*k),
-                            _ => None,
-                        });
-                    }
-                    _ => continue,
+                or_branches(sub, branches);
+            }
+        }
+        SemanticPolicy::Unsatisfiable => {}
+        policy => branches.push(policy),
+    }
+}
+
+// Get the managers and (if any) Cosigning Servers of a spending tier of the form
+// 'and(mans, csv)' or 'and(mans, and(cosigs, csv))'.
+fn unvault_spend_tier<Pk: MiniscriptKey>(
+    tier_branch: &SemanticPolicy<Pk>,
+) -> Option<UnvaultSpendTier<Pk>> {
+    let mut outer_group = None;
+    let mut node = strip_trivial(tier_branch);
+    let (inner_group, csv) = loop {
+        match node {
+            SemanticPolicy::Threshold(2, ref subs) if subs.len() == 2 => {
+                let (with_csv, without_csv) = if contains_csv(&subs[0]) {
+                    (&subs[0], &subs[1])
+                } else {
+                    (&subs[1], &subs[0])
+                };
+                if contains_csv(without_csv) {
+                    return None;
+                }
+
+                if let SemanticPolicy::Older(csv) = strip_trivial(with_csv) {
+                    break (without_csv, *csv);
+                }
+                if outer_group.is_some() {
+                    return None;
                 }
+                outer_group = Some(without_csv);
+                node = strip_trivial(with_csv);
             }
+            _ => return None,
+        }
+    };
 
-            unreachable!(
-                "Given an Unvault descriptor which doesn't contain a second-to-top \
-                         branch with a CSV"
-            );
+    let (managers, cosigners) = match outer_group {
+        Some(mans) => (mans.clone(), Some(inner_group.clone())),
+        None => (inner_group.clone(), None),
+    };
+    Some(UnvaultSpendTier {
+        managers,
+        cosigners,
+        csv,
+    })
+}
+
+// Get the semantic policies of the stakeholders, managers and (if any) Cosigning Servers of an
+// Unvault descriptor. Returns None if the descriptor isn't of the form of an Unvault descriptor.
+fn unvault_descriptor_participants<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Option<UnvaultParticipants<Pk>> {
+    // We need the structure of the policy to tell the managers apart from the Cosigning Servers
+    let policy = lift_structure(wsh_miniscript(desc)?);
+
+    // The Unvault descriptor is always of the form 'or(stks_branch, mans_branch)', where the
+    // 'mans_branch' may itself be an 'or' between several spending tiers. The stakeholders'
+    // branch can be identified as the only one without a CSV.
+    let mut branches = Vec::new();
+    or_branches(&policy, &mut branches);
+    let (stks_branches, tiers_branches): (Vec<_>, Vec<_>) = branches
+        .into_iter()
+        .partition(|branch| !contains_csv(branch));
+    if stks_branches.len() != 1 || tiers_branches.is_empty() {
+        return None;
+    }
+
+    let mut tiers = tiers_branches
+        .into_iter()
+        .map(unvault_spend_tier)
+        .collect::<Option<Vec<UnvaultSpendTier<Pk>>>>()?;
+    tiers.sort_by_key(|tier| tier.csv);
+
+    Some(UnvaultParticipants {
+        stakeholders: stks_branches[0].clone(),
+        tiers,
+    })
+}
+
+fn unvault_descriptor_managers_threshold<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Option<usize> {
+    unvault_descriptor_participants(desc)?
+        .tiers
+        .into_iter()
+        .next()
+        .and_then(|tier| threshold_of(tier.managers))
+}
+
+fn unvault_descriptor_cosigners_threshold<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Option<usize> {
+    unvault_descriptor_participants(desc)?
+        .tiers
+        .into_iter()
+        .next()
+        .and_then(|tier| tier.cosigners)
+        .and_then(threshold_of)
+}
+
+// Whether this policy is a (k-of-n) threshold of keys only
+fn is_keys_threshold<Pk: MiniscriptKey>(policy: &SemanticPolicy<Pk>) -> bool {
+    match policy.clone().normalized() {
+        SemanticPolicy::KeyHash(..) => true,
+        SemanticPolicy::Threshold(k, ref subs) => {
+            k > 0
+                && subs
+                    .iter()
+                    .all(|sub| matches!(sub, SemanticPolicy::KeyHash(..)))
         }
-        _ => unreachable!("Given an Unvault descriptor that doesn't contain a 'or()' at the root"),
+        _ => false,
     }
 }
 
-impl UnvaultDescriptor {
-    /// Get the miniscript descriptors for Unvault outputs.
-    ///
-    /// The Unvault policy allows either all the stakeholders to spend, or the fund managers
-    /// after a timelock.
-    /// Depending on the kind of spending policies required, Cosigning Servers may be deployed
-    /// to be used as anti-replay oracles and their signature required along with the managers'.
-    ///
-    /// # Examples
-    ///
-    /// ## Without Cosigning Servers
-    /// ```rust
-    /// use revault_tx::{scripts, miniscript::{bitcoin::{self, secp256k1, util::bip32}, DescriptorPublicKey, DescriptorTrait}};
-    /// use std::str::FromStr;
-    ///
-    /// let first_stakeholder = DescriptorPublicKey::from_str("xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*").unwrap();
-    /// let second_stakeholder = DescriptorPublicKey::from_str("xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*").unwrap();
-    /// let third_stakeholder = DescriptorPublicKey::from_str("xpub6Br1DUfrzxTVGo1sanuKDCUmSxDfLRrxLQBqpMqygkQLkQWodoyvvGtUV8Rp3r6d6BNYvedBSU8c7whhn2U8haRVxsWwuQiZ9LoFp7jXPQA/*").unwrap();
-    ///
-    /// let first_manager = DescriptorPublicKey::from_str("xpub6Duq1ob3cQ8Wxees2fTGNK2wTsVjgTPQcKJiPquXY2rQJTDjeCxkXFxTCGhcunFDt26Ddz45KQu7pbLmmUGG2PXTRVx3iDpBPEhdrijJf4U/*").unwrap();
-    /// let second_manager = DescriptorPublicKey::from_str("xpub6EWL35hY9uZZs5Ljt6J3G2ZK1Tu4GPVkFdeGvMknG3VmwVRHhtadCaw5hdRDBgrmx1nPVHWjGBb5xeuC1BfbJzjjcic2gNm1aA7ywWjj7G8/*").unwrap();
-    ///
-    ///
-    /// let unvault_descriptor = scripts::UnvaultDescriptor::new(
-    ///     vec![first_stakeholder, second_stakeholder, third_stakeholder],
-    ///     vec![first_manager, second_manager],
-    ///     1,
-    ///     // No cosigning server
-    ///     vec![],
-    ///     // CSV
-    ///     42
-    /// ).expect("Compiling descriptor");
-    /// println!("Unvault descriptor: {}", unvault_descriptor);
-    ///
-    /// let desc_str = unvault_descriptor.to_string();
-    /// assert_eq!(unvault_descriptor, scripts::UnvaultDescriptor::from_str(&desc_str).unwrap());
-    ///
-    /// let secp = secp256k1::Secp256k1::verification_only();
-    /// println!("Tenth child witness script: {}", unvault_descriptor.derive(bip32::ChildNumber::from(10), &secp).inner().explicit_script());
-    /// ```
-    ///
-    /// ## With Cosigning Servers
+/// The participants a set of keys in a descriptor belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParticipantsRole {
+    Stakeholders,
+    Managers,
+    CosigningServers,
+    RecoveryKeys,
+}
+
+impl ParticipantsRole {
+    fn name(&self, plural: bool) -> &'static str {
+        match (self, plural) {
+            (ParticipantsRole::Stakeholders, false) => "stakeholder",
+            (ParticipantsRole::Stakeholders, true) => "stakeholders",
+            (ParticipantsRole::Managers, false) => "manager",
+            (ParticipantsRole::Managers, true) => "managers",
+            (ParticipantsRole::CosigningServers, false) => "Cosigning Server",
+            (ParticipantsRole::CosigningServers, true) => "Cosigning Servers",
+            (ParticipantsRole::RecoveryKeys, false) => "recovery key",
+            (ParticipantsRole::RecoveryKeys, true) => "recovery keys",
+        }
+    }
+}
+
+/// A threshold of signatures required among a set of keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeysThreshold<Pk> {
+    threshold: usize,
+    keys: Vec<Pk>,
+    role: Option<ParticipantsRole>,
+}
+
+impl<Pk> KeysThreshold<Pk> {
+    /// The number of signatures required
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// The keys the signatures may be made with
+    pub fn keys(&self) -> &[Pk] {
+        &self.keys
+    }
+
+    /// The participants these keys belong to, if they are all the keys of a participants set
+    pub fn role(&self) -> Option<ParticipantsRole> {
+        self.role
+    }
+}
+
+impl<Pk: Display> Display for KeysThreshold<Pk> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n = self.keys.len();
+        if let Some(role) = self.role {
+            return match (self.threshold, n) {
+                (_, 1) => write!(f, "the {}", role.name(false)),
+                (2, 2) => write!(f, "both {}", role.name(true)),
+                (k, n) if k == n => write!(f, "all {} {}", n, role.name(true)),
+                (k, n) => write!(f, "{}-of-{} {}", k, n, role.name(true)),
+            };
+        }
+
+        if n == 1 {
+            return write!(f, "{}", self.keys[0]);
+        }
+        if self.threshold == n {
+            write!(f, "all of [")?;
+        } else {
+            write!(f, "{}-of-{} of [", self.threshold, n)?;
+        }
+        for (i, key) in self.keys.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", key)?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// One of the ways to spend an output: the signatures it requires and the relative timelock it
+/// is subject to.
+///
+/// Its `Display` implementation gives a summary such as "after 144 blocks, 2-of-3 managers +
+/// both Cosigning Servers", intended for GUIs and ceremony documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendingPath<Pk> {
+    signers: Vec<KeysThreshold<Pk>>,
+    csv: Option<CsvValue>,
+    other_conditions: Vec<String>,
+}
+
+impl<Pk> SpendingPath<Pk> {
+    fn empty() -> SpendingPath<Pk> {
+        SpendingPath {
+            signers: vec![],
+            csv: None,
+            other_conditions: vec![],
+        }
+    }
+
+    /// The signatures required, all of these thresholds must be met
+    pub fn signers(&self) -> &[KeysThreshold<Pk>] {
+        &self.signers
+    }
+
+    /// The relative timelock this path is subject to, if any
+    pub fn csv(&self) -> Option<CsvValue> {
+        self.csv
+    }
+
+    /// Conditions which are not part of the Revault templates but may be introduced by a custom
+    /// spending policy (eg hash preimages), as policy fragments.
+    pub fn other_conditions(&self) -> &[String] {
+        &self.other_conditions
+    }
+}
+
+impl<Pk: Display> Display for SpendingPath<Pk> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(csv) = self.csv {
+            write!(f, "after {}, ", csv)?;
+        }
+
+        let conditions = self
+            .signers
+            .iter()
+            .map(|signers| signers.to_string())
+            .chain(self.other_conditions.iter().cloned())
+            .collect::<Vec<String>>();
+        write!(f, "{}", conditions.join(" + "))
+    }
+}
+
+// All the ways to pick k items out of these
+fn combinations<T>(items: &[T], k: usize) -> Vec<Vec<&T>> {
+    if k == 0 {
+        return vec![vec![]];
+    }
+    let (first, rest) = match items.split_first() {
+        Some(split) if items.len() >= k => split,
+        _ => return vec![],
+    };
+
+    let mut combs: Vec<Vec<&T>> = combinations(rest, k - 1)
+        .into_iter()
+        .map(|mut comb| {
+            comb.insert(0, first);
+            comb
+        })
+        .collect();
+    combs.extend(combinations(rest, k));
+    combs
+}
+
+// Expand a lifted policy into the alternative sets of conditions that satisfy it
+fn policy_spending_paths<Pk: MiniscriptKey<Hash = Pk>>(
+    policy: &SemanticPolicy<Pk>,
+) -> Vec<SpendingPath<Pk>> {
+    match policy {
+        SemanticPolicy::Unsatisfiable => vec![],
+        SemanticPolicy::Trivial => vec![SpendingPath::empty()],
+        SemanticPolicy::KeyHash(pk) => vec![SpendingPath {
+            signers: vec![KeysThreshold {
+                threshold: 1,
+                keys: vec![pk.clone()],
+                role: None,
+            }],
+            ..SpendingPath::empty()
+        }],
+        SemanticPolicy::Older(csv) => vec![SpendingPath {
+            csv: Some(CsvValue(*csv)),
+            ..SpendingPath::empty()
+        }],
+        // A proper k-of-n between keys. If all of them are required, we treat them as
+        // individual keys to be grouped by participants later on.
+        SemanticPolicy::Threshold(k, ref subs)
+            if *k < subs.len()
+                && subs
+                    .iter()
+                    .all(|sub| matches!(sub, SemanticPolicy::KeyHash(..))) =>
+        {
+            vec![SpendingPath {
+                signers: vec![KeysThreshold {
+                    threshold: *k,
+                    keys: subs
+                        .iter()
+                        .filter_map(|sub| match sub {
+                            SemanticPolicy::KeyHash(pk) => Some(pk.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                    role: None,
+                }],
+                ..SpendingPath::empty()
+            }]
+        }
+        SemanticPolicy::Threshold(k, ref subs) => combinations(subs, *k)
+            .into_iter()
+            .flat_map(|comb| {
+                comb.into_iter()
+                    .fold(vec![SpendingPath::empty()], |paths, sub| {
+                        let sub_paths = policy_spending_paths(sub);
+                        paths
+                            .iter()
+                            .flat_map(|path| {
+                                sub_paths.iter().map(move |sub_path| {
+                                    let mut path = path.clone();
+                                    path.signers.extend(sub_path.signers.iter().cloned());
+                                    path.csv = path.csv.max(sub_path.csv);
+                                    path.other_conditions
+                                        .extend(sub_path.other_conditions.iter().cloned());
+                                    path
+                                })
+                            })
+                            .collect()
+                    })
+            })
+            .collect(),
+        other => vec![SpendingPath {
+            other_conditions: vec![other.to_string()],
+            ..SpendingPath::empty()
+        }],
+    }
+}
+
+// Gather the keys required by a path that belong to the same participants set, and label the
+// thresholds which span a whole participants set.
+fn label_spending_path<Pk: MiniscriptKey>(
+    path: SpendingPath<Pk>,
+    roles: &[(ParticipantsRole, Vec<Pk>)],
+) -> SpendingPath<Pk> {
+    let mut signers: Vec<KeysThreshold<Pk>> = Vec::with_capacity(path.signers.len());
+
+    for signer in path.signers {
+        let role = roles
+            .iter()
+            .find(|(_, role_keys)| signer.keys.iter().all(|k| role_keys.contains(k)))
+            .map(|(role, _)| *role);
+        let all_required = signer.threshold == signer.keys.len();
+
+        // Merge the keys which are all required into a single threshold per participants set
+        if let (Some(role), true) = (role, all_required) {
+            if let Some(group) = signers
+                .iter_mut()
+                .find(|group| group.role == Some(role) && group.threshold == group.keys.len())
+            {
+                for key in signer.keys {
+                    if !group.keys.contains(&key) {
+                        group.keys.push(key);
+                    }
+                }
+                group.threshold = group.keys.len();
+                continue;
+            }
+        }
+
+        signers.push(KeysThreshold { role, ..signer });
+    }
+
+    // Only keep the role for thresholds between all the keys of a participants set
+    for signer in signers.iter_mut() {
+        signer.role = roles
+            .iter()
+            .find(|(role, role_keys)| {
+                signer.role == Some(*role)
+                    && role_keys.len() == signer.keys.len()
+                    && role_keys.iter().all(|k| signer.keys.contains(k))
+            })
+            .map(|(role, _)| *role);
+    }
+
+    // Display the participants in the order of their roles, unknown keys last
+    signers.sort_by_key(|signer| {
+        signer
+            .role
+            .and_then(|r| roles.iter().position(|(role, _)| *role == r))
+            .unwrap_or(roles.len())
+    });
+
+    SpendingPath { signers, ..path }
+}
+
+fn descriptor_spending_paths<Pk: MiniscriptKey<Hash = Pk>>(
+    desc: &Descriptor<Pk>,
+    roles: &[(ParticipantsRole, Vec<Pk>)],
+) -> Vec<SpendingPath<Pk>> {
+    let policy = wsh_miniscript(desc)
+        .expect("All our descriptors are always P2WSH Miniscript")
+        .lift()
+        .expect("Lifting can't fail on a Miniscript");
+
+    // The timelocked paths last
+    let mut paths: Vec<SpendingPath<Pk>> = policy_spending_paths(&policy)
+        .into_iter()
+        .map(|path| label_spending_path(path, roles))
+        .collect();
+    paths.sort_by_key(|path| path.csv);
+    paths
+}
+
+// All the keys of a lifted policy, without duplicates
+fn policy_keys<Pk: MiniscriptKey<Hash = Pk>>(policy: &SemanticPolicy<Pk>) -> Vec<Pk> {
+    let mut keys = Vec::new();
+    let mut stack = vec![policy];
+    while let Some(node) = stack.pop() {
+        match node {
+            SemanticPolicy::KeyHash(pk) if !keys.contains(pk) => keys.push(pk.clone()),
+            SemanticPolicy::Threshold(_, ref subs) => stack.extend(subs.iter().rev()),
+            _ => {}
+        }
+    }
+    keys
+}
+
+// The stakeholders' keys and, if there is a recovery path, the recovery keys
+fn deposit_descriptor_roles<Pk: MiniscriptKey<Hash = Pk>>(
+    desc: &Descriptor<Pk>,
+) -> Vec<(ParticipantsRole, Vec<Pk>)> {
+    let policy =
+        lift_structure(wsh_miniscript(desc).expect("Deposit descriptors are checked at creation"));
+
+    // With a recovery path, it's 'or(stks, and(recovery_keys, csv))'
+    if let SemanticPolicy::Threshold(1, ref subs) = strip_trivial(&policy) {
+        if subs.len() == 2 && subs.iter().any(contains_csv) {
+            let (stks_branch, recovery_branch) = if contains_csv(&subs[0]) {
+                (&subs[1], &subs[0])
+            } else {
+                (&subs[0], &subs[1])
+            };
+            return vec![
+                (ParticipantsRole::Stakeholders, policy_keys(stks_branch)),
+                (ParticipantsRole::RecoveryKeys, policy_keys(recovery_branch)),
+            ];
+        }
+    }
+
+    vec![(ParticipantsRole::Stakeholders, policy_keys(&policy))]
+}
+
+fn unvault_descriptor_roles<Pk: MiniscriptKey<Hash = Pk>>(
+    desc: &Descriptor<Pk>,
+) -> Vec<(ParticipantsRole, Vec<Pk>)> {
+    let participants = match unvault_descriptor_participants(desc) {
+        Some(participants) => participants,
+        // We don't know who these keys belong to
+        None => return vec![],
+    };
+
+    let mut roles = vec![(
+        ParticipantsRole::Stakeholders,
+        policy_keys(&participants.stakeholders),
+    )];
+    for tier in participants.tiers.iter() {
+        roles.push((ParticipantsRole::Managers, policy_keys(&tier.managers)));
+        if let Some(ref cosigners) = tier.cosigners {
+            roles.push((ParticipantsRole::CosigningServers, policy_keys(cosigners)));
+        }
+    }
+    roles
+}
+
+// The keys of these participants in the Unvault descriptor, without duplicates. The managers and
+// Cosigning Servers of all the spending tiers are included.
+fn unvault_descriptor_keys<Pk: MiniscriptKey<Hash = Pk>>(
+    desc: &Descriptor<Pk>,
+    role: ParticipantsRole,
+) -> Vec<Pk> {
+    let mut keys = Vec::new();
+    for (_, role_keys) in unvault_descriptor_roles(desc)
+        .into_iter()
+        .filter(|(r, _)| *r == role)
+    {
+        for key in role_keys {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+    keys
+}
+
+fn cpfp_descriptor_roles<Pk: MiniscriptKey<Hash = Pk>>(
+    desc: &Descriptor<Pk>,
+) -> Vec<(ParticipantsRole, Vec<Pk>)> {
+    let keys = wsh_miniscript(desc)
+        .expect("CPFP descriptors are checked at creation")
+        .iter_pk()
+        .collect();
+    vec![(ParticipantsRole::Managers, keys)]
+}
+
+// Check all the relative timelocks of a descriptor are valid CSV values, in blocks or time-based
+fn check_csv_values<Pk: MiniscriptKey>(
+    ms: &Miniscript<Pk, Segwitv0>,
+) -> Result<(), ScriptCreationError> {
+    for ms in ms.iter() {
+        if let Terminal::Older(csv_value) = ms.node {
+            CsvValue::from_bip68(csv_value)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Check a descriptor is of the form of a Revault deposit descriptor: a P2WSH of a threshold
+// between at least 2 keys.
+fn check_deposit_template<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Result<(), ScriptCreationError> {
+    let ms = wsh_miniscript(desc).ok_or(ScriptCreationError::TemplateMismatch)?;
+    check_csv_values(ms)?;
+    let policy = lift_structure(ms);
+
+    if !contains_csv(&policy) {
+        if !is_keys_threshold(&policy) || policy.n_keys() < 2 {
+            return Err(ScriptCreationError::TemplateMismatch);
+        }
+        return Ok(());
+    }
+
+    // With a recovery path, it's 'or(stks, and(recovery_keys, csv))'
+    let (stks_branch, recovery_branch) = match strip_trivial(&policy) {
+        SemanticPolicy::Threshold(1, ref subs) if subs.len() == 2 => {
+            if contains_csv(&subs[0]) {
+                (&subs[1], &subs[0])
+            } else {
+                (&subs[0], &subs[1])
+            }
+        }
+        _ => return Err(ScriptCreationError::TemplateMismatch),
+    };
+    if contains_csv(stks_branch) || !is_keys_threshold(stks_branch) || stks_branch.n_keys() < 2 {
+        return Err(ScriptCreationError::TemplateMismatch);
+    }
+    match strip_trivial(recovery_branch) {
+        SemanticPolicy::Threshold(2, ref subs) if subs.len() == 2 => {
+            let (csv, keys) = if contains_csv(&subs[0]) {
+                (&subs[0], &subs[1])
+            } else {
+                (&subs[1], &subs[0])
+            };
+            if !matches!(strip_trivial(csv), SemanticPolicy::Older(..))
+                || contains_csv(keys)
+                || !is_keys_threshold(keys)
+            {
+                return Err(ScriptCreationError::TemplateMismatch);
+            }
+        }
+        _ => return Err(ScriptCreationError::TemplateMismatch),
+    }
+
+    Ok(())
+}
+
+// Check a descriptor is of the form of a Revault Unvault descriptor: a P2WSH of either all the
+// stakeholders, or a threshold of the managers along with (optionally) the Cosigning Servers
+// after a relative timelock.
+fn check_unvault_template<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Result<(), ScriptCreationError> {
+    let ms = wsh_miniscript(desc).ok_or(ScriptCreationError::TemplateMismatch)?;
+    check_csv_values(ms)?;
+    let participants =
+        unvault_descriptor_participants(desc).ok_or(ScriptCreationError::TemplateMismatch)?;
+
+    let all_stakeholders = match participants.stakeholders.clone().normalized() {
+        SemanticPolicy::Threshold(k, ref subs) => k == subs.len(),
+        SemanticPolicy::KeyHash(..) => true,
+        _ => false,
+    };
+    if !all_stakeholders || !is_keys_threshold(&participants.stakeholders) {
+        return Err(ScriptCreationError::TemplateMismatch);
+    }
+
+    // The managers' spending paths may be custom policies (see
+    // [UnvaultDescriptor::with_spend_tiers]), we only require them to not contain any other
+    // timelock and the whole Script to always require a signature.
+    let spenders_ok =
+        |policy: &SemanticPolicy<Pk>| policy.n_keys() > 0 && !contains_timelock(policy);
+    if !participants.tiers.iter().all(|tier| {
+        spenders_ok(&tier.managers) && tier.cosigners.as_ref().map(spenders_ok).unwrap_or(true)
+    }) || !ms.requires_sig()
+    {
+        return Err(ScriptCreationError::TemplateMismatch);
+    }
+
+    Ok(())
+}
+
+// Check a descriptor is of the form of a Revault CPFP descriptor: a P2WSH of a 1-of-N between
+// keys.
+fn check_cpfp_template<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Result<(), ScriptCreationError> {
+    let policy = wsh_miniscript(desc)
+        .and_then(|ms| ms.lift().ok())
+        .ok_or(ScriptCreationError::TemplateMismatch)?;
+
+    match policy.normalized() {
+        SemanticPolicy::KeyHash(..) => Ok(()),
+        ref policy @ SemanticPolicy::Threshold(1, _) if is_keys_threshold(policy) => Ok(()),
+        _ => Err(ScriptCreationError::TemplateMismatch),
+    }
+}
+
+impl UnvaultDescriptor {
+    /// Get the miniscript descriptors for Unvault outputs.
+    ///
+    /// The Unvault policy allows either all the stakeholders to spend, or the fund managers
+    /// after a timelock.
+    /// Depending on the kind of spending policies required, Cosigning Servers may be deployed
+    /// to be used as anti-replay oracles and their signature required along with the managers'.
+    ///
+    /// # Examples
+    ///
+    /// ## Without Cosigning Servers
     /// ```rust
     /// use revault_tx::{scripts, miniscript::{bitcoin::{self, secp256k1, util::bip32}, DescriptorPublicKey, DescriptorTrait}};
-    /// use std::str::FromStr;
+    /// use std::{convert::TryFrom, str::FromStr};
+    ///
+    /// let first_stakeholder = DescriptorPublicKey::from_str("xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*").unwrap();
+    /// let second_stakeholder = DescriptorPublicKey::from_str("xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*").unwrap();
+    /// let third_stakeholder = DescriptorPublicKey::from_str("xpub6Br1DUfrzxTVGo1sanuKDCUmSxDfLRrxLQBqpMqygkQLkQWodoyvvGtUV8Rp3r6d6BNYvedBSU8c7whhn2U8haRVxsWwuQiZ9LoFp7jXPQA/*").unwrap();
+    ///
+    /// let first_manager = DescriptorPublicKey::from_str("xpub6Duq1ob3cQ8Wxees2fTGNK2wTsVjgTPQcKJiPquXY2rQJTDjeCxkXFxTCGhcunFDt26Ddz45KQu7pbLmmUGG2PXTRVx3iDpBPEhdrijJf4U/*").unwrap();
+    /// let second_manager = DescriptorPublicKey::from_str("xpub6EWL35hY9uZZs5Ljt6J3G2ZK1Tu4GPVkFdeGvMknG3VmwVRHhtadCaw5hdRDBgrmx1nPVHWjGBb5xeuC1BfbJzjjcic2gNm1aA7ywWjj7G8/*").unwrap();
+    ///
+    ///
+    /// let unvault_descriptor = scripts::UnvaultDescriptor::new(
+    ///     vec![first_stakeholder, second_stakeholder, third_stakeholder],
+    ///     vec![first_manager, second_manager],
+    ///     1,
+    ///     // No cosigning server
+    ///     vec![],
+    ///     scripts::CsvValue::blocks(42),
+    /// ).expect("Compiling descriptor");
+    /// println!("Unvault descriptor: {}", unvault_descriptor);
+    ///
+    /// let desc_str = unvault_descriptor.to_string();
+    /// assert_eq!(unvault_descriptor, scripts::UnvaultDescriptor::from_str(&desc_str).unwrap());
+    ///
+    /// let secp = secp256k1::Secp256k1::verification_only();
+    /// println!("Tenth child witness script: {}", unvault_descriptor.derive(scripts::DerivationIndex::try_from(10).unwrap(), &secp).inner().explicit_script());
+    /// ```
+    ///
+    /// ## With Cosigning Servers
+    /// ```rust
+    /// use revault_tx::{scripts, miniscript::{bitcoin::{self, secp256k1, util::bip32}, DescriptorPublicKey, DescriptorTrait}};
+    /// use std::{convert::TryFrom, str::FromStr};
     ///
     /// let first_stakeholder = DescriptorPublicKey::from_str("xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*").unwrap();
     /// let second_stakeholder = DescriptorPublicKey::from_str("xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*").unwrap();
@@ -623,8 +2321,7 @@ impl UnvaultDescriptor {
     ///     1,
     ///     // Cosigning servers
     ///     vec![first_cosig, second_cosig, third_cosig],
-    ///     // CSV
-    ///     42
+    ///     scripts::CsvValue::blocks(42),
     /// ).expect("Compiling descriptor");
     /// println!("Unvault descriptor: {}", unvault_descriptor);
     ///
@@ -632,7 +2329,7 @@ impl UnvaultDescriptor {
     /// assert_eq!(unvault_descriptor, scripts::UnvaultDescriptor::from_str(&desc_str).unwrap());
     ///
     /// let secp = secp256k1::Secp256k1::verification_only();
-    /// println!("Tenth child witness script: {}", unvault_descriptor.derive(bip32::ChildNumber::from(10), &secp).inner().explicit_script());
+    /// println!("Tenth child witness script: {}", unvault_descriptor.derive(scripts::DerivationIndex::try_from(10).unwrap(), &secp).inner().explicit_script());
     /// ```
     ///
     /// # Errors
@@ -645,41 +2342,172 @@ impl UnvaultDescriptor {
         managers: Vec<DescriptorPublicKey>,
         managers_threshold: usize,
         cosigners: Vec<DescriptorPublicKey>,
-        csv_value: u32,
+        csv_value: CsvValue,
     ) -> Result<UnvaultDescriptor, ScriptCreationError> {
-        unvault_desc_checks!(
+        unvault_desc_checks!(stakeholders, managers, managers_threshold, cosigners);
+
+        // Stakeholders' and managers' must be deriveable xpubs.
+        check_deriveable(stakeholders.iter().chain(managers.iter()))?;
+
+        // Cosigners' key may not be. We use DescriptorSinglePub for them downstream with static raw
+        // keys, but it's not hardcoded into the type system there to allow a more generic usage.
+
+        let desc = UnvaultDescriptor(unvault_desc!(
             stakeholders,
             managers,
             managers_threshold,
             cosigners,
             csv_value
-        );
+        ));
+        desc.sanity_check()?;
 
-        // Stakeholders' and managers' must be deriveable xpubs.
-        check_deriveable(stakeholders.iter().chain(managers.iter()))?;
+        Ok(desc)
+    }
 
-        // Cosigners' key may not be. We use DescriptorSinglePub for them downstream with static raw
-        // keys, but it's not hardcoded into the type system there to allow a more generic usage.
+    /// Get the miniscript descriptors for Unvault outputs, only requiring `cosigners_threshold`
+    /// of the Cosigning Servers to sign along with the managers instead of all of them.
+    ///
+    /// This allows to not block all the spends if one of the Cosigning Servers is offline, at
+    /// the cost of a weaker protection against replays.
+    ///
+    /// # Errors
+    /// - Same as [UnvaultDescriptor::new].
+    /// - If `cosigners_threshold` is larger than the number of cosigners, or is `0` while
+    ///   cosigners are given.
+    pub fn with_cosigners_threshold(
+        stakeholders: Vec<DescriptorPublicKey>,
+        managers: Vec<DescriptorPublicKey>,
+        managers_threshold: usize,
+        cosigners: Vec<DescriptorPublicKey>,
+        cosigners_threshold: usize,
+        csv_value: CsvValue,
+    ) -> Result<UnvaultDescriptor, ScriptCreationError> {
+        unvault_desc_checks!(stakeholders, managers, managers_threshold, cosigners);
+        cosigners_threshold_checks!(cosigners, cosigners_threshold);
+
+        check_deriveable(stakeholders.iter().chain(managers.iter()))?;
 
-        Ok(UnvaultDescriptor(unvault_desc!(
+        let desc = UnvaultDescriptor(unvault_desc!(
             stakeholders,
             managers,
             managers_threshold,
             cosigners,
+            cosigners_threshold,
             csv_value
-        )))
+        ));
+        desc.sanity_check()?;
+
+        Ok(desc)
+    }
+
+    /// Get the miniscript descriptors for Unvault outputs, with a custom policy for the
+    /// managers' spending path.
+    ///
+    /// The Unvault output can be spent by either all the stakeholders, or by the given
+    /// `spend_policy` after `csv_value`. This allows advanced setups, such as a
+    /// combination of hot and cold managers' keys. We still enforce the stakeholders' revocation
+    /// branch and the relative timelock.
+    ///
+    /// Note that [UnvaultDescriptor::managers_threshold] and
+    /// [UnvaultDescriptor::cosigners_threshold] are meaningless for such descriptors.
+    ///
+    /// # Errors
+    /// - If the stakeholders `DescriptorPublickKey`s are not wildcards (can be derived from).
+    /// - If the `spend_policy` is not a valid policy, contains a timelock or no key.
+    /// - If the policy compilation to miniscript failed (eg if a key is used twice, or if the
+    ///   `spend_policy` doesn't require a signature)
+    pub fn with_spend_policy(
+        stakeholders: Vec<DescriptorPublicKey>,
+        spend_policy: &str,
+        csv_value: CsvValue,
+    ) -> Result<UnvaultDescriptor, ScriptCreationError> {
+        UnvaultDescriptor::with_spend_tiers(stakeholders, &[(spend_policy, csv_value)])
+    }
+
+    /// Get the miniscript descriptors for Unvault outputs, with several spending tiers for the
+    /// managers.
+    ///
+    /// Each tier is a custom policy (as in [UnvaultDescriptor::with_spend_policy]) along with
+    /// the relative timelock after which it may spend the Unvault output. This allows for
+    /// instance the full set of managers to spend after a short CSV, and a smaller subset of
+    /// them after a longer one. As Miniscript does not allow key reuse, participants taking
+    /// part in several tiers must use a different key (eg another account xpub) for each.
+    ///
+    /// The tier used when satisfying the Unvault output depends on the nSequence of the
+    /// spending input: the cheapest tier whose CSV is met and whose signatures are available
+    /// is used.
+    ///
+    /// # Errors
+    /// - If the stakeholders `DescriptorPublickKey`s are not wildcards (can be derived from).
+    /// - If there are no tiers, two of them have the same CSV, or their CSVs are not all in
+    ///   blocks or all time-based.
+    /// - If a spending policy is not a valid policy, contains a timelock or no key.
+    /// - If the policy compilation to miniscript failed (eg if a key is used twice, or if a
+    ///   spending policy doesn't require a signature)
+    pub fn with_spend_tiers(
+        stakeholders: Vec<DescriptorPublicKey>,
+        spend_tiers: &[(&str, CsvValue)],
+    ) -> Result<UnvaultDescriptor, ScriptCreationError> {
+        if stakeholders.len() > MAX_STAKEHOLDERS {
+            return Err(ScriptCreationError::TooManyStakeholders(stakeholders.len()));
+        }
+        if stakeholders.is_empty() {
+            return Err(ScriptCreationError::BadParameters);
+        }
+        check_deriveable(stakeholders.iter())?;
+
+        let spend_tiers = unvault_spend_tiers!(spend_tiers, DescriptorPublicKey);
+        let desc = UnvaultDescriptor(unvault_custom_desc!(stakeholders, spend_tiers));
+        // As for FromStr, the managers' xpubs must be deriveable but raw keys are accepted.
+        if !desc.0.for_each_key(|k| match k.as_key() {
+            DescriptorPublicKey::SinglePub(_) => true,
+            DescriptorPublicKey::XPub(xpub) => xpub.wildcard != Wildcard::None,
+        }) {
+            return Err(ScriptCreationError::NonWildcardKeys);
+        }
+        desc.sanity_check()?;
+
+        Ok(desc)
     }
 
-    /// Get the relative locktime in blocks contained in the Unvault descriptor
-    pub fn csv_value(&self) -> u32 {
+    /// Get the relative locktime contained in the Unvault descriptor. If it has
+    /// several spending tiers, this is the shortest one.
+    pub fn csv_value(&self) -> CsvValue {
         unvault_descriptor_csv(&self.0)
     }
 
+    /// Get the relative locktimes of all the spending tiers of the Unvault
+    /// descriptor, from the shortest to the longest.
+    pub fn csv_values(&self) -> Vec<CsvValue> {
+        unvault_descriptor_csvs(&self.0)
+    }
+
     /// Get the minimum number of managers required to sign along with the timelock
     /// and the (optional) Cosigning Servers
     pub fn managers_threshold(&self) -> Option<usize> {
         unvault_descriptor_managers_threshold(&self.0)
     }
+
+    /// Get the minimum number of Cosigning Servers required to sign along with the managers.
+    /// `None` if there are no Cosigning Servers or if all of them are required.
+    pub fn cosigners_threshold(&self) -> Option<usize> {
+        unvault_descriptor_cosigners_threshold(&self.0)
+    }
+
+    /// Get the stakeholders' keys
+    pub fn stakeholders(&self) -> Vec<DescriptorPublicKey> {
+        unvault_descriptor_keys(&self.0, ParticipantsRole::Stakeholders)
+    }
+
+    /// Get the managers' keys, of all the spending tiers
+    pub fn managers(&self) -> Vec<DescriptorPublicKey> {
+        unvault_descriptor_keys(&self.0, ParticipantsRole::Managers)
+    }
+
+    /// Get the Cosigning Servers' keys, of all the spending tiers. Empty if there are none.
+    pub fn cosigners(&self) -> Vec<DescriptorPublicKey> {
+        unvault_descriptor_keys(&self.0, ParticipantsRole::CosigningServers)
+    }
 }
 
 impl Display for UnvaultDescriptor {
@@ -705,6 +2533,8 @@ impl FromStr for UnvaultDescriptor {
             return Err(ScriptCreationError::NoXpub);
         }
 
+        check_unvault_template(&desc)?;
+
         Ok(UnvaultDescriptor(desc))
     }
 }
@@ -736,8 +2566,7 @@ impl DerivedUnvaultDescriptor {
     ///     1,
     ///     // No cosigning server
     ///     vec![],
-    ///     // CSV
-    ///     42
+    ///     scripts::CsvValue::blocks(42),
     /// ).expect("Compiling descriptor");
     /// println!("Unvault descriptor: {}", unvault_descriptor);
     ///
@@ -765,8 +2594,7 @@ impl DerivedUnvaultDescriptor {
     ///     1,
     ///     // Cosigners
     ///     vec![first_cosig, second_cosig, third_cosig],
-    ///     // CSV
-    ///     42
+    ///     scripts::CsvValue::blocks(42),
     /// ).expect("Compiling descriptor");
     /// println!("Unvault descriptor: {}", unvault_descriptor);
     ///
@@ -782,35 +2610,138 @@ impl DerivedUnvaultDescriptor {
         managers: Vec<DerivedPublicKey>,
         managers_threshold: usize,
         cosigners: Vec<DerivedPublicKey>,
-        csv_value: u32,
+        csv_value: CsvValue,
     ) -> Result<DerivedUnvaultDescriptor, ScriptCreationError> {
-        unvault_desc_checks!(
+        unvault_desc_checks!(stakeholders, managers, managers_threshold, cosigners);
+
+        let desc = DerivedUnvaultDescriptor::from_inner(unvault_desc!(
             stakeholders,
             managers,
             managers_threshold,
             cosigners,
             csv_value
-        );
+        ));
+        desc.sanity_check()?;
 
-        Ok(DerivedUnvaultDescriptor(unvault_desc!(
-            stakeholders,
-            managers,
+        Ok(desc)
+    }
+
+    /// Get the miniscript descriptors for Unvault outputs, only requiring `cosigners_threshold`
+    /// of the Cosigning Servers to sign along with the managers instead of all of them.
+    ///
+    /// # Errors
+    /// - Same as [DerivedUnvaultDescriptor::new].
+    /// - If `cosigners_threshold` is larger than the number of cosigners, or is `0` while
+    ///   cosigners are given.
+    pub fn with_cosigners_threshold(
+        stakeholders: Vec<DerivedPublicKey>,
+        managers: Vec<DerivedPublicKey>,
+        managers_threshold: usize,
+        cosigners: Vec<DerivedPublicKey>,
+        cosigners_threshold: usize,
+        csv_value: CsvValue,
+    ) -> Result<DerivedUnvaultDescriptor, ScriptCreationError> {
+        unvault_desc_checks!(stakeholders, managers, managers_threshold, cosigners);
+        cosigners_threshold_checks!(cosigners, cosigners_threshold);
+
+        let desc = DerivedUnvaultDescriptor::from_inner(unvault_desc!(
+            stakeholders,
+            managers,
             managers_threshold,
             cosigners,
+            cosigners_threshold,
             csv_value
-        )))
+        ));
+        desc.sanity_check()?;
+
+        Ok(desc)
+    }
+
+    /// Get the miniscript descriptors for Unvault outputs, with a custom policy for the
+    /// managers' spending path.
+    ///
+    /// See [UnvaultDescriptor::with_spend_policy].
+    ///
+    /// # Errors
+    /// - If the `spend_policy` is not a valid policy, contains a timelock or no key.
+    /// - If the policy compilation to miniscript failed (eg if a key is used twice, or if the
+    ///   `spend_policy` doesn't require a signature)
+    pub fn with_spend_policy(
+        stakeholders: Vec<DerivedPublicKey>,
+        spend_policy: &str,
+        csv_value: CsvValue,
+    ) -> Result<DerivedUnvaultDescriptor, ScriptCreationError> {
+        DerivedUnvaultDescriptor::with_spend_tiers(stakeholders, &[(spend_policy, csv_value)])
     }
 
-    /// Get the relative locktime in blocks contained in the Unvault descriptor
-    pub fn csv_value(&self) -> u32 {
+    /// Get the miniscript descriptors for Unvault outputs, with several spending tiers for the
+    /// managers.
+    ///
+    /// See [UnvaultDescriptor::with_spend_tiers].
+    ///
+    /// # Errors
+    /// - If there are no tiers, two of them have the same CSV, or their CSVs are not all in
+    ///   blocks or all time-based.
+    /// - If a spending policy is not a valid policy, contains a timelock or no key.
+    /// - If the policy compilation to miniscript failed (eg if a key is used twice, or if a
+    ///   spending policy doesn't require a signature)
+    pub fn with_spend_tiers(
+        stakeholders: Vec<DerivedPublicKey>,
+        spend_tiers: &[(&str, CsvValue)],
+    ) -> Result<DerivedUnvaultDescriptor, ScriptCreationError> {
+        if stakeholders.len() > MAX_STAKEHOLDERS {
+            return Err(ScriptCreationError::TooManyStakeholders(stakeholders.len()));
+        }
+        if stakeholders.is_empty() {
+            return Err(ScriptCreationError::BadParameters);
+        }
+
+        let spend_tiers = unvault_spend_tiers!(spend_tiers, DerivedPublicKey);
+        let desc =
+            DerivedUnvaultDescriptor::from_inner(unvault_custom_desc!(stakeholders, spend_tiers));
+        desc.sanity_check()?;
+
+        Ok(desc)
+    }
+
+    /// Get the relative locktime contained in the Unvault descriptor. If it has
+    /// several spending tiers, this is the shortest one.
+    pub fn csv_value(&self) -> CsvValue {
         unvault_descriptor_csv(&self.0)
     }
 
+    /// Get the relative locktimes of all the spending tiers of the Unvault
+    /// descriptor, from the shortest to the longest.
+    pub fn csv_values(&self) -> Vec<CsvValue> {
+        unvault_descriptor_csvs(&self.0)
+    }
+
     /// Get the minimum number of managers required to sign along with the timelock
     /// and the (optional) Cosigning Servers
     pub fn managers_threshold(&self) -> Option<usize> {
         unvault_descriptor_managers_threshold(&self.0)
     }
+
+    /// Get the minimum number of Cosigning Servers required to sign along with the managers.
+    /// `None` if there are no Cosigning Servers or if all of them are required.
+    pub fn cosigners_threshold(&self) -> Option<usize> {
+        unvault_descriptor_cosigners_threshold(&self.0)
+    }
+
+    /// Get the stakeholders' keys
+    pub fn stakeholders(&self) -> Vec<DerivedPublicKey> {
+        unvault_descriptor_keys(&self.0, ParticipantsRole::Stakeholders)
+    }
+
+    /// Get the managers' keys, of all the spending tiers
+    pub fn managers(&self) -> Vec<DerivedPublicKey> {
+        unvault_descriptor_keys(&self.0, ParticipantsRole::Managers)
+    }
+
+    /// Get the Cosigning Servers' keys, of all the spending tiers. Empty if there are none.
+    pub fn cosigners(&self) -> Vec<DerivedPublicKey> {
+        unvault_descriptor_keys(&self.0, ParticipantsRole::CosigningServers)
+    }
 }
 
 impl Display for DerivedUnvaultDescriptor {
@@ -825,14 +2756,16 @@ impl FromStr for DerivedUnvaultDescriptor {
     fn from_str(s: &str) -> Result<DerivedUnvaultDescriptor, Self::Err> {
         let desc: Descriptor<DerivedPublicKey> = FromStr::from_str(s)?;
 
-        Ok(DerivedUnvaultDescriptor(desc))
+        check_unvault_template(&desc)?;
+
+        Ok(DerivedUnvaultDescriptor::from_inner(desc))
     }
 }
 
 macro_rules! cpfp_descriptor {
     ($managers: ident) => {{
         if $managers.len() > MAX_MANAGERS {
-            return Err(ScriptCreationError::BadParameters);
+            return Err(ScriptCreationError::TooManyManagers($managers.len()));
         }
 
         let desc = Descriptor::new_wsh(Miniscript::from_ast(Terminal::Multi(1, $managers))?)?;
@@ -849,7 +2782,7 @@ impl CpfpDescriptor {
     /// # Examples
     /// ```rust
     /// use revault_tx::{scripts, miniscript::{bitcoin::{self, secp256k1, util::bip32}, DescriptorPublicKey, DescriptorTrait}};
-    /// use std::str::FromStr;
+    /// use std::{convert::TryFrom, str::FromStr};
     ///
     /// let first_manager = DescriptorPublicKey::from_str("xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*").unwrap();
     /// let second_manager = DescriptorPublicKey::from_str("xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*").unwrap();
@@ -859,7 +2792,7 @@ impl CpfpDescriptor {
     /// println!("CPFP descriptor: {}", cpfp_descriptor);
     ///
     /// let secp = secp256k1::Secp256k1::verification_only();
-    /// println!("Tenth child witness script: {}", cpfp_descriptor.derive(bip32::ChildNumber::from(10), &secp).inner().explicit_script());
+    /// println!("Tenth child witness script: {}", cpfp_descriptor.derive(scripts::DerivationIndex::try_from(10).unwrap(), &secp).inner().explicit_script());
     /// ```
     ///
     /// let desc_str = cpfp_descriptor.to_string();
@@ -873,7 +2806,10 @@ impl CpfpDescriptor {
     pub fn new(managers: Vec<DescriptorPublicKey>) -> Result<CpfpDescriptor, ScriptCreationError> {
         check_deriveable(managers.iter())?;
 
-        Ok(CpfpDescriptor(cpfp_descriptor!(managers)))
+        let desc = CpfpDescriptor(cpfp_descriptor!(managers));
+        desc.sanity_check()?;
+
+        Ok(desc)
     }
 }
 
@@ -893,6 +2829,8 @@ impl FromStr for CpfpDescriptor {
             return Err(ScriptCreationError::NonWildcardKeys);
         }
 
+        check_cpfp_template(&desc)?;
+
         Ok(CpfpDescriptor(desc))
     }
 }
@@ -925,7 +2863,10 @@ impl DerivedCpfpDescriptor {
     pub fn new(
         managers: Vec<DerivedPublicKey>,
     ) -> Result<DerivedCpfpDescriptor, ScriptCreationError> {
-        Ok(DerivedCpfpDescriptor(cpfp_descriptor!(managers)))
+        let desc = DerivedCpfpDescriptor::from_inner(cpfp_descriptor!(managers));
+        desc.sanity_check()?;
+
+        Ok(desc)
     }
 }
 
@@ -941,7 +2882,153 @@ impl FromStr for DerivedCpfpDescriptor {
     fn from_str(s: &str) -> Result<DerivedCpfpDescriptor, Self::Err> {
         let desc: Descriptor<DerivedPublicKey> = FromStr::from_str(s)?;
 
-        Ok(DerivedCpfpDescriptor(desc))
+        check_cpfp_template(&desc)?;
+
+        Ok(DerivedCpfpDescriptor::from_inner(desc))
+    }
+}
+
+/// The Emergency (or "deep vault") miniscript descriptor.
+///
+/// Unlike the other descriptors it uses static raw public keys, as the Emergency address is
+/// reused for all the vaults. It is usually an N-of-N of the stakeholders' emergency keys, but
+/// may be any P2WSH Miniscript descriptor (see [EmergencyDescriptor::from_descriptor]).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EmergencyDescriptor(Descriptor<PublicKey>);
+
+impl EmergencyDescriptor {
+    /// Get the miniscript descriptor for the Emergency deep vault.
+    ///
+    /// It is an N-of-N between the stakeholders' emergency keys, so
+    /// `thresh(len(all_pubkeys), all_pubkeys)`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use revault_tx::{scripts, miniscript::bitcoin::{Network, PublicKey}};
+    /// use std::str::FromStr;
+    ///
+    /// let first_stakeholder = PublicKey::from_str("02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35").unwrap();
+    /// let second_stakeholder = PublicKey::from_str("02767e6dde4877dcbf64de8a45fe1a0575dfc6b0ed06648f1022412c172ebd875c").unwrap();
+    ///
+    /// let emergency_descriptor =
+    ///     scripts::EmergencyDescriptor::new(vec![first_stakeholder, second_stakeholder]).expect("Compiling descriptor");
+    /// println!("Emergency descriptor: {}", emergency_descriptor);
+    ///
+    /// let desc_str = emergency_descriptor.to_string();
+    /// assert_eq!(emergency_descriptor, scripts::EmergencyDescriptor::from_str(&desc_str).unwrap());
+    ///
+    /// println!("Emergency address: {}", emergency_descriptor.address(Network::Bitcoin));
+    /// ```
+    ///
+    /// # Errors
+    /// - If the given vector contains less than 2 public keys.
+    /// - If the policy compilation to miniscript failed, which should not happen (tm) and would be a
+    ///   bug.
+    pub fn new(stakeholders: Vec<PublicKey>) -> Result<EmergencyDescriptor, ScriptCreationError> {
+        deposit_desc_checks!(stakeholders);
+
+        let desc = EmergencyDescriptor(deposit_desc!(stakeholders));
+        desc.sanity_check()?;
+
+        Ok(desc)
+    }
+
+    /// Use an arbitrary descriptor for the Emergency deep vault, for deployments which don't
+    /// use a plain N-of-N of the stakeholders' keys (for instance with a timelocked recovery
+    /// path, or keys held by a third party).
+    ///
+    /// Note that the [EmergencySweepTransaction](crate::transactions::EmergencySweepTransaction)
+    /// signals for RBF and therefore can't satisfy a relative timelock.
+    ///
+    /// # Errors
+    /// - If the descriptor is not a P2WSH of a Miniscript (a `sortedmulti()` can be expressed as
+    ///   a `multi()` between the sorted keys).
+    /// - If it doesn't pass the [EmergencyDescriptor::sanity_check], for instance if spending it
+    ///   doesn't always require a signature.
+    pub fn from_descriptor(
+        desc: Descriptor<PublicKey>,
+    ) -> Result<EmergencyDescriptor, ScriptCreationError> {
+        if wsh_miniscript(&desc).is_none() {
+            return Err(ScriptCreationError::TemplateMismatch);
+        }
+
+        let desc = EmergencyDescriptor(desc);
+        desc.sanity_check()?;
+
+        Ok(desc)
+    }
+
+    pub fn inner(&self) -> &Descriptor<PublicKey> {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Descriptor<PublicKey> {
+        self.0
+    }
+
+    /// Get all the public keys used in this descriptor
+    pub fn keys(&self) -> Vec<PublicKey> {
+        wsh_miniscript(&self.0)
+            .expect("The Emergency descriptor is always a P2WSH Miniscript")
+            .iter_pk()
+            .collect()
+    }
+
+    /// Check this descriptor doesn't use the same public key twice, that it is safe and
+    /// non-malleable, and that its witness script and satisfaction are within the consensus
+    /// and standardness limits.
+    pub fn sanity_check(&self) -> Result<(), ScriptCreationError> {
+        if let Descriptor::Wsh(ref wsh) = self.0 {
+            if let WshInner::Ms(ref ms) = wsh.as_inner() {
+                check_duplicate_keys(ms.iter_pk())?;
+            }
+        }
+        check_miniscript_analysis(&self.0)?;
+        check_resource_limits(&self.0)
+    }
+
+    /// Get the BIP380 checksum of this descriptor, which is always part of its string
+    /// representation.
+    pub fn checksum(&self) -> String {
+        displayed_checksum(&self.0)
+    }
+
+    /// Get the Emergency address corresponding to this descriptor on the given network
+    pub fn address(&self, network: Network) -> EmergencyAddress {
+        EmergencyAddress(
+            self.0
+                .address(network)
+                .expect("The Emergency descriptor is always a P2WSH"),
+        )
+    }
+
+    /// Get the maximum size, in weight units, a satisfaction for an output of this descriptor
+    /// would cost.
+    pub fn max_sat_weight(&self) -> usize {
+        self.0
+            .max_satisfaction_weight()
+            .expect("It's a sane Script, checked at creation")
+    }
+
+    /// Get the minimum value of an output paying to this descriptor for it to not be dust
+    pub fn dust_limit(&self) -> Amount {
+        self.0.script_pubkey().dust_value()
+    }
+}
+
+impl Display for EmergencyDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for EmergencyDescriptor {
+    type Err = ScriptCreationError;
+
+    fn from_str(s: &str) -> Result<EmergencyDescriptor, Self::Err> {
+        let desc: Descriptor<PublicKey> = FromStr::from_str(s)?;
+
+        EmergencyDescriptor::from_descriptor(desc)
     }
 }
 
@@ -968,6 +3055,11 @@ impl EmergencyAddress {
     pub fn into_address(self) -> Address {
         self.0
     }
+
+    /// Get the network this address is valid for
+    pub fn network(&self) -> Network {
+        self.0.network
+    }
 }
 
 impl fmt::Display for EmergencyAddress {
@@ -994,16 +3086,18 @@ impl<'de> de::Deserialize<'de> for EmergencyAddress {
 mod tests {
 
     use super::{
-        CpfpDescriptor, DepositDescriptor, DerivedCpfpDescriptor, DerivedDepositDescriptor,
-        DerivedPublicKey, DerivedUnvaultDescriptor, ScriptCreationError, UnvaultDescriptor,
-        MAX_MANAGERS, MAX_STAKEHOLDERS,
+        descriptor_checksum, sort_keys, verify_descriptor_checksum, CpfpDescriptor, CsvValue,
+        DepositDescriptor, DerivationIndex, DerivedCpfpDescriptor, DerivedDepositDescriptor,
+        DerivedPublicKey, DerivedUnvaultDescriptor, EmergencyDescriptor, ParticipantsRole,
+        ScriptCreationError, ScriptPubKeysIter, UnvaultDescriptor, MAX_MANAGERS, MAX_STAKEHOLDERS,
     };
 
     use miniscript::{
-        bitcoin::{secp256k1, util::bip32, Network},
+        bitcoin::{secp256k1, util::bip32, Amount, Network, PublicKey, Script},
         descriptor::{DescriptorPublicKey, DescriptorXKey, Wildcard},
+        Descriptor, DescriptorTrait,
     };
-    use std::{iter::repeat_with, str::FromStr};
+    use std::{convert::TryFrom, iter::repeat_with, str::FromStr};
 
     fn rand_xpub<C: secp256k1::Signing>(
         rng: &mut fastrand::Rng,
@@ -1045,6 +3139,15 @@ mod tests {
             "[aaa/]02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35",
         )
         .unwrap_err();
+        // The derivation index is never hardened, but the origin path may be
+        DerivedPublicKey::from_str(
+            "[aabbccdd/48'/0'/0'/2'/0/1']02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35",
+        )
+        .unwrap_err();
+        DerivedPublicKey::from_str(
+            "[aabbccdd]02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35",
+        )
+        .unwrap_err();
         DerivedPublicKey::from_str(
             "[bbbbbbbb/]02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35",
         )
@@ -1087,6 +3190,14 @@ mod tests {
         )
         .unwrap();
 
+        let key_str = "[aabbccdd/48'/0'/0'/2'/0/42]02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35";
+        let derived_key = DerivedPublicKey::from_str(key_str).unwrap();
+        assert_eq!(
+            derived_key.origin.1,
+            bip32::DerivationPath::from_str("m/48'/0'/0'/2'/0/42").unwrap()
+        );
+        assert_eq!(derived_key.to_string(), key_str);
+
         let secp = secp256k1::Secp256k1::verification_only();
         let xpub = bip32::ExtendedPubKey::from_str("xpub6EWL35hY9uZZs5Ljt6J3G2ZK1Tu4GPVkFdeGvMknG3VmwVRHhtadCaw5hdRDBgrmx1nPVHWjGBb5xeuC1BfbJzjjcic2gNm1aA7ywWjj7G8").unwrap();
         let derived_xpub = xpub
@@ -1094,7 +3205,10 @@ mod tests {
             .unwrap();
         let derived_key = DerivedPublicKey {
             key: derived_xpub.public_key,
-            origin: (derived_xpub.parent_fingerprint, 42.into()),
+            origin: (
+                derived_xpub.parent_fingerprint,
+                bip32::DerivationPath::from(vec![42.into()]),
+            ),
         };
         assert_eq!(
             derived_key.to_string(),
@@ -1161,7 +3275,7 @@ mod tests {
                 second_cosig.clone(),
                 third_cosig.clone(),
             ],
-            128,
+            CsvValue::blocks(128),
         )
         .expect_err("Accepting a non wildcard stakeholder xpub");
         UnvaultDescriptor::new(
@@ -1173,7 +3287,7 @@ mod tests {
             vec![first_manager.clone(), invalid_man],
             1,
             vec![first_cosig.clone(), second_cosig.clone(), third_cosig],
-            128,
+            CsvValue::blocks(128),
         )
         .expect_err("Accepting a non wildcard manager xpub");
 
@@ -1191,7 +3305,7 @@ mod tests {
             vec![first_manager.clone(), second_manager.clone()],
             1,
             vec![xpub_first_cosig, xpub_second_cosig],
-            128,
+            CsvValue::blocks(128),
         )
         .expect("Refusing a non wildcard cosigning server xpub");
 
@@ -1208,7 +3322,7 @@ mod tests {
             vec![first_manager.clone(), second_manager.clone()],
             1,
             vec![xpub_first_cosig, xpub_second_cosig],
-            128,
+            CsvValue::blocks(128),
         )
         .expect("Refusing a wildcard cosigning server xpub");
 
@@ -1217,7 +3331,7 @@ mod tests {
             vec![first_manager.clone(), second_manager.clone()],
             1,
             vec![],
-            6789,
+            CsvValue::blocks(6789),
         )
         .expect("Refusing an Unvault descriptor without Cosigning Server");
 
@@ -1267,7 +3381,7 @@ mod tests {
             vec![first_manager.clone(), second_manager.clone()],
             1,
             vec![first_cosig, second_cosig],
-            128,
+            CsvValue::blocks(128),
         )
         .expect("Valid, with xpubs");
         DerivedUnvaultDescriptor::from_str(&unvault_desc.to_string())
@@ -1276,47 +3390,858 @@ mod tests {
             vec![raw_pk_a.clone(), raw_pk_b.clone()],
             vec![raw_pk_c.clone(), raw_pk_d.clone()],
             2,
-            vec![raw_pk_e, raw_pk_f],
-            1024,
+            vec![raw_pk_e, raw_pk_f],
+            CsvValue::blocks(1024),
+        )
+        .expect("Derived pubkeys");
+        UnvaultDescriptor::from_str(&der_unvault_desc.to_string())
+            .expect_err("FromStr on a derived descriptor");
+
+        // Same but without any cosigning server
+        let unvault_desc = UnvaultDescriptor::new(
+            vec![first_stakeholder.clone(), second_stakeholder.clone()],
+            vec![first_manager.clone(), second_manager.clone()],
+            2,
+            vec![],
+            CsvValue::blocks(128),
+        )
+        .expect("Valid, with xpubs");
+        DerivedUnvaultDescriptor::from_str(&unvault_desc.to_string())
+            .expect_err("FromStr on an xpub descriptor");
+        let der_unvault_desc = DerivedUnvaultDescriptor::new(
+            vec![raw_pk_a.clone(), raw_pk_b.clone()],
+            vec![raw_pk_c, raw_pk_d],
+            2,
+            vec![],
+            CsvValue::blocks(1024),
+        )
+        .expect("Derived pubkeys");
+        UnvaultDescriptor::from_str(&der_unvault_desc.to_string())
+            .expect_err("FromStr on a derived descriptor");
+
+        let cpfp_desc = CpfpDescriptor::new(vec![
+            first_stakeholder.clone(),
+            second_stakeholder.clone(),
+            third_stakeholder.clone(),
+        ])
+        .expect("Valid wildcard xpubs");
+        DerivedCpfpDescriptor::from_str(&cpfp_desc.to_string())
+            .expect_err("FromStr on an xpub descriptor");
+        let der_cpfp_desc =
+            DerivedCpfpDescriptor::new(vec![raw_pk_a, raw_pk_b]).expect("Derived pubkeys");
+        CpfpDescriptor::from_str(&der_cpfp_desc.to_string())
+            .expect_err("FromStr on a derived descriptor");
+    }
+
+    #[test]
+    fn deposit_descriptor_threshold() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let stakeholders: Vec<DescriptorPublicKey> =
+            (0..4).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+
+        DepositDescriptor::with_threshold(stakeholders.clone(), 0).unwrap_err();
+        DepositDescriptor::with_threshold(stakeholders.clone(), 5).unwrap_err();
+        DepositDescriptor::with_threshold(stakeholders[..1].to_vec(), 1).unwrap_err();
+
+        // An N-of-N is the same as the default deposit descriptor
+        assert_eq!(
+            DepositDescriptor::with_threshold(stakeholders.clone(), 4).unwrap(),
+            DepositDescriptor::new(stakeholders.clone()).unwrap()
+        );
+
+        let deposit_desc = DepositDescriptor::with_threshold(stakeholders, 3).unwrap();
+        let derived_desc = deposit_desc.derive(DerivationIndex::try_from(42).unwrap(), &secp);
+        assert_eq!(
+            derived_desc,
+            DerivedDepositDescriptor::with_threshold(derived_desc.keys(), 3).unwrap()
+        );
+    }
+
+    #[test]
+    fn descriptors_sanity_check() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        // The same xpub, used under two different derivation paths
+        let xpub = "xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx";
+        let first_key = DescriptorPublicKey::from_str(&format!("{}/0/*", xpub)).unwrap();
+        let second_key = DescriptorPublicKey::from_str(&format!("{}/1/*", xpub)).unwrap();
+        assert_eq!(
+            DepositDescriptor::new(vec![
+                first_key.clone(),
+                get_random_pubkey(&mut rng, &secp),
+                second_key.clone()
+            ]),
+            Err(ScriptCreationError::DuplicateKey)
+        );
+        assert_eq!(
+            CpfpDescriptor::new(vec![first_key, second_key]),
+            Err(ScriptCreationError::DuplicateKey)
+        );
+
+        // A sane configuration
+        let stakeholders: Vec<DescriptorPublicKey> = (0..10)
+            .map(|_| get_random_pubkey(&mut rng, &secp))
+            .collect();
+        let managers: Vec<DescriptorPublicKey> =
+            (0..5).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let cosigners: Vec<DescriptorPublicKey> = (0..10)
+            .map(|_| get_random_pubkey(&mut rng, &secp))
+            .collect();
+        let unvault_desc =
+            UnvaultDescriptor::new(stakeholders, managers, 3, cosigners, CsvValue::blocks(144))
+                .unwrap();
+        unvault_desc.sanity_check().unwrap();
+        unvault_desc
+            .derive(DerivationIndex::try_from(1).unwrap(), &secp)
+            .sanity_check()
+            .unwrap();
+    }
+
+    #[test]
+    fn descriptors_template_parsing() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let stakeholders: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let managers: Vec<DescriptorPublicKey> =
+            (0..2).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let cosigners: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+
+        let deposit_desc = DepositDescriptor::new(stakeholders.clone()).unwrap();
+        let unvault_desc = UnvaultDescriptor::new(
+            stakeholders.clone(),
+            managers.clone(),
+            1,
+            cosigners,
+            CsvValue::blocks(6),
+        )
+        .unwrap();
+        let unvault_desc_no_cosig = UnvaultDescriptor::new(
+            stakeholders,
+            managers.clone(),
+            2,
+            vec![],
+            CsvValue::blocks(6),
+        )
+        .unwrap();
+        let cpfp_desc = CpfpDescriptor::new(managers).unwrap();
+
+        // With and without checksum, and with an invalid one
+        let desc_str = deposit_desc.to_string();
+        assert_eq!(
+            DepositDescriptor::from_str(&desc_str).unwrap(),
+            deposit_desc
+        );
+        let no_checksum = desc_str.split('#').next().unwrap();
+        assert_eq!(
+            DepositDescriptor::from_str(no_checksum).unwrap(),
+            deposit_desc
+        );
+        DepositDescriptor::from_str(&format!("{}#aaaaaaaa", no_checksum)).unwrap_err();
+        assert_eq!(
+            UnvaultDescriptor::from_str(&unvault_desc.to_string()).unwrap(),
+            unvault_desc
+        );
+        assert_eq!(
+            UnvaultDescriptor::from_str(&unvault_desc_no_cosig.to_string()).unwrap(),
+            unvault_desc_no_cosig
+        );
+        assert_eq!(
+            CpfpDescriptor::from_str(&cpfp_desc.to_string()).unwrap(),
+            cpfp_desc
+        );
+
+        // Not the right template
+        assert_eq!(
+            UnvaultDescriptor::from_str(&deposit_desc.to_string()).unwrap_err(),
+            ScriptCreationError::TemplateMismatch
+        );
+        assert_eq!(
+            DepositDescriptor::from_str(&unvault_desc.to_string()).unwrap_err(),
+            ScriptCreationError::TemplateMismatch
+        );
+        assert_eq!(
+            CpfpDescriptor::from_str(&deposit_desc.to_string()).unwrap_err(),
+            ScriptCreationError::TemplateMismatch
+        );
+        assert_eq!(
+            CpfpDescriptor::from_str(&unvault_desc_no_cosig.to_string()).unwrap_err(),
+            ScriptCreationError::TemplateMismatch
+        );
+
+        let derived_unvault_desc =
+            unvault_desc.derive(DerivationIndex::try_from(7).unwrap(), &secp);
+        assert_eq!(
+            DerivedUnvaultDescriptor::from_str(&derived_unvault_desc.to_string()).unwrap(),
+            derived_unvault_desc
+        );
+        let derived_deposit_desc =
+            deposit_desc.derive(DerivationIndex::try_from(7).unwrap(), &secp);
+        assert_eq!(
+            DerivedUnvaultDescriptor::from_str(&derived_deposit_desc.to_string()).unwrap_err(),
+            ScriptCreationError::TemplateMismatch
+        );
+    }
+
+    #[test]
+    fn deposit_descriptor_recovery() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let stakeholders: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let recovery_keys: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+
+        DepositDescriptor::with_recovery(stakeholders.clone(), vec![], 1, CsvValue::blocks(52560))
+            .unwrap_err();
+        DepositDescriptor::with_recovery(
+            stakeholders.clone(),
+            recovery_keys.clone(),
+            0,
+            CsvValue::blocks(52560),
+        )
+        .unwrap_err();
+        DepositDescriptor::with_recovery(
+            stakeholders.clone(),
+            recovery_keys.clone(),
+            4,
+            CsvValue::blocks(52560),
+        )
+        .unwrap_err();
+        // Not in blocks
+        CsvValue::try_from(52560 | (1 << 22)).unwrap_err();
+
+        // All the recovery keys being required must not get them mixed up with the timelock
+        let deposit_desc = DepositDescriptor::with_recovery(
+            stakeholders.clone(),
+            recovery_keys.clone(),
+            3,
+            CsvValue::blocks(52560),
+        )
+        .unwrap();
+        assert_eq!(
+            DepositDescriptor::from_str(&deposit_desc.to_string()).unwrap(),
+            deposit_desc
+        );
+
+        let deposit_desc = DepositDescriptor::with_recovery(
+            stakeholders,
+            recovery_keys,
+            2,
+            CsvValue::blocks(52560),
+        )
+        .unwrap();
+        assert_eq!(
+            DepositDescriptor::from_str(&deposit_desc.to_string()).unwrap(),
+            deposit_desc
+        );
+        let derived_desc = deposit_desc.derive(DerivationIndex::try_from(3).unwrap(), &secp);
+        assert_eq!(
+            DerivedDepositDescriptor::from_str(&derived_desc.to_string()).unwrap(),
+            derived_desc
+        );
+    }
+
+    #[test]
+    fn deposit_descriptor_rotation() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let old_stakeholders: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let new_stakeholders: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+
+        let old_desc = DepositDescriptor::new(old_stakeholders.clone()).unwrap();
+        let rotation_desc = DepositDescriptor::rotation(
+            new_stakeholders.clone(),
+            old_stakeholders,
+            CsvValue::blocks(4032),
+        )
+        .unwrap();
+        let new_desc = DepositDescriptor::new(new_stakeholders).unwrap();
+        assert_eq!(
+            DepositDescriptor::from_str(&rotation_desc.to_string()).unwrap(),
+            rotation_desc
+        );
+
+        // The descriptors of the past epochs would typically be read back from a configuration
+        let epochs: Vec<DepositDescriptor> = [old_desc, rotation_desc, new_desc]
+            .iter()
+            .map(|desc| DepositDescriptor::from_str(&desc.to_string()).unwrap())
+            .collect();
+        let index = DerivationIndex::try_from(89).unwrap();
+        for (i, desc) in epochs.iter().enumerate() {
+            let spk = desc.derive(index, &secp).inner().script_pubkey();
+            assert_eq!(super::deposit_epoch(&epochs, &spk, index, &secp), Some(i));
+            assert_eq!(
+                super::deposit_epoch(&epochs, &spk, DerivationIndex::try_from(90).unwrap(), &secp),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn script_pubkeys_gap_limit() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let stakeholders: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let managers: Vec<DescriptorPublicKey> =
+            (0..2).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let deposit_desc = DepositDescriptor::new(stakeholders.clone()).unwrap();
+        let unvault_desc =
+            UnvaultDescriptor::new(stakeholders, managers, 2, vec![], CsvValue::blocks(6)).unwrap();
+
+        let start = DerivationIndex::try_from(10).unwrap();
+        let mut spks_iter = ScriptPubKeysIter::new(&deposit_desc, &unvault_desc, start, 5, &secp);
+        let watched: Vec<(DerivationIndex, Script, Script)> = spks_iter.by_ref().collect();
+        assert_eq!(watched.len(), 5);
+        for (i, (index, deposit_spk, unvault_spk)) in watched.into_iter().enumerate() {
+            assert_eq!(index, DerivationIndex::try_from(10 + i as u32).unwrap());
+            assert_eq!(
+                deposit_spk,
+                deposit_desc.derive(index, &secp).inner().script_pubkey()
+            );
+            assert_eq!(
+                unvault_spk,
+                unvault_desc.derive(index, &secp).inner().script_pubkey()
+            );
+        }
+        assert!(spks_iter.next().is_none());
+
+        // Finding a deposit extends the range to 5 indexes past it
+        spks_iter.mark_used(DerivationIndex::try_from(12).unwrap());
+        let indexes: Vec<DerivationIndex> = spks_iter.by_ref().map(|(index, _, _)| index).collect();
+        assert_eq!(
+            indexes,
+            (15..18)
+                .map(|i| DerivationIndex::try_from(i).unwrap())
+                .collect::<Vec<_>>()
+        );
+        // But not a deposit at a previous index
+        spks_iter.mark_used(DerivationIndex::try_from(11).unwrap());
+        assert!(spks_iter.next().is_none());
+
+        // We never go past the last unhardened index
+        let last = DerivationIndex::try_from((1 << 31) - 2).unwrap();
+        let spks_iter = ScriptPubKeysIter::new(&deposit_desc, &unvault_desc, last, 5, &secp);
+        assert_eq!(spks_iter.count(), 2);
+    }
+
+    #[test]
+    fn descriptors_strict_check() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let with_origin: Vec<DescriptorPublicKey> = [
+            "[aabbccdd/48'/0'/0'/2']xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*",
+            "[11223344/48'/0'/0'/2']xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*",
+        ]
+        .iter()
+        .map(|s| DescriptorPublicKey::from_str(s).unwrap())
+        .collect();
+        DepositDescriptor::new(with_origin.clone())
+            .unwrap()
+            .strict_check()
+            .unwrap();
+
+        // Keys without origin
+        let without_origin = vec![with_origin[0].clone(), get_random_pubkey(&mut rng, &secp)];
+        assert_eq!(
+            DepositDescriptor::new(without_origin)
+                .unwrap()
+                .strict_check(),
+            Err(ScriptCreationError::MissingKeyOrigin)
+        );
+
+        // Raw keys for the Cosigning Servers
+        let cosigners: Vec<DescriptorPublicKey> = [
+            "02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35",
+            "02767e6dde4877dcbf64de8a45fe1a0575dfc6b0ed06648f1022412c172ebd875c",
+        ]
+        .iter()
+        .map(|s| DescriptorPublicKey::from_str(s).unwrap())
+        .collect();
+        let managers = vec![get_random_pubkey(&mut rng, &secp)];
+        assert_eq!(
+            UnvaultDescriptor::new(with_origin, managers, 1, cosigners, CsvValue::blocks(144))
+                .unwrap()
+                .strict_check(),
+            Err(ScriptCreationError::NonWildcardKeys)
+        );
+    }
+
+    #[test]
+    fn unvault_desc_custom_spend_policy() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let stakeholders: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let (hot_a, hot_b, cold) = (
+            get_random_pubkey(&mut rng, &secp),
+            get_random_pubkey(&mut rng, &secp),
+            get_random_pubkey(&mut rng, &secp),
+        );
+
+        // Either both hot keys, or the cold one
+        let spend_policy = format!("or(and(pk({}),pk({})),pk({}))", hot_a, hot_b, cold);
+        let unvault_desc = UnvaultDescriptor::with_spend_policy(
+            stakeholders.clone(),
+            &spend_policy,
+            CsvValue::blocks(144),
+        )
+        .unwrap();
+        assert_eq!(unvault_desc.csv_value(), CsvValue::blocks(144));
+        assert_eq!(
+            UnvaultDescriptor::from_str(&unvault_desc.to_string()).unwrap(),
+            unvault_desc
+        );
+        let derived_desc = unvault_desc.derive(DerivationIndex::try_from(5).unwrap(), &secp);
+        assert_eq!(derived_desc.csv_value(), CsvValue::blocks(144));
+        assert_eq!(
+            DerivedUnvaultDescriptor::from_str(&derived_desc.to_string()).unwrap(),
+            derived_desc
+        );
+
+        // The CSV is ours to set
+        let spend_policy = format!("and(pk({}),older(12))", hot_a);
+        UnvaultDescriptor::with_spend_policy(
+            stakeholders.clone(),
+            &spend_policy,
+            CsvValue::blocks(144),
+        )
+        .unwrap_err();
+        let spend_policy = format!("and(pk({}),after(12))", hot_a);
+        UnvaultDescriptor::with_spend_policy(
+            stakeholders.clone(),
+            &spend_policy,
+            CsvValue::blocks(144),
+        )
+        .unwrap_err();
+        // It must use a key
+        let spend_policy =
+            "sha256(1f6f5e7e08c3b0f28e8c3f6b5c1a8e0b7d7a2c4f1e3d5b7a9c1e3f5a7b9d1f3a)";
+        UnvaultDescriptor::with_spend_policy(
+            stakeholders.clone(),
+            spend_policy,
+            CsvValue::blocks(144),
+        )
+        .unwrap_err();
+        // Invalid policy
+        UnvaultDescriptor::with_spend_policy(stakeholders, "or(pk(", CsvValue::blocks(144))
+            .unwrap_err();
+    }
+
+    #[test]
+    fn unvault_desc_spend_tiers() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let stakeholders: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let managers: Vec<DescriptorPublicKey> =
+            (0..5).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+
+        // All the main managers' keys after the short CSV, 1-of-2 backup keys after the long one
+        let all_managers = format!(
+            "thresh(3,pk({}),pk({}),pk({}))",
+            managers[0], managers[1], managers[2]
+        );
+        let backup_managers = format!("or(pk({}),pk({}))", managers[3], managers[4]);
+        let unvault_desc = UnvaultDescriptor::with_spend_tiers(
+            stakeholders.clone(),
+            &[
+                (backup_managers.as_str(), CsvValue::blocks(1008)),
+                (all_managers.as_str(), CsvValue::blocks(144)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(unvault_desc.csv_value(), CsvValue::blocks(144));
+        assert_eq!(
+            unvault_desc.csv_values(),
+            vec![CsvValue::blocks(144), CsvValue::blocks(1008)]
+        );
+        assert_eq!(
+            UnvaultDescriptor::from_str(&unvault_desc.to_string()).unwrap(),
+            unvault_desc
+        );
+        let derived_desc = unvault_desc.derive(DerivationIndex::try_from(5).unwrap(), &secp);
+        assert_eq!(
+            derived_desc.csv_values(),
+            vec![CsvValue::blocks(144), CsvValue::blocks(1008)]
+        );
+        assert_eq!(
+            DerivedUnvaultDescriptor::from_str(&derived_desc.to_string()).unwrap(),
+            derived_desc
+        );
+        assert_eq!(
+            unvault_desc
+                .spending_paths()
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "all 3 stakeholders",
+                "after 144 blocks, all 3 managers",
+                "after 1008 blocks, 1-of-2 managers"
+            ]
+        );
+
+        // A single tier is a custom spend policy
+        assert_eq!(
+            UnvaultDescriptor::with_spend_tiers(
+                stakeholders.clone(),
+                &[(all_managers.as_str(), CsvValue::blocks(144))]
+            )
+            .unwrap(),
+            UnvaultDescriptor::with_spend_policy(
+                stakeholders.clone(),
+                &all_managers,
+                CsvValue::blocks(144)
+            )
+            .unwrap()
+        );
+
+        // No tier, tiers with the same CSV, a timelocked policy
+        UnvaultDescriptor::with_spend_tiers(stakeholders.clone(), &[]).unwrap_err();
+        UnvaultDescriptor::with_spend_tiers(
+            stakeholders.clone(),
+            &[
+                (all_managers.as_str(), CsvValue::blocks(144)),
+                (backup_managers.as_str(), CsvValue::blocks(144)),
+            ],
+        )
+        .unwrap_err();
+        let timelocked = format!("and(pk({}),older(12))", managers[3]);
+        UnvaultDescriptor::with_spend_tiers(
+            stakeholders,
+            &[
+                (all_managers.as_str(), CsvValue::blocks(144)),
+                (timelocked.as_str(), CsvValue::blocks(1008)),
+            ],
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn descriptors_spending_paths() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let stakeholders: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let managers: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let cosigners: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let index = DerivationIndex::try_from(42).unwrap();
+
+        let unvault_desc = UnvaultDescriptor::new(
+            stakeholders.clone(),
+            managers.clone(),
+            2,
+            cosigners.clone(),
+            CsvValue::blocks(144),
+        )
+        .unwrap();
+        let paths = unvault_desc.spending_paths();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].csv(), None);
+        assert_eq!(paths[0].to_string(), "all 3 stakeholders");
+        assert_eq!(paths[1].csv(), Some(CsvValue::blocks(144)));
+        assert_eq!(
+            paths[1].to_string(),
+            "after 144 blocks, 2-of-3 managers + all 3 Cosigning Servers"
+        );
+        assert_eq!(
+            paths[1].signers()[0].role(),
+            Some(ParticipantsRole::Managers)
+        );
+        assert_eq!(paths[1].signers()[0].threshold(), 2);
+        assert_eq!(paths[1].signers()[1].keys().len(), 3);
+        let der_paths = unvault_desc.derive(index, &secp).spending_paths();
+        assert_eq!(
+            der_paths.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+            paths.iter().map(|p| p.to_string()).collect::<Vec<_>>()
+        );
+
+        let unvault_desc = UnvaultDescriptor::with_cosigners_threshold(
+            stakeholders.clone(),
+            managers[..2].to_vec(),
+            2,
+            cosigners.clone(),
+            2,
+            CsvValue::blocks(6),
+        )
+        .unwrap();
+        assert_eq!(
+            unvault_desc.spending_paths()[1].to_string(),
+            "after 6 blocks, both managers + 2-of-3 Cosigning Servers"
+        );
+
+        let deposit_desc = DepositDescriptor::new(stakeholders.clone()).unwrap();
+        let paths = deposit_desc.spending_paths();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].to_string(), "all 3 stakeholders");
+        let deposit_desc = DepositDescriptor::with_recovery(
+            stakeholders.clone(),
+            managers.clone(),
+            2,
+            CsvValue::blocks(52560),
         )
-        .expect("Derived pubkeys");
-        UnvaultDescriptor::from_str(&der_unvault_desc.to_string())
-            .expect_err("FromStr on a derived descriptor");
+        .unwrap();
+        let paths = deposit_desc.derive(index, &secp).spending_paths();
+        assert_eq!(paths[0].to_string(), "all 3 stakeholders");
+        assert_eq!(
+            paths[1].to_string(),
+            "after 52560 blocks, 2-of-3 recovery keys"
+        );
 
-        // Same but without any cosigning server
+        let cpfp_desc = CpfpDescriptor::new(managers[..2].to_vec()).unwrap();
+        assert_eq!(cpfp_desc.spending_paths()[0].to_string(), "1-of-2 managers");
+
+        // Groups of keys that don't span a whole participants set are not labeled
+        let (hot_a, hot_b, cold) = (&managers[0], &managers[1], &managers[2]);
+        let spend_policy = format!("or(and(pk({}),pk({})),pk({}))", hot_a, hot_b, cold);
+        let unvault_desc = UnvaultDescriptor::with_spend_policy(
+            stakeholders,
+            &spend_policy,
+            CsvValue::blocks(144),
+        )
+        .unwrap();
+        let paths = unvault_desc.spending_paths();
+        assert_eq!(paths.len(), 3);
+        assert!(paths[1..]
+            .iter()
+            .all(|p| p.csv() == Some(CsvValue::blocks(144))
+                && p.signers().len() == 1
+                && p.signers()[0].role().is_none()));
+        assert!(paths
+            .iter()
+            .any(|p| p.to_string() == format!("after 144 blocks, {}", cold)));
+        assert!(paths
+            .iter()
+            .any(|p| p.signers()[0].keys().len() == 2 && p.signers()[0].threshold() == 2));
+    }
+
+    #[test]
+    fn deterministic_key_ordering() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let stakeholders: Vec<DescriptorPublicKey> =
+            (0..4).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let managers: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let cosigners: Vec<DescriptorPublicKey> =
+            (0..4).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+
+        // Two participants configured with the keys in a different order
+        let configs: Vec<(
+            Vec<DescriptorPublicKey>,
+            Vec<DescriptorPublicKey>,
+            Vec<DescriptorPublicKey>,
+        )> = (0..2)
+            .map(|_| {
+                let (mut stks, mut mans, mut cosigs) =
+                    (stakeholders.clone(), managers.clone(), cosigners.clone());
+                rng.shuffle(&mut stks);
+                rng.shuffle(&mut mans);
+                rng.shuffle(&mut cosigs);
+                sort_keys(&mut stks);
+                sort_keys(&mut mans);
+                sort_keys(&mut cosigs);
+                (stks, mans, cosigs)
+            })
+            .collect();
+        let (first, second) = (&configs[0], &configs[1]);
+        assert_eq!(
+            DepositDescriptor::new(first.0.clone()).unwrap(),
+            DepositDescriptor::new(second.0.clone()).unwrap()
+        );
+        assert_eq!(
+            UnvaultDescriptor::new(
+                first.0.clone(),
+                first.1.clone(),
+                2,
+                first.2.clone(),
+                CsvValue::blocks(144)
+            )
+            .unwrap(),
+            UnvaultDescriptor::new(
+                second.0.clone(),
+                second.1.clone(),
+                2,
+                second.2.clone(),
+                CsvValue::blocks(144)
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            CpfpDescriptor::new(first.1.clone()).unwrap(),
+            CpfpDescriptor::new(second.1.clone()).unwrap()
+        );
+
+        // Concrete keys are sorted as per BIP67
+        let mut keys: Vec<DerivedPublicKey> = [
+            "[21212121/21]0372f4bb19ecf98d7849148b4f40375d2fcef624a1b56fef94489ad012bc11b4df",
+            "[10000000/1]036e7ac7a096270f676b53e9917942cf42c6fb9607e3bc09775b5209c908525e80",
+            "[ffffffff/4]03a02e93cf8c47b250075b0af61f96ebd10376c0aaa7635148e889cb2b51c96927",
+            "[fafafafa/21]02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35",
+        ]
+        .iter()
+        .map(|s| DerivedPublicKey::from_str(s).unwrap())
+        .collect();
+        sort_keys(&mut keys);
+        assert_eq!(
+            keys.iter()
+                .map(|k| k.origin.0.to_string())
+                .collect::<Vec<String>>(),
+            vec!["fafafafa", "10000000", "21212121", "ffffffff"]
+        );
+    }
+
+    #[test]
+    fn descriptors_checksum() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        // BIP380 test vectors
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        verify_descriptor_checksum("raw(deadbeef)#89f8spxm").unwrap();
+        assert_eq!(
+            verify_descriptor_checksum("raw(deadbeef)#89f8spxn"),
+            Err(ScriptCreationError::InvalidChecksum("89f8spxm".to_string()))
+        );
+        assert_eq!(
+            verify_descriptor_checksum("raw(deadbeef)"),
+            Err(ScriptCreationError::MissingChecksum)
+        );
+        assert_eq!(
+            descriptor_checksum("raw(deadbeef)é"),
+            Err(ScriptCreationError::InvalidDescriptorCharacter('é'))
+        );
+
+        let stakeholders: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let managers: Vec<DescriptorPublicKey> =
+            (0..2).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let deposit_desc = DepositDescriptor::new(stakeholders.clone()).unwrap();
         let unvault_desc = UnvaultDescriptor::new(
-            vec![first_stakeholder.clone(), second_stakeholder.clone()],
-            vec![first_manager.clone(), second_manager.clone()],
-            2,
+            stakeholders,
+            managers.clone(),
+            1,
             vec![],
-            128,
+            CsvValue::blocks(6),
         )
-        .expect("Valid, with xpubs");
-        DerivedUnvaultDescriptor::from_str(&unvault_desc.to_string())
-            .expect_err("FromStr on an xpub descriptor");
-        let der_unvault_desc = DerivedUnvaultDescriptor::new(
-            vec![raw_pk_a.clone(), raw_pk_b.clone()],
-            vec![raw_pk_c, raw_pk_d],
-            2,
-            vec![],
-            1024,
+        .unwrap();
+        let cpfp_desc = CpfpDescriptor::new(managers).unwrap();
+        let index = DerivationIndex::try_from(8).unwrap();
+
+        // Our own checksum and the one in the string representation always match
+        for (desc_str, checksum) in &[
+            (deposit_desc.to_string(), deposit_desc.checksum()),
+            (unvault_desc.to_string(), unvault_desc.checksum()),
+            (cpfp_desc.to_string(), cpfp_desc.checksum()),
+            (
+                deposit_desc.derive(index, &secp).to_string(),
+                deposit_desc.derive(index, &secp).checksum(),
+            ),
+            (
+                unvault_desc.derive(index, &secp).to_string(),
+                unvault_desc.derive(index, &secp).checksum(),
+            ),
+            (
+                cpfp_desc.derive(index, &secp).to_string(),
+                cpfp_desc.derive(index, &secp).checksum(),
+            ),
+        ] {
+            verify_descriptor_checksum(desc_str).unwrap();
+            let desc = desc_str.split('#').next().unwrap();
+            assert_eq!(&descriptor_checksum(desc).unwrap(), checksum);
+        }
+        // A corrupted descriptor is detected
+        let corrupted = deposit_desc.to_string().replacen("wsh", "sh", 1);
+        verify_descriptor_checksum(&corrupted).unwrap_err();
+    }
+
+    #[test]
+    fn emergency_descriptor() {
+        let keys: Vec<PublicKey> = [
+            "02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35",
+            "02767e6dde4877dcbf64de8a45fe1a0575dfc6b0ed06648f1022412c172ebd875c",
+            "0371cdea381b365ea159a3cf4f14029d1bff5b36b4cf12ac9e42be6955d2ed4ecf",
+        ]
+        .iter()
+        .map(|s| PublicKey::from_str(s).unwrap())
+        .collect();
+
+        EmergencyDescriptor::new(keys[..1].to_vec()).unwrap_err();
+
+        let emer_desc = EmergencyDescriptor::new(keys.clone()).unwrap();
+        assert_eq!(
+            emer_desc,
+            EmergencyDescriptor::from_str(&emer_desc.to_string()).unwrap()
+        );
+        let emer_addr = emer_desc.address(Network::Regtest);
+        assert!(emer_addr.address().script_pubkey().is_v0_p2wsh());
+        assert_eq!(emer_addr.network(), Network::Regtest);
+
+        assert_eq!(emer_desc.dust_limit(), Amount::from_sat(330));
+
+        // Not a P2WSH
+        EmergencyDescriptor::from_str(
+            "pkh(02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35)",
         )
-        .expect("Derived pubkeys");
-        UnvaultDescriptor::from_str(&der_unvault_desc.to_string())
-            .expect_err("FromStr on a derived descriptor");
+        .unwrap_err();
 
-        let cpfp_desc = CpfpDescriptor::new(vec![
-            first_stakeholder.clone(),
-            second_stakeholder.clone(),
-            third_stakeholder.clone(),
-        ])
-        .expect("Valid wildcard xpubs");
-        DerivedCpfpDescriptor::from_str(&cpfp_desc.to_string())
-            .expect_err("FromStr on an xpub descriptor");
-        let der_cpfp_desc =
-            DerivedCpfpDescriptor::new(vec![raw_pk_a, raw_pk_b]).expect("Derived pubkeys");
-        CpfpDescriptor::from_str(&der_cpfp_desc.to_string())
-            .expect_err("FromStr on a derived descriptor");
+        // Any P2WSH Miniscript is fine, as long as it requires a signature
+        let custom_desc = format!(
+            "wsh(or_d(multi(2,{},{}),and_v(v:pk({}),older(4032))))",
+            keys[0], keys[1], keys[2]
+        );
+        let emer_desc = EmergencyDescriptor::from_str(&custom_desc).unwrap();
+        assert_eq!(
+            emer_desc,
+            EmergencyDescriptor::from_descriptor(Descriptor::from_str(&custom_desc).unwrap())
+                .unwrap()
+        );
+        assert_eq!(
+            emer_desc,
+            EmergencyDescriptor::from_str(&emer_desc.to_string()).unwrap()
+        );
+        assert_eq!(emer_desc.keys(), keys);
+        assert!(emer_desc
+            .address(Network::Bitcoin)
+            .address()
+            .script_pubkey()
+            .is_v0_p2wsh());
+        assert_eq!(
+            EmergencyDescriptor::from_str(&format!("wsh(or_d(pk({}),older(4032)))", keys[0]))
+                .unwrap_err(),
+            ScriptCreationError::SiglessBranch
+        );
+        assert_eq!(
+            EmergencyDescriptor::from_str(&format!(
+                "wsh(and_v(v:pk({}),or_i(older(144),older(4032))))",
+                keys[0]
+            ))
+            .unwrap_err(),
+            ScriptCreationError::MalleableScript
+        );
+        EmergencyDescriptor::from_str(&format!("wsh(sortedmulti(2,{},{}))", keys[0], keys[1]))
+            .unwrap_err();
+        EmergencyDescriptor::from_str(&format!("wsh(multi(2,{},{}))", keys[0], keys[0]))
+            .unwrap_err();
     }
 
     #[test]
@@ -1376,7 +4301,7 @@ mod tests {
                 second_cosig.clone(),
                 third_cosig.clone(),
             ],
-            2018,
+            CsvValue::blocks(2018),
         )
         .expect("Valid, with xpubs");
         let mut xpubs = unvault_desc.xpubs();
@@ -1395,21 +4320,22 @@ mod tests {
         assert_eq!(xpubs, expected_xpubs);
 
         // Now do the same with derived descriptors
-        let deriv_index: bip32::ChildNumber = 420121.into();
+        let deriv_index = DerivationIndex::try_from(420121).unwrap();
         let secp = secp256k1::Secp256k1::verification_only();
 
         fn xpub_to_key<C: secp256k1::Verification>(
             secp: &secp256k1::Secp256k1<C>,
             xpub: DescriptorPublicKey,
-            index: bip32::ChildNumber,
+            index: DerivationIndex,
         ) -> DerivedPublicKey {
+            let index = bip32::ChildNumber::from(index);
             let xpub = match xpub {
                 DescriptorPublicKey::XPub(xpub) => xpub.xkey,
                 _ => unreachable!(),
             };
             DerivedPublicKey {
                 key: xpub.derive_pub(secp, &[index]).unwrap().public_key,
-                origin: (xpub.fingerprint(), index),
+                origin: (xpub.fingerprint(), vec![index].into()),
             }
         }
 
@@ -1497,7 +4423,7 @@ mod tests {
                 managers.clone(),
                 *thresh,
                 cosigners.clone(),
-                18,
+                CsvValue::blocks(18),
             )
             .expect(&format!(
                 "Unvault descriptors creation error with ({}, {})",
@@ -1535,7 +4461,7 @@ mod tests {
                     get_random_pubkey(&mut rng, &secp),
                     get_random_pubkey(&mut rng, &secp)
                 ],
-                6
+                CsvValue::blocks(6)
             )
             .unwrap_err()
             .to_string(),
@@ -1546,26 +4472,31 @@ mod tests {
             UnvaultDescriptor::new(
                 vec![get_random_pubkey(&mut rng, &secp)],
                 vec![get_random_pubkey(&mut rng, &secp)],
-                1,
+                2,
                 vec![get_random_pubkey(&mut rng, &secp)],
-                4194305
+                CsvValue::blocks(6)
             )
             .unwrap_err()
             .to_string(),
             ScriptCreationError::BadParameters.to_string()
         );
 
+        // A CSV too large for a number of blocks is time-based, and disabled ones are refused
         assert_eq!(
-            UnvaultDescriptor::new(
-                vec![get_random_pubkey(&mut rng, &secp)],
-                vec![get_random_pubkey(&mut rng, &secp)],
-                2,
-                vec![get_random_pubkey(&mut rng, &secp)],
-                4194305
-            )
-            .unwrap_err()
-            .to_string(),
-            ScriptCreationError::BadParameters.to_string()
+            CsvValue::try_from(4194305).unwrap_err(),
+            ScriptCreationError::InvalidCsv(4194305)
+        );
+        UnvaultDescriptor::new(
+            vec![get_random_pubkey(&mut rng, &secp)],
+            vec![get_random_pubkey(&mut rng, &secp)],
+            1,
+            vec![get_random_pubkey(&mut rng, &secp)],
+            CsvValue::from_bip68(4194305).unwrap(),
+        )
+        .expect("A time-based CSV is fine");
+        assert_eq!(
+            CsvValue::from_bip68(1 << 31 | 6).unwrap_err(),
+            ScriptCreationError::InvalidCsv(1 << 31 | 6)
         );
 
         // Maximum N-of-N
@@ -1579,7 +4510,7 @@ mod tests {
             .collect::<Vec<DescriptorPublicKey>>();
         assert_eq!(
             DepositDescriptor::new(participants).unwrap_err(),
-            ScriptCreationError::BadParameters
+            ScriptCreationError::TooManyStakeholders(MAX_STAKEHOLDERS + 1)
         );
 
         // Maximum 1-of-N
@@ -1593,7 +4524,7 @@ mod tests {
             .collect::<Vec<DescriptorPublicKey>>();
         assert_eq!(
             CpfpDescriptor::new(managers).unwrap_err(),
-            ScriptCreationError::BadParameters
+            ScriptCreationError::TooManyManagers(MAX_MANAGERS + 1)
         );
 
         // Maximum non-managers for 2 managers
@@ -1606,7 +4537,8 @@ mod tests {
         let cosigners = (0..MAX_STAKEHOLDERS)
             .map(|_| get_random_pubkey(&mut rng, &secp))
             .collect::<Vec<DescriptorPublicKey>>();
-        UnvaultDescriptor::new(stakeholders, managers, 2, cosigners, 145).unwrap();
+        UnvaultDescriptor::new(stakeholders, managers, 2, cosigners, CsvValue::blocks(145))
+            .unwrap();
 
         // Now hit the limit
         let stakeholders = (0..MAX_STAKEHOLDERS + 1)
@@ -1619,8 +4551,9 @@ mod tests {
             .map(|_| get_random_pubkey(&mut rng, &secp))
             .collect::<Vec<DescriptorPublicKey>>();
         assert_eq!(
-            UnvaultDescriptor::new(stakeholders, managers, 2, cosigners, 32).unwrap_err(),
-            ScriptCreationError::BadParameters
+            UnvaultDescriptor::new(stakeholders, managers, 2, cosigners, CsvValue::blocks(32))
+                .unwrap_err(),
+            ScriptCreationError::TooManyStakeholders(MAX_STAKEHOLDERS + 1)
         );
     }
 
@@ -1650,6 +4583,174 @@ mod tests {
         .expect("P2WSH (mainnet)");
     }
 
+    #[test]
+    fn unvault_desc_cosigners_threshold() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let n_stks = 4;
+        let stakes: Vec<DescriptorPublicKey> = (0..n_stks)
+            .map(|_| get_random_pubkey(&mut rng, &secp))
+            .collect();
+        let cosigs: Vec<DescriptorPublicKey> = (0..n_stks)
+            .map(|_| get_random_pubkey(&mut rng, &secp))
+            .collect();
+
+        UnvaultDescriptor::with_cosigners_threshold(
+            stakes.clone(),
+            stakes.clone(),
+            1,
+            cosigs.clone(),
+            0,
+            CsvValue::blocks(6),
+        )
+        .unwrap_err();
+        UnvaultDescriptor::with_cosigners_threshold(
+            stakes.clone(),
+            stakes.clone(),
+            1,
+            cosigs.clone(),
+            n_stks + 1,
+            CsvValue::blocks(6),
+        )
+        .unwrap_err();
+        UnvaultDescriptor::with_cosigners_threshold(
+            stakes.clone(),
+            stakes.clone(),
+            1,
+            vec![],
+            1,
+            CsvValue::blocks(6),
+        )
+        .unwrap_err();
+
+        // Without cosigners, or with all of them, it's the same as the default descriptor
+        let mans: Vec<DescriptorPublicKey> =
+            (0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        assert_eq!(
+            UnvaultDescriptor::with_cosigners_threshold(
+                stakes.clone(),
+                mans.clone(),
+                2,
+                vec![],
+                0,
+                CsvValue::blocks(6)
+            )
+            .unwrap(),
+            UnvaultDescriptor::new(stakes.clone(), mans.clone(), 2, vec![], CsvValue::blocks(6))
+                .unwrap()
+        );
+        let unvault_desc = UnvaultDescriptor::with_cosigners_threshold(
+            stakes.clone(),
+            mans.clone(),
+            2,
+            cosigs.clone(),
+            n_stks,
+            CsvValue::blocks(6),
+        )
+        .unwrap();
+        assert_eq!(
+            unvault_desc,
+            UnvaultDescriptor::new(
+                stakes.clone(),
+                mans.clone(),
+                2,
+                cosigs.clone(),
+                CsvValue::blocks(6)
+            )
+            .unwrap()
+        );
+        assert_eq!(unvault_desc.managers_threshold(), Some(2));
+        assert!(unvault_desc.cosigners_threshold().is_none());
+
+        // We can tell the managers and the cosigners thresholds apart, even if there are as many
+        // managers as cosigners.
+        for n_mans in 1..6 {
+            let mans: Vec<DescriptorPublicKey> = (0..n_mans)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect();
+
+            for mans_thresh in 1..=n_mans {
+                for cosigs_thresh in 1..=n_stks {
+                    let unvault_desc = UnvaultDescriptor::with_cosigners_threshold(
+                        stakes.clone(),
+                        mans.clone(),
+                        mans_thresh,
+                        cosigs.clone(),
+                        cosigs_thresh,
+                        CsvValue::blocks(6),
+                    )
+                    .unwrap();
+                    let derived_desc =
+                        unvault_desc.derive(DerivationIndex::try_from(42).unwrap(), &secp);
+
+                    let expected_mans_thresh = if mans_thresh < n_mans {
+                        Some(mans_thresh)
+                    } else {
+                        None
+                    };
+                    let expected_cosigs_thresh = if cosigs_thresh < n_stks {
+                        Some(cosigs_thresh)
+                    } else {
+                        None
+                    };
+                    assert_eq!(unvault_desc.managers_threshold(), expected_mans_thresh);
+                    assert_eq!(derived_desc.managers_threshold(), expected_mans_thresh);
+                    assert_eq!(unvault_desc.cosigners_threshold(), expected_cosigs_thresh);
+                    assert_eq!(derived_desc.cosigners_threshold(), expected_cosigs_thresh);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unvault_desc_participants_keys() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let random_keys = |n: usize, rng: &mut fastrand::Rng| -> Vec<DescriptorPublicKey> {
+            (0..n).map(|_| get_random_pubkey(rng, &secp)).collect()
+        };
+        let same_keys = |a: &[DescriptorPublicKey], b: &[DescriptorPublicKey]| {
+            a.len() == b.len() && a.iter().all(|k| b.contains(k))
+        };
+        let stakes = random_keys(4, &mut rng);
+        let mans = random_keys(3, &mut rng);
+        let cosigs = random_keys(4, &mut rng);
+
+        let desc = UnvaultDescriptor::new(
+            stakes.clone(),
+            mans.clone(),
+            2,
+            cosigs.clone(),
+            CsvValue::blocks(144),
+        )
+        .unwrap();
+        assert!(same_keys(&desc.stakeholders(), &stakes));
+        assert!(same_keys(&desc.managers(), &mans));
+        assert!(same_keys(&desc.cosigners(), &cosigs));
+        assert_eq!(desc.csv_value(), CsvValue::blocks(144));
+
+        let der_desc = desc.derive(DerivationIndex::try_from(345678).unwrap(), &secp);
+        assert_eq!(der_desc.stakeholders().len(), stakes.len());
+        assert_eq!(der_desc.managers().len(), mans.len());
+        assert_eq!(der_desc.cosigners().len(), cosigs.len());
+        assert_eq!(der_desc.csv_value(), CsvValue::blocks(144));
+
+        // Without Cosigning Servers
+        let desc = UnvaultDescriptor::new(
+            stakes.clone(),
+            mans.clone(),
+            2,
+            vec![],
+            CsvValue::blocks(144),
+        )
+        .unwrap();
+        assert!(same_keys(&desc.stakeholders(), &stakes));
+        assert!(same_keys(&desc.managers(), &mans));
+        assert!(desc.cosigners().is_empty());
+    }
+
     #[test]
     fn unvault_desc_managers_threshold() {
         let secp = secp256k1::Secp256k1::new();
@@ -1671,25 +4772,38 @@ mod tests {
 
             for t in (1..n_mans - 1).step_by(2) {
                 assert_eq!(
-                    UnvaultDescriptor::new(stakes.clone(), mans.clone(), t, cosigs.clone(), 6)
-                        .unwrap()
-                        .derive(bip32::ChildNumber::from(345678), &secp)
-                        .managers_threshold()
-                        .unwrap(),
+                    UnvaultDescriptor::new(
+                        stakes.clone(),
+                        mans.clone(),
+                        t,
+                        cosigs.clone(),
+                        CsvValue::blocks(6)
+                    )
+                    .unwrap()
+                    .derive(DerivationIndex::try_from(345678).unwrap(), &secp)
+                    .managers_threshold()
+                    .unwrap(),
                     t
                 );
             }
-            assert!(UnvaultDescriptor::new(
+            let unvault_desc = UnvaultDescriptor::new(
                 stakes.clone(),
                 mans.clone(),
                 n_mans,
                 cosigs.clone(),
-                6
+                CsvValue::blocks(6),
             )
-            .unwrap()
-            .derive(bip32::ChildNumber::from(345678), &secp)
-            .managers_threshold()
-            .is_none(),);
+            .unwrap();
+            // The thresholds of the managers and Cosigning Servers must not get mixed up
+            assert_eq!(
+                UnvaultDescriptor::from_str(&unvault_desc.to_string()).unwrap(),
+                unvault_desc
+            );
+            assert_eq!(unvault_desc.cosigners_threshold(), None);
+            assert!(unvault_desc
+                .derive(DerivationIndex::try_from(345678).unwrap(), &secp)
+                .managers_threshold()
+                .is_none(),);
         }
 
         // Large setups
@@ -1708,10 +4822,16 @@ mod tests {
 
             for t in (1..n_mans - 1).step_by(4) {
                 assert_eq!(
-                    UnvaultDescriptor::new(stakes.clone(), mans.clone(), t, cosigs.clone(), 6)
-                        .unwrap()
-                        .managers_threshold()
-                        .unwrap(),
+                    UnvaultDescriptor::new(
+                        stakes.clone(),
+                        mans.clone(),
+                        t,
+                        cosigs.clone(),
+                        CsvValue::blocks(6)
+                    )
+                    .unwrap()
+                    .managers_threshold()
+                    .unwrap(),
                     t
                 );
             }
@@ -1720,7 +4840,7 @@ mod tests {
                 mans.clone(),
                 n_mans,
                 cosigs.clone(),
-                6
+                CsvValue::blocks(6)
             )
             .unwrap()
             .managers_threshold()
@@ -1743,10 +4863,16 @@ mod tests {
 
             for t in (1..n_mans - 1).step_by(3) {
                 assert_eq!(
-                    UnvaultDescriptor::new(stakes.clone(), mans.clone(), t, cosigs.clone(), 6)
-                        .unwrap()
-                        .managers_threshold()
-                        .unwrap(),
+                    UnvaultDescriptor::new(
+                        stakes.clone(),
+                        mans.clone(),
+                        t,
+                        cosigs.clone(),
+                        CsvValue::blocks(6)
+                    )
+                    .unwrap()
+                    .managers_threshold()
+                    .unwrap(),
                     t
                 );
             }
@@ -1755,11 +4881,192 @@ mod tests {
                 mans.clone(),
                 n_mans,
                 cosigs.clone(),
-                6
+                CsvValue::blocks(6)
             )
             .unwrap()
             .managers_threshold()
             .is_none(),);
         }
     }
+
+    #[test]
+    fn descriptor_derive_range() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let stakeholders = vec![
+            // Without origin, with a derivation path
+            DescriptorPublicKey::from_str("xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/0/*").unwrap(),
+            // With origin and a derivation path
+            DescriptorPublicKey::from_str("[aabbccdd/48'/1']xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/2/3/*").unwrap(),
+            // Directly from master
+            DescriptorPublicKey::from_str("xpub6EWL35hY9uZZs5Ljt6J3G2ZK1Tu4GPVkFdeGvMknG3VmwVRHhtadCaw5hdRDBgrmx1nPVHWjGBb5xeuC1BfbJzjjcic2gNm1aA7ywWjj7G8/*").unwrap(),
+        ];
+        let deposit_descriptor = DepositDescriptor::new(stakeholders).unwrap();
+
+        let (start, end) = (
+            DerivationIndex::try_from(5).unwrap(),
+            DerivationIndex::try_from(25).unwrap(),
+        );
+        let derived = deposit_descriptor.derive_range(start, end, &secp);
+        assert_eq!(derived.len(), 20);
+        for (i, der_desc) in (5..25).zip(derived.iter()) {
+            assert_eq!(
+                der_desc,
+                &deposit_descriptor.derive(DerivationIndex::try_from(i).unwrap(), &secp)
+            );
+        }
+
+        // The full path from the master xpub is recorded for each derived key
+        let origins: Vec<String> = derived[0]
+            .keys()
+            .into_iter()
+            .map(|k| {
+                let key_str = k.to_string();
+                key_str[..key_str.find(']').unwrap() + 1].to_string()
+            })
+            .collect();
+        assert!(origins.contains(&"[aabbccdd/48'/1'/2/3/5]".to_string()));
+        assert_eq!(derived[0].derivation_index(), start);
+
+        let spks = deposit_descriptor.script_pubkeys(start, end, &secp);
+        assert_eq!(spks.len(), 20);
+        assert_eq!(spks[0], derived[0].inner().script_pubkey());
+
+        assert!(deposit_descriptor
+            .derive_range(end, start, &secp)
+            .is_empty());
+    }
+
+    #[test]
+    fn derived_descriptor_index() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let stakeholders = vec![
+            DescriptorPublicKey::from_str("xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*").unwrap(),
+            DescriptorPublicKey::from_str("xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*").unwrap(),
+        ];
+        let managers = vec![DescriptorPublicKey::from_str("xpub6Br1DUfrzxTVGo1sanuKDCUmSxDfLRrxLQBqpMqygkQLkQWodoyvvGtUV8Rp3r6d6BNYvedBSU8c7whhn2U8haRVxsWwuQiZ9LoFp7jXPQA/*").unwrap()];
+        // Non-wildcard cosigners keys, one whose origin ends with a hardened step and one with an
+        // unhardened derivation path.
+        let cosigners = vec![
+            DescriptorPublicKey::from_str("[aabbccdd/48'/1'/0']xpub6Duq1ob3cQ8Wxees2fTGNK2wTsVjgTPQcKJiPquXY2rQJTDjeCxkXFxTCGhcunFDt26Ddz45KQu7pbLmmUGG2PXTRVx3iDpBPEhdrijJf4U").unwrap(),
+            DescriptorPublicKey::from_str("xpub6EWL35hY9uZZs5Ljt6J3G2ZK1Tu4GPVkFdeGvMknG3VmwVRHhtadCaw5hdRDBgrmx1nPVHWjGBb5xeuC1BfbJzjjcic2gNm1aA7ywWjj7G8/1000").unwrap(),
+        ];
+        let unvault_descriptor =
+            UnvaultDescriptor::new(stakeholders, managers, 1, cosigners, CsvValue::blocks(6))
+                .unwrap();
+
+        // The derivation index is the one the descriptor was derived at, whatever the paths of
+        // the non-wildcard keys.
+        for index in [0, 42, 1001].iter() {
+            let index = DerivationIndex::try_from(*index).unwrap();
+            let der_desc = unvault_descriptor.derive(index, &secp);
+            assert_eq!(der_desc.derivation_index(), index);
+            assert!(unvault_descriptor
+                .derive_range(
+                    index,
+                    DerivationIndex::try_from(u32::from(index) + 3).unwrap(),
+                    &secp
+                )
+                .iter()
+                .zip(u32::from(index)..)
+                .all(|(d, i)| d.derivation_index() == DerivationIndex::try_from(i).unwrap()));
+        }
+    }
+
+    #[test]
+    fn csv_value() {
+        let csv = CsvValue::try_from(144).unwrap();
+        assert_eq!(csv, CsvValue::blocks(144));
+        assert_eq!(csv.sequence(), 144);
+        assert_eq!(u32::from(csv), 144);
+        assert_eq!(csv.to_string(), "144 blocks");
+        assert_eq!(
+            CsvValue::try_from(0xff_ff).unwrap(),
+            CsvValue::blocks(u16::MAX)
+        );
+
+        // Disabled, time-based, or with bits set outside of the mask
+        for invalid in &[1 << 31 | 144, 1 << 22 | 144, 0x01_00_00, 1 << 23 | 6] {
+            assert_eq!(
+                CsvValue::try_from(*invalid).unwrap_err(),
+                ScriptCreationError::InvalidCsv(*invalid)
+            );
+        }
+
+        // Time-based timelocks must be explicitly asked for
+        let time_csv = CsvValue::time_intervals(144);
+        assert!(time_csv.is_time_based() && !csv.is_time_based());
+        assert_eq!(time_csv.value(), 144);
+        assert_eq!(time_csv.sequence(), 1 << 22 | 144);
+        assert_eq!(time_csv.to_string(), "73728 seconds");
+        assert_eq!(CsvValue::from_bip68(1 << 22 | 144).unwrap(), time_csv);
+        assert_eq!(CsvValue::from_bip68(144).unwrap(), csv);
+        for invalid in &[1 << 31 | 144, 0x01_00_00, 1 << 23 | 6] {
+            CsvValue::from_bip68(*invalid).unwrap_err();
+        }
+
+        // The Unvault delay may be time-based
+        let secp = secp256k1::Secp256k1::signing_only();
+        let mut rng = fastrand::Rng::new();
+        let stakeholders: Vec<DescriptorPublicKey> =
+            (0..2).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let managers: Vec<DescriptorPublicKey> =
+            (0..4).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let time_unvault_desc = UnvaultDescriptor::new(
+            stakeholders.clone(),
+            managers[..2].to_vec(),
+            2,
+            vec![],
+            time_csv,
+        )
+        .unwrap();
+        assert_eq!(time_unvault_desc.csv_value(), time_csv);
+        assert_eq!(
+            UnvaultDescriptor::from_str(&time_unvault_desc.to_string()).unwrap(),
+            time_unvault_desc
+        );
+        assert_eq!(
+            time_unvault_desc.spending_paths()[1].to_string(),
+            "after 73728 seconds, both managers"
+        );
+
+        // But the spending tiers can't mix units
+        let (first_tier, second_tier) = (
+            format!("and(pk({}),pk({}))", managers[0], managers[1]),
+            format!("and(pk({}),pk({}))", managers[2], managers[3]),
+        );
+        UnvaultDescriptor::with_spend_tiers(
+            stakeholders.clone(),
+            &[
+                (first_tier.as_str(), CsvValue::blocks(144)),
+                (second_tier.as_str(), CsvValue::time_intervals(2_000)),
+            ],
+        )
+        .unwrap_err();
+        UnvaultDescriptor::with_spend_tiers(
+            stakeholders.clone(),
+            &[
+                (first_tier.as_str(), CsvValue::time_intervals(144)),
+                (second_tier.as_str(), CsvValue::time_intervals(2_000)),
+            ],
+        )
+        .unwrap();
+
+        // We can't be given a descriptor with an invalid CSV
+        let unvault_desc = UnvaultDescriptor::new(
+            stakeholders,
+            managers[..2].to_vec(),
+            2,
+            vec![],
+            CsvValue::blocks(144),
+        )
+        .unwrap();
+        let desc_str = unvault_desc.to_string();
+        let desc_str = desc_str.split('#').next().unwrap();
+        assert!(desc_str.contains("older(144)"));
+        let non_minimal = desc_str.replace("older(144)", &format!("older({})", 1 << 23 | 144));
+        assert_eq!(
+            UnvaultDescriptor::from_str(&non_minimal).unwrap_err(),
+            ScriptCreationError::InvalidCsv(1 << 23 | 144)
+        );
+    }
 }
@@ -0,0 +1,349 @@
+//! # Test vectors
+//!
+//! Deterministically generate, out of a seed, the descriptors of a Revault deployment and all
+//! the transactions of a vault at each step of their signing. Other implementations can use them
+//! to check they create byte-for-byte the same scripts and transactions as this crate.
+//!
+//! The master key of each participant is created from the first 32 bytes of
+//! `SHA256(seed || role || index)`, where `role` is one of `stakeholder`, `manager`, `cosigner`
+//! or `cpfp` and `index` is the 4-bytes big-endian index of the participant in its role. All the
+//! descriptors use the xpubs of these master keys with no origin and an unhardened wildcard. The
+//! deposit outpoint is the first output of the transaction whose id is `SHA256d(seed)`.
+//!
+//! ECDSA signatures are deterministic (RFC6979), so are the signed PSBTs.
+
+use crate::{
+    error::Error,
+    scripts::{
+        CpfpDescriptor, CsvValue, DepositDescriptor, DerivationIndex, EmergencyAddress,
+        UnvaultDescriptor,
+    },
+    transactions::{cancel_feerates, transaction_chain, RevaultTransaction, SpendTransaction},
+    txins::RevaultTxIn,
+    txouts::{RevaultTxOut, SpendTxOut},
+};
+
+use miniscript::{
+    bitcoin::{
+        hashes::{hex::ToHex, sha256, sha256d, Hash},
+        secp256k1,
+        util::bip32,
+        Address, Amount, Network, OutPoint, Script, SigHashType, TxOut, Txid,
+    },
+    descriptor::{DescriptorPublicKey, DescriptorXKey, Wildcard},
+};
+
+use std::convert::TryFrom;
+
+/// The number of stakeholders in the test vectors deployment
+pub const N_STAKEHOLDERS: usize = 3;
+/// The number of managers in the test vectors deployment
+pub const N_MANAGERS: usize = 3;
+/// The number of managers required to sign a Spend in the test vectors deployment
+pub const MANAGERS_THRESHOLD: usize = 2;
+/// The Unvault CSV of the test vectors deployment
+pub const CSV: CsvValue = CsvValue::blocks(144);
+/// The derivation index of the vault of the test vectors
+pub const DERIVATION_INDEX: u32 = 42;
+/// The value of the deposit of the test vectors, in satoshis
+pub const DEPOSIT_VALUE: u64 = 100_000_000;
+/// The fees paid by the Spend transaction of the test vectors, in satoshis
+pub const SPEND_FEES: u64 = 10_000;
+
+/// The PSBTs of a transaction at each step of its signing, and the final network transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionVectors {
+    /// The kind of transaction, such as "unvault" or "cancel_20"
+    pub name: String,
+    /// The base64-encoded PSBT as created
+    pub unsigned_psbt: String,
+    /// The base64-encoded PSBT signed by the first signer only
+    pub partially_signed_psbt: String,
+    /// The base64-encoded PSBT signed by all the signers
+    pub signed_psbt: String,
+    /// The hex-encoded finalized network transaction
+    pub final_tx: String,
+}
+
+/// The descriptors of a deployment and the transactions of one of its vaults, as generated by
+/// [generate].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestVectors {
+    /// The hex-encoded seed the vectors were generated from
+    pub seed: String,
+    /// The Deposit descriptor
+    pub deposit_descriptor: String,
+    /// The Unvault descriptor
+    pub unvault_descriptor: String,
+    /// The CPFP descriptor
+    pub cpfp_descriptor: String,
+    /// The Emergency address
+    pub emergency_address: String,
+    /// The derivation index of the vault
+    pub derivation_index: u32,
+    /// The deposit outpoint
+    pub deposit_outpoint: OutPoint,
+    /// The deposit value, in satoshis
+    pub deposit_value: u64,
+    /// The deposit address, on mainnet
+    pub deposit_address: String,
+    /// The Unvault, Cancel(s), Emergency, Unvault Emergency and Spend transactions, in this order
+    pub transactions: Vec<TransactionVectors>,
+}
+
+impl TestVectors {
+    /// Get the test vectors as JSON, for implementations in other languages.
+    pub fn to_json(&self) -> serde_json::Value {
+        let transactions: Vec<serde_json::Value> = self
+            .transactions
+            .iter()
+            .map(|tx| {
+                serde_json::json!({
+                    "name": tx.name,
+                    "unsigned_psbt": tx.unsigned_psbt,
+                    "partially_signed_psbt": tx.partially_signed_psbt,
+                    "signed_psbt": tx.signed_psbt,
+                    "final_tx": tx.final_tx,
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "seed": self.seed,
+            "deposit_descriptor": self.deposit_descriptor,
+            "unvault_descriptor": self.unvault_descriptor,
+            "cpfp_descriptor": self.cpfp_descriptor,
+            "emergency_address": self.emergency_address,
+            "derivation_index": self.derivation_index,
+            "deposit_outpoint": self.deposit_outpoint.to_string(),
+            "deposit_value": self.deposit_value,
+            "deposit_address": self.deposit_address,
+            "transactions": transactions,
+        })
+    }
+}
+
+// The master private keys of the participants with this role
+fn master_keys(seed: &[u8], role: &str, n: usize) -> Vec<bip32::ExtendedPrivKey> {
+    (0..n as u32)
+        .map(|i| {
+            let mut preimage = seed.to_vec();
+            preimage.extend_from_slice(role.as_bytes());
+            preimage.extend_from_slice(&i.to_be_bytes());
+            let key_seed = sha256::Hash::hash(&preimage);
+            bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &key_seed[..])
+                .expect("A SHA256 is a valid seed, and the odds of an invalid key are negligible")
+        })
+        .collect()
+}
+
+fn xpubs(
+    xprivs: &[bip32::ExtendedPrivKey],
+    secp: &secp256k1::Secp256k1<secp256k1::All>,
+) -> Vec<DescriptorPublicKey> {
+    xprivs
+        .iter()
+        .map(|xpriv| {
+            DescriptorPublicKey::XPub(DescriptorXKey {
+                origin: None,
+                xkey: bip32::ExtendedPubKey::from_private(secp, xpriv),
+                derivation_path: bip32::DerivationPath::from(vec![]),
+                wildcard: Wildcard::Unhardened,
+            })
+        })
+        .collect()
+}
+
+// Sign all the inputs of this transaction with each of these master keys, derived at the index
+// of the vault, recording the PSBT once signed by the first one.
+fn transaction_vectors<T: RevaultTransaction>(
+    name: &str,
+    mut tx: T,
+    signers: &[bip32::ExtendedPrivKey],
+    secp: &secp256k1::Secp256k1<secp256k1::All>,
+) -> Result<TransactionVectors, Error> {
+    let derivation_index =
+        DerivationIndex::try_from(DERIVATION_INDEX).expect("It's not a hardened index");
+    let derivation_path =
+        bip32::DerivationPath::from(vec![bip32::ChildNumber::from(derivation_index)]);
+
+    let unsigned_psbt = tx.as_psbt_string();
+    let mut partially_signed_psbt = None;
+    for xpriv in signers {
+        let privkey = xpriv
+            .derive_priv(secp, &derivation_path)
+            .expect("Unhardened derivation")
+            .private_key
+            .key;
+        let pubkey = secp256k1::PublicKey::from_secret_key(secp, &privkey);
+
+        for (i, input_sighash) in tx.all_sighashes()?.into_iter().enumerate() {
+            let sighash = secp256k1::Message::from_slice(&input_sighash.sighash())
+                .expect("sighash is a 32 bytes hash");
+            let signature = secp.sign(&sighash, &privkey);
+            tx.add_signature(i, pubkey, (signature, SigHashType::All), secp)?;
+        }
+
+        if partially_signed_psbt.is_none() {
+            partially_signed_psbt = Some(tx.as_psbt_string());
+        }
+    }
+    let signed_psbt = tx.as_psbt_string();
+
+    tx.finalize(secp)?;
+
+    Ok(TransactionVectors {
+        name: name.to_string(),
+        unsigned_psbt,
+        partially_signed_psbt: partially_signed_psbt.expect("There is always a signer"),
+        signed_psbt,
+        final_tx: tx.hex(),
+    })
+}
+
+/// Generate the test vectors for this seed.
+pub fn generate(seed: &[u8]) -> Result<TestVectors, Error> {
+    let secp = secp256k1::Secp256k1::new();
+    let derivation_index =
+        DerivationIndex::try_from(DERIVATION_INDEX).expect("It's not a hardened index");
+
+    let stakeholders_priv = master_keys(seed, "stakeholder", N_STAKEHOLDERS);
+    let managers_priv = master_keys(seed, "manager", N_MANAGERS);
+    let cosigners_priv = master_keys(seed, "cosigner", N_STAKEHOLDERS);
+    let cpfp_priv = master_keys(seed, "cpfp", N_MANAGERS);
+
+    let deposit_descriptor = DepositDescriptor::new(xpubs(&stakeholders_priv, &secp))?;
+    let unvault_descriptor = UnvaultDescriptor::new(
+        xpubs(&stakeholders_priv, &secp),
+        xpubs(&managers_priv, &secp),
+        MANAGERS_THRESHOLD,
+        xpubs(&cosigners_priv, &secp),
+        CSV,
+    )?;
+    let cpfp_descriptor = CpfpDescriptor::new(xpubs(&cpfp_priv, &secp))?;
+    let emergency_address =
+        EmergencyAddress::from(Address::p2wsh(&Script::new(), Network::Bitcoin))?;
+
+    let deposit_outpoint = OutPoint {
+        txid: Txid::from_hash(sha256d::Hash::hash(seed)),
+        vout: 0,
+    };
+    let (unvault_tx, cancel_batch, emergency_tx, unvault_emergency_tx) = transaction_chain(
+        deposit_outpoint,
+        Amount::from_sat(DEPOSIT_VALUE),
+        &deposit_descriptor,
+        &unvault_descriptor,
+        &cpfp_descriptor,
+        derivation_index,
+        emergency_address.clone(),
+        &secp,
+    )?;
+
+    // The Spend pays everything but the CPFP reserve and the fees to a single output
+    let der_unvault_descriptor = unvault_descriptor.derive(derivation_index, &secp);
+    let der_cpfp_descriptor = cpfp_descriptor.derive(derivation_index, &secp);
+    let spend_txin = unvault_tx.spend_unvault_txin(&der_unvault_descriptor);
+    let spend_script = Script::new_v0_wsh(&Default::default());
+    let dummy_txo = SpendTxOut::new(TxOut {
+        value: 0,
+        script_pubkey: spend_script.clone(),
+    });
+    let cpfp_value = SpendTransaction::cpfp_txout(
+        std::slice::from_ref(&spend_txin),
+        &[dummy_txo],
+        None,
+        &der_cpfp_descriptor,
+        0,
+    )
+    .txout()
+    .value;
+    let spend_value = spend_txin.txout().txout().value - cpfp_value - SPEND_FEES;
+    let spend_tx = SpendTransaction::new(
+        vec![spend_txin],
+        vec![SpendTxOut::new(TxOut {
+            value: spend_value,
+            script_pubkey: spend_script,
+        })],
+        None,
+        &der_cpfp_descriptor,
+        0,
+        true,
+    )?;
+
+    let mut transactions = vec![transaction_vectors(
+        "unvault",
+        unvault_tx,
+        &stakeholders_priv,
+        &secp,
+    )?];
+    let cancel_txs = cancel_batch.all_feerates();
+    for (feerate, cancel_tx) in cancel_feerates().iter().zip(cancel_txs.iter()) {
+        let name = format!("cancel_{}", feerate.as_sat());
+        transactions.push(transaction_vectors(
+            &name,
+            cancel_tx.clone(),
+            &stakeholders_priv,
+            &secp,
+        )?);
+    }
+    transactions.push(transaction_vectors(
+        "emergency",
+        emergency_tx,
+        &stakeholders_priv,
+        &secp,
+    )?);
+    transactions.push(transaction_vectors(
+        "unvault_emergency",
+        unvault_emergency_tx,
+        &stakeholders_priv,
+        &secp,
+    )?);
+    let spenders_priv: Vec<bip32::ExtendedPrivKey> = managers_priv
+        .into_iter()
+        .take(MANAGERS_THRESHOLD)
+        .chain(cosigners_priv)
+        .collect();
+    transactions.push(transaction_vectors(
+        "spend",
+        spend_tx,
+        &spenders_priv,
+        &secp,
+    )?);
+
+    let der_deposit_descriptor = deposit_descriptor.derive(derivation_index, &secp);
+    Ok(TestVectors {
+        seed: seed.to_hex(),
+        deposit_descriptor: deposit_descriptor.to_string(),
+        unvault_descriptor: unvault_descriptor.to_string(),
+        cpfp_descriptor: cpfp_descriptor.to_string(),
+        emergency_address: emergency_address.to_string(),
+        derivation_index: DERIVATION_INDEX,
+        deposit_outpoint,
+        deposit_value: DEPOSIT_VALUE,
+        deposit_address: der_deposit_descriptor.address(Network::Bitcoin).to_string(),
+        transactions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate;
+
+    #[test]
+    fn test_vectors_deterministic() {
+        let vectors = generate(&[0; 32]).unwrap();
+        assert_eq!(vectors, generate(&[0; 32]).unwrap());
+        assert_ne!(vectors, generate(&[1; 32]).unwrap());
+
+        // Unvault, the 5 Cancels, Emergency, Unvault Emergency and Spend
+        assert_eq!(vectors.transactions.len(), 9);
+        for tx in vectors.transactions.iter() {
+            assert_ne!(tx.unsigned_psbt, tx.partially_signed_psbt);
+            assert_ne!(tx.partially_signed_psbt, tx.signed_psbt);
+        }
+
+        let json = vectors.to_json();
+        assert_eq!(json["transactions"][0]["name"], "unvault");
+        assert_eq!(json["transactions"][8]["name"], "spend");
+    }
+}
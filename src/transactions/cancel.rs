@@ -39,22 +39,19 @@ impl CancelTransaction {
     ) -> Result<CancelTransaction, TransactionCreationError> {
         // First, create a dummy transaction to get its weight without Witness.
         let dummy_deposit_txo = DepositTxOut::new(Amount::from_sat(u64::MAX), deposit_descriptor);
-        let dummy_tx = utils::create_psbt(unvault_input.clone(), dummy_deposit_txo)
-            .global
-            .unsigned_tx;
+        let dummy_tx = utils::create_unsigned_tx(&unvault_input, &dummy_deposit_txo);
 
         // The weight of the cancel transaction is the weight of the witness-stripped transaction
         // plus the weight required to satisfy the Unvault txin
         let total_weight = dummy_tx
             .get_weight()
-            .checked_add(unvault_input.txout().max_sat_weight())
+            .checked_add(unvault_input.max_sat_weight())
             .expect("Properly computed weight won't overflow");
         let total_weight: u64 = total_weight.try_into().expect("usize in u64");
         let fees = feerate
-            .as_sat()
             .checked_mul(total_weight)
             .expect("Properly computed weight won't overflow");
-        assert!(fees < INSANE_FEES);
+        assert!(fees < Amount::from_sat(INSANE_FEES));
 
         assert!(
             total_weight <= MAX_STANDARD_TX_WEIGHT as u64,
@@ -62,18 +59,16 @@ impl CancelTransaction {
         );
 
         // Now, get the revaulting output value out of it.
-        let unvault_value = unvault_input.txout().txout().value;
+        let unvault_value = Amount::from_sat(unvault_input.txout().txout().value);
         let revault_value = unvault_value
             .checked_sub(fees)
+            .filter(|v| *v >= Amount::from_sat(CANCEL_DEPOSIT_MIN_SATS))
             .ok_or(TransactionCreationError::FeerateTooHigh)?;
-        if revault_value < CANCEL_DEPOSIT_MIN_SATS {
-            return Err(TransactionCreationError::FeerateTooHigh);
-        }
         assert!(
-            revault_value < max_money(Network::Bitcoin),
+            revault_value < Amount::from_sat(max_money(Network::Bitcoin)),
             "Checked in UnvaultTransaction constructor already"
         );
-        let deposit_txo = DepositTxOut::new(Amount::from_sat(revault_value), deposit_descriptor);
+        let deposit_txo = DepositTxOut::new(revault_value, deposit_descriptor);
 
         Ok(CancelTransaction(utils::create_psbt(
             unvault_input,
@@ -81,9 +76,13 @@ impl CancelTransaction {
         )))
     }
 
-    /// Parse a Cancel transaction from a PSBT
+    /// Parse a Cancel transaction from a BIP174-serialized PSBT
     pub fn from_raw_psbt(raw_psbt: &[u8]) -> Result<Self, TransactionSerialisationError> {
-        let psbt = Decodable::consensus_decode(raw_psbt)?;
+        Self::from_psbt(Decodable::consensus_decode(raw_psbt)?)
+    }
+
+    /// Check this PSBT is a valid Cancel transaction
+    pub fn from_psbt(psbt: Psbt) -> Result<Self, TransactionSerialisationError> {
         let psbt = utils::psbt_common_sanity_checks(psbt)?;
 
         // Deposit txo
@@ -92,22 +91,53 @@ impl CancelTransaction {
             return Err(PsbtValidationError::InvalidOutputCount(output_count).into());
         }
 
-        for output in psbt.outputs.iter() {
+        for (i, output) in psbt.outputs.iter().enumerate() {
             if output.bip32_derivation.is_empty() {
-                return Err(PsbtValidationError::InvalidOutputField(output.clone()).into());
+                return Err(
+                    PsbtValidationError::InvalidOutputField(i, "missing bip32 derivation").into(),
+                );
             }
         }
 
         // Deposit txo is P2WSH
         let output = &psbt.outputs[0];
         if output.redeem_script.is_some() {
-            return Err(PsbtValidationError::InvalidOutputField(output.clone()).into());
+            return Err(PsbtValidationError::InvalidOutputField(0, "redeem_script is set").into());
         }
 
         if psbt.inputs.len() != 1 {
             return Err(PsbtValidationError::InvalidInputCount(psbt.inputs.len()).into());
         }
 
+        // The Unvault txo is spent through the stakeholders' path, signaling for RBF
+        let sequence = psbt.global.unsigned_tx.input[0].sequence;
+        if sequence != RBF_SEQUENCE {
+            return Err(PsbtValidationError::InvalidSequence(sequence).into());
+        }
+
+        if !psbt.global.unsigned_tx.output[0]
+            .script_pubkey
+            .is_v0_p2wsh()
+        {
+            return Err(PsbtValidationError::InvalidOutputField(0, "not P2WSH").into());
+        }
+
+        // The deposit txo must be derived at the same index as the Unvault txo. The stakeholders'
+        // keys are part of both descriptors, so each of the deposit keys must be in the Unvault
+        // witness script with the same origin. The derivation paths are blanked when finalized.
+        let input = &psbt.inputs[0];
+        if input.final_script_witness.is_none() {
+            for (key, origin) in output.bip32_derivation.iter() {
+                if input.bip32_derivation.get(key) != Some(origin) {
+                    return Err(PsbtValidationError::InvalidOutputField(
+                        0,
+                        "bip32 derivation not found in the input",
+                    )
+                    .into());
+                }
+            }
+        }
+
         Ok(CancelTransaction(psbt))
     }
 
@@ -0,0 +1,340 @@
+//! Selection of the Unvault outputs to spend in a Spend transaction, and of the coins to add to
+//! a CPFP transaction in order to bump the fees of the Unvault and Spend transactions.
+
+use crate::{
+    error::TransactionCreationError,
+    scripts::{DerivedCpfpDescriptor, DerivedDepositDescriptor},
+    transactions::{
+        fees::{self, FeeEstimator},
+        SpendTransaction,
+    },
+    txins::{CpfpTxIn, RevaultTxIn, UnvaultTxIn},
+    txouts::{DepositTxOut, RevaultTxOut, SpendTxOut},
+};
+
+use miniscript::bitcoin::{consensus::encode, Amount};
+
+use std::convert::TryInto;
+
+/// The maximum number of branches explored by the Branch and Bound search before giving up.
+const BNB_MAX_TRIES: usize = 100_000;
+
+/// The algorithm used to select the coins to spend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoinSelectionStrategy {
+    /// Add coins by decreasing value until the target is met
+    LargestFirst,
+    /// Search for a set of coins meeting the target without the need for a change output (Branch
+    /// and Bound), and fall back to largest-first if there is none.
+    BranchAndBound,
+}
+
+/// The Unvault outputs selected to be spent by a Spend transaction, along with the value of its
+/// change output (if any).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpendCoinSelection {
+    inputs: Vec<UnvaultTxIn>,
+    change: Option<Amount>,
+}
+
+impl SpendCoinSelection {
+    /// Get the selected Unvault inputs
+    pub fn inputs(&self) -> &[UnvaultTxIn] {
+        &self.inputs
+    }
+
+    /// Get the value of the change output, if the selection needs one
+    pub fn change(&self) -> Option<Amount> {
+        self.change
+    }
+
+    /// Move out the selected Unvault inputs and the value of the change output
+    pub fn into_parts(self) -> (Vec<UnvaultTxIn>, Option<Amount>) {
+        (self.inputs, self.change)
+    }
+}
+
+// What a Spend transaction must pay for, besides its destinations.
+struct SpendTarget<'a> {
+    spend_txouts: &'a [SpendTxOut],
+    value_out: Amount,
+    dummy_change: DepositTxOut,
+    cpfp_descriptor: &'a DerivedCpfpDescriptor,
+    lock_time: u32,
+    feerate: Amount,
+}
+
+impl<'a> SpendTarget<'a> {
+    // The value these inputs must sum to in order to pay for the destinations, the CPFP output
+    // and the fees of a Spend transaction with or without a change output.
+    fn needed_value(&self, inputs: &[UnvaultTxIn], with_change: bool) -> Option<Amount> {
+        let change = if with_change {
+            Some(&self.dummy_change)
+        } else {
+            None
+        };
+        let weight = SpendTransaction::max_weight_for(
+            inputs,
+            self.spend_txouts,
+            change,
+            self.cpfp_descriptor,
+            self.lock_time,
+        );
+        let cpfp_txo = SpendTransaction::cpfp_txout(
+            inputs,
+            self.spend_txouts,
+            change,
+            self.cpfp_descriptor,
+            self.lock_time,
+        );
+
+        self.value_out
+            .checked_add(cpfp_txo.value())?
+            .checked_add(self.feerate.checked_mul(weight)?)
+    }
+
+    // Get the change for this set of inputs, if they are enough to pay for the Spend. The change
+    // is dropped to the fees if it would be dust.
+    fn change_for(&self, inputs: Vec<UnvaultTxIn>) -> Option<SpendCoinSelection> {
+        let value_in = inputs.iter().try_fold(Amount::from_sat(0), |sum, txin| {
+            sum.checked_add(txin.txout().value())
+        })?;
+
+        let change = self
+            .needed_value(&inputs, true)
+            .and_then(|needed| value_in.checked_sub(needed))
+            .filter(|change| *change >= self.dummy_change.script_pubkey().dust_value());
+        if change.is_some() {
+            return Some(SpendCoinSelection { inputs, change });
+        }
+
+        if value_in >= self.needed_value(&inputs, false)? {
+            Some(SpendCoinSelection {
+                inputs,
+                change: None,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+// Add coins by decreasing value until the target is met.
+fn largest_first(
+    mut candidates: Vec<UnvaultTxIn>,
+    target: &SpendTarget,
+) -> Option<SpendCoinSelection> {
+    candidates.sort_unstable_by_key(|txin| std::cmp::Reverse(txin.txout().value()));
+
+    let mut selected = Vec::with_capacity(candidates.len());
+    for txin in candidates {
+        selected.push(txin);
+        if let Some(selection) = target.change_for(selected.clone()) {
+            return Some(selection);
+        }
+    }
+
+    None
+}
+
+// The state of the depth-first Branch and Bound search.
+struct BnbSearch<'a> {
+    // The effective values of the candidates, sorted by decreasing value
+    values: &'a [u64],
+    // The sum of the values from each index to the end
+    remaining: Vec<u64>,
+    target: u64,
+    upper_bound: u64,
+    tries: usize,
+    current: Vec<usize>,
+    best: Option<(u64, Vec<usize>)>,
+}
+
+impl<'a> BnbSearch<'a> {
+    fn explore(&mut self, index: usize, sum: u64) {
+        self.tries += 1;
+        if self.tries > BNB_MAX_TRIES || sum > self.upper_bound {
+            return;
+        }
+        if sum >= self.target {
+            let waste = sum - self.target;
+            if self.best.as_ref().map(|(w, _)| waste < *w).unwrap_or(true) {
+                self.best = Some((waste, self.current.clone()));
+            }
+            return;
+        }
+        if index >= self.values.len() || sum + self.remaining[index] < self.target {
+            return;
+        }
+
+        // Try with this coin, then without it
+        self.current.push(index);
+        self.explore(index + 1, sum + self.values[index]);
+        self.current.pop();
+        self.explore(index + 1, sum);
+    }
+}
+
+// Search for a set of coins whose value, net of the cost of spending them, meets the target
+// without exceeding it by more than the cost of creating a change output.
+fn branch_and_bound(
+    candidates: &[UnvaultTxIn],
+    target: &SpendTarget,
+) -> Option<SpendCoinSelection> {
+    let base_no_change = target.needed_value(&[], false)?.as_sat();
+    let base_change = target.needed_value(&[], true)?.as_sat();
+    let cost_of_change = base_change
+        .checked_sub(base_no_change)?
+        .checked_add(target.dummy_change.script_pubkey().dust_value().as_sat())?;
+
+    // The value of each coin net of the fees and CPFP value its input adds. Coins costing more
+    // than they are worth are never worth selecting.
+    let mut effective: Vec<(u64, &UnvaultTxIn)> = candidates
+        .iter()
+        .filter_map(|txin| {
+            let cost = target
+                .needed_value(std::slice::from_ref(txin), false)?
+                .as_sat()
+                .checked_sub(base_no_change)?;
+            let value = txin.txout().value().as_sat().checked_sub(cost)?;
+            if value > 0 {
+                Some((value, txin))
+            } else {
+                None
+            }
+        })
+        .collect();
+    effective.sort_unstable_by_key(|(value, _)| std::cmp::Reverse(*value));
+
+    let values: Vec<u64> = effective.iter().map(|(value, _)| *value).collect();
+    let mut remaining: Vec<u64> = vec![0; values.len() + 1];
+    for i in (0..values.len()).rev() {
+        remaining[i] = remaining[i + 1].checked_add(values[i])?;
+    }
+    let mut search = BnbSearch {
+        values: &values,
+        remaining,
+        target: base_no_change,
+        upper_bound: base_no_change.checked_add(cost_of_change)?,
+        tries: 0,
+        current: Vec::new(),
+        best: None,
+    };
+    search.explore(0, 0);
+
+    let (_, indexes) = search.best?;
+    let inputs = indexes
+        .into_iter()
+        .map(|i| effective[i].1.clone())
+        .collect();
+    // The effective values are an approximation of the actual weight, make sure the selection
+    // does pay for the Spend.
+    target.change_for(inputs)
+}
+
+/// Select among `candidates` the Unvault outputs to spend in order to pay to these
+/// `spend_txouts`, along with the CPFP output and the fees at the feerate given by the
+/// `estimator` for a confirmation within `target_blocks` blocks.
+///
+/// The returned change value, if any, is to be paid to a [DepositTxOut] for the
+/// `change_descriptor`. A change that would be dust is left to the fees.
+///
+/// Will error if the candidates' value isn't enough to cover the target.
+#[allow(clippy::too_many_arguments)]
+pub fn select_unvault_coins(
+    candidates: Vec<UnvaultTxIn>,
+    spend_txouts: &[SpendTxOut],
+    change_descriptor: &DerivedDepositDescriptor,
+    cpfp_descriptor: &DerivedCpfpDescriptor,
+    lock_time: u32,
+    estimator: &impl FeeEstimator,
+    target_blocks: u16,
+    strategy: CoinSelectionStrategy,
+) -> Result<SpendCoinSelection, TransactionCreationError> {
+    let mut value_out = Amount::from_sat(0);
+    for txout in spend_txouts.iter() {
+        value_out = value_out
+            .checked_add(txout.value())
+            .ok_or(TransactionCreationError::InsaneAmounts)?;
+    }
+
+    let target = SpendTarget {
+        spend_txouts,
+        value_out,
+        // The weight, hence the fees, don't depend on the value of the change output.
+        dummy_change: DepositTxOut::new(Amount::from_sat(u64::MAX), change_descriptor),
+        cpfp_descriptor,
+        lock_time,
+        feerate: fees::sat_per_wu(estimator.feerate(target_blocks)),
+    };
+
+    let selection = match strategy {
+        CoinSelectionStrategy::LargestFirst => largest_first(candidates, &target),
+        CoinSelectionStrategy::BranchAndBound => {
+            branch_and_bound(&candidates, &target).or_else(|| largest_first(candidates, &target))
+        }
+    };
+
+    selection.ok_or(TransactionCreationError::InsufficientFunds)
+}
+
+// The value of a fee-bumping coin net of the fees to pay for its input at `feerate` sats/kWU, if
+// it's worth more than it costs.
+fn feebump_effective_value(txin: &CpfpTxIn, feerate: u64) -> Option<u64> {
+    let txin_weight: u64 = (encode::serialize(&txin.unsigned_txin()).len() * 4
+        + txin.max_sat_weight())
+    .try_into()
+    .expect("Weight doesn't fit in u64?");
+    let cost = feerate.checked_mul(txin_weight)? / 1_000;
+    txin.txout()
+        .value()
+        .as_sat()
+        .checked_sub(cost)
+        .filter(|value| *value > 0)
+}
+
+/// Select among `candidates` the coins to add to a CPFP transaction in order to pay for
+/// `needed_fees`, accounting for the cost of their inputs at `feerate` sats/kWU.
+///
+/// The smallest coin covering the remaining fees by itself is selected if there is one,
+/// otherwise the largest coin is selected and the remaining fees are covered by the next ones.
+/// This minimizes both the number of coins and the value that is left for change.
+///
+/// Will error if the coins are not enough to reach the target feerate, before selecting any of
+/// them.
+pub fn select_feebump_coins(
+    candidates: Vec<CpfpTxIn>,
+    needed_fees: Amount,
+    feerate: u64,
+) -> Result<Vec<CpfpTxIn>, TransactionCreationError> {
+    // Coins costing more than they are worth at this feerate are never worth selecting.
+    let mut candidates: Vec<(u64, CpfpTxIn)> = candidates
+        .into_iter()
+        .filter_map(|txin| feebump_effective_value(&txin, feerate).map(|value| (value, txin)))
+        .collect();
+
+    let total_value = candidates
+        .iter()
+        .try_fold(0u64, |sum, (value, _)| sum.checked_add(*value))
+        .ok_or(TransactionCreationError::InsaneAmounts)?;
+    if total_value < needed_fees.as_sat() {
+        return Err(TransactionCreationError::InsufficientFunds);
+    }
+
+    // Sorted by ascending effective value
+    candidates.sort_unstable_by_key(|(value, _)| *value);
+
+    let mut selected = Vec::new();
+    let mut remaining = needed_fees.as_sat();
+    while remaining > 0 {
+        let index = candidates
+            .iter()
+            .position(|(value, _)| *value >= remaining)
+            .unwrap_or(candidates.len() - 1);
+        let (value, txin) = candidates.remove(index);
+        remaining = remaining.saturating_sub(value);
+        selected.push(txin);
+    }
+
+    Ok(selected)
+}
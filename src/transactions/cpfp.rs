@@ -1,6 +1,9 @@
 use crate::{
     error::*,
-    transactions::{utils, CPFP_MIN_CHANGE},
+    transactions::{
+        fees::{self, FeeEstimator},
+        utils, CPFP_MIN_CHANGE,
+    },
     txins::*,
     txouts::*,
 };
@@ -51,6 +54,11 @@ impl fmt::Display for CpfpTransaction {
     }
 }
 
+// The feerate, in sats/kWU, of the package of transactions to be CPFP'd
+fn package_feerate(tbc_weight: u64, tbc_fees: Amount) -> u64 {
+    1_000 * (tbc_fees.as_sat() + tbc_weight) / tbc_weight // * 1000 for kWU
+}
+
 impl CpfpTransaction {
     /// Create a CPFP tx to bump a set of transactions by a specified feerate.
     /// The current implementation will return a CPFP tx that either pays to a 0-value OP_RETURN
@@ -68,7 +76,9 @@ impl CpfpTransaction {
         added_feerate: u64,
         mut available_utxos: Vec<CpfpTxIn>,
     ) -> Result<CpfpTransaction, TransactionCreationError> {
-        assert!(!to_be_cpfped.is_empty());
+        if to_be_cpfped.is_empty() {
+            return Err(TransactionCreationError::NoInput);
+        }
         // This will sort the vector in ascending order.
         // Since we're going to pop() from it, we're using a largest first CS.
         available_utxos.sort_unstable_by_key(|l| l.txout().txout().value);
@@ -85,7 +95,6 @@ impl CpfpTransaction {
             // I can't collapse this in one call (total_satisfation_weight += ...)
             // as I have a "cannot infer type"
             let w: u64 = cpfp_txin
-                .txout()
                 .max_sat_weight()
                 .try_into()
                 .expect("Weight doesn't fit in u64?");
@@ -97,11 +106,8 @@ impl CpfpTransaction {
                 witness: vec![],
             });
             psbtins.push(PsbtIn {
-                witness_script: Some(cpfp_txin.txout().witness_script().clone()),
-                bip32_derivation: cpfp_txin.txout().bip32_derivation().clone(),
                 sighash_type: Some(SigHashType::All),
-                witness_utxo: Some(cpfp_txin.into_txout().into_txout()),
-                ..PsbtIn::default()
+                ..cpfp_txin.into_psbtin()
             });
         }
 
@@ -121,7 +127,7 @@ impl CpfpTransaction {
         };
 
         // We discard the CPFP descriptors in to_be_cpfped as we don't need them anymore
-        let tbc_feerate = 1_000 * (tbc_fees.as_sat() + tbc_weight) / tbc_weight; // * 1000 for kWU
+        let tbc_feerate = package_feerate(tbc_weight, tbc_fees);
 
         let target_feerate = tbc_feerate + added_feerate;
         loop {
@@ -178,23 +184,18 @@ impl CpfpTransaction {
                             witness: vec![],
                         });
 
-                        let bip32_derivation = new_input.txout().clone().into_bip32_derivation();
-
-                        psbt.inputs.push(PsbtIn {
-                            witness_utxo: Some(new_input.txout().txout().clone()),
-                            witness_script: Some(new_input.txout().clone().into_witness_script()),
-                            sighash_type: Some(SigHashType::All),
-                            bip32_derivation,
-                            ..Default::default()
-                        });
                         inputs_sum += Amount::from_sat(new_input.txout().txout().value);
 
                         let input_sat_weight: u64 = new_input
-                            .txout()
                             .max_sat_weight()
                             .try_into()
                             .expect("Weight doesn't fit in u64?");
                         total_satisfation_weight += input_sat_weight;
+
+                        psbt.inputs.push(PsbtIn {
+                            sighash_type: Some(SigHashType::All),
+                            ..new_input.into_psbtin()
+                        });
                     }
                     None => {
                         return Err(TransactionCreationError::InsufficientFunds);
@@ -208,6 +209,34 @@ impl CpfpTransaction {
         &self.0
     }
 
+    /// Create a CPFP tx to bump a set of transactions to the feerate given by the `estimator` for
+    /// a confirmation within `target_blocks` blocks. See [CpfpTransaction::from_txins].
+    ///
+    /// Returns `None` if the transactions to be bumped already pay at least this feerate.
+    pub fn with_estimator(
+        to_be_cpfped: Vec<CpfpTxIn>,
+        tbc_weight: u64,
+        tbc_fees: Amount,
+        estimator: &impl FeeEstimator,
+        target_blocks: u16,
+        available_utxos: Vec<CpfpTxIn>,
+    ) -> Result<Option<CpfpTransaction>, TransactionCreationError> {
+        let target_feerate = fees::sat_per_kwu(estimator.feerate(target_blocks));
+        let tbc_feerate = package_feerate(tbc_weight, tbc_fees);
+        if target_feerate <= tbc_feerate {
+            return Ok(None);
+        }
+
+        CpfpTransaction::from_txins(
+            to_be_cpfped,
+            tbc_weight,
+            tbc_fees,
+            target_feerate - tbc_feerate,
+            available_utxos,
+        )
+        .map(Some)
+    }
+
     pub fn into_psbt(self) -> Psbt {
         self.0
     }
@@ -30,56 +30,69 @@ impl RevaultPresignedTransaction for EmergencyTransaction {}
 impl EmergencyTransaction {
     /// The first emergency transaction always spends a deposit output and pays to the Emergency
     /// Script.
-    /// Will error **only** when trying to spend a dust deposit.
+    /// Will error **only** when trying to spend a deposit that would leave a dust emergency output.
     ///
     /// BIP174 Creator and Updater roles.
     pub fn new(
         deposit_input: DepositTxIn,
         emer_address: EmergencyAddress,
     ) -> Result<EmergencyTransaction, TransactionCreationError> {
+        let fees = Self::fees_for(&deposit_input, &emer_address);
+
+        // Now, get the emergency output value out of it.
+        let deposit_value = deposit_input.txout().value();
+        let emer_value = deposit_value
+            .checked_sub(fees)
+            .ok_or(TransactionCreationError::Dust)?;
+        // The emer output is the single one
+        if emer_value > Amount::from_sat(max_money(Network::Bitcoin)) {
+            return Err(TransactionCreationError::InsaneAmounts);
+        }
+        // We never leave less than the fees computed above, so it can only fail if it's dust.
+        let emer_txo =
+            EmergencyTxOut::new_checked_value(emer_address, emer_value, deposit_value, fees)
+                .map_err(|_| TransactionCreationError::Dust)?;
+
+        Ok(EmergencyTransaction(utils::create_psbt(
+            deposit_input,
+            emer_txo,
+        )))
+    }
+
+    /// Get the absolute fees an Emergency transaction spending this deposit txo would pay, at
+    /// the fixed [EMER_TX_FEERATE].
+    pub fn fees_for(deposit_input: &DepositTxIn, emer_address: &EmergencyAddress) -> Amount {
         // First, create a dummy transaction to get its weight without Witness.
         let emer_txo = EmergencyTxOut::new(emer_address.clone(), Amount::from_sat(u64::MAX));
-        let dummy_tx = utils::create_psbt(deposit_input.clone(), emer_txo)
-            .global
-            .unsigned_tx;
+        let dummy_tx = utils::create_unsigned_tx(deposit_input, &emer_txo);
 
         // The weight of the Emergency transaction is the weight of the witness-stripped transaction
         // plus the weight required to satisfy the Deposit txin
         let total_weight = dummy_tx
             .get_weight()
-            .checked_add(deposit_input.txout().max_sat_weight())
+            .checked_add(deposit_input.max_sat_weight())
             .expect("Weight computation bug");
         let total_weight: u64 = total_weight.try_into().expect("usize in u64");
-        let fees = EMER_TX_FEERATE
+        let fees = Amount::from_sat(EMER_TX_FEERATE)
             .checked_mul(total_weight)
             .expect("Weight computation bug");
-        assert!(fees < INSANE_FEES);
+        assert!(fees < Amount::from_sat(INSANE_FEES));
 
         assert!(
             total_weight <= MAX_STANDARD_TX_WEIGHT as u64,
             "Single input and single output"
         );
 
-        // Now, get the emergency output value out of it.
-        let deposit_value = deposit_input.txout().txout().value;
-        let emer_value = deposit_value
-            .checked_sub(fees)
-            .ok_or(TransactionCreationError::Dust)?;
-        // The emer output is the single one
-        if emer_value > max_money(Network::Bitcoin) {
-            return Err(TransactionCreationError::InsaneAmounts);
-        }
-        let emer_txo = EmergencyTxOut::new(emer_address, Amount::from_sat(emer_value));
-
-        Ok(EmergencyTransaction(utils::create_psbt(
-            deposit_input,
-            emer_txo,
-        )))
+        fees
     }
 
-    /// Parse an Emergency transaction from a PSBT
+    /// Parse an Emergency transaction from a BIP174-serialized PSBT
     pub fn from_raw_psbt(raw_psbt: &[u8]) -> Result<Self, TransactionSerialisationError> {
-        let psbt = Decodable::consensus_decode(raw_psbt)?;
+        Self::from_psbt(Decodable::consensus_decode(raw_psbt)?)
+    }
+
+    /// Check this PSBT is a valid Emergency transaction
+    pub fn from_psbt(psbt: Psbt) -> Result<Self, TransactionSerialisationError> {
         let psbt = utils::psbt_common_sanity_checks(psbt)?;
 
         // Emergency txo
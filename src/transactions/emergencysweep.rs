@@ -0,0 +1,124 @@
+use crate::{
+    error::*,
+    scripts::*,
+    transactions::{utils, RevaultTransaction},
+    txins::*,
+    txouts::*,
+};
+
+use miniscript::{
+    bitcoin::{
+        consensus::encode::Decodable,
+        util::psbt::{Output as PsbtOut, PartiallySignedTransaction as Psbt},
+        Address, Amount, OutPoint, TxOut,
+    },
+    DescriptorTrait,
+};
+
+#[cfg(feature = "use-serde")]
+use {
+    serde::de::{self, Deserialize, Deserializer},
+    serde::ser::{Serialize, Serializer},
+};
+
+/// Where the funds of the Emergency Deep Vault are swept to once the crisis is over.
+// It's only passed around when creating the transaction, boxing the descriptor isn't worth it
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum SweepDestination {
+    /// A new deposit, to be vaulted again
+    Deposit(DerivedDepositDescriptor),
+    /// An address external to the Revault setup
+    External(Address),
+}
+
+impl SweepDestination {
+    // Get the txout paying `value` to this destination, along with its PSBT output
+    fn txout(&self, value: Amount) -> (TxOut, PsbtOut) {
+        match self {
+            SweepDestination::Deposit(descriptor) => {
+                let txo = DepositTxOut::new(value, descriptor);
+                let psbtout = txo.psbtout();
+                (txo.into_txout(), psbtout)
+            }
+            SweepDestination::External(address) => (
+                TxOut {
+                    value: value.as_sat(),
+                    script_pubkey: address.script_pubkey(),
+                },
+                PsbtOut::default(),
+            ),
+        }
+    }
+}
+
+impl_revault_transaction!(
+    EmergencySweepTransaction,
+    doc = "The transaction spending the Emergency Deep Vault outputs, once the crisis is over, \
+    to a new deposit or to an external address."
+);
+impl EmergencySweepTransaction {
+    /// Create a transaction spending these `emergency_inputs` to the `destination`, at the given
+    /// `feerate` in sats/WU.
+    ///
+    /// Will error if the fees would leave a dust output or be insane, if the transaction would
+    /// be too large, or if `emergency_inputs` is empty.
+    ///
+    /// BIP174 Creator and Updater roles.
+    pub fn new(
+        emergency_inputs: Vec<EmergencyTxIn>,
+        destination: SweepDestination,
+        feerate: Amount,
+    ) -> Result<EmergencySweepTransaction, TransactionCreationError> {
+        utils::create_sweep_psbt(emergency_inputs, |value| destination.txout(value), feerate)
+            .map(EmergencySweepTransaction)
+    }
+
+    /// Parse an Emergency sweep transaction from a BIP174-serialized PSBT
+    pub fn from_raw_psbt(raw_psbt: &[u8]) -> Result<Self, TransactionSerialisationError> {
+        Self::from_psbt(Decodable::consensus_decode(raw_psbt)?)
+    }
+
+    /// Check this PSBT is a valid Emergency sweep transaction
+    pub fn from_psbt(psbt: Psbt) -> Result<Self, TransactionSerialisationError> {
+        let psbt = utils::psbt_common_sanity_checks(psbt)?;
+
+        if psbt.inputs.is_empty() {
+            return Err(PsbtValidationError::InvalidInputCount(0).into());
+        }
+
+        // The sweep txo
+        let output_count = psbt.global.unsigned_tx.output.len();
+        if output_count != 1 {
+            return Err(PsbtValidationError::InvalidOutputCount(output_count).into());
+        }
+
+        Ok(EmergencySweepTransaction(psbt))
+    }
+
+    /// Get the outpoint of the output created by this transaction
+    pub fn sweep_outpoint(&self) -> OutPoint {
+        // We only ever have a single output
+        OutPoint {
+            txid: self.txid(),
+            vout: 0,
+        }
+    }
+
+    /// Get the new deposit created by this transaction as a [DepositTxIn], if it was swept to a
+    /// [SweepDestination::Deposit]. The `deposit_descriptor` must be the one this transaction was
+    /// created with.
+    pub fn deposit_txin(&self, deposit_descriptor: &DerivedDepositDescriptor) -> DepositTxIn {
+        let txo = &self.tx().output[0];
+        assert_eq!(
+            txo.script_pubkey,
+            deposit_descriptor.inner().script_pubkey(),
+            "Not the descriptor of the new deposit"
+        );
+
+        DepositTxIn::new(
+            self.sweep_outpoint(),
+            DepositTxOut::new(Amount::from_sat(txo.value), deposit_descriptor),
+        )
+    }
+}
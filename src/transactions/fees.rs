@@ -0,0 +1,65 @@
+//! Feerate estimation for the transactions whose fees are decided at creation or broadcast time
+//! (Spend and CPFP), as opposed to the presigned ones.
+
+use miniscript::bitcoin::Amount;
+
+/// A source of feerate estimates, for instance bitcoind's `estimatesmartfee`.
+pub trait FeeEstimator {
+    /// Get the feerate, as an amount per 1000 virtual bytes, for a transaction to be confirmed
+    /// within `target_blocks` blocks.
+    fn feerate(&self, target_blocks: u16) -> Amount;
+}
+
+/// A feerate policy which always returns the same feerate regardless of the confirmation target.
+/// The feerate is an amount per 1000 virtual bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedFeerate(pub Amount);
+
+impl FeeEstimator for FixedFeerate {
+    fn feerate(&self, _target_blocks: u16) -> Amount {
+        self.0
+    }
+}
+
+/// Convert a feerate per 1000 virtual bytes to a feerate per weight unit, rounded up.
+pub fn sat_per_wu(feerate: Amount) -> Amount {
+    // 1000 vbytes is 4000 WU
+    Amount::from_sat(
+        feerate
+            .as_sat()
+            .checked_add(3_999)
+            .expect("Feerate cannot be larger than u64::MAX - 4000")
+            / 4_000,
+    )
+}
+
+/// Convert a feerate per 1000 virtual bytes to a feerate in sats per 1000 weight units, rounded
+/// up.
+pub fn sat_per_kwu(feerate: Amount) -> u64 {
+    // 1000 vbytes is 4000 WU
+    feerate
+        .as_sat()
+        .checked_add(3)
+        .expect("Feerate cannot be larger than u64::MAX - 4")
+        / 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sat_per_kwu, sat_per_wu, FeeEstimator, FixedFeerate};
+
+    use miniscript::bitcoin::Amount;
+
+    #[test]
+    fn feerate_conversions() {
+        let estimator = FixedFeerate(Amount::from_sat(10_000));
+        assert_eq!(estimator.feerate(1), estimator.feerate(1_008));
+
+        assert_eq!(sat_per_wu(estimator.feerate(2)), Amount::from_sat(3));
+        assert_eq!(sat_per_kwu(estimator.feerate(2)), 2_500);
+        assert_eq!(sat_per_wu(Amount::from_sat(4_000)), Amount::from_sat(1));
+        assert_eq!(sat_per_wu(Amount::from_sat(4_001)), Amount::from_sat(2));
+        assert_eq!(sat_per_kwu(Amount::from_sat(1)), 1);
+        assert_eq!(sat_per_kwu(Amount::from_sat(0)), 0);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{
+    error::*,
+    scripts::*,
+    transactions::{utils, RevaultTransaction},
+    txins::*,
+    txouts::*,
+};
+
+use miniscript::{
+    bitcoin::{
+        consensus::encode::Decodable, secp256k1, util::psbt::PartiallySignedTransaction as Psbt,
+        Amount, OutPoint,
+    },
+    DescriptorTrait,
+};
+
+#[cfg(feature = "use-serde")]
+use {
+    serde::de::{self, Deserialize, Deserializer},
+    serde::ser::{Serialize, Serializer},
+};
+
+impl_revault_transaction!(
+    MigrationTransaction,
+    doc = "The transaction spending deposit outputs to a single deposit under a new deposit \
+    descriptor, for instance after a key rotation or a change of participants."
+);
+impl MigrationTransaction {
+    /// Create a transaction spending these `deposit_inputs` to a deposit under the
+    /// `new_descriptor` derived at `derivation_index`, at the given `feerate` in sats/WU.
+    ///
+    /// Will error if one of the deposits is already under the new descriptor, if the fees would
+    /// leave a dust deposit or be insane, if the transaction would be too large, or if
+    /// `deposit_inputs` is empty.
+    ///
+    /// BIP174 Creator and Updater roles.
+    pub fn new<C: secp256k1::Verification>(
+        deposit_inputs: Vec<DepositTxIn>,
+        new_descriptor: &DepositDescriptor,
+        derivation_index: DerivationIndex,
+        feerate: Amount,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<MigrationTransaction, TransactionCreationError> {
+        // We must actually migrate the funds somewhere else
+        for txin in deposit_inputs.iter() {
+            let prev_descriptor = txin.txout().descriptor();
+            if &new_descriptor.derive(prev_descriptor.derivation_index(), secp) == prev_descriptor {
+                return Err(TransactionCreationError::SameDescriptor);
+            }
+        }
+
+        let der_new_descriptor = new_descriptor.derive(derivation_index, secp);
+        utils::create_sweep_psbt(
+            deposit_inputs,
+            |value| {
+                let deposit_txo = DepositTxOut::new(value, &der_new_descriptor);
+                let psbtout = deposit_txo.psbtout();
+                (deposit_txo.into_txout(), psbtout)
+            },
+            feerate,
+        )
+        .map(MigrationTransaction)
+    }
+
+    /// Parse a Migration transaction from a BIP174-serialized PSBT
+    pub fn from_raw_psbt(raw_psbt: &[u8]) -> Result<Self, TransactionSerialisationError> {
+        Self::from_psbt(Decodable::consensus_decode(raw_psbt)?)
+    }
+
+    /// Check this PSBT is a valid Migration transaction
+    pub fn from_psbt(psbt: Psbt) -> Result<Self, TransactionSerialisationError> {
+        let psbt = utils::psbt_common_sanity_checks(psbt)?;
+
+        if psbt.inputs.is_empty() {
+            return Err(PsbtValidationError::InvalidInputCount(0).into());
+        }
+
+        // The new deposit txo
+        let output_count = psbt.global.unsigned_tx.output.len();
+        if output_count != 1 {
+            return Err(PsbtValidationError::InvalidOutputCount(output_count).into());
+        }
+
+        // We create the new deposit, so we must know how to spend it
+        if psbt.outputs[0].bip32_derivation.is_empty() {
+            return Err(
+                PsbtValidationError::InvalidOutputField(0, "missing bip32 derivation").into(),
+            );
+        }
+
+        Ok(MigrationTransaction(psbt))
+    }
+
+    /// Get the outpoint of the new deposit created by this transaction
+    pub fn deposit_outpoint(&self) -> OutPoint {
+        // We only ever have a single output, the new deposit.
+        OutPoint {
+            txid: self.txid(),
+            vout: 0,
+        }
+    }
+
+    /// Get the new deposit created by this transaction as a [DepositTxIn], to create the
+    /// transaction chain of the new vault. The `new_descriptor` must be the one this
+    /// transaction was created with, derived at the same index.
+    pub fn deposit_txin(&self, new_descriptor: &DerivedDepositDescriptor) -> DepositTxIn {
+        let txo = &self.tx().output[0];
+        assert_eq!(
+            txo.script_pubkey,
+            new_descriptor.inner().script_pubkey(),
+            "Not the descriptor of the new deposit"
+        );
+
+        DepositTxIn::new(
+            self.deposit_outpoint(),
+            DepositTxOut::new(Amount::from_sat(txo.value), new_descriptor),
+        )
+    }
+}
@@ -9,36 +9,51 @@ use crate::{error::*, scripts::*, txins::*, txouts::*};
 use miniscript::{
     bitcoin::{
         consensus::encode::Encodable,
+        hashes::{hash160, ripemd160, sha256, sha256d, Hash},
         secp256k1,
         util::{
-            bip143::SigHashCache, bip32::ChildNumber, psbt::PartiallySignedTransaction as Psbt,
+            bip143::SigHashCache,
+            bip32::KeySource,
+            psbt::{Input as PsbtIn, PartiallySignedTransaction as Psbt},
         },
-        Address, Amount, Network, OutPoint, PublicKey as BitcoinPubKey, SigHash, SigHashType,
-        Transaction, Txid, Wtxid,
+        Address, Amount, Network, OutPoint, PublicKey as BitcoinPubKey, Script, SigHash,
+        SigHashType, Transaction, Txid, Wtxid,
     },
-    DescriptorTrait,
+    DescriptorTrait, Miniscript, Segwitv0, Terminal,
 };
 
 use std::{
-    collections::{BTreeMap, HashMap},
-    convert::TryInto,
+    collections::{btree_map, BTreeMap, HashMap},
+    convert::{TryFrom, TryInto},
     fmt,
 };
 
 #[macro_use]
 mod utils;
 
+pub mod coin_selection;
+pub mod fees;
+pub mod roles;
+
 mod cancel;
 mod cpfp;
 mod emergency;
+mod emergencysweep;
+mod migration;
 mod spend;
 mod unvault;
 mod unvaultemergency;
 
 pub use cancel::CancelTransaction;
+pub use coin_selection::{
+    select_feebump_coins, select_unvault_coins, CoinSelectionStrategy, SpendCoinSelection,
+};
 pub use cpfp::CpfpTransaction;
 pub use emergency::EmergencyTransaction;
-pub use spend::SpendTransaction;
+pub use emergencysweep::{EmergencySweepTransaction, SweepDestination};
+pub use fees::{FeeEstimator, FixedFeerate};
+pub use migration::MigrationTransaction;
+pub use spend::{SpendPolicy, SpendTransaction, SpendVerdict, SpendViolation};
 pub use unvault::UnvaultTransaction;
 pub use unvaultemergency::UnvaultEmergencyTransaction;
 
@@ -46,12 +61,51 @@ pub use unvaultemergency::UnvaultEmergencyTransaction;
 /// See [practical-revault](https://github.com/revault/practical-revault/blob/master/transactions.md#unvault_tx).
 pub const UNVAULT_CPFP_VALUE: u64 = 30000;
 
-/// The feerate, in sat / WU, to create the unvaulting transactions with.
+/// The feerate, in sat / WU, to create the unvaulting transactions with. The Unvault
+/// transactions are bumped through their CPFP output, so it only needs to be above the minimum
+/// relay feerate.
 pub const UNVAULT_TX_FEERATE: u64 = 6;
 
-/// The feerate, in sat / WU, to create the Emergency transactions with.
+/// The feerate, in sat / WU, the CPFP output of a Spend transaction reserves funds for. The
+/// managers must be able to bump the Spend to at least this feerate using only its CPFP output.
+/// See [practical-revault](https://github.com/revault/practical-revault/blob/master/transactions.md#spend_tx).
+pub const CPFP_RESERVE_FEERATE: u64 = 16;
+
+/// The minimum value of a CPFP output, the dust threshold of its P2WSH scriptPubKey.
+pub const CPFP_MIN_VALUE: u64 = 330;
+
+/// The feerate, in sat / WU, to create the Emergency transactions with. They are a last resort
+/// and can't be fee-bumped, so this is assumed to be high enough for them to confirm in any fee
+/// market (1000 sat/vbyte).
 pub const EMER_TX_FEERATE: u64 = 250;
 
+/// The feerates, in sat / WU, to create the batch of Cancel transactions with: 20, 100, 200, 500
+/// and 1000 sat/vbyte. They can't be fee-bumped, so the stakeholders presign a range assumed to
+/// cover the fee market at the time of a revocation and broadcast the one fitting it.
+pub const CANCEL_TX_FEERATES: [u64; 5] = [5, 25, 50, 125, 250];
+
+/// Get the feerate, as an amount per WU, the Unvault transactions are presigned at.
+/// See [UNVAULT_TX_FEERATE].
+pub fn unvault_feerate() -> Amount {
+    Amount::from_sat(UNVAULT_TX_FEERATE)
+}
+
+/// Get the feerate, as an amount per WU, the Emergency transactions are presigned at.
+/// See [EMER_TX_FEERATE].
+pub fn emergency_feerate() -> Amount {
+    Amount::from_sat(EMER_TX_FEERATE)
+}
+
+/// Get the feerates, as amounts per WU, the batch of Cancel transactions is presigned at in
+/// increasing order. See [CANCEL_TX_FEERATES].
+pub fn cancel_feerates() -> [Amount; 5] {
+    let mut feerates = [Amount::from_sat(0); 5];
+    for (feerate, sats) in feerates.iter_mut().zip(CANCEL_TX_FEERATES.iter()) {
+        *feerate = Amount::from_sat(*sats);
+    }
+    feerates
+}
+
 /// The minimum value of a deposit UTxO for creating a transaction chain from it.
 pub const DEPOSIT_MIN_SATS: u64 = 500_000;
 
@@ -78,6 +132,16 @@ pub const MAX_STANDARD_TX_WEIGHT: u32 = 400_000;
 /// of the inputs minus the fees is less than CPFP_MIN_CHANGE, we'll throw everything in fees.
 pub const CPFP_MIN_CHANGE: u64 = 10_000;
 
+/// Get the value of a CPFP output reserving enough funds to bump a transaction of `tx_weight`
+/// weight units at `feerate` sat / WU, and never less than [CPFP_MIN_VALUE].
+///
+/// Returns None if the value would overflow.
+pub fn cpfp_reserve(tx_weight: u64, feerate: u64) -> Option<Amount> {
+    tx_weight
+        .checked_mul(feerate)
+        .map(|value| Amount::from_sat(value.max(CPFP_MIN_VALUE)))
+}
+
 /// This private module is used to make mutable references to the PSBT inside transaction newtypes
 /// available to functions inside the transaction module, but not beyond that. This is needed to
 /// guarantee invariants that could not be guaranteed if users had arbitrary mutable access to the
@@ -86,6 +150,9 @@ pub(super) mod inner_mut {
     use super::{Psbt, TransactionSerialisationError};
 
     pub trait PrivateInnerMut: Sized {
+        /// The name of the transaction type, as used in its [super::TransactionSummary]
+        const TYPE_NAME: &'static str;
+
         /// Get a mutable reference to the inner transaction, this is only used internally
         fn psbt_mut(&mut self) -> &mut Psbt;
 
@@ -100,6 +167,302 @@ pub(super) mod inner_mut {
     }
 }
 
+/// The signatures of a transaction input, as counted by
+/// [RevaultTransaction::signatures_count].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignaturesCount {
+    valid: usize,
+    needed: usize,
+    missing: usize,
+}
+
+impl SignaturesCount {
+    /// The number of valid signatures this input holds
+    pub fn valid(&self) -> usize {
+        self.valid
+    }
+
+    /// The minimum number of signatures needed to satisfy this input's witness script
+    pub fn needed(&self) -> usize {
+        self.needed
+    }
+
+    /// The minimum number of signatures still needed to satisfy this input's witness script
+    /// given the valid signatures it already holds. It may be more than `needed - valid` if the
+    /// signatures are not all for the cheapest spending path.
+    pub fn missing(&self) -> usize {
+        self.missing
+    }
+}
+
+/// The sighash of an input, along with the keys which are expected to sign it and their
+/// derivation paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputSigHash {
+    sighash: SigHash,
+    derivation_paths: BTreeMap<BitcoinPubKey, KeySource>,
+}
+
+impl InputSigHash {
+    /// The message to sign, with SIGHASH_ALL
+    pub fn sighash(&self) -> SigHash {
+        self.sighash
+    }
+
+    /// The keys expected to sign this input, along with their origin
+    pub fn derivation_paths(&self) -> &BTreeMap<BitcoinPubKey, KeySource> {
+        &self.derivation_paths
+    }
+}
+
+/// The outcome of adding a signature to a transaction input, as returned by
+/// [RevaultTransaction::add_signature].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureInsertion {
+    /// There was no signature for this key yet
+    Inserted,
+    /// This raw signature for the same key was replaced
+    Replaced(Vec<u8>),
+}
+
+impl SignatureInsertion {
+    /// Whether a previous signature for the same key was replaced
+    pub fn is_replacement(&self) -> bool {
+        matches!(self, Self::Replaced(_))
+    }
+}
+
+// Clear the fields of a finalized input which are not needed anymore to extract the transaction,
+// as required by the BIP174 Input Finalizer role. Miniscript's finalize already clears the
+// signatures, sighash type, scripts and derivation paths, but leaves the hash preimages.
+fn clear_finalized_input(psbtin: &mut PsbtIn) {
+    psbtin.ripemd160_preimages.clear();
+    psbtin.sha256_preimages.clear();
+    psbtin.hash160_preimages.clear();
+    psbtin.hash256_preimages.clear();
+}
+
+// The BIP143 script code of this input. We only ever spend P2WSH outputs, for which it's the
+// witness script (see [RevaultInternalTxOut::script_code]).
+fn psbtin_script_code(psbtin: &PsbtIn) -> Result<&Script, InputSatisfactionError> {
+    let prev_txo = psbtin
+        .witness_utxo
+        .as_ref()
+        .expect("We always set witness_txo");
+    assert!(prev_txo.script_pubkey.is_v0_p2wsh());
+
+    psbtin
+        .witness_script
+        .as_ref()
+        .ok_or(InputSatisfactionError::MissingWitnessScript)
+}
+
+// Count the valid signatures of this non-finalized input against the number of signatures its
+// witness script needs. None if the witness script is not a Miniscript.
+fn count_signatures<C: secp256k1::Verification>(
+    psbtin: &PsbtIn,
+    witness_script: &Script,
+    sighash: &SigHash,
+    secp: &secp256k1::Secp256k1<C>,
+) -> Option<SignaturesCount> {
+    let sighash = secp256k1::Message::from_slice(sighash).expect("sighash is a 32 bytes hash");
+
+    let signers: Vec<BitcoinPubKey> = psbtin
+        .partial_sigs
+        .iter()
+        .filter_map(|(pubkey, rawsig)| {
+            let (sighash_type, der_sig) = rawsig.split_last()?;
+            if *sighash_type != SigHashType::All.as_u32() as u8 {
+                return None;
+            }
+            let signature = secp256k1::Signature::from_der(der_sig).ok()?;
+            secp.verify(&sighash, &signature, &pubkey.key).ok()?;
+            Some(*pubkey)
+        })
+        .collect();
+    let (needed, missing) = utils::needed_signatures(witness_script, &signers)?;
+
+    Some(SignaturesCount {
+        valid: signers.len(),
+        needed,
+        missing,
+    })
+}
+
+/// The next BIP174 role a transaction, or one of its inputs, needs to go through. Ordered from
+/// the earliest to the latest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PsbtRole {
+    /// Information about the spent output is missing
+    Updater,
+    /// Signatures are missing
+    Signer,
+    /// All the signatures are there, the witness can be created
+    Finalizer,
+    /// Finalized, the transaction can be extracted
+    Extractor,
+}
+
+/// The status of a PSBT input, as reported by [RevaultTransaction::analyze].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputAnalysis {
+    has_utxo: bool,
+    has_witness_script: bool,
+    signatures: Option<SignaturesCount>,
+    is_final: bool,
+    next: PsbtRole,
+}
+
+impl InputAnalysis {
+    /// Whether the spent output is present
+    pub fn has_utxo(&self) -> bool {
+        self.has_utxo
+    }
+
+    /// Whether the witness script of the spent output is present. It is wiped once finalized.
+    pub fn has_witness_script(&self) -> bool {
+        self.has_witness_script
+    }
+
+    /// The valid signatures this input holds against the ones it needs. None if they can't be
+    /// counted because the spent output or its witness script is missing, or because the
+    /// witness script is not a Miniscript.
+    pub fn signatures(&self) -> Option<&SignaturesCount> {
+        self.signatures.as_ref()
+    }
+
+    /// Whether enough signatures are present to finalize this input
+    pub fn is_finalizable(&self) -> bool {
+        self.next == PsbtRole::Finalizer
+    }
+
+    /// Whether this input is finalized
+    pub fn is_final(&self) -> bool {
+        self.is_final
+    }
+
+    /// The next role this input needs
+    pub fn next(&self) -> PsbtRole {
+        self.next
+    }
+}
+
+/// The status of a PSBT, as reported by [RevaultTransaction::analyze].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtAnalysis {
+    inputs: Vec<InputAnalysis>,
+    next: PsbtRole,
+}
+
+impl PsbtAnalysis {
+    /// The status of each input, in input order
+    pub fn inputs(&self) -> &[InputAnalysis] {
+        &self.inputs
+    }
+
+    /// The next role the transaction needs, that is the earliest role one of its inputs needs
+    pub fn next(&self) -> PsbtRole {
+        self.next
+    }
+}
+
+/// A short description of a transaction, as returned by [RevaultTransaction::summary].
+///
+/// Its [fmt::Display] implementation fits on a single line, and doesn't contain any script or
+/// signature, to be used in logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionSummary {
+    tx_type: &'static str,
+    txid: Txid,
+    inputs: usize,
+    outputs: usize,
+    value: Amount,
+    fees: Amount,
+    signatures: usize,
+    final_inputs: usize,
+}
+
+impl TransactionSummary {
+    /// The name of the type of the transaction, for instance "CancelTransaction"
+    pub fn tx_type(&self) -> &'static str {
+        self.tx_type
+    }
+
+    /// The unsigned transaction id
+    pub fn txid(&self) -> Txid {
+        self.txid
+    }
+
+    /// The number of inputs
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    /// The number of outputs
+    pub fn outputs(&self) -> usize {
+        self.outputs
+    }
+
+    /// The sum of the outputs' value
+    pub fn value(&self) -> Amount {
+        self.value
+    }
+
+    /// The absolute fees paid by the transaction
+    pub fn fees(&self) -> Amount {
+        self.fees
+    }
+
+    /// The number of partial signatures of the non-finalized inputs. They are not checked.
+    pub fn signatures(&self) -> usize {
+        self.signatures
+    }
+
+    /// The number of finalized inputs
+    pub fn final_inputs(&self) -> usize {
+        self.final_inputs
+    }
+}
+
+impl fmt::Display for TransactionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} ({} input(s), {} output(s), value: {}, fees: {}, {} partial signature(s), \
+             {}/{} input(s) finalized)",
+            self.tx_type,
+            self.txid,
+            self.inputs,
+            self.outputs,
+            self.value,
+            self.fees,
+            self.signatures,
+            self.final_inputs,
+            self.inputs
+        )
+    }
+}
+
+// Verify all PSBT inputs as [RevaultTransaction::verify_inputs] does, but reusing the caller's
+// context for the Miniscript interpreter on wasm32. libbitcoinconsensus doesn't need one.
+#[cfg(not(target_arch = "wasm32"))]
+fn verify_inputs_with<C: secp256k1::Verification>(
+    tx: &impl RevaultTransaction,
+    _: &secp256k1::Secp256k1<C>,
+) -> Result<(), Error> {
+    tx.verify_inputs()
+}
+
+// libbitcoinconsensus is not available on wasm32, the Miniscript interpreter uses the caller's
+// context.
+#[cfg(target_arch = "wasm32")]
+fn verify_inputs_with<C: secp256k1::Verification>(
+    tx: &impl RevaultTransaction,
+    ctx: &secp256k1::Secp256k1<C>,
+) -> Result<(), Error> {
+    miniscript::psbt::interpreter_check(tx.psbt(), ctx).map_err(Error::TransactionFinalisation)
+}
+
 /// A Revault transaction.
 ///
 /// Wraps a rust-bitcoin PSBT and defines some BIP174 roles as methods.
@@ -108,6 +471,11 @@ pub(super) mod inner_mut {
 /// - Signer
 /// - Finalizer
 /// - Extractor and serializer
+///
+/// The methods needing a libsecp256k1 context never create one but take it as a parameter, so
+/// that a single context can be reused for verifying any number of signatures (only
+/// [RevaultTransaction::verify_inputs] creates one, on wasm32). The descriptor
+/// methods they rely on for computing sizes and sanity checking don't need one at all.
 pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
     /// Get a reference to the inner PSBT
     fn psbt(&self) -> &Psbt;
@@ -131,21 +499,126 @@ pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
         cache: &mut SigHashCache<&Transaction>,
     ) -> Result<SigHash, InputSatisfactionError>;
 
+    /// Get the sighash of every input of this transaction, in input order, along with the
+    /// derivation paths of the keys expected to sign it. This is all a signing device needs to
+    /// sign the whole transaction at once.
+    ///
+    /// ## Errors
+    /// - if the RevaultTransaction was already finalized
+    fn all_sighashes(&self) -> Result<Vec<InputSigHash>, InputSatisfactionError>;
+
+    /// Get the Miniscript of the witness script of this input, that is the exact script its
+    /// signatures commit to.
+    ///
+    /// ## Errors
+    /// - if the input is out of bounds
+    /// - if the input was already finalized
+    /// - if the witness script is missing or is not a Miniscript
+    fn input_miniscript(
+        &self,
+        input_index: usize,
+    ) -> Result<Miniscript<BitcoinPubKey, Segwitv0>, InputSatisfactionError>;
+
     /// Add a signature in order to eventually satisfy this input.
     ///
-    /// NOTE: this checks the signature. The expected signature type is ALL.
+    /// The signature is given along with its sighash type, which is appended to the DER-encoded
+    /// signature in the PSBT. All Revault transactions are signed with ALL, any other sighash type
+    /// is refused.
+    ///
+    /// NOTE: this checks the signature, and that the key is one of the input's witness script.
+    /// Returns whether a previous signature for this key was replaced.
     ///
     /// The BIP174 Signer role.
     fn add_signature<C: secp256k1::Verification>(
         &mut self,
         input_index: usize,
         pubkey: secp256k1::PublicKey,
-        signature: secp256k1::Signature,
+        signature: (secp256k1::Signature, SigHashType),
         secp: &secp256k1::Secp256k1<C>,
-    ) -> Result<Option<Vec<u8>>, InputSatisfactionError>;
+    ) -> Result<SignatureInsertion, InputSatisfactionError>;
+
+    /// Add the preimage of a hashlock of this input's witness script, as may be used by custom
+    /// spending policies (see [UnvaultDescriptor::with_spend_policy]). It is stored in the PSBT
+    /// input for each of the hashlocks it satisfies, to be used at finalization.
+    ///
+    /// ## Errors
+    /// - if the input is out of bounds
+    /// - if the input was already finalized
+    /// - if the witness script is missing or is not a Miniscript
+    /// - if no hashlock of the witness script is satisfied by this preimage
+    fn add_preimage(
+        &mut self,
+        input_index: usize,
+        preimage: [u8; 32],
+    ) -> Result<(), InputSatisfactionError>;
+
+    /// Verify all the partial signatures of all the inputs of this transaction.
+    ///
+    /// The sighashes are computed once per input using a single [SigHashCache] for the whole
+    /// transaction, which is much cheaper than calling [RevaultTransaction::signature_hash] for
+    /// each signature.
+    /// NOTE: libsecp256k1 does not provide batch verification for ECDSA signatures, each of them
+    /// is checked against its precomputed sighash using the same context.
+    fn verify_signatures<C: secp256k1::Verification>(
+        &self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), InputSatisfactionError>;
+
+    /// Merge the partial signatures of `other`, another version of this transaction, into this
+    /// one.
+    ///
+    /// Both versions must be for the same unsigned transaction, and their inputs must agree on
+    /// the spent txout, the witness script, the sighash type and the derivation paths. Conflicting
+    /// fields are reported instead of preferring one side, and the signatures of `other` are
+    /// checked before being added: they must be valid, by a key of the witness script, and not
+    /// conflict with a signature we already have for this key. If `other` is finalized and this transaction is not, its final
+    /// witnesses are checked and adopted. A finalized transaction is left as is. This
+    /// transaction is not modified on error.
+    ///
+    /// The BIP174 Combiner role.
+    fn combine<C: secp256k1::Verification>(
+        &mut self,
+        other: Self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), PsbtCombinationError>;
+
+    /// Count, for each input, the valid partial signatures it holds against the number of
+    /// signatures its witness script needs. Invalid signatures are not counted.
+    ///
+    /// A finalized input doesn't need any more signature.
+    ///
+    /// ## Errors
+    /// - if a non-finalized input has no witness script
+    /// - if the witness script of a non-finalized input is not a Miniscript
+    fn signatures_count<C: secp256k1::Verification>(
+        &self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Vec<SignaturesCount>, InputSatisfactionError>;
+
+    /// Get the indexes of the inputs which don't hold enough valid signatures to be finalized.
+    ///
+    /// See [RevaultTransaction::signatures_count] for the errors.
+    fn inputs_missing_sigs<C: secp256k1::Verification>(
+        &self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Vec<usize>, InputSatisfactionError>;
+
+    /// Get the status of each input and the next BIP174 role this transaction needs, in the
+    /// manner of bitcoind's `analyzepsbt`. Unlike [RevaultTransaction::signatures_count], this
+    /// never errors in order to explain why a transaction is not ready.
+    fn analyze<C: secp256k1::Verification>(&self, secp: &secp256k1::Secp256k1<C>) -> PsbtAnalysis;
 
     /// Check and satisfy the scripts, create the witnesses.
     ///
+    /// Either all the inputs are finalized or, on error, the transaction is left untouched.
+    /// Finalizing an already finalized transaction is a no-op.
+    ///
+    /// ## Errors
+    /// - [Error::InputsFinalisation] listing all the inputs missing a witness script or
+    ///   signatures, so that they can all be requested at once
+    /// - [Error::TransactionFinalisation] or [Error::TransactionVerification] if the
+    ///   satisfaction failed for another reason
+    ///
     /// The BIP174 Input Finalizer role.
     fn finalize(
         &mut self,
@@ -160,10 +633,22 @@ pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
     /// Check if the transaction was already finalized.
     fn is_finalized(&self) -> bool;
 
+    /// Check if this input was already finalized.
+    ///
+    /// ## Errors
+    /// - if the input is out of bounds
+    fn is_input_finalized(&self, input_index: usize) -> Result<bool, InputSatisfactionError>;
+
+    /// Check whether this transaction and `other` are the same unsigned transaction, regardless
+    /// of their signatures and of whether their inputs were finalized. This is the case for
+    /// differently-signed versions of the same PSBT, which can then be merged.
+    fn same_unsigned_tx(&self, other: &Self) -> bool;
+
     /// Check the transaction is valid
     fn is_valid(&self, ctx: &secp256k1::Secp256k1<impl secp256k1::Verification>) -> bool;
 
-    /// Verify all PSBT inputs against libbitcoinconsensus
+    /// Verify all PSBT inputs against libbitcoinconsensus (the Miniscript interpreter on wasm32,
+    /// for which a verification context is created)
     fn verify_inputs(&self) -> Result<(), Error>;
 
     /// Get the network-serialized (inner) transaction. You likely want to be sure
@@ -176,6 +661,12 @@ pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
     /// Get the BIP174-serialized (inner) transaction.
     fn as_psbt_serialized(&self) -> Vec<u8>;
 
+    /// Get the hex-encoded network-serialized (inner) transaction.
+    ///
+    /// A thin wrapper around the [fmt::LowerHex] implementation of the transaction, which is
+    /// also available as [fmt::UpperHex].
+    fn hex(&self) -> String;
+
     /// Create a RevaultTransaction from a base64-encoded BIP174-serialized transaction.
     fn from_psbt_serialized(raw_psbt: &[u8]) -> Result<Self, TransactionSerialisationError>;
 
@@ -203,6 +694,10 @@ pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
     /// The BIP174 Transaction Extractor (without any check, which are done in
     /// [RevaultTransaction.finalize]).
     fn into_tx(self) -> Transaction;
+
+    /// Get a short description of this transaction, to be logged. Unlike the [fmt::Display]
+    /// implementation of the transaction, this is not its base64-encoded PSBT.
+    fn summary(&self) -> TransactionSummary;
 }
 
 /// A Revault transaction which is signed in advance and whose signatures are exchanged by
@@ -238,9 +733,9 @@ pub trait RevaultPresignedTransaction: RevaultTransaction {
     fn add_sig<C: secp256k1::Verification>(
         &mut self,
         pubkey: secp256k1::PublicKey,
-        signature: secp256k1::Signature,
+        signature: (secp256k1::Signature, SigHashType),
         secp: &secp256k1::Secp256k1<C>,
-    ) -> Result<Option<Vec<u8>>, InputSatisfactionError> {
+    ) -> Result<SignatureInsertion, InputSatisfactionError> {
         debug_assert_eq!(
             self.psbt().inputs.len(),
             1,
@@ -255,7 +750,9 @@ pub trait RevaultPresignedTransaction: RevaultTransaction {
     }
 }
 
-impl<T: inner_mut::PrivateInnerMut + fmt::Debug + Clone + PartialEq> RevaultTransaction for T {
+impl<T: inner_mut::PrivateInnerMut + fmt::Debug + fmt::LowerHex + Clone + PartialEq>
+    RevaultTransaction for T
+{
     fn psbt(&self) -> &Psbt {
         inner_mut::PrivateInnerMut::psbt(self)
     }
@@ -284,21 +781,61 @@ impl<T: inner_mut::PrivateInnerMut + fmt::Debug + Clone + PartialEq> RevaultTran
             .as_ref()
             .expect("We always set witness_txo");
 
-        assert!(prev_txo.script_pubkey.is_v0_p2wsh());
-        let witscript = psbtin
+        let script_code = psbtin_script_code(psbtin)?;
+        Ok(cache.signature_hash(input_index, script_code, prev_txo.value, SigHashType::All))
+    }
+
+    fn all_sighashes(&self) -> Result<Vec<InputSigHash>, InputSatisfactionError> {
+        let mut cache = SigHashCache::new(self.tx());
+
+        self.psbt()
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, psbtin)| {
+                Ok(InputSigHash {
+                    sighash: self.signature_hash_cached(i, &mut cache)?,
+                    derivation_paths: psbtin.bip32_derivation.clone(),
+                })
+            })
+            .collect()
+    }
+
+    fn input_miniscript(
+        &self,
+        input_index: usize,
+    ) -> Result<Miniscript<BitcoinPubKey, Segwitv0>, InputSatisfactionError> {
+        let psbtin = self
+            .psbt()
+            .inputs
+            .get(input_index)
+            .ok_or(InputSatisfactionError::OutOfBounds)?;
+
+        // If we were already finalized, our witness script was wiped.
+        if psbtin.final_script_witness.is_some() {
+            return Err(InputSatisfactionError::AlreadyFinalized);
+        }
+
+        let witness_script = psbtin
             .witness_script
             .as_ref()
             .ok_or(InputSatisfactionError::MissingWitnessScript)?;
-        Ok(cache.signature_hash(input_index, &witscript, prev_txo.value, SigHashType::All))
+        Miniscript::parse(witness_script).map_err(|_| InputSatisfactionError::NotMiniscript)
     }
 
     fn add_signature<C: secp256k1::Verification>(
         &mut self,
         input_index: usize,
         pubkey: secp256k1::PublicKey,
-        signature: secp256k1::Signature,
+        signature: (secp256k1::Signature, SigHashType),
         secp: &secp256k1::Secp256k1<C>,
-    ) -> Result<Option<Vec<u8>>, InputSatisfactionError> {
+    ) -> Result<SignatureInsertion, InputSatisfactionError> {
+        let (signature, sighash_type) = signature;
+        // We only ever sign (and compute the sighash for) ALL
+        if sighash_type != SigHashType::All {
+            return Err(InputSatisfactionError::InvalidSighashType(sighash_type));
+        }
+
         let psbtin = self
             .psbt()
             .inputs
@@ -343,36 +880,366 @@ impl<T: inner_mut::PrivateInnerMut + fmt::Debug + Clone + PartialEq> RevaultTran
         secp.verify(&sighash, &signature, &pubkey)
             .map_err(|_| InputSatisfactionError::InvalidSignature(signature, pubkey, sighash))?;
 
+        // A valid signature for a key we don't expect likely means the signer derived the wrong
+        // key, refuse it instead of carrying it until finalization.
         let pubkey = BitcoinPubKey {
             compressed: true,
             key: pubkey,
         };
+        if !utils::script_has_key(witness_script, &pubkey)
+            .ok_or(InputSatisfactionError::NotMiniscript)?
+        {
+            return Err(InputSatisfactionError::UnknownKey(pubkey.key));
+        }
         let mut rawsig = signature.serialize_der().to_vec();
-        rawsig.push(SigHashType::All.as_u32() as u8);
+        rawsig.push(sighash_type.as_u32() as u8);
 
         let psbtin = self
             .psbt_mut()
             .inputs
             .get_mut(input_index)
             .expect("Checked at the beginning.");
-        Ok(psbtin.partial_sigs.insert(pubkey, rawsig))
+        Ok(match psbtin.partial_sigs.insert(pubkey, rawsig) {
+            Some(prev_rawsig) => SignatureInsertion::Replaced(prev_rawsig),
+            None => SignatureInsertion::Inserted,
+        })
+    }
+
+    fn add_preimage(
+        &mut self,
+        input_index: usize,
+        preimage: [u8; 32],
+    ) -> Result<(), InputSatisfactionError> {
+        let ms = self.input_miniscript(input_index)?;
+        let psbtin = self
+            .psbt_mut()
+            .inputs
+            .get_mut(input_index)
+            .expect("Checked by input_miniscript.");
+
+        let mut is_known = false;
+        for node in ms.iter() {
+            match node.node {
+                Terminal::Sha256(h) if h == sha256::Hash::hash(&preimage) => {
+                    psbtin.sha256_preimages.insert(h, preimage.to_vec());
+                }
+                Terminal::Hash256(h) if h == sha256d::Hash::hash(&preimage) => {
+                    psbtin.hash256_preimages.insert(h, preimage.to_vec());
+                }
+                Terminal::Ripemd160(h) if h == ripemd160::Hash::hash(&preimage) => {
+                    psbtin.ripemd160_preimages.insert(h, preimage.to_vec());
+                }
+                Terminal::Hash160(h) if h == hash160::Hash::hash(&preimage) => {
+                    psbtin.hash160_preimages.insert(h, preimage.to_vec());
+                }
+                _ => continue,
+            }
+            is_known = true;
+        }
+
+        if !is_known {
+            return Err(InputSatisfactionError::UnknownPreimage);
+        }
+        Ok(())
+    }
+
+    fn verify_signatures<C: secp256k1::Verification>(
+        &self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), InputSatisfactionError> {
+        let mut cache = SigHashCache::new(self.tx());
+
+        for (i, psbtin) in self.psbt().inputs.iter().enumerate() {
+            // Finalized inputs don't have partial signatures anymore
+            if psbtin.partial_sigs.is_empty() {
+                continue;
+            }
+
+            let sighash = self.signature_hash_cached(i, &mut cache)?;
+            let sighash =
+                secp256k1::Message::from_slice(&sighash).expect("sighash is a 32 bytes hash");
+
+            for (pubkey, rawsig) in psbtin.partial_sigs.iter() {
+                let (sighash_type, der_sig) = rawsig
+                    .split_last()
+                    .ok_or_else(|| InputSatisfactionError::InvalidRawSignature(rawsig.clone()))?;
+                if *sighash_type != SigHashType::All.as_u32() as u8 {
+                    return Err(InputSatisfactionError::InvalidRawSignature(rawsig.clone()));
+                }
+                let signature = secp256k1::Signature::from_der(der_sig)
+                    .map_err(|_| InputSatisfactionError::InvalidRawSignature(rawsig.clone()))?;
+
+                secp.verify(&sighash, &signature, &pubkey.key)
+                    .map_err(|_| {
+                        InputSatisfactionError::InvalidSignature(signature, pubkey.key, sighash)
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn combine<C: secp256k1::Verification>(
+        &mut self,
+        other: Self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), PsbtCombinationError> {
+        let other_is_finalized = other.is_finalized();
+        let other = RevaultTransaction::into_psbt(other);
+        let psbt = self.psbt();
+        if psbt.global != other.global
+            || psbt.outputs != other.outputs
+            || psbt.inputs.len() != other.inputs.len()
+        {
+            return Err(PsbtCombinationError::DifferentTransactions);
+        }
+
+        // There is nothing left to merge into a finalized transaction, but we must not drop the
+        // witnesses of a finalized peer. We never mix finalized and non-finalized inputs.
+        if other_is_finalized && !self.is_finalized() {
+            for (i, (psbtin, other_psbtin)) in
+                psbt.inputs.iter().zip(other.inputs.iter()).enumerate()
+            {
+                if psbtin.witness_utxo != other_psbtin.witness_utxo {
+                    return Err(PsbtCombinationError::ConflictingWitnessUtxo(i));
+                }
+            }
+
+            let unfinalized_psbt = std::mem::replace(self.psbt_mut(), other);
+            if verify_inputs_with(self, secp).is_err() {
+                *self.psbt_mut() = unfinalized_psbt;
+                return Err(PsbtCombinationError::InvalidFinalWitness);
+            }
+            return Ok(());
+        }
+
+        // Work on a copy of the inputs, not to leave a half-merged transaction on error
+        let mut inputs = psbt.inputs.clone();
+        let mut cache = SigHashCache::new(self.tx());
+        for (i, (psbtin, other_psbtin)) in inputs.iter_mut().zip(other.inputs).enumerate() {
+            // A malicious or buggy peer must not be able to swap the txout under a signer
+            if psbtin.witness_utxo != other_psbtin.witness_utxo {
+                return Err(PsbtCombinationError::ConflictingWitnessUtxo(i));
+            }
+
+            // The below fields are blanked when finalized
+            if psbtin.final_script_witness.is_some() || other_psbtin.final_script_witness.is_some()
+            {
+                continue;
+            }
+            if psbtin.witness_script != other_psbtin.witness_script {
+                return Err(PsbtCombinationError::ConflictingWitnessScript(i));
+            }
+            if psbtin.sighash_type != other_psbtin.sighash_type {
+                return Err(PsbtCombinationError::ConflictingSighashType(i));
+            }
+            if psbtin.bip32_derivation != other_psbtin.bip32_derivation {
+                return Err(PsbtCombinationError::ConflictingDerivationPaths(i));
+            }
+
+            let sighash = self
+                .signature_hash_cached(i, &mut cache)
+                .map_err(|_| PsbtCombinationError::InvalidSignature(i))?;
+            let sighash =
+                secp256k1::Message::from_slice(&sighash).expect("sighash is a 32 bytes hash");
+            for (pubkey, rawsig) in other_psbtin.partial_sigs {
+                // Same check as for the signatures we add ourselves
+                let is_known_key = psbtin
+                    .witness_script
+                    .as_ref()
+                    .and_then(|witness_script| utils::script_has_key(witness_script, &pubkey))
+                    .unwrap_or(false);
+                if !is_known_key {
+                    return Err(PsbtCombinationError::UnknownKey(i));
+                }
+                let (sighash_type, der_sig) = rawsig
+                    .split_last()
+                    .ok_or(PsbtCombinationError::InvalidSignature(i))?;
+                if *sighash_type != SigHashType::All.as_u32() as u8 {
+                    return Err(PsbtCombinationError::InvalidSignature(i));
+                }
+                let signature = secp256k1::Signature::from_der(der_sig)
+                    .map_err(|_| PsbtCombinationError::InvalidSignature(i))?;
+                secp.verify(&sighash, &signature, &pubkey.key)
+                    .map_err(|_| PsbtCombinationError::InvalidSignature(i))?;
+
+                match psbtin.partial_sigs.entry(pubkey) {
+                    btree_map::Entry::Vacant(entry) => {
+                        entry.insert(rawsig);
+                    }
+                    btree_map::Entry::Occupied(entry) => {
+                        if *entry.get() != rawsig {
+                            return Err(PsbtCombinationError::ConflictingSignature(i));
+                        }
+                    }
+                }
+            }
+        }
+
+        self.psbt_mut().inputs = inputs;
+        Ok(())
+    }
+
+    fn signatures_count<C: secp256k1::Verification>(
+        &self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Vec<SignaturesCount>, InputSatisfactionError> {
+        let mut cache = SigHashCache::new(self.tx());
+
+        self.psbt()
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, psbtin)| {
+                if psbtin.final_script_witness.is_some() {
+                    return Ok(SignaturesCount {
+                        valid: 0,
+                        needed: 0,
+                        missing: 0,
+                    });
+                }
+
+                let witness_script = psbtin
+                    .witness_script
+                    .as_ref()
+                    .ok_or(InputSatisfactionError::MissingWitnessScript)?;
+                let sighash = self.signature_hash_cached(i, &mut cache)?;
+
+                count_signatures(psbtin, witness_script, &sighash, secp)
+                    .ok_or(InputSatisfactionError::NotMiniscript)
+            })
+            .collect()
+    }
+
+    fn inputs_missing_sigs<C: secp256k1::Verification>(
+        &self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Vec<usize>, InputSatisfactionError> {
+        Ok(self
+            .signatures_count(secp)?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, count)| if count.missing > 0 { Some(i) } else { None })
+            .collect())
+    }
+
+    fn analyze<C: secp256k1::Verification>(&self, secp: &secp256k1::Secp256k1<C>) -> PsbtAnalysis {
+        let mut cache = SigHashCache::new(self.tx());
+
+        let inputs: Vec<InputAnalysis> = self
+            .psbt()
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, psbtin)| {
+                let has_utxo = psbtin.witness_utxo.is_some();
+                let has_witness_script = psbtin.witness_script.is_some();
+                let is_final = psbtin.final_script_witness.is_some();
+
+                let signatures = if is_final {
+                    Some(SignaturesCount {
+                        valid: 0,
+                        needed: 0,
+                        missing: 0,
+                    })
+                } else if has_utxo {
+                    psbtin.witness_script.as_ref().and_then(|witness_script| {
+                        let sighash = self.signature_hash_cached(i, &mut cache).ok()?;
+                        count_signatures(psbtin, witness_script, &sighash, secp)
+                    })
+                } else {
+                    None
+                };
+
+                let next = if is_final {
+                    PsbtRole::Extractor
+                } else {
+                    match signatures {
+                        None => PsbtRole::Updater,
+                        Some(ref count) if count.missing > 0 => PsbtRole::Signer,
+                        Some(_) => PsbtRole::Finalizer,
+                    }
+                };
+
+                InputAnalysis {
+                    has_utxo,
+                    has_witness_script,
+                    signatures,
+                    is_final,
+                    next,
+                }
+            })
+            .collect();
+        let next = inputs
+            .iter()
+            .map(|input| input.next)
+            .min()
+            .unwrap_or(PsbtRole::Extractor);
+
+        PsbtAnalysis { inputs, next }
     }
 
     fn finalize(
         &mut self,
         ctx: &secp256k1::Secp256k1<impl secp256k1::Verification>,
     ) -> Result<(), Error> {
-        // We could operate on a clone for state consistency in case of error. But we can only end
-        // up in an inconsistent state if miniscript's interpreter checks pass but not
-        // libbitcoinconsensus' one.
-        let mut psbt = self.psbt_mut();
+        // The fields needed to satisfy the inputs were cleared once finalized, don't try to
+        // finalize them again. We never mix finalized and non-finalized inputs.
+        if self.is_finalized() {
+            return Ok(());
+        }
 
-        miniscript::psbt::finalize(&mut psbt, ctx)
-            .map_err(|e| Error::TransactionFinalisation(e.to_string()))?;
+        // Miniscript's finalize sets the witnesses one input at a time, and we check them
+        // afterward. Keep a copy to restore in case of error so that we are never left with some
+        // of the inputs finalized.
+        let unfinalized_psbt = self.psbt().clone();
+
+        let res = miniscript::psbt::finalize(self.psbt_mut(), ctx)
+            .map_err(Error::TransactionFinalisation)
+            // Miniscript's finalize does not check against libbitcoinconsensus. And we are better
+            // safe than sorry when dealing with Script ...
+            .and_then(|_| verify_inputs_with(self, ctx));
+        if let Err(e) = res {
+            *self.psbt_mut() = unfinalized_psbt;
+
+            // Don't stop at the first input Miniscript failed to satisfy, report all the inputs
+            // we can tell are missing something.
+            let mut cache = SigHashCache::new(self.tx());
+            let failures: Vec<(usize, InputSatisfactionError)> = self
+                .psbt()
+                .inputs
+                .iter()
+                .enumerate()
+                .filter_map(|(i, psbtin)| {
+                    let witness_script = match psbtin.witness_script.as_ref() {
+                        Some(witness_script) => witness_script,
+                        None => return Some((i, InputSatisfactionError::MissingWitnessScript)),
+                    };
+                    let sighash = match self.signature_hash_cached(i, &mut cache) {
+                        Ok(sighash) => sighash,
+                        Err(e) => return Some((i, e)),
+                    };
+
+                    match count_signatures(psbtin, witness_script, &sighash, ctx) {
+                        None => Some((i, InputSatisfactionError::NotMiniscript)),
+                        Some(count) if count.missing > 0 => {
+                            Some((i, InputSatisfactionError::MissingSignatures(count.missing)))
+                        }
+                        Some(_) => None,
+                    }
+                })
+                .collect();
+
+            return if failures.is_empty() {
+                Err(e)
+            } else {
+                Err(Error::InputsFinalisation(failures))
+            };
+        }
 
-        // Miniscript's finalize does not check against libbitcoinconsensus. And we are better safe
-        // than sorry when dealing with Script ...
-        self.verify_inputs()?;
+        for psbtin in self.psbt_mut().inputs.iter_mut() {
+            clear_finalized_input(psbtin);
+        }
 
         Ok(())
     }
@@ -396,6 +1263,40 @@ impl<T: inner_mut::PrivateInnerMut + fmt::Debug + Clone + PartialEq> RevaultTran
         false
     }
 
+    fn is_input_finalized(&self, input_index: usize) -> Result<bool, InputSatisfactionError> {
+        self.psbt()
+            .inputs
+            .get(input_index)
+            .map(|psbtin| psbtin.final_script_witness.is_some())
+            .ok_or(InputSatisfactionError::OutOfBounds)
+    }
+
+    fn same_unsigned_tx(&self, other: &Self) -> bool {
+        let (psbt, other_psbt) = (self.psbt(), other.psbt());
+        if psbt.global != other_psbt.global
+            || psbt.outputs != other_psbt.outputs
+            || psbt.inputs.len() != other_psbt.inputs.len()
+        {
+            return false;
+        }
+
+        psbt.inputs
+            .iter()
+            .zip(other_psbt.inputs.iter())
+            .all(|(psbtin, other_psbtin)| {
+                if psbtin.witness_utxo != other_psbtin.witness_utxo {
+                    return false;
+                }
+
+                // The witness script and the derivation paths are cleared when finalized
+                psbtin.final_script_witness.is_some()
+                    || other_psbtin.final_script_witness.is_some()
+                    || (psbtin.witness_script == other_psbtin.witness_script
+                        && psbtin.bip32_derivation == other_psbtin.bip32_derivation
+                        && psbtin.sighash_type == other_psbtin.sighash_type)
+            })
+    }
+
     /// Check the transaction is valid
     fn is_valid(&self, ctx: &secp256k1::Secp256k1<impl secp256k1::Verification>) -> bool {
         if !self.is_finalized() {
@@ -404,7 +1305,7 @@ impl<T: inner_mut::PrivateInnerMut + fmt::Debug + Clone + PartialEq> RevaultTran
 
         // Miniscript's finalize does not check against libbitcoinconsensus. And we are better safe
         // than sorry when dealing with Script ...
-        if self.verify_inputs().is_err() {
+        if verify_inputs_with(self, ctx).is_err() {
             return false;
         }
         assert_eq!(self.psbt().inputs.len(), self.tx().input.len());
@@ -413,8 +1314,12 @@ impl<T: inner_mut::PrivateInnerMut + fmt::Debug + Clone + PartialEq> RevaultTran
     }
 
     /// Verify all PSBT inputs against libbitcoinconsensus
+    #[cfg(not(target_arch = "wasm32"))]
     fn verify_inputs(&self) -> Result<(), Error> {
-        let ser_tx = self.clone().into_bitcoin_serialized();
+        let mut ser_tx = Vec::with_capacity(256);
+        utils::final_tx(self.psbt())
+            .consensus_encode(&mut ser_tx)
+            .expect("We only create valid PSBT, serialization cannot fail");
 
         for (i, psbtin) in self.psbt().inputs.iter().enumerate() {
             let utxo = psbtin
@@ -429,6 +1334,13 @@ impl<T: inner_mut::PrivateInnerMut + fmt::Debug + Clone + PartialEq> RevaultTran
         Ok(())
     }
 
+    /// Verify all PSBT inputs against the Miniscript interpreter, as libbitcoinconsensus is not
+    /// available on wasm32.
+    #[cfg(target_arch = "wasm32")]
+    fn verify_inputs(&self) -> Result<(), Error> {
+        verify_inputs_with(self, &secp256k1::Secp256k1::verification_only())
+    }
+
     /// Get the network-serialized (inner) transaction. You likely want to be sure
     /// the transaction [RevaultTransaction.is_finalized] before serializing it.
     ///
@@ -452,6 +1364,10 @@ impl<T: inner_mut::PrivateInnerMut + fmt::Debug + Clone + PartialEq> RevaultTran
         buff
     }
 
+    fn hex(&self) -> String {
+        format!("{:x}", self)
+    }
+
     /// Get the BIP174-serialized (inner) transaction encoded in base64.
     fn as_psbt_string(&self) -> String {
         base64::encode(self.as_psbt_serialized())
@@ -497,6 +1413,31 @@ impl<T: inner_mut::PrivateInnerMut + fmt::Debug + Clone + PartialEq> RevaultTran
     fn into_tx(self) -> Transaction {
         self.into_psbt().extract_tx()
     }
+
+    fn summary(&self) -> TransactionSummary {
+        let psbt = self.psbt();
+        TransactionSummary {
+            tx_type: <T as inner_mut::PrivateInnerMut>::TYPE_NAME,
+            txid: self.txid(),
+            inputs: psbt.inputs.len(),
+            outputs: psbt.global.unsigned_tx.output.len(),
+            value: Amount::from_sat(
+                psbt.global
+                    .unsigned_tx
+                    .output
+                    .iter()
+                    .map(|txo| txo.value)
+                    .sum(),
+            ),
+            fees: self.fees(),
+            signatures: psbt.inputs.iter().map(|i| i.partial_sigs.len()).sum(),
+            final_inputs: psbt
+                .inputs
+                .iter()
+                .filter(|i| i.final_script_witness.is_some())
+                .count(),
+        }
+    }
 }
 
 /// A transaction that can be CPFPed
@@ -515,10 +1456,17 @@ pub trait CpfpableTransaction: RevaultTransaction {
                 continue;
             }
 
-            // But when we do set it, the path always have a depth of 1
+            // But when we do set it, the path always ends with the derivation index
             let der_path = &psbtouts[i].bip32_derivation.values().next().unwrap().1;
-            assert_eq!(der_path.len(), 1,);
-            let der_index = der_path[0];
+            let der_index = match der_path
+                .as_ref()
+                .last()
+                .map(|c| DerivationIndex::try_from(*c))
+            {
+                Some(Ok(index)) => index,
+                // We never derive at hardened indexes, it can't be our CPFP txo
+                _ => continue,
+            };
             let der_cpfp_desc = cpfp_descriptor.derive(der_index, secp);
 
             // Is it the CPFP txo?
@@ -555,12 +1503,59 @@ pub trait CpfpableTransaction: RevaultTransaction {
     /// is already finalized, returns the exact size in witness units. Otherwise computes the
     /// maximum reasonable weight of a satisfaction.
     fn max_weight(&self) -> u64;
+
+    /// Get the feerate, in sats/kWU, to add to this transaction through a CPFP in order to reach
+    /// the feerate given by the `estimator` for a confirmation within `target_blocks` blocks.
+    /// Returns 0 if this transaction already pays at least this feerate.
+    fn feebump_feerate(&self, estimator: &impl FeeEstimator, target_blocks: u16) -> u64 {
+        let target_feerate = fees::sat_per_kwu(estimator.feerate(target_blocks));
+
+        // Don't use max_feerate(), which is rounded up to a whole sat/WU and would underestimate
+        // the bump by up to 999 sats/kWU.
+        let weight = self.max_weight();
+        let feerate = self
+            .fees()
+            .as_sat()
+            .checked_mul(1_000)
+            .and_then(|fees| fees.checked_add(weight - 1)) // Weight is never 0
+            .expect("Feerate computation bug, fees >u64::MAX / 1000")
+            .checked_div(weight)
+            .expect("Weight is never 0");
+
+        target_feerate.saturating_sub(feerate)
+    }
 }
 
 /// The funding transaction, we don't create nor sign it.
 #[derive(Debug, Clone, PartialEq)]
 pub struct DepositTransaction(pub Transaction);
 impl DepositTransaction {
+    /// Create a DepositTransaction out of a transaction paying to our deposit descriptor.
+    ///
+    /// Returns along with it the position and derivation index of each of its outputs paying to
+    /// the `deposit_descriptor` derived at an index from `start` (included) to `end` (excluded).
+    ///
+    /// # Errors
+    /// - If no output of the transaction pays to the deposit descriptor within this range
+    pub fn new_checked<C: secp256k1::Verification>(
+        tx: Transaction,
+        deposit_descriptor: &DepositDescriptor,
+        start: DerivationIndex,
+        end: DerivationIndex,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(DepositTransaction, Vec<(u32, DerivationIndex)>), TransactionCreationError> {
+        let deposits: Vec<(u32, DerivationIndex)> =
+            find_deposits(&tx, deposit_descriptor, start, end, secp)
+                .into_iter()
+                .map(|(vout, index, _)| (vout, index))
+                .collect();
+        if deposits.is_empty() {
+            return Err(TransactionCreationError::NoDepositTxOut);
+        }
+
+        Ok((DepositTransaction(tx), deposits))
+    }
+
     /// Assumes that the outpoint actually refers to this transaction. Will panic otherwise.
     pub fn deposit_txin(
         &self,
@@ -577,6 +1572,38 @@ impl DepositTransaction {
     }
 }
 
+/// Find the outputs of this transaction paying to the `deposit_descriptor` derived at an index
+/// from `start` (included) to `end` (excluded).
+///
+/// Returns the position, derivation index and value of each of them.
+pub fn find_deposits<C: secp256k1::Verification>(
+    tx: &Transaction,
+    deposit_descriptor: &DepositDescriptor,
+    start: DerivationIndex,
+    end: DerivationIndex,
+    secp: &secp256k1::Secp256k1<C>,
+) -> Vec<(u32, DerivationIndex, Amount)> {
+    let deposit_spks: HashMap<Script, DerivationIndex> = deposit_descriptor
+        .derive_range(start, end, secp)
+        .into_iter()
+        .map(|desc| (desc.inner().script_pubkey(), desc.derivation_index()))
+        .collect();
+
+    tx.output
+        .iter()
+        .enumerate()
+        .filter_map(|(vout, txo)| {
+            deposit_spks.get(&txo.script_pubkey).map(|index| {
+                (
+                    vout.try_into().expect("vout doesn't fit in a u32?"),
+                    *index,
+                    Amount::from_sat(txo.value),
+                )
+            })
+        })
+        .collect()
+}
+
 /// A set of Cancel transactions signed at fixed feerates.
 #[derive(Debug, Clone)]
 pub struct CancelTransactionsBatch {
@@ -599,31 +1626,32 @@ impl CancelTransactionsBatch {
         unvault_txin: UnvaultTxIn,
         der_deposit_descriptor: &DerivedDepositDescriptor,
     ) -> Result<CancelTransactionsBatch, TransactionCreationError> {
+        let feerates = cancel_feerates();
         Ok(CancelTransactionsBatch {
             feerate_20: CancelTransaction::new(
                 unvault_txin.clone(),
                 &der_deposit_descriptor,
-                Amount::from_sat(5), // vbytes to WU
+                feerates[0],
             )?,
             feerate_100: CancelTransaction::new(
                 unvault_txin.clone(),
                 &der_deposit_descriptor,
-                Amount::from_sat(25), // vbytes to WU
+                feerates[1],
             )?,
             feerate_200: CancelTransaction::new(
                 unvault_txin.clone(),
                 &der_deposit_descriptor,
-                Amount::from_sat(50), // vbytes to WU
+                feerates[2],
             )?,
             feerate_500: CancelTransaction::new(
                 unvault_txin.clone(),
                 &der_deposit_descriptor,
-                Amount::from_sat(125), // vbytes to WU
+                feerates[3],
             )?,
             feerate_1000: CancelTransaction::new(
                 unvault_txin,
                 &der_deposit_descriptor,
-                Amount::from_sat(250), // vbytes to WU
+                feerates[4],
             )?,
         })
     }
@@ -712,7 +1740,7 @@ pub fn transaction_chain_manager<C: secp256k1::Verification>(
     deposit_descriptor: &DepositDescriptor,
     unvault_descriptor: &UnvaultDescriptor,
     cpfp_descriptor: &CpfpDescriptor,
-    derivation_index: ChildNumber,
+    derivation_index: DerivationIndex,
     secp: &secp256k1::Secp256k1<C>,
 ) -> Result<(UnvaultTransaction, CancelTransactionsBatch), Error> {
     let (der_deposit_descriptor, der_unvault_descriptor, der_cpfp_descriptor) = (
@@ -742,7 +1770,7 @@ pub fn transaction_chain<C: secp256k1::Verification>(
     deposit_descriptor: &DepositDescriptor,
     unvault_descriptor: &UnvaultDescriptor,
     cpfp_descriptor: &CpfpDescriptor,
-    derivation_index: ChildNumber,
+    derivation_index: DerivationIndex,
     emer_address: EmergencyAddress,
     secp: &secp256k1::Secp256k1<C>,
 ) -> Result<
@@ -782,7 +1810,7 @@ pub fn transaction_chain<C: secp256k1::Verification>(
 /// The derivation index used for the Spend CPFP is the highest of the deposits one.
 #[allow(clippy::too_many_arguments)]
 pub fn spend_tx_from_deposits<C: secp256k1::Verification>(
-    deposit_txins: Vec<(OutPoint, Amount, ChildNumber)>,
+    deposit_txins: Vec<(OutPoint, Amount, DerivationIndex)>,
     spend_txos: Vec<SpendTxOut>,
     change_txo: Option<DepositTxOut>,
     deposit_descriptor: &DepositDescriptor,
@@ -792,7 +1820,7 @@ pub fn spend_tx_from_deposits<C: secp256k1::Verification>(
     check_insane_fees: bool,
     secp: &secp256k1::Secp256k1<C>,
 ) -> Result<SpendTransaction, TransactionCreationError> {
-    let mut max_deriv_index = ChildNumber::from(0);
+    let mut max_deriv_index = DerivationIndex::default();
     let unvault_txins = deposit_txins
         .into_iter()
         .map(|(outpoint, amount, deriv_index)| {
@@ -821,22 +1849,22 @@ pub fn spend_tx_from_deposits<C: secp256k1::Verification>(
     )
 }
 
-#[cfg(any(test, feature = "fuzz"))]
+#[cfg(any(test, feature = "test-helpers"))]
 pub mod tests_helpers;
 
 #[cfg(test)]
 mod tests {
-    use super::tests_helpers::derive_transactions;
-    use crate::{error::*, scripts::*};
+    use super::tests_helpers::{derive_transactions, CosigningServers};
+    use crate::{error::*, scripts::*, txins::RevaultTxIn};
 
-    use miniscript::bitcoin::{blockdata::constants::COIN_VALUE, secp256k1, OutPoint};
+    use miniscript::bitcoin::{blockdata::constants::COIN_VALUE, secp256k1, Amount, OutPoint};
 
-    use std::str::FromStr;
+    use std::{convert::TryFrom, str::FromStr};
 
     #[test]
     fn transaction_derivation() {
         let secp = secp256k1::Secp256k1::new();
-        let csv = fastrand::u32(..SEQUENCE_LOCKTIME_MASK);
+        let csv = CsvValue::blocks(fastrand::u16(..));
         eprintln!("Using a CSV of '{}'", csv);
 
         let deposit_prevout = OutPoint::from_str(
@@ -849,28 +1877,28 @@ mod tests {
                     "0ed7dc14fe8d1364b3185fa46e940cb8e858f8de32e63f88353a2bd66eb99e2a:0",
                 )
                 .unwrap(),
-                1_000_000,
+                Amount::from_sat(1_000_000),
             ),
             (
                 OutPoint::from_str(
                     "23aacfca328942892bb007a86db0bf5337005f642b3c46aef50c23af03ec333a:1",
                 )
                 .unwrap(),
-                2_897_120,
+                Amount::from_sat(2_897_120),
             ),
             (
                 OutPoint::from_str(
                     "fccabf4077b7e44ba02378a97a84611b545c11a1ef2af16cbb6e1032aa059b1d:0",
                 )
                 .unwrap(),
-                9_327_465_907_334,
+                Amount::from_sat(9_327_465_907_334),
             ),
             (
                 OutPoint::from_str(
                     "71dc04303184d54e6cc2f92d843282df2854d6dd66f10081147b84aeed830ae1:0",
                 )
                 .unwrap(),
-                234_631,
+                Amount::from_sat(234_631),
             ),
         ];
         // Test the dust limit
@@ -880,9 +1908,9 @@ mod tests {
                 1,
                 csv,
                 deposit_prevout,
-                234_631,
+                Amount::from_sat(234_631),
                 unvaults_spent.clone(),
-                true,
+                CosigningServers::Xpubs,
                 &secp
             )
             .unwrap_err()
@@ -890,17 +1918,7 @@ mod tests {
             Error::TransactionCreation(TransactionCreationError::Dust).to_string()
         );
         // Non-minimal CSV
-        derive_transactions(
-            2,
-            1,
-            SEQUENCE_LOCKTIME_MASK + 1,
-            deposit_prevout,
-            600_000,
-            unvaults_spent.clone(),
-            true,
-            &secp,
-        )
-        .expect_err("Unclean CSV");
+        CsvValue::try_from(SEQUENCE_LOCKTIME_MASK + 1).expect_err("Unclean CSV");
 
         // Absolute minimum
         derive_transactions(
@@ -908,9 +1926,9 @@ mod tests {
             1,
             csv,
             deposit_prevout,
-            534_632,
+            Amount::from_sat(534_632),
             unvaults_spent.clone(),
-            true,
+            CosigningServers::Xpubs,
             &secp,
         )
         .unwrap_or_else(|_| {
@@ -925,24 +1943,42 @@ mod tests {
             3,
             csv,
             deposit_prevout,
-            COIN_VALUE,
+            Amount::from_sat(COIN_VALUE),
             unvaults_spent.clone(),
-            true,
+            CosigningServers::Xpubs,
             &secp,
         )
         .expect(&format!(
             "Tx chain with 8 stakeholders, 3 managers, {} csv, 1_000_000 deposit",
             csv
         ));
+        // 1 BTC, with a time-based Unvault delay
+        let time_csv = CsvValue::time_intervals(fastrand::u16(..));
+        derive_transactions(
+            8,
+            3,
+            time_csv,
+            deposit_prevout,
+            Amount::from_sat(COIN_VALUE),
+            unvaults_spent.clone(),
+            CosigningServers::Xpubs,
+            &secp,
+        )
+        .unwrap_or_else(|_| {
+            panic!(
+                "Tx chain with 8 stakeholders, 3 managers, {} csv, 1_000_000 deposit",
+                time_csv
+            )
+        });
         // 100 000 BTC
         derive_transactions(
             8,
             3,
             csv,
             deposit_prevout,
-            100_000 * COIN_VALUE,
+            Amount::from_sat(100_000 * COIN_VALUE),
             unvaults_spent.clone(),
-            true,
+            CosigningServers::Xpubs,
             &secp,
         )
         .expect(&format!(
@@ -955,9 +1991,9 @@ mod tests {
             5,
             csv,
             deposit_prevout,
-            100 * COIN_VALUE,
+            Amount::from_sat(100 * COIN_VALUE),
             unvaults_spent.clone(),
-            true,
+            CosigningServers::Xpubs,
             &secp,
         )
         .expect(&format!(
@@ -970,15 +2006,253 @@ mod tests {
             5,
             csv,
             deposit_prevout,
-            100 * COIN_VALUE,
-            unvaults_spent,
-            false,
+            Amount::from_sat(100 * COIN_VALUE),
+            unvaults_spent.clone(),
+            CosigningServers::Absent,
             &secp,
         )
         .expect(&format!(
             "Tx chain with {} stakeholders, 5 manager, {} csv, 100_000_000_000 deposit, no cosig",
             MAX_STAKEHOLDERS, csv
         ));
+        // 1 BTC, Cosigning Servers with raw keys
+        derive_transactions(
+            8,
+            3,
+            csv,
+            deposit_prevout,
+            Amount::from_sat(COIN_VALUE),
+            unvaults_spent,
+            CosigningServers::RawKeys,
+            &secp,
+        )
+        .unwrap_or_else(|_| {
+            panic!(
+                "Tx chain with 8 stakeholders, 3 managers, {} csv, 1_000_000 deposit, raw cosig keys",
+                csv
+            )
+        });
+    }
+
+    #[test]
+    fn error_sources() {
+        use std::error::Error as StdError;
+
+        let error = Error::from(PsbtValidationError::DuplicatedInput);
+        let serialisation_error = error.source().unwrap();
+        assert_eq!(
+            serialisation_error.to_string(),
+            "Invalid Revault transaction: 'Transaction contains duplicated inputs'"
+        );
+        assert_eq!(
+            serialisation_error.source().unwrap().to_string(),
+            "Transaction contains duplicated inputs"
+        );
+        assert!(serialisation_error.source().unwrap().source().is_none());
+    }
+
+    #[test]
+    fn multi_tier_unvault_spend() {
+        use super::{
+            tests_helpers::{get_participants_sets, satisfy_transaction_input},
+            RevaultTransaction, SpendTransaction,
+        };
+        use crate::{
+            txins::{Sequence, UnvaultTxIn},
+            txouts::{SpendTxOut, UnvaultTxOut},
+        };
+        use miniscript::bitcoin::{util::bip32, Script, TxOut};
+
+        let secp = secp256k1::Secp256k1::new();
+        // The "cosigners" are used as the backup managers' keys
+        let ((managers_priv, managers), (_, mancpfp), (_, stakeholders), (backup_priv, backup)) =
+            get_participants_sets(3, 3, true, &secp);
+        let backup_priv = backup_priv[..2].to_vec();
+
+        // All the managers after 6 blocks, or 2 backup keys after 144 blocks
+        let all_managers = format!(
+            "thresh(3,pk({}),pk({}),pk({}))",
+            managers[0], managers[1], managers[2]
+        );
+        let backup_managers = format!("and(pk({}),pk({}))", backup[0], backup[1]);
+        let unvault_descriptor = UnvaultDescriptor::with_spend_tiers(
+            stakeholders,
+            &[
+                (backup_managers.as_str(), CsvValue::blocks(144)),
+                (all_managers.as_str(), CsvValue::blocks(6)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(unvault_descriptor.csv_value(), CsvValue::blocks(6));
+        assert_eq!(
+            unvault_descriptor.csv_values(),
+            vec![CsvValue::blocks(6), CsvValue::blocks(144)]
+        );
+
+        let derivation_index = DerivationIndex::try_from(7).unwrap();
+        let child_number = bip32::ChildNumber::from(derivation_index);
+        let der_unvault_descriptor = unvault_descriptor.derive(derivation_index, &secp);
+        let der_cpfp_descriptor = CpfpDescriptor::new(mancpfp)
+            .unwrap()
+            .derive(derivation_index, &secp);
+        let unvault_txin = |blocks| {
+            UnvaultTxIn::new(
+                OutPoint::default(),
+                UnvaultTxOut::new(Amount::from_sat(COIN_VALUE), &der_unvault_descriptor),
+                Sequence::Csv(CsvValue::blocks(blocks)),
+            )
+        };
+        let max_sat_weight = unvault_txin(6).max_sat_weight();
+        let signed_spend = |sequence, xprivs: &Vec<bip32::ExtendedPrivKey>| {
+            let mut spend_tx = SpendTransaction::new(
+                vec![unvault_txin(sequence)],
+                vec![SpendTxOut::new(TxOut {
+                    value: COIN_VALUE / 2,
+                    script_pubkey: Script::new_v0_wsh(&Default::default()),
+                })],
+                None,
+                &der_cpfp_descriptor,
+                0,
+                false,
+            )
+            .unwrap();
+            let sighash = spend_tx.all_sighashes().unwrap()[0].sighash();
+            satisfy_transaction_input(&secp, &mut spend_tx, 0, &sighash, xprivs, child_number)
+                .unwrap();
+            spend_tx
+        };
+        let witness_weight = |spend_tx: &SpendTransaction| {
+            let witness = spend_tx.psbt().inputs[0]
+                .final_script_witness
+                .as_ref()
+                .unwrap();
+            1 + witness.iter().map(|elem| 1 + elem.len()).sum::<usize>()
+        };
+
+        // The backup keys can only spend after the longer CSV
+        let mut spend_tx = signed_spend(6, &backup_priv);
+        spend_tx.finalize(&secp).unwrap_err();
+        let mut backup_spend_tx = signed_spend(144, &backup_priv);
+        backup_spend_tx.finalize(&secp).unwrap();
+        assert!(witness_weight(&backup_spend_tx) <= max_sat_weight);
+
+        // The full set of managers can spend after both
+        let mut spend_tx = signed_spend(6, &managers_priv);
+        spend_tx.finalize(&secp).unwrap();
+        assert!(witness_weight(&spend_tx) <= max_sat_weight);
+        let mut spend_tx = signed_spend(144, &managers_priv);
+        spend_tx.finalize(&secp).unwrap();
+
+        // If both tiers are available, the cheapest one is used
+        let all_privs: Vec<bip32::ExtendedPrivKey> = managers_priv
+            .iter()
+            .chain(backup_priv.iter())
+            .cloned()
+            .collect();
+        let mut spend_tx = signed_spend(144, &all_privs);
+        spend_tx.finalize(&secp).unwrap();
+        assert_eq!(
+            spend_tx.psbt().inputs[0].final_script_witness,
+            backup_spend_tx.psbt().inputs[0].final_script_witness
+        );
+    }
+
+    #[test]
+    fn hashlock_unvault_spend() {
+        use super::{
+            tests_helpers::{get_participants_sets, satisfy_transaction_input},
+            RevaultTransaction, SpendTransaction,
+        };
+        use crate::{
+            txins::{Sequence, UnvaultTxIn},
+            txouts::{SpendTxOut, UnvaultTxOut},
+        };
+        use miniscript::bitcoin::{
+            hashes::{hash160, ripemd160, sha256, sha256d, Hash},
+            util::bip32,
+            Script, TxOut,
+        };
+
+        let secp = secp256k1::Secp256k1::new();
+        let ((managers_priv, managers), (_, mancpfp), (_, stakeholders), _) =
+            get_participants_sets(3, 2, false, &secp);
+        let preimage = [42; 32];
+
+        let derivation_index = DerivationIndex::try_from(3).unwrap();
+        let child_number = bip32::ChildNumber::from(derivation_index);
+        let der_cpfp_descriptor = CpfpDescriptor::new(mancpfp)
+            .unwrap()
+            .derive(derivation_index, &secp);
+        let signed_spend = |spend_policy: &str| {
+            let der_unvault_descriptor = UnvaultDescriptor::with_spend_policy(
+                stakeholders.clone(),
+                spend_policy,
+                CsvValue::blocks(6),
+            )
+            .unwrap()
+            .derive(derivation_index, &secp);
+            let mut spend_tx = SpendTransaction::new(
+                vec![UnvaultTxIn::new(
+                    OutPoint::default(),
+                    UnvaultTxOut::new(Amount::from_sat(COIN_VALUE), &der_unvault_descriptor),
+                    Sequence::Csv(CsvValue::blocks(6)),
+                )],
+                vec![SpendTxOut::new(TxOut {
+                    value: COIN_VALUE / 2,
+                    script_pubkey: Script::new_v0_wsh(&Default::default()),
+                })],
+                None,
+                &der_cpfp_descriptor,
+                0,
+                false,
+            )
+            .unwrap();
+            let sighash = spend_tx.all_sighashes().unwrap()[0].sighash();
+            satisfy_transaction_input(
+                &secp,
+                &mut spend_tx,
+                0,
+                &sighash,
+                &managers_priv,
+                child_number,
+            )
+            .unwrap();
+            spend_tx
+        };
+
+        // All the hashlock fragments, along with the managers' signatures
+        for hashlock in &[
+            format!("sha256({})", sha256::Hash::hash(&preimage)),
+            format!("hash256({})", sha256d::Hash::hash(&preimage)),
+            format!("ripemd160({})", ripemd160::Hash::hash(&preimage)),
+            format!("hash160({})", hash160::Hash::hash(&preimage)),
+        ] {
+            let spend_policy = format!(
+                "and(thresh(2,pk({}),pk({})),{})",
+                managers[0], managers[1], hashlock
+            );
+            let mut spend_tx = signed_spend(&spend_policy);
+
+            // The signatures are not enough without the preimage
+            let unsatisfied_spend_tx = spend_tx.clone();
+            spend_tx.finalize(&secp).unwrap_err();
+            assert_eq!(spend_tx, unsatisfied_spend_tx);
+
+            assert_eq!(
+                spend_tx.add_preimage(0, [43; 32]),
+                Err(InputSatisfactionError::UnknownPreimage)
+            );
+            assert_eq!(
+                spend_tx.add_preimage(1, preimage),
+                Err(InputSatisfactionError::OutOfBounds)
+            );
+            spend_tx.add_preimage(0, preimage).unwrap();
+            spend_tx.finalize(&secp).unwrap();
+            assert_eq!(
+                spend_tx.add_preimage(0, preimage),
+                Err(InputSatisfactionError::AlreadyFinalized)
+            );
+        }
     }
 
     // Small sanity checks, see fuzzing targets for more.
@@ -1004,9 +2278,13 @@ mod tests {
         assert_eq!(serialize_hex(cancel_tx.tx()), "020000000106bd9b170bc56925d787095ffb219d6e9e3548abadb7b6f3742ce8951f2efe730000000000fdffffff01e8ca0200000000002200204a72ecb2cce46b23bf7f38049aa9761572c73a02f7ab2741b28dfcf978c85a9200000000");
 
         let unemergency_psbt_str = "\"cHNidP8BAF4CAAAAAfeVYT6dSrDTzQekeDseTQmpQChdIx9Fm/7yvPBvdu7HAAAAAAD9////AdLKAgAAAAAAIgAgojUvLaQJe+wXmRaaDIAaYfa2HbdfCBsgMdGjuwed2F4AAAAAAAEBK0ANAwAAAAAAIgAgXA0s+qynDjinXOmpJ/Qhuj87xEB7YcLEVdz7OX5B+l8iAgI+TfqYOB5AvGLZO2C3OWNepPtB2MXltlovJy9aNEUezEcwRAIgP5ZgDmwPn1fh7jKgweTU+K0Os/vsY+kKYnScOYewCVgCIG+KZrvbn8DPDrvJQwsbMz8PF208zO2W6hIeTGswSJOYgSICAtk/sjHYB5gv7nUSr0k25UlmeCn+7ztrilD5aKBYhOZ/RzBEAiBV9TTu0tVLMTlIlBSg/Pr21pqpehOIHduNDC7NB7ewPwIgF4p8WgqEN+haJCEG0jwmmCOjiQz7EzN2H3ps6CE7yh2BAQWrIQM3WBCQMxhfyw+ncsDqRpNgRhc1S3J5E2eZkyramf/yYqxRh2R2qRT8N/OAaFe4awdH/SRrJWbCdbsgrIisa3apFM86etaiSkLAb1YEkvfBiGPhb0XZiKxsk1KHZ1IhAvI/1b7NH17PoNpLnY2BLYTBQFM7DJReEselwbrXknJaIQIYmQoDfe8y/MUX5oa8N2g2GePHXKP5+olBBjXHgsQuF1KvA7WEALJoIgYCGJkKA33vMvzFF+aGvDdoNhnjx1yj+fqJQQY1x4LELhcIwx/TKAoAAAAiBgI+TfqYOB5AvGLZO2C3OWNepPtB2MXltlovJy9aNEUezAh4xhChCgAAACIGAtk/sjHYB5gv7nUSr0k25UlmeCn+7ztrilD5aKBYhOZ/CBtBXXMKAAAAIgYC8j/Vvs0fXs+g2kudjYEthMFAUzsMlF4Sx6XButeScloI1AXIVAoAAAAiBgM3WBCQMxhfyw+ncsDqRpNgRhc1S3J5E2eZkyramf/yYgjQeHAnCgAAAAAA\"";
-        let unemergency_tx: UnvaultEmergencyTransaction =
-            serde_json::from_str(&unemergency_psbt_str).unwrap();
-        assert_eq!(serialize_hex(unemergency_tx.tx()), "0200000001f795613e9d4ab0d3cd07a4783b1e4d09a940285d231f459bfef2bcf06f76eec70000000000fdffffff01d2ca020000000000220020a2352f2da4097bec1799169a0c801a61f6b61db75f081b2031d1a3bb079dd85e00000000");
+        // This one was signed with SIGHASH_ALL|ANYONECANPAY, which is refused
+        assert!(
+            serde_json::from_str::<UnvaultEmergencyTransaction>(unemergency_psbt_str)
+                .unwrap_err()
+                .to_string()
+                .contains("partial signature is not SIGHASH_ALL")
+        );
 
         let spend_psbt_str = "\"cHNidP8BAIkCAAAAAdKM0NH1IfB5EqCmcrExViMrYq0YCHkfmZvTSzFoVNmJAAAAAAD9////AkANAwAAAAAAIgAgWfVjq6I2IH//GE9+5VT1A85InZCfKg9BfxCTDKdmEFUwdQAAAAAAACIAIPkvfw7mDhcLjDoAv/ciWdH+adf8/RRqXZEu2BCe9ZsUAAAAAAABASuIlAMAAAAAACIAIFyKAdGPlWYmCg7Lut2cL8DgFJiAKJItdJTyaYGQbCNWAQjbBABHMEQCIFmUwt4fnJL3eRAWqklyV3Aikc8TYwv7CrhxPRicUbU5AiB8g+ASYSGglLZleMFDh9Pi2W/FqQYwEWesor9Bv/EiQQFIMEUCIQCGvJsPxgFZtpsNRQ3VETEkDB78gcsgB4W9hkrkBXCMBgIgIDIbqQtHakOcqtl14jpPjiMVz0KO0HVJB51tvGDU/4wBR1IhAwl6ytUyWFcjWXapo8WMj2sasbgUCRx5K+F2jeGXb8d/IQJM5T/F+uoP2b/xce+xNoDZ9+6ocbz/8PSVoayx6TJnrlKuACICAkzlP8X66g/Zv/Fx77E2gNn37qhxvP/w9JWhrLHpMmeuCLhkVBQKAAAAIgICjlU/HP1v6DJ8m2Z5ANX5jZeC9cJ/Z0eakLYfzX5gX6YISNmuZwoAAAAiAgMJesrVMlhXI1l2qaPFjI9rGrG4FAkceSvhdo3hl2/HfwiILvO9CgAAACICAzkvyp9Q3knkMYAWBKeo5xcgiaoOwUdF/SQVMdYU3QtdCBxghxwKAAAAIgIDmeAIO+xbMz8grQfSwjY97Vgl7NHkVth6Z0JfrPpBaMAIsVo/DgoAAAAAIgIC640I7MqUC5FxRyF6yE8OB2aK8YojzUiyDmWrvnjn6lgIo2rccQoAAAAAcHNidP8BAGcCAAAAAVYetH70pzOUyZwutTULwN97mzGRBqx2K/u/qMstAMuxAAAAAAB6GwAAAoAyAAAAAAAAIgAg+S9/DuYOFwuMOgC/9yJZ0f5p1/z9FGpdkS7YEJ71mxSwswIAAAAAAAAAAAAAAAEBK0ANAwAAAAAAIgAgWfVjq6I2IH//GE9+5VT1A85InZCfKg9BfxCTDKdmEFUBAwQBAAAAAQWqIQM5L8qfUN5J5DGAFgSnqOcXIImqDsFHRf0kFTHWFN0LXaxRh2R2qRSLYmchXl+UoOeURf6sOKVrNpQlfIisa3apFOlaWTA4VwFVjhhA7wAx6l1dCbTKiKxsk1KHZ1IhA5ngCDvsWzM/IK0H0sI2Pe1YJezR5FbYemdCX6z6QWjAIQKOVT8c/W/oMnybZnkA1fmNl4L1wn9nR5qQth/NfmBfplKvAnobsmgiBgJM5T/F+uoP2b/xce+xNoDZ9+6ocbz/8PSVoayx6TJnrgi4ZFQUCgAAACIGAo5VPxz9b+gyfJtmeQDV+Y2XgvXCf2dHmpC2H81+YF+mCEjZrmcKAAAAIgYDCXrK1TJYVyNZdqmjxYyPaxqxuBQJHHkr4XaN4Zdvx38IiC7zvQoAAAAiBgM5L8qfUN5J5DGAFgSnqOcXIImqDsFHRf0kFTHWFN0LXQgcYIccCgAAACIGA5ngCDvsWzM/IK0H0sI2Pe1YJezR5FbYemdCX6z6QWjACLFaPw4KAAAAACICAuuNCOzKlAuRcUcheshPDgdmivGKI81Isg5lq7545+pYCKNq3HEKAAAAAAA\"";
         let spend_tx: SpendTransaction = serde_json::from_str(&spend_psbt_str).unwrap();
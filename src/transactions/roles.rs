@@ -0,0 +1,179 @@
+//! # BIP174 roles
+//!
+//! The capabilities of [RevaultTransaction] split by
+//! [BIP174 role](https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki#roles), for
+//! the different daemons to only depend on what they need. For instance a Cosigning Server only
+//! signs, and a watchtower only combines, finalizes and extracts the revocation transactions.
+//!
+//! The Creator role is fulfilled by the constructors of each transaction, and there is no
+//! Updater role as these constructors already fill in all the information the Signers need.
+//!
+//! All the Revault transactions implement all the roles. As the methods are named after those of
+//! [RevaultTransaction], don't import these traits along with it.
+
+use crate::{
+    error::{Error, InputSatisfactionError, PsbtCombinationError},
+    transactions::{InputSigHash, RevaultTransaction, SignatureInsertion},
+};
+
+use miniscript::{
+    bitcoin::{secp256k1, PublicKey as BitcoinPubKey, SigHash, SigHashType, Transaction},
+    Miniscript, Segwitv0,
+};
+
+/// The BIP174 Signer role.
+pub trait Signer {
+    /// See [RevaultTransaction::signature_hash].
+    fn signature_hash(&self, input_index: usize) -> Result<SigHash, InputSatisfactionError>;
+
+    /// See [RevaultTransaction::all_sighashes].
+    fn all_sighashes(&self) -> Result<Vec<InputSigHash>, InputSatisfactionError>;
+
+    /// See [RevaultTransaction::input_miniscript].
+    fn input_miniscript(
+        &self,
+        input_index: usize,
+    ) -> Result<Miniscript<BitcoinPubKey, Segwitv0>, InputSatisfactionError>;
+
+    /// See [RevaultTransaction::add_signature].
+    fn add_signature<C: secp256k1::Verification>(
+        &mut self,
+        input_index: usize,
+        pubkey: secp256k1::PublicKey,
+        signature: (secp256k1::Signature, SigHashType),
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<SignatureInsertion, InputSatisfactionError>;
+
+    /// See [RevaultTransaction::add_preimage].
+    fn add_preimage(
+        &mut self,
+        input_index: usize,
+        preimage: [u8; 32],
+    ) -> Result<(), InputSatisfactionError>;
+}
+
+/// The BIP174 Combiner role.
+pub trait Combiner: Sized {
+    /// See [RevaultTransaction::same_unsigned_tx].
+    fn same_unsigned_tx(&self, other: &Self) -> bool;
+
+    /// See [RevaultTransaction::combine].
+    fn combine<C: secp256k1::Verification>(
+        &mut self,
+        other: Self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), PsbtCombinationError>;
+}
+
+/// The BIP174 Input Finalizer role.
+pub trait Finalizer {
+    /// See [RevaultTransaction::inputs_missing_sigs].
+    fn inputs_missing_sigs<C: secp256k1::Verification>(
+        &self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Vec<usize>, InputSatisfactionError>;
+
+    /// See [RevaultTransaction::is_finalizable].
+    fn is_finalizable(&self, ctx: &secp256k1::Secp256k1<impl secp256k1::Verification>) -> bool;
+
+    /// See [RevaultTransaction::is_finalized].
+    fn is_finalized(&self) -> bool;
+
+    /// See [RevaultTransaction::finalize].
+    fn finalize(
+        &mut self,
+        ctx: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    ) -> Result<(), Error>;
+}
+
+/// The BIP174 Transaction Extractor role.
+pub trait Extractor: Sized {
+    /// See [RevaultTransaction::into_tx].
+    fn into_tx(self) -> Transaction;
+
+    /// See [RevaultTransaction::into_bitcoin_serialized].
+    fn into_bitcoin_serialized(self) -> Vec<u8>;
+}
+
+impl<T: RevaultTransaction> Signer for T {
+    fn signature_hash(&self, input_index: usize) -> Result<SigHash, InputSatisfactionError> {
+        RevaultTransaction::signature_hash(self, input_index)
+    }
+
+    fn all_sighashes(&self) -> Result<Vec<InputSigHash>, InputSatisfactionError> {
+        RevaultTransaction::all_sighashes(self)
+    }
+
+    fn input_miniscript(
+        &self,
+        input_index: usize,
+    ) -> Result<Miniscript<BitcoinPubKey, Segwitv0>, InputSatisfactionError> {
+        RevaultTransaction::input_miniscript(self, input_index)
+    }
+
+    fn add_signature<C: secp256k1::Verification>(
+        &mut self,
+        input_index: usize,
+        pubkey: secp256k1::PublicKey,
+        signature: (secp256k1::Signature, SigHashType),
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<SignatureInsertion, InputSatisfactionError> {
+        RevaultTransaction::add_signature(self, input_index, pubkey, signature, secp)
+    }
+
+    fn add_preimage(
+        &mut self,
+        input_index: usize,
+        preimage: [u8; 32],
+    ) -> Result<(), InputSatisfactionError> {
+        RevaultTransaction::add_preimage(self, input_index, preimage)
+    }
+}
+
+impl<T: RevaultTransaction> Combiner for T {
+    fn same_unsigned_tx(&self, other: &Self) -> bool {
+        RevaultTransaction::same_unsigned_tx(self, other)
+    }
+
+    fn combine<C: secp256k1::Verification>(
+        &mut self,
+        other: Self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), PsbtCombinationError> {
+        RevaultTransaction::combine(self, other, secp)
+    }
+}
+
+impl<T: RevaultTransaction> Finalizer for T {
+    fn inputs_missing_sigs<C: secp256k1::Verification>(
+        &self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Vec<usize>, InputSatisfactionError> {
+        RevaultTransaction::inputs_missing_sigs(self, secp)
+    }
+
+    fn is_finalizable(&self, ctx: &secp256k1::Secp256k1<impl secp256k1::Verification>) -> bool {
+        RevaultTransaction::is_finalizable(self, ctx)
+    }
+
+    fn is_finalized(&self) -> bool {
+        RevaultTransaction::is_finalized(self)
+    }
+
+    fn finalize(
+        &mut self,
+        ctx: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    ) -> Result<(), Error> {
+        RevaultTransaction::finalize(self, ctx)
+    }
+}
+
+impl<T: RevaultTransaction> Extractor for T {
+    fn into_tx(self) -> Transaction {
+        RevaultTransaction::into_tx(self)
+    }
+
+    fn into_bitcoin_serialized(self) -> Vec<u8> {
+        RevaultTransaction::into_bitcoin_serialized(self)
+    }
+}
@@ -2,8 +2,10 @@ use crate::{
     error::*,
     scripts::*,
     transactions::{
-        utils, CpfpableTransaction, RevaultTransaction, INSANE_FEES, MAX_STANDARD_TX_WEIGHT,
-        TX_VERSION,
+        cpfp_reserve,
+        fees::{self, FeeEstimator},
+        utils, CpfpableTransaction, RevaultTransaction, CPFP_RESERVE_FEERATE, INSANE_FEES,
+        MAX_STANDARD_TX_WEIGHT, TX_VERSION,
     },
     txins::*,
     txouts::*,
@@ -13,11 +15,8 @@ use miniscript::{
     bitcoin::{
         blockdata::constants::max_money,
         consensus::encode::Decodable,
-        util::psbt::{
-            Global as PsbtGlobal, Input as PsbtIn, Output as PsbtOut,
-            PartiallySignedTransaction as Psbt,
-        },
-        Amount, Network, OutPoint, Transaction,
+        util::psbt::{Global as PsbtGlobal, Output as PsbtOut, PartiallySignedTransaction as Psbt},
+        Amount, Network, OutPoint, Script, Transaction,
     },
     DescriptorTrait,
 };
@@ -33,6 +32,83 @@ use std::{
     convert::TryInto,
 };
 
+/// The rules a Spend transaction proposed by the managers must obey for the stakeholders (or
+/// their watchtowers) to let it be broadcast.
+#[derive(Debug, Clone)]
+pub struct SpendPolicy<'a> {
+    /// The Unvault outputs the Spend may spend, derived at their expected index
+    pub unvault_txins: &'a [UnvaultTxIn],
+    /// The scriptPubKeys the Spend may pay to, besides the change and CPFP outputs. Any
+    /// destination is allowed if `None`.
+    pub destinations: Option<&'a [Script]>,
+    /// The descriptor the change output, if any, must pay to
+    pub change_descriptor: &'a DerivedDepositDescriptor,
+    /// The descriptor the CPFP output must pay to
+    pub cpfp_descriptor: &'a DerivedCpfpDescriptor,
+    /// The minimum feerate, in sats/WU, of the Spend
+    pub min_feerate: u64,
+    /// The maximum feerate, in sats/WU, of the Spend
+    pub max_feerate: u64,
+}
+
+/// A rule of a [SpendPolicy] a Spend transaction does not obey
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpendViolation {
+    /// The input at this index does not spend any of the known Unvault outputs
+    UnknownInput(usize),
+    /// The input at this index spends a known Unvault outpoint, but not the expected txout
+    UnexpectedUnvaultTxout(usize),
+    /// The input at this index has this nSequence instead of one of the Unvault CSVs
+    InvalidSequence(usize, u32),
+    /// The output at this index pays neither to an allowed destination nor to the change or
+    /// CPFP descriptors
+    UnauthorizedOutput(usize),
+    /// The Spend has this number of CPFP outputs instead of a single one
+    InvalidCpfpOutputCount(usize),
+    /// The Spend pays these insane fees
+    InsaneFees(Amount),
+    /// The Spend pays this feerate, in sats/WU, out of the policy bounds
+    FeerateOutOfBounds(u64),
+}
+
+impl fmt::Display for SpendViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownInput(i) => write!(f, "Input '{}' spends an unknown Unvault output", i),
+            Self::UnexpectedUnvaultTxout(i) => {
+                write!(f, "Input '{}' spends an unexpected Unvault txout", i)
+            }
+            Self::InvalidSequence(i, seq) => {
+                write!(f, "Input '{}' has an invalid nSequence '{}'", i, seq)
+            }
+            Self::UnauthorizedOutput(i) => write!(f, "Output '{}' is not authorized", i),
+            Self::InvalidCpfpOutputCount(c) => write!(f, "Invalid CPFP output count '{}'", c),
+            Self::InsaneFees(fees) => write!(f, "Insane fees '{}'", fees),
+            Self::FeerateOutOfBounds(feerate) => {
+                write!(f, "Feerate '{}' sats/WU out of bounds", feerate)
+            }
+        }
+    }
+}
+
+/// The verdict of the validation of a Spend transaction against a [SpendPolicy]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendVerdict {
+    violations: Vec<SpendViolation>,
+}
+
+impl SpendVerdict {
+    /// Whether the Spend obeys all the rules of the policy
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Get the rules of the policy the Spend does not obey
+    pub fn violations(&self) -> &[SpendViolation] {
+        &self.violations
+    }
+}
+
 impl_revault_transaction!(
     SpendTransaction,
     doc = "The transaction spending the unvaulting transaction, paying to one or multiple \
@@ -42,6 +118,8 @@ impl SpendTransaction {
     /// A spend transaction can batch multiple unvault txouts, and may have any number of
     /// txouts (destination and change) in addition to the CPFP one..
     ///
+    /// Will error if there is no unvault input, or neither a destination nor a change output.
+    ///
     /// The insane fees check is gated behind the `insane_fee_checks` parameter as the caller
     /// may want to create a transaction without a change output.
     ///
@@ -54,18 +132,23 @@ impl SpendTransaction {
         lock_time: u32,
         insane_fee_check: bool,
     ) -> Result<SpendTransaction, TransactionCreationError> {
+        if unvault_inputs.is_empty() {
+            return Err(TransactionCreationError::NoInput);
+        }
+        if spend_txouts.is_empty() && change_txout.is_none() {
+            return Err(TransactionCreationError::NoOutput);
+        }
+
         // Check for duplicated inputs
         let uniq_txins: HashSet<OutPoint> = unvault_inputs.iter().map(|i| i.outpoint()).collect();
         if uniq_txins.len() != unvault_inputs.len() {
             return Err(TransactionCreationError::DuplicatedInput);
         }
 
-        // The CPFP is tricky to compute. We could be smart and avoid some allocations here
-        // but at the cost of clarity.
         let cpfp_txo = SpendTransaction::cpfp_txout(
-            unvault_inputs.clone(),
-            spend_txouts.clone(),
-            change_txout.clone(),
+            &unvault_inputs,
+            &spend_txouts,
+            change_txout.as_ref(),
             cpfp_descriptor,
             lock_time,
         );
@@ -73,12 +156,12 @@ impl SpendTransaction {
         // Used later to check the maximum transaction size.
         let sat_weight = unvault_inputs
             .iter()
-            .map(|txin| txin.txout().max_sat_weight())
+            .map(|txin| txin.max_sat_weight())
             .sum::<usize>();
 
         // Record the value spent and sent
-        let mut value_in: u64 = 0;
-        let mut value_out: u64 = 0;
+        let mut value_in = Amount::from_sat(0);
+        let mut value_out = Amount::from_sat(0);
 
         let mut txos = if change_txout.is_some() {
             Vec::with_capacity(spend_txouts.len() + 2)
@@ -86,9 +169,10 @@ impl SpendTransaction {
             Vec::with_capacity(spend_txouts.len() + 1)
         };
         let mut psbtouts = Vec::with_capacity(txos.len());
-        txos.push(cpfp_txo.txout().clone());
+        let (cpfp_txo, _, cpfp_bip32_derivation) = cpfp_txo.into_parts();
+        txos.push(cpfp_txo);
         psbtouts.push(PsbtOut {
-            bip32_derivation: cpfp_txo.bip32_derivation().clone(),
+            bip32_derivation: cpfp_bip32_derivation,
             ..PsbtOut::default()
         });
         for spend_txout in spend_txouts.into_iter() {
@@ -98,30 +182,39 @@ impl SpendTransaction {
                 return Err(TransactionCreationError::Dust);
             }
 
-            value_out += txo.value;
+            value_out = value_out
+                .checked_add(Amount::from_sat(txo.value))
+                .ok_or(TransactionCreationError::InsaneAmounts)?;
 
             txos.push(txo);
             psbtouts.push(PsbtOut::default());
         }
 
         if let Some(change_txout) = change_txout {
+            let (txo, _, bip32_derivation) = change_txout.into_parts();
             let psbtout = PsbtOut {
-                bip32_derivation: change_txout.bip32_derivation().clone(),
+                bip32_derivation,
                 ..PsbtOut::default()
             };
 
-            let txo = change_txout.into_txout();
-
             if txo.value < txo.script_pubkey.dust_value().as_sat() {
                 return Err(TransactionCreationError::Dust);
             }
 
-            value_out += txo.value;
+            value_out = value_out
+                .checked_add(Amount::from_sat(txo.value))
+                .ok_or(TransactionCreationError::InsaneAmounts)?;
 
             txos.push(txo);
             psbtouts.push(psbtout);
         }
 
+        for input in unvault_inputs.iter() {
+            value_in = value_in
+                .checked_add(Amount::from_sat(input.txout().txout().value))
+                .ok_or(TransactionCreationError::InsaneAmounts)?;
+        }
+
         let psbt = Psbt {
             global: PsbtGlobal {
                 unsigned_tx: Transaction {
@@ -140,16 +233,7 @@ impl SpendTransaction {
             },
             inputs: unvault_inputs
                 .into_iter()
-                .map(|input| {
-                    let prev_txout = input.into_txout();
-                    value_in += prev_txout.txout().value;
-                    PsbtIn {
-                        witness_script: Some(prev_txout.witness_script().clone()),
-                        bip32_derivation: prev_txout.bip32_derivation().clone(),
-                        witness_utxo: Some(prev_txout.into_txout()),
-                        ..PsbtIn::default()
-                    }
-                })
+                .map(|input| input.into_psbtin())
                 .collect(),
             outputs: psbtouts,
         };
@@ -164,43 +248,39 @@ impl SpendTransaction {
             return Err(TransactionCreationError::TooLarge);
         }
 
-        if value_out > max_money(Network::Bitcoin) {
+        if value_out > Amount::from_sat(max_money(Network::Bitcoin)) {
             return Err(TransactionCreationError::InsaneAmounts);
         }
         let fees = value_in
             .checked_sub(value_out)
             .ok_or(TransactionCreationError::NegativeFees)?;
-        if insane_fee_check && fees > INSANE_FEES {
+        if insane_fee_check && fees > Amount::from_sat(INSANE_FEES) {
             return Err(TransactionCreationError::InsaneFees);
         }
 
         Ok(SpendTransaction(psbt))
     }
 
-    /// Get the CPFP transaction output for a Spend transaction spending these `unvault_inputs`
-    /// and creating these `spend_txouts`.
-    ///
-    /// The CPFP output value is dependant on the transaction size, see [practical-revaul
-    /// t](https://github.com/revault/practical-revault/blob/master/transactions.md#spend_tx) for
-    /// more details.
-    pub fn cpfp_txout(
-        unvault_inputs: Vec<UnvaultTxIn>,
-        spend_txouts: Vec<SpendTxOut>,
-        change_txout: Option<DepositTxOut>,
+    // Get the weight of a Spend transaction spending these `unvault_inputs` and creating these
+    // `spend_txouts`, assuming fully-satisfied inputs.
+    pub(super) fn max_weight_for(
+        unvault_inputs: &[UnvaultTxIn],
+        spend_txouts: &[SpendTxOut],
+        change_txout: Option<&DepositTxOut>,
         cpfp_descriptor: &DerivedCpfpDescriptor,
         lock_time: u32,
-    ) -> CpfpTxOut {
-        let mut txos = Vec::with_capacity(spend_txouts.len() + 1);
+    ) -> u64 {
+        let mut txos = Vec::with_capacity(spend_txouts.len() + 2);
         let dummy_cpfp_txo = CpfpTxOut::new(Amount::from_sat(u64::MAX), &cpfp_descriptor);
-        txos.push(dummy_cpfp_txo.txout().clone());
+        txos.push(dummy_cpfp_txo.into_txout());
         txos.extend(
             spend_txouts
-                .into_iter()
-                .map(|spend_txout| spend_txout.into_txout()),
+                .iter()
+                .map(|spend_txout| spend_txout.txout().clone()),
         );
 
         if let Some(change_txout) = change_txout {
-            txos.push(change_txout.into_txout());
+            txos.push(change_txout.txout().clone());
         }
 
         let dummy_tx = Transaction {
@@ -215,7 +295,7 @@ impl SpendTransaction {
 
         let sat_weight: u64 = unvault_inputs
             .iter()
-            .map(|txin| txin.txout().max_sat_weight())
+            .map(|txin| txin.max_sat_weight())
             .sum::<usize>()
             .try_into()
             .expect("An usize doesn't fit in an u64?");
@@ -223,20 +303,184 @@ impl SpendTransaction {
             .get_weight()
             .try_into()
             .expect("Bug: an usize that doesn't fit in a u64?");
-        let total_weight = sat_weight
+        sat_weight
             .checked_add(witstrip_weight)
+            .expect("Weight computation bug: cannot overflow")
+    }
+
+    /// Get the CPFP transaction output for a Spend transaction spending these `unvault_inputs`
+    /// and creating these `spend_txouts`.
+    ///
+    /// The CPFP output value is dependant on the transaction size, see [practical-revaul
+    /// t](https://github.com/revault/practical-revault/blob/master/transactions.md#spend_tx) for
+    /// more details.
+    pub fn cpfp_txout(
+        unvault_inputs: &[UnvaultTxIn],
+        spend_txouts: &[SpendTxOut],
+        change_txout: Option<&DepositTxOut>,
+        cpfp_descriptor: &DerivedCpfpDescriptor,
+        lock_time: u32,
+    ) -> CpfpTxOut {
+        let total_weight = SpendTransaction::max_weight_for(
+            unvault_inputs,
+            spend_txouts,
+            change_txout,
+            cpfp_descriptor,
+            lock_time,
+        );
+
+        let cpfp_value = cpfp_reserve(total_weight, CPFP_RESERVE_FEERATE)
             .expect("Weight computation bug: cannot overflow");
+        CpfpTxOut::new(cpfp_value, cpfp_descriptor)
+    }
+
+    /// Create a Spend transaction paying the feerate given by the `estimator` for a confirmation
+    /// within `target_blocks` blocks. The remaining value, if any, is sent back to a change
+    /// output paying to the `change_descriptor` unless it would be dust, in which case it is left
+    /// to the fees.
+    ///
+    /// BIP174 Creator and Updater roles.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_estimator(
+        unvault_inputs: Vec<UnvaultTxIn>,
+        spend_txouts: Vec<SpendTxOut>,
+        change_descriptor: &DerivedDepositDescriptor,
+        cpfp_descriptor: &DerivedCpfpDescriptor,
+        lock_time: u32,
+        estimator: &impl FeeEstimator,
+        target_blocks: u16,
+    ) -> Result<SpendTransaction, TransactionCreationError> {
+        let feerate = fees::sat_per_wu(estimator.feerate(target_blocks));
+
+        let mut value_in = Amount::from_sat(0);
+        for txin in unvault_inputs.iter() {
+            value_in = value_in
+                .checked_add(txin.txout().value())
+                .ok_or(TransactionCreationError::InsaneAmounts)?;
+        }
+        let mut value_out = Amount::from_sat(0);
+        for txout in spend_txouts.iter() {
+            value_out = value_out
+                .checked_add(txout.value())
+                .ok_or(TransactionCreationError::InsaneAmounts)?;
+        }
+
+        // The weight, hence the fees, don't depend on the value of the change output.
+        let dummy_change = DepositTxOut::new(Amount::from_sat(u64::MAX), change_descriptor);
+        let cpfp_txo = SpendTransaction::cpfp_txout(
+            &unvault_inputs,
+            &spend_txouts,
+            Some(&dummy_change),
+            cpfp_descriptor,
+            lock_time,
+        );
+        let weight = SpendTransaction::max_weight_for(
+            &unvault_inputs,
+            &spend_txouts,
+            Some(&dummy_change),
+            cpfp_descriptor,
+            lock_time,
+        );
+        let fees = feerate
+            .checked_mul(weight)
+            .ok_or(TransactionCreationError::InsaneFees)?;
+
+        let change_value = value_in
+            .checked_sub(value_out)
+            .and_then(|v| v.checked_sub(cpfp_txo.value()))
+            .and_then(|v| v.checked_sub(fees))
+            .ok_or(TransactionCreationError::NegativeFees)?;
+        let change_txout = if change_value < dummy_change.script_pubkey().dust_value() {
+            None
+        } else {
+            Some(DepositTxOut::new(change_value, change_descriptor))
+        };
+
+        SpendTransaction::new(
+            unvault_inputs,
+            spend_txouts,
+            change_txout,
+            cpfp_descriptor,
+            lock_time,
+            true,
+        )
+    }
+
+    /// Validate a Spend transaction proposed by the managers against the `policy` of the
+    /// stakeholders, reporting all the rules it does not obey.
+    pub fn validate_against(&self, policy: &SpendPolicy) -> SpendVerdict {
+        let mut violations = Vec::new();
+        let psbt = self.psbt();
+        let tx = self.tx();
+
+        for (i, (txin, psbtin)) in tx.input.iter().zip(psbt.inputs.iter()).enumerate() {
+            let known_txin = match policy
+                .unvault_txins
+                .iter()
+                .find(|known| known.outpoint() == txin.previous_output)
+            {
+                Some(known_txin) => known_txin,
+                None => {
+                    violations.push(SpendViolation::UnknownInput(i));
+                    continue;
+                }
+            };
 
-        // See https://github.com/revault/practical-revault/blob/master/transactions.md#spend_tx
-        // for this arbirtrary value.
-        let cpfp_value = 16 * total_weight;
-        CpfpTxOut::new(Amount::from_sat(cpfp_value), &cpfp_descriptor)
+            if psbtin.witness_utxo.as_ref() != Some(known_txin.txout().txout()) {
+                violations.push(SpendViolation::UnexpectedUnvaultTxout(i));
+            }
+            if !known_txin
+                .txout()
+                .descriptor()
+                .csv_values()
+                .iter()
+                .any(|csv| csv.sequence() == txin.sequence)
+            {
+                violations.push(SpendViolation::InvalidSequence(i, txin.sequence));
+            }
+        }
+
+        let change_spk = policy.change_descriptor.inner().script_pubkey();
+        let cpfp_spk = policy.cpfp_descriptor.inner().script_pubkey();
+        let mut cpfp_count = 0;
+        for (i, txo) in tx.output.iter().enumerate() {
+            if txo.script_pubkey == cpfp_spk {
+                cpfp_count += 1;
+                continue;
+            }
+            if txo.script_pubkey == change_spk {
+                continue;
+            }
+            if let Some(destinations) = policy.destinations {
+                if !destinations.contains(&txo.script_pubkey) {
+                    violations.push(SpendViolation::UnauthorizedOutput(i));
+                }
+            }
+        }
+        if cpfp_count != 1 {
+            violations.push(SpendViolation::InvalidCpfpOutputCount(cpfp_count));
+        }
+
+        let fees = self.fees();
+        if fees > Amount::from_sat(INSANE_FEES) {
+            violations.push(SpendViolation::InsaneFees(fees));
+        }
+        let feerate = self.max_feerate();
+        if feerate < policy.min_feerate || feerate > policy.max_feerate {
+            violations.push(SpendViolation::FeerateOutOfBounds(feerate));
+        }
+
+        SpendVerdict { violations }
     }
 
     // FIXME: feerate sanity checks
-    /// Parse a Spend transaction from a PSBT
+    /// Parse a Spend transaction from a BIP174-serialized PSBT
     pub fn from_raw_psbt(raw_psbt: &[u8]) -> Result<Self, TransactionSerialisationError> {
-        let psbt = Decodable::consensus_decode(raw_psbt)?;
+        Self::from_psbt(Decodable::consensus_decode(raw_psbt)?)
+    }
+
+    /// Check this PSBT is a valid Spend transaction
+    pub fn from_psbt(psbt: Psbt) -> Result<Self, TransactionSerialisationError> {
         let psbt = utils::psbt_common_sanity_checks(psbt)?;
 
         if psbt.inputs.is_empty() {
@@ -244,14 +488,16 @@ impl SpendTransaction {
         }
 
         let mut max_sat_weight = 0;
-        for input in psbt.inputs.iter() {
+        for (i, input) in psbt.inputs.iter().enumerate() {
             // We must only have Unvault inputs
             let txo = input
                 .witness_utxo
                 .as_ref()
                 .expect("Checked by the common parsing checks");
             if !txo.script_pubkey.is_v0_p2wsh() {
-                return Err(PsbtValidationError::InvalidInputField(input.clone()).into());
+                return Err(
+                    PsbtValidationError::InvalidInputField(i, "spent output is not P2WSH").into(),
+                );
             }
 
             if input.final_script_witness.is_some() {
@@ -261,15 +507,17 @@ impl SpendTransaction {
             // The Unvault input must contain a valid witness script
             if let Some(ref ws) = input.witness_script {
                 if ws.to_v0_p2wsh() != txo.script_pubkey {
-                    return Err(PsbtValidationError::InvalidInWitnessScript(input.clone()).into());
+                    return Err(PsbtValidationError::InvalidInWitnessScript(i).into());
                 }
             } else {
-                return Err(PsbtValidationError::MissingInWitnessScript(input.clone()).into());
+                return Err(PsbtValidationError::MissingInWitnessScript(i).into());
             }
 
             // And since it has a witscript it must have derivation paths for it set
             if input.bip32_derivation.is_empty() {
-                return Err(PsbtValidationError::InvalidInputField(input.clone()).into());
+                return Err(
+                    PsbtValidationError::InvalidInputField(i, "missing bip32 derivation").into(),
+                );
             }
 
             max_sat_weight += miniscript::descriptor::Wsh::new(
@@ -277,13 +525,13 @@ impl SpendTransaction {
                     input
                         .witness_script
                         .as_ref()
-                        .ok_or_else(|| PsbtValidationError::InvalidInputField(input.clone()))?,
+                        .ok_or(PsbtValidationError::MissingInWitnessScript(i))?,
                 )
-                .map_err(|_| PsbtValidationError::InvalidInputField(input.clone()))?,
+                .map_err(|_| PsbtValidationError::InvalidInWitnessScript(i))?,
             )
-            .map_err(|_| PsbtValidationError::InvalidInputField(input.clone()))?
+            .map_err(|_| PsbtValidationError::InvalidInWitnessScript(i))?
             .max_satisfaction_weight()
-            .map_err(|_| PsbtValidationError::InvalidInputField(input.clone()))?;
+            .map_err(|_| PsbtValidationError::InvalidInWitnessScript(i))?;
         }
 
         let mut derivation_count: usize = 0;
@@ -1,35 +1,59 @@
+//! Utilities to generate keys and derive realistic chains of Revault transactions for testing.
+//!
+//! Available to downstream crates under the `test-helpers` feature.
+
 use super::{
-    transaction_chain, CancelTransaction, CpfpTransaction, CpfpableTransaction, DepositTransaction,
-    EmergencyAddress, EmergencyTransaction, RevaultPresignedTransaction, RevaultTransaction,
-    SpendTransaction, UnvaultEmergencyTransaction, UnvaultTransaction, CPFP_MIN_CHANGE,
-    DEPOSIT_MIN_SATS,
+    cancel_feerates, cpfp_reserve, find_deposits, select_feebump_coins, select_unvault_coins,
+    transaction_chain, CancelTransaction, CoinSelectionStrategy, CpfpTransaction,
+    CpfpableTransaction, DepositTransaction, EmergencyAddress, EmergencySweepTransaction,
+    EmergencyTransaction, FixedFeerate, MigrationTransaction, PsbtRole,
+    RevaultPresignedTransaction, RevaultTransaction, SignatureInsertion, SpendPolicy,
+    SpendTransaction, SpendViolation, SweepDestination, UnvaultEmergencyTransaction,
+    UnvaultTransaction, CPFP_MIN_CHANGE, CPFP_MIN_VALUE, CPFP_RESERVE_FEERATE, DEPOSIT_MIN_SATS,
 };
 
-use crate::{error::*, scripts::*, txins::*, txouts::*};
+use crate::{
+    chain::{PresignedTx, VaultState, VaultStatus},
+    cosigning::{SignRequest, SignResult},
+    error::*,
+    scripts::*,
+    txins::*,
+    txouts::*,
+    watchtower::WatchtowerBundle,
+};
 
-use std::{iter::repeat_with, str::FromStr};
+use std::{
+    convert::{TryFrom, TryInto},
+    iter::repeat_with,
+    str::FromStr,
+};
 
 use miniscript::{
     bitcoin::{
         secp256k1,
         util::psbt::PartiallySignedTransaction as Psbt,
         util::{bip143::SigHashCache, bip32},
-        Address, Amount, Network, OutPoint, SigHash, SigHashType, Transaction, TxIn, TxOut,
+        Address, Amount, Network, OutPoint, Script, SigHash, SigHashType, Transaction, TxIn, TxOut,
+        Txid,
     },
-    descriptor::{DescriptorPublicKey, DescriptorXKey, Wildcard},
+    descriptor::{DescriptorPublicKey, DescriptorSinglePub, DescriptorXKey, Wildcard},
     Descriptor, DescriptorTrait, MiniscriptKey,
 };
 
-fn get_random_privkey(rng: &mut fastrand::Rng) -> bip32::ExtendedPrivKey {
+/// Generate a random BIP32 master private key
+pub fn get_random_privkey(rng: &mut fastrand::Rng) -> bip32::ExtendedPrivKey {
     let rand_bytes: Vec<u8> = repeat_with(|| rng.u8(..)).take(64).collect();
 
     bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &rand_bytes)
         .unwrap_or_else(|_| get_random_privkey(rng))
 }
 
-// This generates the master private keys to derive directly from master, so it's
-// [None]<xpub_goes_here>m/* descriptor pubkeys
-fn get_participants_sets(
+/// Generate the master private keys and the corresponding descriptor public keys of the
+/// managers, the managers' CPFP keys, the stakeholders and the cosigning servers (if
+/// `with_cosig_servers` is set), in this order.
+///
+/// The keys derive directly from master, so it's `[None]<xpub_goes_here>m/*` descriptor pubkeys.
+pub fn get_participants_sets(
     n_stk: usize,
     n_man: usize,
     with_cosig_servers: bool,
@@ -96,8 +120,9 @@ fn get_participants_sets(
     )
 }
 
-// Routine for ""signing"" a transaction
-fn satisfy_transaction_input(
+/// Sign the input at `input_index` of this transaction with each of these master private keys
+/// derived at `child_number`.
+pub fn satisfy_transaction_input(
     secp: &secp256k1::Secp256k1<secp256k1::All>,
     tx: &mut impl RevaultTransaction,
     input_index: usize,
@@ -128,7 +153,7 @@ fn satisfy_transaction_input(
             .derive_public_key(secp)
             .unwrap();
 
-        tx.add_signature(input_index, key.key, sig, secp)?;
+        tx.add_signature(input_index, key.key, (sig, SigHashType::All), secp)?;
     }
 
     Ok(())
@@ -178,7 +203,7 @@ fn sign_psbt(
 
 fn finalize_psbt(secp: &secp256k1::Secp256k1<impl secp256k1::Verification>, psbt: &mut Psbt) {
     miniscript::psbt::finalize(psbt, secp)
-        .map_err(|e| Error::TransactionFinalisation(e.to_string()))
+        .map_err(Error::TransactionFinalisation)
         .unwrap();
 }
 
@@ -189,6 +214,56 @@ fn desc_san_check<P: MiniscriptKey>(desc: &Descriptor<P>) -> Result<(), ScriptCr
     }
 }
 
+/// Create a dummy transaction funding a deposit of `deposit_value` to this `deposit_descriptor`,
+/// along with the deposit txin to be spent by the Unvault and Emergency transactions.
+pub fn dummy_deposit(
+    funding_prevout: OutPoint,
+    deposit_value: Amount,
+    deposit_descriptor: &DerivedDepositDescriptor,
+) -> (DepositTransaction, DepositTxIn) {
+    let deposit_txo = DepositTxOut::new(deposit_value, deposit_descriptor);
+    let deposit_tx = DepositTransaction(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: funding_prevout,
+            ..TxIn::default()
+        }],
+        output: vec![deposit_txo.txout().clone()],
+    });
+    let deposit_outpoint = OutPoint {
+        txid: deposit_tx.0.txid(),
+        vout: 0,
+    };
+
+    (deposit_tx, DepositTxIn::new(deposit_outpoint, deposit_txo))
+}
+
+/// Create dummy coins of these `values` paying to this `cpfp_descriptor`, to be used to
+/// fee-bump the Unvault and Spend transactions. Think of a fake `listunspent`.
+pub fn dummy_feebump_coins(
+    values: &[Amount],
+    cpfp_descriptor: &DerivedCpfpDescriptor,
+) -> Vec<CpfpTxIn> {
+    // The transaction does not matter (random txid from my mempool)
+    let txid = Txid::from_str("f21596dd9df36b86bcf65f0884f1f20675c1fc185bc78a37a9cddb4ae5e3dd9f")
+        .expect("Valid txid");
+
+    values
+        .iter()
+        .enumerate()
+        .map(|(vout, value)| {
+            CpfpTxIn::new(
+                OutPoint {
+                    txid,
+                    vout: vout.try_into().expect("Not that many values"),
+                },
+                CpfpTxOut::new(*value, cpfp_descriptor),
+            )
+        })
+        .collect()
+}
+
 macro_rules! roundtrip {
     ($tx:ident, $tx_type:ident) => {
         #[cfg(feature = "use-serde")]
@@ -201,23 +276,54 @@ macro_rules! roundtrip {
         let serialized_tx = $tx.to_string();
         let deserialized_tx: $tx_type = FromStr::from_str(&serialized_tx).unwrap();
         assert_eq!($tx, deserialized_tx);
+
+        let serialized_tx = miniscript::bitcoin::consensus::encode::serialize(&$tx);
+        assert_eq!(serialized_tx, $tx.as_psbt_serialized());
+        let deserialized_tx: $tx_type =
+            miniscript::bitcoin::consensus::encode::deserialize(&serialized_tx).unwrap();
+        assert_eq!($tx, deserialized_tx);
+        assert_eq!($tx, $tx_type::try_from(&serialized_tx[..]).unwrap());
+        assert_eq!($tx, $tx_type::try_from($tx.psbt().clone()).unwrap());
+        assert_eq!($tx, $tx_type::from_psbt($tx.psbt().clone()).unwrap());
     };
 }
 
+// What a watchtower does with a fully-signed revocation transaction, with only the roles it needs
+fn finalize_and_extract<T: super::roles::Finalizer + super::roles::Extractor>(
+    mut tx: T,
+    secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+) -> Result<Transaction, Error> {
+    tx.finalize(secp)?;
+    assert!(tx.is_finalized());
+    Ok(tx.into_tx())
+}
+
+/// How the Cosigning Servers of the deployment are configured in [derive_transactions]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CosigningServers {
+    /// There is no Cosigning Server
+    Absent,
+    /// The Cosigning Servers' keys are xpubs, derived at each index like the others
+    Xpubs,
+    /// The Cosigning Servers' keys are raw keys, the same for all vaults
+    RawKeys,
+}
+
 /// Derive transactions for a given deployment configuration, asserting some invariants
 pub fn derive_transactions(
     n_stk: usize,
     n_man: usize,
-    csv: u32,
+    csv: CsvValue,
     deposit_prevout: OutPoint,
-    deposit_value: u64,
+    deposit_value: Amount,
     // Outpoint and amount of inputs of a Spend
-    unvault_spends: Vec<(OutPoint, u64)>,
-    with_cosig_servers: bool,
+    unvault_spends: Vec<(OutPoint, Amount)>,
+    cosigning_servers: CosigningServers,
     secp: &secp256k1::Secp256k1<secp256k1::All>,
 ) -> Result<(), Error> {
     // Let's get the 10th key of each
-    let child_number = bip32::ChildNumber::from(10);
+    let derivation_index = DerivationIndex::try_from(10).unwrap();
+    let child_number = bip32::ChildNumber::from(derivation_index);
 
     // Keys, keys, keys everywhere !
     let (
@@ -225,7 +331,34 @@ pub fn derive_transactions(
         (mancpfp_priv, mancpfp),
         (stakeholders_priv, stakeholders),
         (cosigners_priv, cosigners),
-    ) = get_participants_sets(n_stk, n_man, with_cosig_servers, secp);
+    ) = get_participants_sets(
+        n_stk,
+        n_man,
+        cosigning_servers != CosigningServers::Absent,
+        secp,
+    );
+    // Raw keys can't be derived, use the keys the xpubs derive to at our index so that the
+    // signing code is the same for both.
+    let cosigners: Vec<DescriptorPublicKey> = if cosigning_servers == CosigningServers::RawKeys {
+        cosigners
+            .into_iter()
+            .map(|cosig| match cosig {
+                DescriptorPublicKey::XPub(xpub) => {
+                    DescriptorPublicKey::SinglePub(DescriptorSinglePub {
+                        origin: None,
+                        key: xpub
+                            .xkey
+                            .derive_pub(secp, &[child_number])
+                            .expect("Unhardened derivation")
+                            .public_key,
+                    })
+                }
+                DescriptorPublicKey::SinglePub(_) => unreachable!("Only xpubs are generated"),
+            })
+            .collect()
+    } else {
+        cosigners
+    };
 
     // Get the script descriptors for the txos we're going to create
     let unvault_descriptor = UnvaultDescriptor::new(
@@ -239,60 +372,184 @@ pub fn derive_transactions(
     let cpfp_descriptor = CpfpDescriptor::new(mancpfp)?;
     let deposit_descriptor = DepositDescriptor::new(stakeholders)?;
 
-    desc_san_check(
-        deposit_descriptor
-            .derive(child_number.into(), &secp)
-            .inner(),
-    )?;
-    desc_san_check(
-        unvault_descriptor
-            .derive(child_number.into(), &secp)
-            .inner(),
-    )?;
-    desc_san_check(cpfp_descriptor.derive(child_number.into(), &secp).inner())?;
+    desc_san_check(deposit_descriptor.derive(derivation_index, secp).inner())?;
+    desc_san_check(unvault_descriptor.derive(derivation_index, secp).inner())?;
+    desc_san_check(cpfp_descriptor.derive(derivation_index, secp).inner())?;
 
     // We reuse the deposit descriptor for the emergency address
     let emergency_address = EmergencyAddress::from(Address::p2wsh(
         &deposit_descriptor
-            .derive(child_number, secp)
+            .derive(derivation_index, secp)
             .inner()
             .explicit_script(),
         Network::Bitcoin,
     ))
     .expect("It's a P2WSH");
+    assert_eq!(emergency_address.network(), Network::Bitcoin);
+    assert_eq!(
+        EmergencyTxOut::new_checked_value(
+            emergency_address.clone(),
+            Amount::from_sat(329),
+            deposit_value,
+            Amount::from_sat(0)
+        ),
+        Err(TxoutCreationError::Dust)
+    );
+    assert_eq!(
+        EmergencyTxOut::new_checked_value(
+            emergency_address.clone(),
+            deposit_value,
+            deposit_value,
+            Amount::from_sat(1)
+        ),
+        Err(TxoutCreationError::InsufficientFee(Amount::from_sat(1)))
+    );
+    EmergencyTxOut::new_checked_value(
+        emergency_address.clone(),
+        deposit_value - Amount::from_sat(1),
+        deposit_value,
+        Amount::from_sat(1),
+    )
+    .expect("Above dust and leaves the minimum fee");
+    assert_eq!(
+        EmergencyTxOut::new_checked(emergency_address.clone(), deposit_value, Network::Testnet),
+        Err(TxoutCreationError::NetworkMismatch(
+            Network::Testnet,
+            Network::Bitcoin
+        ))
+    );
 
-    let der_deposit_descriptor = deposit_descriptor.derive(child_number, secp);
-    let der_unvault_descriptor = unvault_descriptor.derive(child_number, secp);
+    let der_deposit_descriptor = deposit_descriptor.derive(derivation_index, secp);
+    let der_unvault_descriptor = unvault_descriptor.derive(derivation_index, secp);
     assert_eq!(
         der_unvault_descriptor.csv_value(),
         unvault_descriptor.csv_value()
     );
-    let der_cpfp_descriptor = cpfp_descriptor.derive(child_number, secp);
+    let der_cpfp_descriptor = cpfp_descriptor.derive(derivation_index, secp);
+    // The satisfaction cost doesn't depend on the derivation index
+    assert_eq!(
+        deposit_descriptor.max_sat_weight(),
+        der_deposit_descriptor.max_sat_weight()
+    );
+    assert_eq!(
+        unvault_descriptor.max_sat_weight(),
+        der_unvault_descriptor.max_sat_weight()
+    );
+    assert_eq!(
+        cpfp_descriptor.max_sat_weight(),
+        der_cpfp_descriptor.max_sat_weight()
+    );
+    assert_eq!(
+        der_unvault_descriptor.address(Network::Testnet).network,
+        Network::Testnet
+    );
+    assert_eq!(
+        der_cpfp_descriptor
+            .address(Network::Bitcoin)
+            .script_pubkey(),
+        der_cpfp_descriptor.inner().script_pubkey()
+    );
+    assert_eq!(
+        unvault_descriptor.address(derivation_index, Network::Regtest, secp),
+        der_unvault_descriptor.address(Network::Regtest)
+    );
+    assert_eq!(
+        cpfp_descriptor.address(derivation_index, Network::Bitcoin, secp),
+        der_cpfp_descriptor.address(Network::Bitcoin)
+    );
+
+    // A bitcoind watch-only wallet can watch all of them
+    #[cfg(feature = "core-rpc")]
+    {
+        use crate::{config::Descriptors, corerpc::import_descriptors_requests};
+
+        let descriptors = Descriptors {
+            deposit: deposit_descriptor.clone(),
+            unvault: unvault_descriptor.clone(),
+            cpfp: cpfp_descriptor.clone(),
+            emergency_address: emergency_address.clone(),
+        };
+        let requests = import_descriptors_requests(
+            &descriptors,
+            DerivationIndex::try_from(0).unwrap(),
+            DerivationIndex::try_from(1_000).unwrap(),
+            None,
+        );
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0]["desc"], deposit_descriptor.to_string());
+        assert_eq!(requests[0]["internal"], false);
+        assert_eq!(requests[1]["desc"], unvault_descriptor.to_string());
+        assert_eq!(requests[2]["internal"], true);
+        assert_eq!(requests[2]["range"], serde_json::json!([0, 999]));
+        assert_eq!(requests[2]["timestamp"], "now");
+        assert!(import_descriptors_requests(
+            &descriptors,
+            derivation_index,
+            derivation_index,
+            Some(1_600_000_000)
+        )
+        .is_empty());
+    }
 
-    // The funding transaction does not matter (random txid from my mempool)
     let deposit_scriptpubkey = der_deposit_descriptor.inner().script_pubkey();
-    let deposit_raw_tx = Transaction {
-        version: 2,
-        lock_time: 0,
-        input: vec![TxIn {
-            previous_output: deposit_prevout,
-            ..TxIn::default()
-        }],
-        output: vec![TxOut {
-            value: deposit_value,
-            script_pubkey: deposit_scriptpubkey.clone(),
-        }],
-    };
-    let deposit_txo = DepositTxOut::new(
-        Amount::from_sat(deposit_raw_tx.output[0].value),
-        &der_deposit_descriptor,
+    let (deposit_tx, deposit_txin) =
+        dummy_deposit(deposit_prevout, deposit_value, &der_deposit_descriptor);
+    let deposit_txo = deposit_txin.txout().clone();
+    let deposit_outpoint = deposit_txin.outpoint();
+    assert_eq!(deposit_outpoint.txid, deposit_tx.0.txid());
+    // We can find it back among the deposits in a range of derivation indexes
+    let (checked_deposit_tx, deposits) = DepositTransaction::new_checked(
+        deposit_tx.0.clone(),
+        &deposit_descriptor,
+        DerivationIndex::try_from(5).unwrap(),
+        DerivationIndex::try_from(15).unwrap(),
+        secp,
+    )
+    .unwrap();
+    assert_eq!(checked_deposit_tx, deposit_tx);
+    assert_eq!(deposits, vec![(0, derivation_index)]);
+    assert_eq!(
+        DepositTransaction::new_checked(
+            deposit_tx.0.clone(),
+            &deposit_descriptor,
+            DerivationIndex::try_from(0).unwrap(),
+            derivation_index,
+            secp,
+        ),
+        Err(TransactionCreationError::NoDepositTxOut)
     );
-    let deposit_tx = DepositTransaction(deposit_raw_tx);
-    let deposit_outpoint = OutPoint {
-        txid: deposit_tx.0.txid(),
-        vout: 0,
-    };
-    let deposit_txin = DepositTxIn::new(deposit_outpoint, deposit_txo.clone());
+    assert_eq!(
+        find_deposits(
+            &deposit_tx.0,
+            &deposit_descriptor,
+            derivation_index,
+            DerivationIndex::try_from(u32::from(derivation_index) + 1).unwrap(),
+            secp
+        ),
+        vec![(0, derivation_index, deposit_value)]
+    );
+    assert!(find_deposits(
+        &deposit_tx.0,
+        &deposit_descriptor,
+        derivation_index,
+        derivation_index,
+        secp
+    )
+    .is_empty());
+    assert_eq!(deposit_txo.value(), deposit_value);
+    assert_eq!(deposit_txo.derivation_index(), derivation_index);
+    assert_eq!(deposit_txo.script_pubkey(), &deposit_scriptpubkey);
+    assert_eq!(deposit_txo.descriptor(), &der_deposit_descriptor);
+    assert_eq!(
+        deposit_txo.address(Network::Bitcoin).script_pubkey(),
+        deposit_scriptpubkey
+    );
+    // The BIP143 script code of a P2WSH is the witness script, not the scriptPubKey
+    assert_eq!(
+        deposit_txo.script_code(),
+        &der_deposit_descriptor.inner().explicit_script()
+    );
+    assert_ne!(deposit_txo.script_code(), deposit_txo.script_pubkey());
 
     // Test that the transaction helper(s) derive the same transactions as we do
     let (h_unvault, h_cancel, h_emer, h_unemer) = transaction_chain(
@@ -301,14 +558,112 @@ pub fn derive_transactions(
         &deposit_descriptor,
         &unvault_descriptor,
         &cpfp_descriptor,
-        child_number,
+        derivation_index,
         emergency_address.clone(),
         secp,
     )?;
 
+    // Track the signatures of the presigned transactions
+    let mut vault_state = VaultState::new(
+        h_unvault.clone(),
+        h_cancel.clone(),
+        h_emer.clone(),
+        h_unemer.clone(),
+        &der_deposit_descriptor,
+    );
+    assert_eq!(vault_state.status(), VaultStatus::Funded);
+    assert_eq!(
+        vault_state.missing().len(),
+        vault_state.cancel_txs().len() + 3
+    );
+    let stk_derivation_path = bip32::DerivationPath::from(vec![child_number]);
+    let stk_sigs = |sighash: SigHash| -> Vec<(secp256k1::PublicKey, secp256k1::Signature)> {
+        stakeholders_priv
+            .iter()
+            .map(|xpriv| {
+                let privkey = xpriv
+                    .derive_priv(secp, &stk_derivation_path)
+                    .unwrap()
+                    .private_key;
+                let msg = secp256k1::Message::from_slice(&sighash).unwrap();
+                (privkey.public_key(secp).key, secp.sign(&msg, &privkey.key))
+            })
+            .collect()
+    };
+    // A signing device can get the whole chain to sign at once
+    let chain_sighashes = vault_state.all_sighashes().unwrap();
+    assert_eq!(chain_sighashes.len(), vault_state.cancel_txs().len() + 3);
+    assert_eq!(chain_sighashes.last().unwrap().0, PresignedTx::Unvault);
+    assert_eq!(
+        chain_sighashes.last().unwrap().1.sighash(),
+        vault_state.unvault_tx().sig_hash().unwrap()
+    );
+    assert_eq!(
+        chain_sighashes.last().unwrap().1.derivation_paths().len(),
+        n_stk
+    );
+    for (tx, input_sighash) in chain_sighashes.iter() {
+        assert!(input_sighash.derivation_paths().len() >= n_stk, "{:?}", tx);
+    }
+    let unvault_sigs = stk_sigs(vault_state.unvault_tx().sig_hash().unwrap());
+    assert_eq!(
+        vault_state.add_signature(
+            PresignedTx::Unvault,
+            unvault_sigs[0].0,
+            (unvault_sigs[0].1, SigHashType::All),
+            secp
+        ),
+        Err(VaultStateError::NotSecured)
+    );
+    let mut revocation_sigs = vec![
+        (
+            PresignedTx::Emergency,
+            stk_sigs(vault_state.emergency_tx().sig_hash().unwrap()),
+        ),
+        (
+            PresignedTx::UnvaultEmergency,
+            stk_sigs(vault_state.unvault_emergency_tx().sig_hash().unwrap()),
+        ),
+    ];
+    for (i, cancel_tx) in vault_state.cancel_txs().iter().enumerate() {
+        revocation_sigs.push((
+            PresignedTx::Cancel(i),
+            stk_sigs(cancel_tx.sig_hash().unwrap()),
+        ));
+    }
+    for (tx, sigs) in revocation_sigs {
+        assert_eq!(vault_state.missing_signatures(tx).unwrap().len(), n_stk);
+        for (pubkey, sig) in sigs {
+            vault_state
+                .add_signature(tx, pubkey, (sig, SigHashType::All), secp)
+                .unwrap();
+        }
+        assert!(vault_state.missing_signatures(tx).unwrap().is_empty());
+    }
+    assert_eq!(vault_state.status(), VaultStatus::Secured);
+    assert_eq!(vault_state.missing().len(), 1);
+    assert_eq!(
+        vault_state.mark_unvaulting(),
+        Err(VaultStateError::NotActive)
+    );
+    for (pubkey, sig) in unvault_sigs {
+        vault_state
+            .add_signature(PresignedTx::Unvault, pubkey, (sig, SigHashType::All), secp)
+            .unwrap();
+    }
+    assert_eq!(vault_state.status(), VaultStatus::Active);
+    assert!(vault_state.missing().is_empty());
+    vault_state.mark_unvaulting().unwrap();
+    assert!(vault_state.is_unvaulting());
+    assert_eq!(vault_state.status(), VaultStatus::Unvaulting);
+
     // Create and sign the first (deposit) emergency transaction
     let mut emergency_tx =
         EmergencyTransaction::new(deposit_txin.clone(), emergency_address.clone())?;
+    assert_eq!(
+        emergency_tx.fees(),
+        EmergencyTransaction::fees_for(&deposit_txin, &emergency_address)
+    );
     assert_eq!(h_emer, emergency_tx);
     assert_eq!(
         emergency_tx.emergency_outpoint(),
@@ -328,9 +683,57 @@ pub fn derive_transactions(
         emergency_tx.signature_hash(10),
         Err(InputSatisfactionError::OutOfBounds)
     );
+    // The signatures commit to the deposit's witness script
+    assert_eq!(
+        &emergency_tx.input_miniscript(0)?.encode(),
+        deposit_txin.txout().witness_script()
+    );
+    assert_eq!(
+        emergency_tx.input_miniscript(10),
+        Err(InputSatisfactionError::OutOfBounds)
+    );
     // But for an existing one, all good
     let emergency_tx_sighash_vault = emergency_tx.sig_hash().expect("Input exists");
     roundtrip!(emergency_tx, EmergencyTransaction);
+    let sigs_count = emergency_tx.signatures_count(secp)?;
+    assert_eq!(sigs_count.len(), 1);
+    assert_eq!(sigs_count[0].valid(), 0);
+    assert_eq!(sigs_count[0].needed(), n_stk);
+    assert_eq!(sigs_count[0].missing(), n_stk);
+    assert_eq!(emergency_tx.inputs_missing_sigs(secp)?, vec![0]);
+    let analysis = emergency_tx.analyze(secp);
+    assert_eq!(analysis.next(), PsbtRole::Signer);
+    assert!(analysis.inputs()[0].has_utxo() && analysis.inputs()[0].has_witness_script());
+    assert_eq!(analysis.inputs()[0].signatures(), Some(&sigs_count[0]));
+    // We only ever sign with SIGHASH_ALL, even a valid signature is refused otherwise
+    let seckey = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+    let sig = secp.sign(
+        &secp256k1::Message::from_slice(&emergency_tx_sighash_vault).unwrap(),
+        &seckey,
+    );
+    assert_eq!(
+        emergency_tx.add_sig(
+            secp256k1::PublicKey::from_secret_key(secp, &seckey),
+            (sig, SigHashType::AllPlusAnyoneCanPay),
+            secp
+        ),
+        Err(InputSatisfactionError::InvalidSighashType(
+            SigHashType::AllPlusAnyoneCanPay
+        ))
+    );
+    // Nor do we accept a valid signature for a key which is not part of the script
+    assert_eq!(
+        emergency_tx.add_sig(
+            secp256k1::PublicKey::from_secret_key(secp, &seckey),
+            (sig, SigHashType::All),
+            secp
+        ),
+        Err(InputSatisfactionError::UnknownKey(
+            secp256k1::PublicKey::from_secret_key(secp, &seckey)
+        ))
+    );
+    assert!(emergency_tx.psbt().inputs[0].partial_sigs.is_empty());
+    let unsigned_emergency_tx = emergency_tx.clone();
     satisfy_transaction_input(
         &secp,
         &mut emergency_tx,
@@ -339,13 +742,403 @@ pub fn derive_transactions(
         &stakeholders_priv,
         child_number,
     )?;
+    emergency_tx.verify_signatures(secp)?;
+    // Adding a signature for the same key again replaces it
+    let (pubkey, rawsig) = emergency_tx.psbt().inputs[0]
+        .partial_sigs
+        .iter()
+        .next()
+        .map(|(pk, sig)| (pk.key, sig.clone()))
+        .unwrap();
+    let sig = secp256k1::Signature::from_der(&rawsig[..rawsig.len() - 1]).unwrap();
+    assert_eq!(
+        emergency_tx.add_sig(pubkey, (sig, SigHashType::All), secp),
+        Ok(SignatureInsertion::Replaced(rawsig))
+    );
+    let sigs_count = emergency_tx.signatures_count(secp)?;
+    assert_eq!(sigs_count[0].valid(), n_stk);
+    assert_eq!(sigs_count[0].missing(), 0);
+    assert!(emergency_tx.inputs_missing_sigs(secp)?.is_empty());
+    let analysis = emergency_tx.analyze(secp);
+    assert_eq!(analysis.next(), PsbtRole::Finalizer);
+    assert!(analysis.inputs()[0].is_finalizable());
+    // Differently-signed versions can be combined, unless they conflict
+    let mut combined_emergency_tx = unsigned_emergency_tx.clone();
+    combined_emergency_tx.combine(emergency_tx.clone(), secp)?;
+    assert_eq!(combined_emergency_tx, emergency_tx);
+    let mut swapped_psbt = emergency_tx.psbt().clone();
+    swapped_psbt.inputs[0].witness_utxo.as_mut().unwrap().value += 1;
+    let swapped_emergency_tx = EmergencyTransaction::from_psbt_serialized(
+        &miniscript::bitcoin::consensus::encode::serialize(&swapped_psbt),
+    )
+    .expect("Still a valid Emergency transaction");
+    assert_eq!(
+        combined_emergency_tx.combine(swapped_emergency_tx, secp),
+        Err(PsbtCombinationError::ConflictingWitnessUtxo(0))
+    );
+    assert_eq!(combined_emergency_tx, emergency_tx);
+    // Nor can a peer sneak in a signature by a foreign key, or another one for a key we have
+    let sighash_msg = secp256k1::Message::from_slice(&emergency_tx_sighash_vault).unwrap();
+    let mut foreign_rawsig = secp.sign(&sighash_msg, &seckey).serialize_der().to_vec();
+    foreign_rawsig.push(SigHashType::All.as_u32() as u8);
+    let mut foreign_sig_psbt = emergency_tx.psbt().clone();
+    foreign_sig_psbt.inputs[0].partial_sigs.insert(
+        miniscript::bitcoin::PublicKey {
+            compressed: true,
+            key: secp256k1::PublicKey::from_secret_key(secp, &seckey),
+        },
+        foreign_rawsig,
+    );
+    let foreign_sig_emergency_tx = EmergencyTransaction::from_psbt_serialized(
+        &miniscript::bitcoin::consensus::encode::serialize(&foreign_sig_psbt),
+    )
+    .expect("Still a valid Emergency transaction");
+    assert_eq!(
+        combined_emergency_tx.combine(foreign_sig_emergency_tx, secp),
+        Err(PsbtCombinationError::UnknownKey(0))
+    );
+    let stk_privkey = stakeholders_priv[0]
+        .derive_priv(secp, &bip32::DerivationPath::from(vec![child_number]))
+        .unwrap()
+        .private_key;
+    let stk_sig = secp.sign(&sighash_msg, &stk_privkey.key).serialize_der();
+    // Grind the nonce for a shorter, hence different, valid signature
+    let mut other_rawsig = secp
+        .sign_grind_r(&sighash_msg, &stk_privkey.key, 72 - stk_sig.len())
+        .serialize_der()
+        .to_vec();
+    other_rawsig.push(SigHashType::All.as_u32() as u8);
+    let mut conflicting_psbt = emergency_tx.psbt().clone();
+    conflicting_psbt.inputs[0]
+        .partial_sigs
+        .insert(stk_privkey.public_key(secp), other_rawsig);
+    let conflicting_emergency_tx = EmergencyTransaction::from_psbt_serialized(
+        &miniscript::bitcoin::consensus::encode::serialize(&conflicting_psbt),
+    )
+    .expect("Still a valid Emergency transaction");
+    assert_eq!(
+        combined_emergency_tx.combine(conflicting_emergency_tx, secp),
+        Err(PsbtCombinationError::ConflictingSignature(0))
+    );
+    assert_eq!(combined_emergency_tx, emergency_tx);
+    // The signatures may come from HWI, either as a signed PSBT or as a map of signatures
+    #[cfg(feature = "hwi")]
+    {
+        use crate::hwi::merge_hwi_signatures;
+        use miniscript::bitcoin::hashes::hex::ToHex;
+
+        let hwi_output = serde_json::json!({"psbt": emergency_tx.as_psbt_string(), "signed": true});
+        let mut hwi_signed_tx = unsigned_emergency_tx.clone();
+        merge_hwi_signatures(&mut hwi_signed_tx, &hwi_output.to_string(), secp).unwrap();
+        assert_eq!(hwi_signed_tx, emergency_tx);
+
+        let hwi_output: serde_json::Map<String, serde_json::Value> = emergency_tx.psbt().inputs[0]
+            .partial_sigs
+            .iter()
+            .map(|(pubkey, sig)| (pubkey.to_string(), sig.to_hex().into()))
+            .collect();
+        let mut hwi_signed_tx = unsigned_emergency_tx.clone();
+        merge_hwi_signatures(
+            &mut hwi_signed_tx,
+            &serde_json::Value::from(hwi_output).to_string(),
+            secp,
+        )
+        .unwrap();
+        assert_eq!(hwi_signed_tx, emergency_tx);
+
+        let hwi_output = serde_json::json!({"error": "No device path found", "code": -3});
+        let mut hwi_signed_tx = unsigned_emergency_tx.clone();
+        assert!(matches!(
+            merge_hwi_signatures(&mut hwi_signed_tx, &hwi_output.to_string(), secp),
+            Err(HwiError::Hwi(_))
+        ));
+        assert_eq!(hwi_signed_tx, unsigned_emergency_tx);
+    }
     roundtrip!(emergency_tx, EmergencyTransaction);
+    let extracted_emergency_tx = finalize_and_extract(emergency_tx.clone(), secp)?;
+    let signed_emergency_tx = emergency_tx.clone();
     emergency_tx.finalize(&secp)?;
+    assert_eq!(extracted_emergency_tx, emergency_tx.clone().into_tx());
+    // Combining with a finalized version adopts its witnesses, and a finalized version is
+    // left as is.
+    let mut combined_emergency_tx = signed_emergency_tx.clone();
+    combined_emergency_tx.combine(emergency_tx.clone(), secp)?;
+    assert_eq!(combined_emergency_tx, emergency_tx);
+    let mut combined_emergency_tx = emergency_tx.clone();
+    combined_emergency_tx.combine(signed_emergency_tx.clone(), secp)?;
+    assert_eq!(combined_emergency_tx, emergency_tx);
+    // But the witnesses of a finalized version must be valid
+    let mut bad_final_psbt = emergency_tx.psbt().clone();
+    bad_final_psbt.inputs[0].final_script_witness = Some(vec![vec![]]);
+    let bad_final_emergency_tx = EmergencyTransaction::from_psbt_serialized(
+        &miniscript::bitcoin::consensus::encode::serialize(&bad_final_psbt),
+    )
+    .expect("Still a valid Emergency transaction");
+    let mut combined_emergency_tx = signed_emergency_tx.clone();
+    assert_eq!(
+        combined_emergency_tx.combine(bad_final_emergency_tx, secp),
+        Err(PsbtCombinationError::InvalidFinalWitness)
+    );
+    assert_eq!(combined_emergency_tx, signed_emergency_tx);
     roundtrip!(emergency_tx, EmergencyTransaction);
+    assert!(emergency_tx.inputs_missing_sigs(secp)?.is_empty());
+    let analysis = emergency_tx.analyze(secp);
+    assert_eq!(analysis.next(), PsbtRole::Extractor);
+    assert_eq!(format!("{:x}", emergency_tx), emergency_tx.hex());
+    // The clone is fine here: rust-bitcoin's extractor is only used as a reference for the
+    // final transaction we build without cloning the PSBT.
+    assert_eq!(
+        emergency_tx.hex(),
+        miniscript::bitcoin::consensus::encode::serialize_hex(
+            &emergency_tx.clone().into_psbt().extract_tx()
+        )
+    );
+    assert_eq!(format!("{:.8x}", emergency_tx), emergency_tx.hex()[..8]);
+    assert_eq!(
+        format!("{:X}", emergency_tx),
+        emergency_tx.hex().to_uppercase()
+    );
+    assert!(analysis.inputs()[0].is_final() && !analysis.inputs()[0].has_witness_script());
+    assert_ne!(unsigned_emergency_tx, emergency_tx);
+    assert!(emergency_tx.same_unsigned_tx(&unsigned_emergency_tx));
+    assert!(unsigned_emergency_tx.same_unsigned_tx(&emergency_tx));
+    // It may round-trip through a bitcoind wallet
+    #[cfg(feature = "core-rpc")]
+    {
+        use crate::corerpc::{finalized_transaction, from_wallet_process_psbt};
+        use bitcoincore_rpc_json::{FinalizePsbtResult, WalletProcessPsbtResult};
+
+        let processed = WalletProcessPsbtResult {
+            psbt: emergency_tx.as_psbt_string(),
+            complete: true,
+        };
+        let (processed_tx, complete): (EmergencyTransaction, _) =
+            from_wallet_process_psbt(&processed).unwrap();
+        assert!(complete);
+        assert_eq!(processed_tx, emergency_tx);
+
+        let mut finalized = FinalizePsbtResult {
+            psbt: None,
+            hex: Some(emergency_tx.clone().into_bitcoin_serialized()),
+            complete: true,
+        };
+        let final_tx = finalized_transaction(&emergency_tx, &finalized).unwrap();
+        assert_eq!(final_tx.txid(), emergency_tx.txid());
+        assert_eq!(
+            finalized_transaction(&h_unvault, &finalized),
+            Err(CoreRpcError::TxidMismatch(
+                h_unvault.txid(),
+                final_tx.txid()
+            ))
+        );
+        finalized.complete = false;
+        assert_eq!(
+            finalized_transaction(&emergency_tx, &finalized),
+            Err(CoreRpcError::Incomplete)
+        );
+    }
+    assert_eq!(
+        emergency_tx.hex(),
+        miniscript::bitcoin::consensus::encode::serialize_hex(&emergency_tx.clone().into_tx())
+    );
+
+    // Once the crisis is over, the stakeholders may sweep the Emergency Deep Vault back to a
+    // deposit or to an external address
+    let emergency_descriptor = EmergencyDescriptor::new(
+        der_deposit_descriptor
+            .keys()
+            .into_iter()
+            .map(|k| k.key)
+            .collect(),
+    )?;
+    assert_eq!(
+        emergency_descriptor.address(Network::Bitcoin),
+        emergency_address
+    );
+    let emergency_txin = EmergencyTxIn::new(
+        emergency_tx.emergency_outpoint(),
+        EmergencyTxOut::new(
+            emergency_address.clone(),
+            Amount::from_sat(emergency_tx.tx().output[0].value),
+        ),
+        emergency_descriptor.clone(),
+    )?;
+    assert_eq!(
+        EmergencyTxIn::new(
+            emergency_tx.emergency_outpoint(),
+            EmergencyTxOut::new(
+                EmergencyAddress::from(der_unvault_descriptor.address(Network::Bitcoin))?,
+                Amount::from_sat(emergency_tx.tx().output[0].value),
+            ),
+            emergency_descriptor.clone(),
+        ),
+        Err(TxoutCreationError::DescriptorMismatch)
+    );
+    assert_eq!(emergency_txin.sequence(), RBF_SEQUENCE);
+    assert_eq!(
+        emergency_txin.max_sat_weight(),
+        emergency_descriptor.max_sat_weight()
+    );
+    assert_eq!(
+        EmergencySweepTransaction::new(
+            vec![emergency_txin.clone(), emergency_txin.clone()],
+            SweepDestination::Deposit(der_deposit_descriptor.clone()),
+            Amount::from_sat(1),
+        ),
+        Err(TransactionCreationError::DuplicatedInput)
+    );
+    assert_eq!(
+        EmergencySweepTransaction::new(
+            vec![],
+            SweepDestination::Deposit(der_deposit_descriptor.clone()),
+            Amount::from_sat(1),
+        ),
+        Err(TransactionCreationError::NoInput)
+    );
+    let mut sweep_tx = EmergencySweepTransaction::new(
+        vec![emergency_txin.clone()],
+        SweepDestination::Deposit(der_deposit_descriptor.clone()),
+        Amount::from_sat(1),
+    )?;
+    roundtrip!(sweep_tx, EmergencySweepTransaction);
+    let swept_deposit_txin = sweep_tx.deposit_txin(&der_deposit_descriptor);
+    assert_eq!(swept_deposit_txin.outpoint(), sweep_tx.sweep_outpoint());
+    assert_eq!(
+        swept_deposit_txin.txout().value() + sweep_tx.fees(),
+        emergency_txin.txout().value()
+    );
+    assert_eq!(
+        sweep_tx.fees().as_sat(),
+        sweep_tx.tx().get_weight() as u64 + emergency_txin.max_sat_weight() as u64
+    );
+    let sweep_tx_sighash = sweep_tx.signature_hash(0).expect("Input exists");
+    satisfy_transaction_input(
+        secp,
+        &mut sweep_tx,
+        0,
+        &sweep_tx_sighash,
+        &stakeholders_priv,
+        child_number,
+    )?;
+    sweep_tx.finalize(secp)?;
+    roundtrip!(sweep_tx, EmergencySweepTransaction);
+    let external_address = Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap();
+    let ext_sweep_tx = EmergencySweepTransaction::new(
+        vec![emergency_txin.clone()],
+        SweepDestination::External(external_address.clone()),
+        Amount::from_sat(1),
+    )?;
+    assert_eq!(
+        ext_sweep_tx.tx().output[0].script_pubkey,
+        external_address.script_pubkey()
+    );
+    assert!(ext_sweep_tx.psbt().outputs[0].bip32_derivation.is_empty());
+    assert!(!ext_sweep_tx.same_unsigned_tx(&sweep_tx));
+
+    // The Emergency descriptor may be any Miniscript, for instance a multi() of the same keys
+    // along with a timelocked recovery key
+    let emergency_keys: Vec<String> = emergency_descriptor
+        .keys()
+        .iter()
+        .map(|k| k.to_string())
+        .collect();
+    let recovery_key = secp256k1::PublicKey::from_secret_key(
+        secp,
+        &secp256k1::SecretKey::from_slice(&[0x42; 32]).unwrap(),
+    );
+    let custom_emer_descriptor = EmergencyDescriptor::from_str(&format!(
+        "wsh(or_d(multi({},{}),and_v(v:pk({}),older(4032))))",
+        emergency_keys.len(),
+        emergency_keys.join(","),
+        recovery_key
+    ))?;
+    assert_ne!(custom_emer_descriptor, emergency_descriptor);
+    let custom_emer_txin = EmergencyTxIn::new(
+        emergency_tx.emergency_outpoint(),
+        EmergencyTxOut::new(
+            custom_emer_descriptor.address(Network::Bitcoin),
+            Amount::from_sat(emergency_tx.tx().output[0].value),
+        ),
+        custom_emer_descriptor,
+    )?;
+    let mut custom_sweep_tx = EmergencySweepTransaction::new(
+        vec![custom_emer_txin],
+        SweepDestination::Deposit(der_deposit_descriptor.clone()),
+        Amount::from_sat(1),
+    )?;
+    let custom_sweep_tx_sighash = custom_sweep_tx.signature_hash(0).expect("Input exists");
+    satisfy_transaction_input(
+        secp,
+        &mut custom_sweep_tx,
+        0,
+        &custom_sweep_tx_sighash,
+        &stakeholders_priv,
+        child_number,
+    )?;
+    custom_sweep_tx.finalize(secp)?;
+
+    // The stakeholders may migrate the deposit to a new descriptor, but not to the same one
+    assert_eq!(
+        MigrationTransaction::new(
+            vec![deposit_txin.clone()],
+            &deposit_descriptor,
+            derivation_index,
+            Amount::from_sat(1),
+            secp,
+        ),
+        Err(TransactionCreationError::SameDescriptor)
+    );
+    let (_, _, (_, new_stakeholders), _) = get_participants_sets(n_stk, 0, false, secp);
+    let new_deposit_descriptor = DepositDescriptor::new(new_stakeholders)?;
+    assert_eq!(
+        MigrationTransaction::new(
+            vec![],
+            &new_deposit_descriptor,
+            derivation_index,
+            Amount::from_sat(1),
+            secp,
+        ),
+        Err(TransactionCreationError::NoInput)
+    );
+    let mut migration_tx = MigrationTransaction::new(
+        vec![deposit_txin.clone()],
+        &new_deposit_descriptor,
+        derivation_index,
+        Amount::from_sat(1),
+        secp,
+    )?;
+    roundtrip!(migration_tx, MigrationTransaction);
+    let new_deposit_txin =
+        migration_tx.deposit_txin(&new_deposit_descriptor.derive(derivation_index, secp));
+    assert_eq!(
+        new_deposit_txin.txout().value() + migration_tx.fees(),
+        deposit_txin.txout().value()
+    );
+    assert_eq!(
+        migration_tx.fees().as_sat(),
+        migration_tx.tx().get_weight() as u64 + deposit_txin.max_sat_weight() as u64
+    );
+    let migration_tx_sighash = migration_tx.signature_hash(0).expect("Input exists");
+    satisfy_transaction_input(
+        secp,
+        &mut migration_tx,
+        0,
+        &migration_tx_sighash,
+        &stakeholders_priv,
+        child_number,
+    )?;
+    migration_tx.finalize(secp)?;
+    roundtrip!(migration_tx, MigrationTransaction);
 
     // Create but don't sign the unvaulting transaction until all revaulting transactions
     // are finalized
-    let deposit_txin_sat_cost = deposit_txin.txout().max_sat_weight();
+    let deposit_txin_sat_cost = deposit_txin.max_sat_weight();
+    assert_eq!(deposit_txin_sat_cost, deposit_txin.txout().max_sat_weight());
+    assert_eq!(
+        deposit_txin_sat_cost,
+        der_deposit_descriptor.max_sat_weight()
+    );
+    assert_eq!(deposit_txin.sequence(), RBF_SEQUENCE);
     let mut unvault_tx = UnvaultTransaction::new(
         deposit_txin.clone(),
         &der_unvault_descriptor,
@@ -354,6 +1147,7 @@ pub fn derive_transactions(
     roundtrip!(unvault_tx, UnvaultTransaction);
 
     assert_eq!(h_unvault, unvault_tx);
+    assert_eq!(unvault_tx.deposit_outpoint(), deposit_txin.outpoint());
     let unvault_value = unvault_tx.psbt().global.unsigned_tx.output[0].value;
     // 548 is the witstrip weight of an unvault tx (1 segwit input, 2 P2WSH txouts), 6 is the
     // feerate is sat/WU, and 30_000 is the CPFP output value.
@@ -365,13 +1159,65 @@ pub fn derive_transactions(
     // Create and sign the cancel transaction
     let rev_unvault_txin = unvault_tx.revault_unvault_txin(&der_unvault_descriptor);
     assert_eq!(rev_unvault_txin.txout().txout().value, unvault_value);
+    assert_eq!(rev_unvault_txin.derivation_index(), derivation_index);
+    assert_eq!(rev_unvault_txin.sequence(), RBF_SEQUENCE);
+    // The batch is presigned at 20, 100, 200, 500 and 1000 sat/vbyte
+    assert_eq!(
+        cancel_feerates()
+            .iter()
+            .map(|feerate| feerate.as_sat() * 4)
+            .collect::<Vec<u64>>(),
+        vec![20, 100, 200, 500, 1_000]
+    );
     let mut cancel_tx = CancelTransaction::new(
         rev_unvault_txin.clone(),
         &der_deposit_descriptor,
-        Amount::from_sat(50),
+        cancel_feerates()[2],
     )?;
     roundtrip!(cancel_tx, CancelTransaction);
     assert_eq!(h_cancel.feerate_200(), &cancel_tx);
+    let summary = cancel_tx.summary();
+    assert_eq!(summary.tx_type(), "CancelTransaction");
+    assert_eq!(summary.txid(), cancel_tx.txid());
+    assert_eq!((summary.inputs(), summary.outputs()), (1, 1));
+    assert_eq!(summary.fees(), cancel_tx.fees());
+    assert_eq!((summary.signatures(), summary.final_inputs()), (0, 0));
+    assert!(summary.to_string().starts_with(&format!(
+        "CancelTransaction {} (1 input(s)",
+        cancel_tx.txid()
+    )));
+    // A Cancel must signal for RBF and pay to the deposit at the same index as the Unvault
+    let mut bad_cancel_psbt = cancel_tx.psbt().clone();
+    bad_cancel_psbt.global.unsigned_tx.input[0].sequence = 0;
+    assert_eq!(
+        CancelTransaction::from_psbt_serialized(
+            &miniscript::bitcoin::consensus::encode::serialize(&bad_cancel_psbt)
+        ),
+        Err(TransactionSerialisationError::Validation(
+            PsbtValidationError::InvalidSequence(0)
+        ))
+    );
+    // The consensus decoding goes through the same checks
+    assert!(
+        miniscript::bitcoin::consensus::encode::deserialize::<CancelTransaction>(
+            &miniscript::bitcoin::consensus::encode::serialize(&bad_cancel_psbt)
+        )
+        .is_err()
+    );
+    let mut bad_cancel_psbt = cancel_tx.psbt().clone();
+    for (_, origin) in bad_cancel_psbt.outputs[0].bip32_derivation.iter_mut() {
+        origin.1 = bip32::DerivationPath::from(vec![bip32::ChildNumber::from(
+            DerivationIndex::try_from(11u32).unwrap(),
+        )]);
+    }
+    assert_eq!(
+        CancelTransaction::from_psbt_serialized(
+            &miniscript::bitcoin::consensus::encode::serialize(&bad_cancel_psbt)
+        ),
+        Err(TransactionSerialisationError::Validation(
+            PsbtValidationError::InvalidOutputField(0, "bip32 derivation not found in the input")
+        ))
+    );
     assert_eq!(
         cancel_tx.deposit_txin(&der_deposit_descriptor).outpoint(),
         OutPoint {
@@ -395,11 +1241,34 @@ pub fn derive_transactions(
         child_number,
     )?;
     roundtrip!(cancel_tx, CancelTransaction);
+    // A signature with another flag than SIGHASH_ALL is refused
+    let mut acp_cancel_psbt = cancel_tx.psbt().clone();
+    for rawsig in acp_cancel_psbt.inputs[0].partial_sigs.values_mut() {
+        *rawsig.last_mut().unwrap() = SigHashType::AllPlusAnyoneCanPay.as_u32() as u8;
+    }
+    assert_eq!(
+        CancelTransaction::from_psbt_serialized(
+            &miniscript::bitcoin::consensus::encode::serialize(&acp_cancel_psbt)
+        ),
+        Err(TransactionSerialisationError::Validation(
+            PsbtValidationError::InvalidInputField(0, "partial signature is not SIGHASH_ALL")
+        ))
+    );
+    // The error only carries the index of the input, its content may be rendered on demand
+    let acp_error =
+        PsbtValidationError::InvalidInputField(0, "partial signature is not SIGHASH_ALL");
+    assert_eq!(acp_error.input_index(), Some(0));
+    assert!(acp_error.verbose(&acp_cancel_psbt).contains("partial_sigs"));
+    assert!(!acp_error.to_string().contains("partial_sigs"));
     cancel_tx.finalize(&secp).unwrap();
     roundtrip!(cancel_tx, CancelTransaction);
 
     let mut unemergency_tx =
         UnvaultEmergencyTransaction::new(rev_unvault_txin.clone(), emergency_address.clone())?;
+    assert_eq!(
+        unemergency_tx.fees(),
+        UnvaultEmergencyTransaction::fees_for(&rev_unvault_txin, &emergency_address)
+    );
     roundtrip!(unemergency_tx, UnvaultEmergencyTransaction);
     assert_eq!(h_unemer, unemergency_tx);
     assert_eq!(
@@ -429,8 +1298,55 @@ pub fn derive_transactions(
     unemergency_tx.finalize(&secp)?;
     roundtrip!(unemergency_tx, UnvaultEmergencyTransaction);
 
+    // All the revocation transactions can be bundled for a watchtower
+    let bundle = WatchtowerBundle::new(
+        deposit_outpoint,
+        derivation_index,
+        &deposit_descriptor,
+        &unvault_descriptor,
+        h_cancel.clone(),
+        emergency_tx.clone(),
+        unemergency_tx.clone(),
+    );
+    bundle.verify(secp).expect("The bundle is consistent");
+    assert!(bundle.matches_descriptors(&deposit_descriptor, &unvault_descriptor));
+    assert!(!bundle.matches_descriptors(&new_deposit_descriptor, &unvault_descriptor));
+    assert_eq!(bundle.unvault_outpoint(), rev_unvault_txin.outpoint());
+    assert_eq!(bundle.cancel_txs()[2], *h_cancel.feerate_200());
+    #[cfg(feature = "use-serde")]
+    {
+        let serialized_bundle = serde_json::to_string(&bundle).unwrap();
+        let deserialized_bundle: WatchtowerBundle =
+            serde_json::from_str(&serialized_bundle).unwrap();
+        assert_eq!(bundle, deserialized_bundle);
+    }
+    let bad_bundle = WatchtowerBundle::new(
+        rev_unvault_txin.outpoint(),
+        derivation_index,
+        &deposit_descriptor,
+        &unvault_descriptor,
+        h_cancel.clone(),
+        emergency_tx.clone(),
+        unemergency_tx.clone(),
+    );
+    assert_eq!(
+        bad_bundle.verify(secp),
+        Err(WatchtowerBundleError::EmergencyOutpointMismatch(
+            deposit_outpoint
+        ))
+    );
+
     // Now we can sign the unvault
     let unvault_tx_sighash = unvault_tx.sig_hash().expect("Input exists");
+    assert_eq!(
+        unvault_tx_sighash,
+        SigHashCache::new(unvault_tx.tx()).signature_hash(
+            0,
+            deposit_txo.script_code(),
+            deposit_txo.value().as_sat(),
+            SigHashType::All
+        )
+    );
     satisfy_transaction_input(
         &secp,
         &mut unvault_tx,
@@ -445,22 +1361,10 @@ pub fn derive_transactions(
 
     // Create a CPFP transaction for the unvault
     // Some fake listunspent outputs
-    let listunspent = vec![
-        CpfpTxIn::new(
-            OutPoint::from_str(
-                "f21596dd9df36b86bcf65f0884f1f20675c1fc185bc78a37a9cddb4ae5e3dd9f:0",
-            )
-            .unwrap(),
-            CpfpTxOut::new(Amount::from_sat(30_000), &der_cpfp_descriptor),
-        ),
-        CpfpTxIn::new(
-            OutPoint::from_str(
-                "f21596dd9df36b86bcf65f0884f1f20675c1fc185bc78a37a9cddb4ae5e3dd9f:1",
-            )
-            .unwrap(),
-            CpfpTxOut::new(Amount::from_sat(30_000), &der_cpfp_descriptor),
-        ),
-    ];
+    let listunspent = dummy_feebump_coins(
+        &[Amount::from_sat(30_000), Amount::from_sat(30_000)],
+        &der_cpfp_descriptor,
+    );
 
     let cpfp_txin = unvault_tx.cpfp_txin(&cpfp_descriptor, &secp).unwrap();
     let cpfp_txins = vec![cpfp_txin.clone(), cpfp_txin];
@@ -477,6 +1381,16 @@ pub fn derive_transactions(
         listunspent.clone(),
     )
     .unwrap();
+    assert_eq!(
+        CpfpTransaction::from_txins(
+            vec![],
+            tbc_weight,
+            tbc_fees,
+            added_feerate,
+            listunspent.clone()
+        ),
+        Err(TransactionCreationError::NoInput)
+    );
 
     // The cpfp tx contains the input of the tx to be cpfped, right?
     assert!(cpfp_tx.tx().input.contains(&cpfp_txins[0].unsigned_txin()));
@@ -494,6 +1408,63 @@ pub fn derive_transactions(
         );
     }
 
+    // The same CPFP can be created out of a fee estimator
+    let tbc_feerate = 1_000 * (tbc_fees.as_sat() + tbc_weight) / tbc_weight;
+    let estimator = FixedFeerate(Amount::from_sat(4 * (tbc_feerate + added_feerate)));
+    assert_eq!(
+        CpfpTransaction::with_estimator(
+            cpfp_txins.clone(),
+            tbc_weight,
+            tbc_fees,
+            &estimator,
+            2,
+            listunspent.clone(),
+        )
+        .unwrap(),
+        Some(cpfp_tx.clone())
+    );
+    let low_estimator = FixedFeerate(Amount::from_sat(4 * tbc_feerate));
+    assert_eq!(
+        CpfpTransaction::with_estimator(
+            cpfp_txins.clone(),
+            tbc_weight,
+            tbc_fees,
+            &low_estimator,
+            2,
+            listunspent.clone(),
+        )
+        .unwrap(),
+        None
+    );
+    // The current feerate in sats/kWU, not rounded to a whole sat/WU
+    let unvault_feerate =
+        (unvault_tx.fees().as_sat() * 1000 + unvault_tx.max_weight() - 1) / unvault_tx.max_weight();
+    assert!(unvault_feerate <= unvault_tx.max_feerate() * 1000);
+    let estimator = FixedFeerate(Amount::from_sat(4 * (unvault_feerate + added_feerate)));
+    assert_eq!(unvault_tx.feebump_feerate(&estimator, 2), added_feerate);
+    assert_eq!(
+        unvault_tx.feebump_feerate(&FixedFeerate(Amount::from_sat(0)), 2),
+        0
+    );
+
+    // We can select the coins to pay for a fee-bump out of the wallet's ones. A single coin is
+    // selected if it's enough.
+    let selected =
+        select_feebump_coins(listunspent.clone(), Amount::from_sat(10_000), 1_000).unwrap();
+    assert_eq!(selected.len(), 1);
+    let selected =
+        select_feebump_coins(listunspent.clone(), Amount::from_sat(40_000), 1_000).unwrap();
+    assert_eq!(selected.len(), 2);
+    assert_eq!(
+        select_feebump_coins(listunspent.clone(), Amount::from_sat(60_000), 1_000),
+        Err(TransactionCreationError::InsufficientFunds)
+    );
+    // At a high enough feerate the coins are not worth spending anymore
+    assert_eq!(
+        select_feebump_coins(listunspent.clone(), Amount::from_sat(1), 1_000_000),
+        Err(TransactionCreationError::InsufficientFunds)
+    );
+
     // we sign the cpfp and then check the package feerate
     let cpfp_fees = cpfp_tx.fees();
     let inputs_len = cpfp_tx.psbt().inputs.len();
@@ -510,24 +1481,44 @@ pub fn derive_transactions(
 
     // Create and sign a spend transaction
     let spend_unvault_txin = unvault_tx.spend_unvault_txin(&der_unvault_descriptor);
+    assert_eq!(spend_unvault_txin.sequence(), csv.sequence());
+    assert_eq!(spend_unvault_txin.txout().csv_value(), csv);
     let unvault_value = spend_unvault_txin.txout().txout().value;
     let dummy_txo = TxOut::default();
     let cpfp_value = SpendTransaction::cpfp_txout(
-        vec![spend_unvault_txin.clone()],
-        vec![SpendTxOut::new(dummy_txo.clone())],
+        std::slice::from_ref(&spend_unvault_txin),
+        &[SpendTxOut::new(dummy_txo.clone())],
         None,
         &der_cpfp_descriptor,
         0,
     )
     .txout()
     .value;
+    assert_eq!(
+        Some(Amount::from_sat(cpfp_value)),
+        cpfp_reserve(
+            SpendTransaction::max_weight_for(
+                std::slice::from_ref(&spend_unvault_txin),
+                &[SpendTxOut::new(dummy_txo.clone())],
+                None,
+                &der_cpfp_descriptor,
+                0,
+            ),
+            CPFP_RESERVE_FEERATE
+        )
+    );
+    assert_eq!(
+        cpfp_reserve(0, CPFP_RESERVE_FEERATE),
+        Some(Amount::from_sat(CPFP_MIN_VALUE))
+    );
+    assert_eq!(cpfp_reserve(u64::MAX, CPFP_RESERVE_FEERATE), None);
     let change_value = unvault_value
         .checked_sub(cpfp_value)
         .expect("We would never create such a tx chain (dust)");
     // The overhead incurred to the value of the CPFP output by the change output
     // See https://github.com/revault/practical-revault/blob/master/transactions.md#spend_tx
     const P2WSH_TXO_WEIGHT: u64 = 43 * 4;
-    let cpfp_change_overhead = 16 * P2WSH_TXO_WEIGHT;
+    let cpfp_change_overhead = CPFP_RESERVE_FEERATE * P2WSH_TXO_WEIGHT;
     let fees = 10_000;
     let (spend_txo, change_txo) = if unvault_value
         > change_value + cpfp_value + cpfp_change_overhead + fees
@@ -552,6 +1543,28 @@ pub fn derive_transactions(
             None,
         )
     };
+    assert_eq!(
+        SpendTransaction::new(
+            vec![],
+            vec![SpendTxOut::new(spend_txo.clone())],
+            change_txo.clone(),
+            &der_cpfp_descriptor,
+            0,
+            true,
+        ),
+        Err(TransactionCreationError::NoInput)
+    );
+    assert_eq!(
+        SpendTransaction::new(
+            vec![spend_unvault_txin.clone()],
+            vec![],
+            None,
+            &der_cpfp_descriptor,
+            0,
+            false,
+        ),
+        Err(TransactionCreationError::NoOutput)
+    );
     let mut spend_tx = SpendTransaction::new(
         vec![spend_unvault_txin.clone()],
         vec![SpendTxOut::new(spend_txo.clone())],
@@ -562,23 +1575,102 @@ pub fn derive_transactions(
     )
     .expect("Amounts ok");
     roundtrip!(spend_tx, SpendTransaction);
+    // We can also let the change be computed out of a fee estimator. 1sat/vb is 1sat/WU when
+    // rounded up.
+    let est_spend_tx = SpendTransaction::with_estimator(
+        vec![spend_unvault_txin.clone()],
+        vec![],
+        &der_deposit_descriptor,
+        &der_cpfp_descriptor,
+        0,
+        &FixedFeerate(Amount::from_sat(1_000)),
+        6,
+    )
+    .expect("Amounts ok");
+    assert_eq!(est_spend_tx.tx().output.len(), 2);
+    assert!(est_spend_tx.max_feerate() >= 1);
     let spend_tx_sighash = spend_tx.signature_hash(0).expect("Input exists");
     satisfy_transaction_input(
         &secp,
         &mut spend_tx,
         0,
         &spend_tx_sighash,
-        &managers_priv
-            .iter()
-            .chain(cosigners_priv.iter())
-            .copied()
-            .collect::<Vec<bip32::ExtendedPrivKey>>(),
+        &managers_priv,
         child_number,
     )?;
     roundtrip!(spend_tx, SpendTransaction);
+    // The managers signed, only the cosigning servers' signatures are missing (if any)
+    let sigs_count = spend_tx.signatures_count(secp)?;
+    assert_eq!(sigs_count[0].valid(), n_man);
+    assert_eq!(sigs_count[0].needed(), n_stk.min(n_man + cosigners.len()));
+    assert_eq!(sigs_count[0].missing(), cosigners.len());
+
+    // Now request the signatures of the cosigning servers
+    let sign_req = SignRequest::new(spend_tx.clone(), vec![deposit_outpoint]).unwrap();
+    #[cfg(feature = "use-serde")]
+    {
+        let serialized_req = serde_json::to_string(&sign_req).unwrap();
+        let deserialized_req: SignRequest = serde_json::from_str(&serialized_req).unwrap();
+        assert_eq!(sign_req, deserialized_req);
+    }
+    assert_eq!(
+        SignRequest::new(spend_tx.clone(), vec![deposit_outpoint, deposit_outpoint]),
+        Err(CosigningError::DepositCountMismatch(1, 2))
+    );
+    assert_eq!(
+        SignResult::new(vec![]).apply(&mut spend_tx, secp),
+        Err(CosigningError::SignatureCountMismatch(1, 0))
+    );
+    let derivation_path = bip32::DerivationPath::from(vec![child_number]);
+    for cosig_priv in cosigners_priv.iter() {
+        let privkey = cosig_priv.derive_priv(secp, &derivation_path).unwrap();
+        let sighash = sign_req.spend_tx().signature_hash(0).unwrap();
+        let sig = secp.sign(
+            &secp256k1::Message::from_slice(&sighash).unwrap(),
+            &privkey.private_key.key,
+        );
+        let sign_res = SignResult::new(vec![(privkey.private_key.public_key(secp), sig)]);
+        #[cfg(feature = "use-serde")]
+        {
+            let serialized_res = serde_json::to_string(&sign_res).unwrap();
+            let deserialized_res: SignResult = serde_json::from_str(&serialized_res).unwrap();
+            assert_eq!(sign_res, deserialized_res);
+        }
+        sign_res.apply(&mut spend_tx, secp).unwrap();
+    }
+    roundtrip!(spend_tx, SpendTransaction);
     spend_tx.finalize(&secp)?;
     roundtrip!(spend_tx, SpendTransaction);
 
+    // The stakeholders can check the Spend against their policy
+    let destinations = [spend_txo.script_pubkey.clone()];
+    let mut spend_policy = SpendPolicy {
+        unvault_txins: std::slice::from_ref(&spend_unvault_txin),
+        destinations: Some(&destinations),
+        change_descriptor: &der_deposit_descriptor,
+        cpfp_descriptor: &der_cpfp_descriptor,
+        min_feerate: 1,
+        max_feerate: spend_tx.max_feerate(),
+    };
+    let verdict = spend_tx.validate_against(&spend_policy);
+    assert!(verdict.is_valid(), "{:?}", verdict.violations());
+    spend_policy.destinations = Some(&[]);
+    spend_policy.max_feerate = spend_tx.max_feerate() - 1;
+    assert_eq!(
+        spend_tx.validate_against(&spend_policy).violations(),
+        &[
+            SpendViolation::UnauthorizedOutput(1),
+            SpendViolation::FeerateOutOfBounds(spend_tx.max_feerate())
+        ]
+    );
+    spend_policy.destinations = None;
+    spend_policy.max_feerate = spend_tx.max_feerate();
+    spend_policy.unvault_txins = &[];
+    assert_eq!(
+        spend_tx.validate_against(&spend_policy).violations(),
+        &[SpendViolation::UnknownInput(0)]
+    );
+
     // We can't create a dust output with the Spend
     let dust_txo = TxOut {
         value: 470,
@@ -593,6 +1685,80 @@ pub fn derive_transactions(
         true,
     )
     .expect_err("Creating a dust output");
+    let dest_address = der_deposit_descriptor.address(Network::Bitcoin);
+    assert_eq!(
+        SpendTxOut::destination(
+            dest_address.clone(),
+            Amount::from_sat(329),
+            Network::Bitcoin
+        ),
+        Err(TxoutCreationError::Dust)
+    );
+    assert_eq!(
+        SpendTxOut::destination(
+            dest_address.clone(),
+            Amount::from_sat(330),
+            Network::Regtest
+        ),
+        Err(TxoutCreationError::NetworkMismatch(
+            Network::Regtest,
+            Network::Bitcoin
+        ))
+    );
+    // Signet addresses are parsed as Testnet ones, as they share the same prefixes. Regtest
+    // has its own bech32 prefix.
+    let test_address =
+        Address::from_str(&der_deposit_descriptor.address(Network::Testnet).to_string()).unwrap();
+    SpendTxOut::destination(test_address.clone(), Amount::from_sat(330), Network::Signet)
+        .expect("Testnet addresses are valid on Signet");
+    assert_eq!(
+        SpendTxOut::destination(test_address, Amount::from_sat(330), Network::Regtest),
+        Err(TxoutCreationError::NetworkMismatch(
+            Network::Regtest,
+            Network::Testnet
+        ))
+    );
+    assert_eq!(
+        SpendTxOut::destination(
+            dest_address.clone(),
+            Amount::from_sat(330),
+            Network::Bitcoin
+        )
+        .unwrap()
+        .txout()
+        .script_pubkey,
+        dest_address.script_pubkey()
+    );
+
+    // Only standard outputs above the dust threshold may be created with the checked constructor
+    assert_eq!(
+        SpendTxOut::new_checked(dust_txo),
+        Err(TxoutCreationError::InvalidScriptPubkeyType)
+    );
+    assert_eq!(
+        SpendTxOut::new_checked(TxOut {
+            value: 329,
+            script_pubkey: dest_address.script_pubkey()
+        }),
+        Err(TxoutCreationError::Dust)
+    );
+    assert_eq!(
+        SpendTxOut::new_checked(TxOut {
+            value: 0,
+            script_pubkey: Script::new_op_return(&[0; MAX_OP_RETURN_RELAY])
+        }),
+        Err(TxoutCreationError::InvalidScriptPubkeyType)
+    );
+    SpendTxOut::new_checked(TxOut {
+        value: 0,
+        script_pubkey: Script::new_op_return(&[0; 80]),
+    })
+    .expect("Standard OP_RETURN");
+    SpendTxOut::new_checked(TxOut {
+        value: 330,
+        script_pubkey: dest_address.script_pubkey(),
+    })
+    .expect("Standard P2WSH above dust");
 
     // We can't create a dust change output with the Spend
     SpendTransaction::new(
@@ -617,18 +1783,89 @@ pub fn derive_transactions(
     let spend_unvault_txins: Vec<UnvaultTxIn> = unvault_spends
         .into_iter()
         .map(|(outpoint, value)| {
-            UnvaultTxIn::new(
-                outpoint,
-                UnvaultTxOut::new(Amount::from_sat(value), &der_unvault_descriptor),
-                csv,
-            )
+            UnvaultTxIn::new_spend(outpoint, UnvaultTxOut::new(value, &der_unvault_descriptor))
         })
         .collect();
     let n_txins = spend_unvault_txins.len();
+
+    // We can select the coins to spend among the Unvault outputs. At 1sat/WU an exact match
+    // with the first coin is found by the BnB search, without change.
+    let one_sat_wu = FixedFeerate(Amount::from_sat(4_000));
+    let exact_coin = spend_unvault_txins[0].clone();
+    let exact_weight = SpendTransaction::max_weight_for(
+        std::slice::from_ref(&exact_coin),
+        &[SpendTxOut::new(TxOut::default())],
+        None,
+        &der_cpfp_descriptor,
+        0,
+    );
+    let exact_txo = SpendTxOut::new(TxOut {
+        value: exact_coin.txout().txout().value - 17 * exact_weight,
+        ..TxOut::default()
+    });
+    let selection = select_unvault_coins(
+        spend_unvault_txins.clone(),
+        std::slice::from_ref(&exact_txo),
+        &der_deposit_descriptor,
+        &der_cpfp_descriptor,
+        0,
+        &one_sat_wu,
+        2,
+        CoinSelectionStrategy::BranchAndBound,
+    )
+    .expect("Enough funds");
+    assert_eq!(selection.inputs(), &[exact_coin][..]);
+    assert_eq!(selection.change(), None);
+    // The largest-first strategy will spend the largest coin and create a change output
+    let selection = select_unvault_coins(
+        spend_unvault_txins.clone(),
+        std::slice::from_ref(&exact_txo),
+        &der_deposit_descriptor,
+        &der_cpfp_descriptor,
+        0,
+        &one_sat_wu,
+        2,
+        CoinSelectionStrategy::LargestFirst,
+    )
+    .expect("Enough funds");
+    assert_eq!(selection.inputs().len(), 1);
+    let (selected_inputs, change) = selection.into_parts();
+    let selected_spend_tx = SpendTransaction::new(
+        selected_inputs,
+        vec![exact_txo],
+        change.map(|value| DepositTxOut::new(value, &der_deposit_descriptor)),
+        &der_cpfp_descriptor,
+        0,
+        true,
+    )
+    .expect("The selection pays for the Spend");
+    assert!(selected_spend_tx.max_feerate() >= 1);
+    // We can't spend more than we have
+    let total_value = spend_unvault_txins
+        .iter()
+        .map(|txin| txin.txout().txout().value)
+        .sum::<u64>();
+    assert_eq!(
+        select_unvault_coins(
+            spend_unvault_txins.clone(),
+            &[SpendTxOut::new(TxOut {
+                value: total_value,
+                ..TxOut::default()
+            })],
+            &der_deposit_descriptor,
+            &der_cpfp_descriptor,
+            0,
+            &one_sat_wu,
+            2,
+            CoinSelectionStrategy::BranchAndBound,
+        ),
+        Err(TransactionCreationError::InsufficientFunds)
+    );
+
     let dummy_txo = TxOut::default();
     let cpfp_value = SpendTransaction::cpfp_txout(
-        spend_unvault_txins.clone(),
-        vec![SpendTxOut::new(dummy_txo.clone())],
+        &spend_unvault_txins,
+        &[SpendTxOut::new(dummy_txo.clone())],
         None,
         &der_cpfp_descriptor,
         0,
@@ -669,7 +1906,32 @@ pub fn derive_transactions(
                 .expect("Input exists")
         })
         .collect();
+    // All the inputs missing signatures are reported at once
+    match spend_tx.clone().finalize(secp) {
+        Err(Error::InputsFinalisation(failures)) => {
+            assert_eq!(
+                failures.iter().map(|(i, _)| *i).collect::<Vec<usize>>(),
+                (0..n_txins).collect::<Vec<usize>>()
+            );
+            assert!(failures
+                .iter()
+                .all(|(_, e)| matches!(e, InputSatisfactionError::MissingSignatures(_))));
+        }
+        res => panic!("Unexpected finalization result: {:?}", res),
+    }
     for (i, spend_tx_sighash) in sighashes.into_iter().enumerate() {
+        // If a single input can't be finalized, none of them is
+        if i == n_txins - 1 {
+            let unfinalized_spend_tx = spend_tx.clone();
+            match spend_tx.finalize(secp) {
+                Err(Error::InputsFinalisation(failures)) => {
+                    assert_eq!(failures.len(), 1);
+                    assert_eq!(failures[0].0, i);
+                }
+                res => panic!("Unexpected finalization result: {:?}", res),
+            }
+            assert_eq!(spend_tx, unfinalized_spend_tx);
+        }
         satisfy_transaction_input(
             &secp,
             &mut spend_tx,
@@ -683,6 +1945,7 @@ pub fn derive_transactions(
             child_number,
         )?
     }
+    spend_tx.verify_signatures(secp)?;
 
     // Create a CPFP transaction for the (not yet finalized) Spend
     // Some fake listunspent outputs
@@ -747,8 +2010,31 @@ pub fn derive_transactions(
     );
 
     roundtrip!(spend_tx, SpendTransaction);
+    assert!(!spend_tx.is_input_finalized(0).unwrap());
     spend_tx.finalize(&secp)?;
+    assert!(spend_tx.is_input_finalized(0).unwrap());
+    assert_eq!(
+        spend_tx.input_miniscript(0),
+        Err(InputSatisfactionError::AlreadyFinalized)
+    );
+    assert_eq!(
+        spend_tx.is_input_finalized(spend_tx.psbt().inputs.len()),
+        Err(InputSatisfactionError::OutOfBounds)
+    );
+    // Finalizing it again is a no-op
+    let finalized_spend_tx = spend_tx.clone();
+    spend_tx.finalize(secp)?;
+    assert_eq!(spend_tx, finalized_spend_tx);
     roundtrip!(spend_tx, SpendTransaction);
+    // Only what's needed to extract the transaction is kept
+    for psbtin in spend_tx.psbt().inputs.iter() {
+        assert!(psbtin.final_script_witness.is_some());
+        assert!(psbtin.witness_utxo.is_some());
+        assert!(psbtin.partial_sigs.is_empty());
+        assert!(psbtin.sighash_type.is_none());
+        assert!(psbtin.witness_script.is_none());
+        assert!(psbtin.bip32_derivation.is_empty());
+    }
 
     Ok(())
 }
@@ -14,10 +14,7 @@ use miniscript::{
     bitcoin::{
         blockdata::constants::max_money,
         consensus::encode::Decodable,
-        util::psbt::{
-            Global as PsbtGlobal, Input as PsbtIn, Output as PsbtOut,
-            PartiallySignedTransaction as Psbt,
-        },
+        util::psbt::{Global as PsbtGlobal, Output as PsbtOut, PartiallySignedTransaction as Psbt},
         Amount, Network, OutPoint, Transaction,
     },
     DescriptorTrait,
@@ -43,15 +40,18 @@ impl UnvaultTransaction {
         unvault_txout: UnvaultTxOut,
         cpfp_txout: CpfpTxOut,
     ) -> Psbt {
+        let (unvault_txout, _, unvault_bip32_derivation) = unvault_txout.into_parts();
+        let (cpfp_txout, _, cpfp_bip32_derivation) = cpfp_txout.into_parts();
+
         Psbt {
             // 1 Unvault, 1 CPFP
             outputs: vec![
                 PsbtOut {
-                    bip32_derivation: unvault_txout.bip32_derivation().clone(),
+                    bip32_derivation: unvault_bip32_derivation,
                     ..PsbtOut::default()
                 },
                 PsbtOut {
-                    bip32_derivation: cpfp_txout.bip32_derivation().clone(),
+                    bip32_derivation: cpfp_bip32_derivation,
                     ..PsbtOut::default()
                 },
             ],
@@ -60,19 +60,14 @@ impl UnvaultTransaction {
                     version: TX_VERSION,
                     lock_time: TX_LOCKTIME,
                     input: vec![deposit_txin.unsigned_txin()],
-                    output: vec![unvault_txout.into_txout(), cpfp_txout.into_txout()],
+                    output: vec![unvault_txout, cpfp_txout],
                 },
                 version: 0,
                 xpub: BTreeMap::new(),
                 proprietary: BTreeMap::new(),
                 unknown: BTreeMap::new(),
             },
-            inputs: vec![PsbtIn {
-                witness_script: Some(deposit_txin.txout().witness_script().clone()),
-                bip32_derivation: deposit_txin.txout().bip32_derivation().clone(),
-                witness_utxo: Some(deposit_txin.into_txout().into_txout()),
-                ..PsbtIn::default()
-            }],
+            inputs: vec![deposit_txin.into_psbtin()],
         }
     }
 
@@ -89,26 +84,28 @@ impl UnvaultTransaction {
         // First, create a dummy transaction to get its weight without Witness
         let dummy_unvault_txout = UnvaultTxOut::new(Amount::from_sat(u64::MAX), unvault_descriptor);
         let dummy_cpfp_txout = CpfpTxOut::new(Amount::from_sat(u64::MAX), cpfp_descriptor);
-        let dummy_tx = UnvaultTransaction::create_psbt(
-            deposit_input.clone(),
-            dummy_unvault_txout,
-            dummy_cpfp_txout,
-        )
-        .global
-        .unsigned_tx;
+        let dummy_tx = Transaction {
+            version: TX_VERSION,
+            lock_time: TX_LOCKTIME,
+            input: vec![deposit_input.unsigned_txin()],
+            output: vec![
+                dummy_unvault_txout.into_txout(),
+                dummy_cpfp_txout.into_txout(),
+            ],
+        };
 
         // The weight of the transaction once signed will be the size of the witness-stripped
         // transaction plus the size of the single input's witness.
         let total_weight = dummy_tx
             .get_weight()
-            .checked_add(deposit_input.txout().max_sat_weight())
+            .checked_add(deposit_input.max_sat_weight())
             .expect("Properly-computed weights cannot overflow");
         let total_weight: u64 = total_weight.try_into().expect("usize in u64");
-        let fees = UNVAULT_TX_FEERATE
+        let fees = Amount::from_sat(UNVAULT_TX_FEERATE)
             .checked_mul(total_weight)
             .expect("Properly-computed weights cannot overflow");
         // Nobody wants to pay 3k€ fees if we had a bug.
-        if fees > INSANE_FEES {
+        if fees > Amount::from_sat(INSANE_FEES) {
             return Err(TransactionCreationError::InsaneFees);
         }
 
@@ -118,16 +115,17 @@ impl UnvaultTransaction {
         );
 
         // The unvault output value is then equal to the deposit value minus the fees and the CPFP.
-        let deposit_value = deposit_input.txout().txout().value;
-        if fees + UNVAULT_CPFP_VALUE + DEPOSIT_MIN_SATS > deposit_value {
-            return Err(TransactionCreationError::Dust);
-        }
-        let unvault_value = deposit_value - fees - UNVAULT_CPFP_VALUE; // Arithmetic checked above
-        if unvault_value > max_money(Network::Bitcoin) {
+        let deposit_value = Amount::from_sat(deposit_input.txout().txout().value);
+        let unvault_value = deposit_value
+            .checked_sub(fees)
+            .and_then(|v| v.checked_sub(Amount::from_sat(UNVAULT_CPFP_VALUE)))
+            .filter(|v| *v >= Amount::from_sat(DEPOSIT_MIN_SATS))
+            .ok_or(TransactionCreationError::Dust)?;
+        if unvault_value > Amount::from_sat(max_money(Network::Bitcoin)) {
             return Err(TransactionCreationError::InsaneAmounts);
         }
 
-        let unvault_txout = UnvaultTxOut::new(Amount::from_sat(unvault_value), unvault_descriptor);
+        let unvault_txout = UnvaultTxOut::new(unvault_value, unvault_descriptor);
         let cpfp_txout = CpfpTxOut::new(Amount::from_sat(UNVAULT_CPFP_VALUE), cpfp_descriptor);
         Ok(UnvaultTransaction(UnvaultTransaction::create_psbt(
             deposit_input,
@@ -136,11 +134,11 @@ impl UnvaultTransaction {
         )))
     }
 
-    fn unvault_txin(
+    // The outpoint and txout of the Unvault output, to be referenced by a spending transaction
+    fn unvault_txo(
         &self,
         unvault_descriptor: &DerivedUnvaultDescriptor,
-        sequence: u32,
-    ) -> UnvaultTxIn {
+    ) -> (OutPoint, UnvaultTxOut) {
         let spk = unvault_descriptor.inner().script_pubkey();
         let index = self
             .psbt()
@@ -154,19 +152,18 @@ impl UnvaultTransaction {
         // Unwraped above
         let txo = &self.psbt().global.unsigned_tx.output[index];
         let prev_txout = UnvaultTxOut::new(Amount::from_sat(txo.value), unvault_descriptor);
-        UnvaultTxIn::new(
-            OutPoint {
-                txid: self.psbt().global.unsigned_tx.txid(),
-                vout: index.try_into().expect("There are two outputs"),
-            },
-            prev_txout,
-            sequence,
-        )
+        let outpoint = OutPoint {
+            txid: self.psbt().global.unsigned_tx.txid(),
+            vout: index.try_into().expect("There are two outputs"),
+        };
+
+        (outpoint, prev_txout)
     }
 
     /// Get the Unvault txo to be referenced in a spending transaction
     pub fn spend_unvault_txin(&self, unvault_descriptor: &DerivedUnvaultDescriptor) -> UnvaultTxIn {
-        self.unvault_txin(unvault_descriptor, unvault_descriptor.csv_value())
+        let (outpoint, prev_txout) = self.unvault_txo(unvault_descriptor);
+        UnvaultTxIn::new_spend(outpoint, prev_txout)
     }
 
     /// Get the Unvault txo to be referenced in a revocation transaction
@@ -174,12 +171,23 @@ impl UnvaultTransaction {
         &self,
         unvault_descriptor: &DerivedUnvaultDescriptor,
     ) -> UnvaultTxIn {
-        self.unvault_txin(unvault_descriptor, RBF_SEQUENCE)
+        let (outpoint, prev_txout) = self.unvault_txo(unvault_descriptor);
+        UnvaultTxIn::new_revocation(outpoint, prev_txout)
+    }
+
+    /// Get the outpoint of the deposit this Unvault transaction spends
+    pub fn deposit_outpoint(&self) -> OutPoint {
+        // We are only ever created with exactly one input, the deposit.
+        self.tx().input[0].previous_output
     }
 
-    /// Parse an Unvault transaction from a PSBT
+    /// Parse an Unvault transaction from a BIP174-serialized PSBT
     pub fn from_raw_psbt(raw_psbt: &[u8]) -> Result<Self, TransactionSerialisationError> {
-        let psbt = Decodable::consensus_decode(raw_psbt)?;
+        Self::from_psbt(Decodable::consensus_decode(raw_psbt)?)
+    }
+
+    /// Check this PSBT is a valid Unvault transaction
+    pub fn from_psbt(psbt: Psbt) -> Result<Self, TransactionSerialisationError> {
         let psbt = utils::psbt_common_sanity_checks(psbt)?;
 
         // Unvault + CPFP txos
@@ -188,9 +196,11 @@ impl UnvaultTransaction {
             return Err(PsbtValidationError::InvalidOutputCount(output_count).into());
         }
 
-        for output in psbt.outputs.iter() {
+        for (i, output) in psbt.outputs.iter().enumerate() {
             if output.bip32_derivation.is_empty() {
-                return Err(PsbtValidationError::InvalidOutputField(output.clone()).into());
+                return Err(
+                    PsbtValidationError::InvalidOutputField(i, "missing bip32 derivation").into(),
+                );
             }
         }
 
@@ -36,49 +36,62 @@ impl UnvaultEmergencyTransaction {
         unvault_input: UnvaultTxIn,
         emer_address: EmergencyAddress,
     ) -> Result<UnvaultEmergencyTransaction, TransactionCreationError> {
+        let fees = Self::fees_for(&unvault_input, &emer_address);
+
+        // Now, get the emergency output value out of it.
+        let unvault_value = unvault_input.txout().value();
+        let emer_value = unvault_value
+            .checked_sub(fees)
+            .expect("We would never create a dust unvault txo");
+        assert!(
+            emer_value < Amount::from_sat(max_money(Network::Bitcoin)),
+            "Checked in UnvaultTransaction constructor already"
+        );
+        // We never leave less than the fees computed above, so it can only fail if it's dust.
+        let emer_txo =
+            EmergencyTxOut::new_checked_value(emer_address, emer_value, unvault_value, fees)
+                .map_err(|_| TransactionCreationError::Dust)?;
+
+        Ok(UnvaultEmergencyTransaction(utils::create_psbt(
+            unvault_input,
+            emer_txo,
+        )))
+    }
+
+    /// Get the absolute fees an Emergency transaction spending this unvault txo would pay, at
+    /// the fixed [EMER_TX_FEERATE].
+    pub fn fees_for(unvault_input: &UnvaultTxIn, emer_address: &EmergencyAddress) -> Amount {
         // First, create a dummy transaction to get its weight without Witness.
         let emer_txo = EmergencyTxOut::new(emer_address.clone(), Amount::from_sat(u64::MAX));
-        let dummy_tx = utils::create_psbt(unvault_input.clone(), emer_txo)
-            .global
-            .unsigned_tx;
+        let dummy_tx = utils::create_unsigned_tx(unvault_input, &emer_txo);
 
         // The weight of the Unvault Emergency transaction is the weight of the witness-stripped
         // transaction plus the weight required to satisfy the Unvault txin
         let total_weight = dummy_tx
             .get_weight()
-            .checked_add(unvault_input.txout().max_sat_weight())
+            .checked_add(unvault_input.max_sat_weight())
             .expect("Weight computation bug");
         let total_weight: u64 = total_weight.try_into().expect("usize in u64");
-        let fees = EMER_TX_FEERATE
+        let fees = Amount::from_sat(EMER_TX_FEERATE)
             .checked_mul(total_weight)
             .expect("Weight computation bug");
-        assert!(fees < INSANE_FEES);
+        assert!(fees < Amount::from_sat(INSANE_FEES));
 
         assert!(
             total_weight <= MAX_STANDARD_TX_WEIGHT as u64,
             "A single output and a single output"
         );
 
-        // Now, get the emergency output value out of it.
-        let deposit_value = unvault_input.txout().txout().value;
-        let emer_value = deposit_value
-            .checked_sub(fees)
-            .expect("We would never create a dust unvault txo");
-        assert!(
-            emer_value < max_money(Network::Bitcoin),
-            "Checked in UnvaultTransaction constructor already"
-        );
-        let emer_txo = EmergencyTxOut::new(emer_address, Amount::from_sat(emer_value));
-
-        Ok(UnvaultEmergencyTransaction(utils::create_psbt(
-            unvault_input,
-            emer_txo,
-        )))
+        fees
     }
 
-    /// Parse an UnvaultEmergency transaction from a PSBT
+    /// Parse an UnvaultEmergency transaction from a BIP174-serialized PSBT
     pub fn from_raw_psbt(raw_psbt: &[u8]) -> Result<Self, TransactionSerialisationError> {
-        let psbt = Decodable::consensus_decode(raw_psbt)?;
+        Self::from_psbt(Decodable::consensus_decode(raw_psbt)?)
+    }
+
+    /// Check this PSBT is a valid UnvaultEmergency transaction
+    pub fn from_psbt(psbt: Psbt) -> Result<Self, TransactionSerialisationError> {
         let psbt = utils::psbt_common_sanity_checks(psbt)?;
 
         // Emergency txo
@@ -1,17 +1,27 @@
 use crate::{
     error::*,
-    transactions::{TX_LOCKTIME, TX_VERSION},
+    transactions::{INSANE_FEES, MAX_STANDARD_TX_WEIGHT, TX_LOCKTIME, TX_VERSION},
     txins::RevaultTxIn,
     txouts::{RevaultInternalTxOut, RevaultTxOut},
 };
 
-use miniscript::bitcoin::{
-    blockdata::constants::max_money,
-    util::psbt::{Global as PsbtGlobal, Input as PsbtIn, PartiallySignedTransaction as Psbt},
-    Amount, Network, OutPoint, Transaction,
+use miniscript::{
+    bitcoin::{
+        blockdata::constants::max_money,
+        hashes::hash160,
+        util::psbt::{Global as PsbtGlobal, Output as PsbtOut, PartiallySignedTransaction as Psbt},
+        Amount, Network, OutPoint, PublicKey, Script, SigHashType, Transaction, TxOut,
+    },
+    miniscript::iter::PkPkh,
+    policy::{semantic::Policy as SemanticPolicy, Liftable},
+    Miniscript, MiniscriptKey, Segwitv0,
 };
 
-use std::collections::{BTreeMap, HashSet};
+use std::{
+    collections::{BTreeMap, HashSet},
+    convert::TryInto,
+    fmt,
+};
 
 /// Boilerplate for defining a Revault transaction as a newtype over a Psbt and implementing
 /// RevaultTransaction for it.
@@ -25,6 +35,8 @@ macro_rules! impl_revault_transaction {
         pub struct $transaction_name(Psbt);
 
         impl inner_mut::PrivateInnerMut for $transaction_name {
+            const TYPE_NAME: &'static str = stringify!($transaction_name);
+
             fn psbt(&self) -> &Psbt {
                 &self.0
             }
@@ -80,6 +92,24 @@ macro_rules! impl_revault_transaction {
             }
         }
 
+        impl fmt::LowerHex for $transaction_name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+                let ser_tx = miniscript::bitcoin::consensus::encode::serialize(
+                    &crate::transactions::utils::final_tx(&self.0),
+                );
+                crate::transactions::utils::format_hex(&ser_tx, false, f)
+            }
+        }
+
+        impl fmt::UpperHex for $transaction_name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+                let ser_tx = miniscript::bitcoin::consensus::encode::serialize(
+                    &crate::transactions::utils::final_tx(&self.0),
+                );
+                crate::transactions::utils::format_hex(&ser_tx, true, f)
+            }
+        }
+
         impl str::FromStr for $transaction_name {
             type Err = TransactionSerialisationError;
 
@@ -87,6 +117,51 @@ macro_rules! impl_revault_transaction {
                 $transaction_name::from_psbt_str(s)
             }
         }
+
+        impl std::convert::TryFrom<Psbt> for $transaction_name {
+            type Error = TransactionSerialisationError;
+
+            /// Check this PSBT is a valid transaction of this type.
+            fn try_from(psbt: Psbt) -> Result<Self, Self::Error> {
+                $transaction_name::from_psbt(psbt)
+            }
+        }
+
+        impl std::convert::TryFrom<&[u8]> for $transaction_name {
+            type Error = TransactionSerialisationError;
+
+            /// Parse a BIP174-serialized PSBT as a transaction of this type.
+            fn try_from(raw_psbt: &[u8]) -> Result<Self, Self::Error> {
+                $transaction_name::from_raw_psbt(raw_psbt)
+            }
+        }
+
+        // The consensus encoding of a Revault transaction is the BIP174 serialization of its
+        // inner PSBT.
+        impl miniscript::bitcoin::consensus::encode::Encodable for $transaction_name {
+            fn consensus_encode<W: std::io::Write>(
+                &self,
+                writer: W,
+            ) -> Result<usize, std::io::Error> {
+                miniscript::bitcoin::consensus::encode::Encodable::consensus_encode(&self.0, writer)
+            }
+        }
+
+        impl miniscript::bitcoin::consensus::encode::Decodable for $transaction_name {
+            fn consensus_decode<D: std::io::Read>(
+                d: D,
+            ) -> Result<Self, miniscript::bitcoin::consensus::encode::Error> {
+                let psbt: Psbt =
+                    miniscript::bitcoin::consensus::encode::Decodable::consensus_decode(d)?;
+                // Go through the same checks as when parsing a PSBT
+                $transaction_name::from_psbt(psbt).map_err(|e| {
+                    miniscript::bitcoin::consensus::encode::Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        e.to_string(),
+                    ))
+                })
+            }
+        }
     };
 }
 
@@ -128,18 +203,24 @@ pub fn psbt_common_sanity_checks(psbt: Psbt) -> Result<Psbt, PsbtValidationError
     let mut is_final = None;
     // Record the number of coins spent by the transaction
     let mut value_in: u64 = 0;
-    for input in psbt.inputs.iter() {
+    for (i, input) in psbt.inputs.iter().enumerate() {
         // We restrict to native segwit
         if input.witness_utxo.is_none() {
-            return Err(PsbtValidationError::MissingWitnessUtxo(input.clone()));
+            return Err(PsbtValidationError::MissingWitnessUtxo(i));
         }
 
         if input.non_witness_utxo.is_some() {
-            return Err(PsbtValidationError::InvalidInputField(input.clone()));
+            return Err(PsbtValidationError::InvalidInputField(
+                i,
+                "non_witness_utxo is set",
+            ));
         }
 
         if input.redeem_script.is_some() {
-            return Err(PsbtValidationError::InvalidInputField(input.clone()));
+            return Err(PsbtValidationError::InvalidInputField(
+                i,
+                "redeem_script is set",
+            ));
         }
 
         // Make sure it does not mix finalized and non-finalized inputs or final scripts
@@ -156,11 +237,32 @@ pub fn psbt_common_sanity_checks(psbt: Psbt) -> Result<Psbt, PsbtValidationError
             is_final = Some(false);
         }
 
+        // All the inputs of all the Revault transactions are signed with SIGHASH_ALL, a
+        // signature with another flag would make the transaction unusable.
+        if input
+            .sighash_type
+            .map(|sighash_type| sighash_type != SigHashType::All)
+            .unwrap_or(false)
+        {
+            return Err(PsbtValidationError::InvalidInputField(
+                i,
+                "sighash type is not ALL",
+            ));
+        }
+        for rawsig in input.partial_sigs.values() {
+            if rawsig.last() != Some(&(SigHashType::All.as_u32() as u8)) {
+                return Err(PsbtValidationError::InvalidInputField(
+                    i,
+                    "partial signature is not SIGHASH_ALL",
+                ));
+            }
+        }
+
         // If the witness script is provided, it must be a sane Miniscript
         if let Some(ref script) = input.witness_script {
             let _: miniscript::Miniscript<_, miniscript::Segwitv0> =
                 miniscript::Miniscript::parse(script)
-                    .map_err(|_| PsbtValidationError::InvalidInWitnessScript(input.clone()))?;
+                    .map_err(|_| PsbtValidationError::InvalidInWitnessScript(i))?;
         }
 
         // We'll then check it doesn't create more than it spends
@@ -179,7 +281,10 @@ pub fn psbt_common_sanity_checks(psbt: Psbt) -> Result<Psbt, PsbtValidationError
         // The previous output must be P2WSH
         let spk = &input.witness_utxo.as_ref().unwrap().script_pubkey;
         if !spk.is_v0_p2wsh() {
-            return Err(PsbtValidationError::InvalidInputField(input.clone()));
+            return Err(PsbtValidationError::InvalidInputField(
+                i,
+                "spent output is not P2WSH",
+            ));
         }
 
         // The below fields are blanked when finalized
@@ -189,15 +294,18 @@ pub fn psbt_common_sanity_checks(psbt: Psbt) -> Result<Psbt, PsbtValidationError
 
         // It must have derivation paths set since it must have a witscript
         if input.bip32_derivation.is_empty() {
-            return Err(PsbtValidationError::InvalidInputField(input.clone()));
+            return Err(PsbtValidationError::InvalidInputField(
+                i,
+                "missing bip32 derivation",
+            ));
         }
 
         let ws = input
             .witness_script
             .as_ref()
-            .ok_or_else(|| PsbtValidationError::MissingInWitnessScript(input.clone()))?;
+            .ok_or_else(|| PsbtValidationError::MissingInWitnessScript(i))?;
         if &ws.to_v0_p2wsh() != spk {
-            return Err(PsbtValidationError::InvalidInWitnessScript(input.clone()));
+            return Err(PsbtValidationError::InvalidInWitnessScript(i));
         }
     }
 
@@ -223,6 +331,38 @@ pub fn psbt_common_sanity_checks(psbt: Psbt) -> Result<Psbt, PsbtValidationError
     Ok(psbt)
 }
 
+/// Get the transaction that would be extracted from this PSBT, without cloning it whole.
+///
+/// Only the parts of the PSBT needed for the final transaction are cloned, not the maps of
+/// signatures and derivation paths.
+pub fn final_tx(psbt: &Psbt) -> Transaction {
+    let mut final_tx = psbt.global.unsigned_tx.clone();
+    for (txin, psbtin) in final_tx.input.iter_mut().zip(psbt.inputs.iter()) {
+        txin.script_sig = psbtin.final_script_sig.clone().unwrap_or_default();
+        txin.witness = psbtin.final_script_witness.clone().unwrap_or_default();
+    }
+
+    final_tx
+}
+
+/// Write these bytes as hex to the formatter, in lower or upper case. Honors the formatter's
+/// width, fill, alignment and precision.
+pub fn format_hex(bytes: &[u8], upper: bool, f: &mut fmt::Formatter) -> fmt::Result {
+    let charset = if upper {
+        b"0123456789ABCDEF"
+    } else {
+        b"0123456789abcdef"
+    };
+
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        hex.push(charset[(byte >> 4) as usize] as char);
+        hex.push(charset[(byte & 0x0f) as usize] as char);
+    }
+
+    f.pad(&hex)
+}
+
 /// Returns the absolute fees paid by a PSBT.
 ///
 /// Returns None if:
@@ -242,6 +382,80 @@ pub fn psbt_fees(psbt: &Psbt) -> Option<Amount> {
     value_in.checked_sub(value_out)
 }
 
+// The minimum number of signatures, besides the ones of the `signed` keys, needed to satisfy this
+// policy. None if it can't be satisfied at all.
+fn missing_signatures(
+    policy: &SemanticPolicy<PublicKey>,
+    signed: &[hash160::Hash],
+) -> Option<usize> {
+    match policy {
+        SemanticPolicy::Unsatisfiable => None,
+        SemanticPolicy::KeyHash(pkh) => Some(if signed.contains(pkh) { 0 } else { 1 }),
+        SemanticPolicy::Threshold(k, subs) => {
+            let mut missing: Vec<usize> = subs
+                .iter()
+                .filter_map(|sub| missing_signatures(sub, signed))
+                .collect();
+            if missing.len() < *k {
+                return None;
+            }
+            missing.sort_unstable();
+            Some(missing.into_iter().take(*k).sum())
+        }
+        // Timelocks and hashlocks don't need any signature
+        _ => Some(0),
+    }
+}
+
+/// Returns the minimum number of signatures needed to satisfy this witness script, and the
+/// minimum number of signatures still needed to satisfy it given the `signers` already signed.
+///
+/// Returns None if the witness script is not a satisfiable Miniscript.
+pub fn needed_signatures(witness_script: &Script, signers: &[PublicKey]) -> Option<(usize, usize)> {
+    let policy = Miniscript::<PublicKey, Segwitv0>::parse(witness_script)
+        .ok()?
+        .lift()
+        .ok()?;
+    let signed: Vec<hash160::Hash> = signers.iter().map(|pk| pk.to_pubkeyhash()).collect();
+
+    Some((
+        missing_signatures(&policy, &[])?,
+        missing_signatures(&policy, &signed)?,
+    ))
+}
+
+/// Returns whether this key is one of the keys of this witness script.
+///
+/// Returns None if the witness script is not a Miniscript.
+pub fn script_has_key(witness_script: &Script, pubkey: &PublicKey) -> Option<bool> {
+    let ms = Miniscript::<PublicKey, Segwitv0>::parse(witness_script).ok()?;
+    // Keys may only be committed to by their hash, as in the pk_h() fragments of our descriptors
+    let pubkey_hash = pubkey.to_pubkeyhash();
+    let has_key = ms.iter_pk_pkh().any(|pkpkh| match pkpkh {
+        PkPkh::PlainPubkey(pk) => pk == *pubkey,
+        PkPkh::HashedPubkey(pkh) => pkh == pubkey_hash,
+    });
+    Some(has_key)
+}
+
+/// Create a single-input single-output unsigned transaction, without consuming the input or the
+/// output. Used to compute the weight of a transaction before knowing its output value.
+pub fn create_unsigned_tx<
+    Out: RevaultTxOut,
+    IntOut: RevaultInternalTxOut,
+    In: RevaultTxIn<IntOut>,
+>(
+    txin: &In,
+    txo: &Out,
+) -> Transaction {
+    Transaction {
+        version: TX_VERSION,
+        lock_time: TX_LOCKTIME,
+        input: vec![txin.unsigned_txin()],
+        output: vec![txo.txout().clone()],
+    }
+}
+
 /// Create a single-input single-output PSBT.
 /// PSBT information is filled depending on the input/output type.
 pub fn create_psbt<Out: RevaultTxOut, IntOut: RevaultInternalTxOut, In: RevaultTxIn<IntOut>>(
@@ -249,12 +463,7 @@ pub fn create_psbt<Out: RevaultTxOut, IntOut: RevaultInternalTxOut, In: RevaultT
     txo: Out,
 ) -> Psbt {
     let input = vec![txin.unsigned_txin()];
-    let psbtins = vec![PsbtIn {
-        witness_script: Some(txin.txout().witness_script().clone()),
-        bip32_derivation: txin.txout().bip32_derivation().clone(),
-        witness_utxo: Some(txin.into_txout().into_txout()),
-        ..PsbtIn::default()
-    }];
+    let psbtins = vec![txin.into_psbtin()];
     let psbtouts = vec![txo.psbtout()];
     let output = vec![txo.into_txout()];
 
@@ -275,3 +484,89 @@ pub fn create_psbt<Out: RevaultTxOut, IntOut: RevaultInternalTxOut, In: RevaultT
         outputs: psbtouts,
     }
 }
+
+/// Create a PSBT spending all these `txins` to a single output, at the given `feerate` in
+/// sats/WU. `txout` must return the output paying the given value, along with its PSBT output.
+///
+/// Will error if `txins` is empty or contains duplicates, if the fees would leave a dust output
+/// or be insane, or if the transaction would be too large.
+pub fn create_sweep_psbt<Out: RevaultTxOut, In: RevaultTxIn<Out>>(
+    txins: Vec<In>,
+    txout: impl Fn(Amount) -> (TxOut, PsbtOut),
+    feerate: Amount,
+) -> Result<Psbt, TransactionCreationError> {
+    if txins.is_empty() {
+        return Err(TransactionCreationError::NoInput);
+    }
+
+    // Check for duplicated inputs
+    let uniq_txins: HashSet<OutPoint> = txins.iter().map(|i| i.outpoint()).collect();
+    if uniq_txins.len() != txins.len() {
+        return Err(TransactionCreationError::DuplicatedInput);
+    }
+
+    let mut value_in = Amount::from_sat(0);
+    for txin in txins.iter() {
+        value_in = value_in
+            .checked_add(txin.txout().value())
+            .ok_or(TransactionCreationError::InsaneAmounts)?;
+    }
+
+    // First, create a dummy transaction to get its weight without Witness.
+    let input: Vec<_> = txins.iter().map(|txin| txin.unsigned_txin()).collect();
+    let (dummy_txo, _) = txout(Amount::from_sat(u64::MAX));
+    let dummy_tx = Transaction {
+        version: TX_VERSION,
+        lock_time: TX_LOCKTIME,
+        input,
+        output: vec![dummy_txo],
+    };
+
+    // The weight of the transaction is the weight of the witness-stripped transaction plus the
+    // weight required to satisfy the txins
+    let sat_weight = txins
+        .iter()
+        .map(|txin| txin.max_sat_weight())
+        .sum::<usize>();
+    let total_weight = dummy_tx
+        .get_weight()
+        .checked_add(sat_weight)
+        .expect("Properly computed weight won't overflow");
+    if total_weight > MAX_STANDARD_TX_WEIGHT as usize {
+        return Err(TransactionCreationError::TooLarge);
+    }
+    let total_weight: u64 = total_weight.try_into().expect("usize in u64");
+    let fees = feerate
+        .checked_mul(total_weight)
+        .ok_or(TransactionCreationError::InsaneFees)?;
+    if fees > Amount::from_sat(INSANE_FEES) {
+        return Err(TransactionCreationError::InsaneFees);
+    }
+
+    // The amounts are conserved, minus the fees.
+    let value = value_in
+        .checked_sub(fees)
+        .ok_or(TransactionCreationError::Dust)?;
+    if value > Amount::from_sat(max_money(Network::Bitcoin)) {
+        return Err(TransactionCreationError::InsaneAmounts);
+    }
+    let (txo, psbtout) = txout(value);
+    if value < txo.script_pubkey.dust_value() {
+        return Err(TransactionCreationError::Dust);
+    }
+
+    Ok(Psbt {
+        global: PsbtGlobal {
+            unsigned_tx: Transaction {
+                output: vec![txo],
+                ..dummy_tx
+            },
+            version: 0,
+            xpub: BTreeMap::new(),
+            proprietary: BTreeMap::new(),
+            unknown: BTreeMap::new(),
+        },
+        inputs: txins.into_iter().map(|txin| txin.into_psbtin()).collect(),
+        outputs: vec![psbtout],
+    })
+}
@@ -3,15 +3,57 @@
 //! Wrappers around bitcoin's OutPoint and previous TxOut to statically check Revault
 //! transaction creation and ease PSBT management.
 
-use crate::txouts::{CpfpTxOut, DepositTxOut, UnvaultTxOut};
+use crate::{
+    error::TxoutCreationError,
+    scripts::{CsvValue, DerivationIndex, EmergencyDescriptor},
+    txouts::{
+        CpfpTxOut, DepositTxOut, EmergencyTxOut, RevaultInternalTxOut, RevaultTxOut, UnvaultTxOut,
+    },
+};
 
-use miniscript::bitcoin::{OutPoint, TxIn};
+use miniscript::{
+    bitcoin::{
+        util::{bip32, psbt::Input as PsbtIn},
+        OutPoint, TxIn,
+    },
+    DescriptorTrait,
+};
 
 use std::fmt;
 
 /// The default sequence used by bitcoind to signal for RBF: 0xff_ff_ff_fd
 pub const RBF_SEQUENCE: u32 = u32::MAX - 2;
 
+/// The nSequence of a txin, depending on how the spent txo is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sequence {
+    /// Signal for RBF, see [RBF_SEQUENCE]
+    Rbf,
+    /// Wait for this relative timelock to be matured
+    Csv(CsvValue),
+    /// Neither signal for RBF nor enable the transaction's nLockTime: 0xff_ff_ff_ff
+    Final,
+}
+
+impl From<Sequence> for u32 {
+    fn from(sequence: Sequence) -> u32 {
+        match sequence {
+            Sequence::Rbf => RBF_SEQUENCE,
+            Sequence::Csv(csv) => csv.sequence(),
+            Sequence::Final => u32::MAX,
+        }
+    }
+}
+
+/// The maximum size, in weight units, a satisfaction for a P2WPKH txin would cost: the number of
+/// witness elements, a low-S DER signature with its sighash type and a compressed public key,
+/// each with its length prefix. As for [RevaultTxIn::max_sat_weight], this accounts for the
+/// empty scriptSig length.
+///
+/// Useful to account for the fee-bumping coins of a regular P2WPKH wallet, which are not
+/// Revault txos.
+pub const P2WPKH_MAX_SAT_WEIGHT: usize = 4 + 1 + (1 + 72) + (1 + 33);
+
 /// A transaction input used by a Revault transaction.
 pub trait RevaultTxIn<T>: fmt::Debug + Clone + PartialEq {
     /// Get the actual outpoint
@@ -20,8 +62,16 @@ pub trait RevaultTxIn<T>: fmt::Debug + Clone + PartialEq {
     fn txout(&self) -> &T;
     /// Get the actual txout this txin refers
     fn into_txout(self) -> T;
+    /// Get the nSequence this txin is spent with
+    fn sequence(&self) -> u32;
+    /// Get the maximum size, in weight units, a satisfaction for this txin would cost.
+    fn max_sat_weight(&self) -> usize;
+    /// Get the derivation index of the descriptor the spent txout was created from
+    fn derivation_index(&self) -> DerivationIndex;
     /// Get an actual Bitcoin TxIn out of the OutPoint and the nSequence
     fn unsigned_txin(&self) -> TxIn;
+    /// Create a Psbt input for this txin, moving the spent txout's data into it
+    fn into_psbtin(self) -> PsbtIn;
 }
 
 macro_rules! implem_revault_txin {
@@ -47,6 +97,18 @@ macro_rules! implem_revault_txin {
                 self.prev_txout
             }
 
+            fn sequence(&self) -> u32 {
+                self.sequence
+            }
+
+            fn max_sat_weight(&self) -> usize {
+                self.prev_txout.max_sat_weight()
+            }
+
+            fn derivation_index(&self) -> DerivationIndex {
+                self.prev_txout.derivation_index()
+            }
+
             fn unsigned_txin(&self) -> TxIn {
                 TxIn {
                     previous_output: self.outpoint,
@@ -54,6 +116,16 @@ macro_rules! implem_revault_txin {
                     ..TxIn::default()
                 }
             }
+
+            fn into_psbtin(self) -> PsbtIn {
+                let (txout, witness_script, bip32_derivation) = self.prev_txout.into_parts();
+                PsbtIn {
+                    witness_script: Some(witness_script),
+                    bip32_derivation,
+                    witness_utxo: Some(txout),
+                    ..PsbtIn::default()
+                }
+            }
         }
     };
 }
@@ -70,7 +142,7 @@ impl DepositTxIn {
         DepositTxIn {
             outpoint,
             prev_txout,
-            sequence: RBF_SEQUENCE,
+            sequence: Sequence::Rbf.into(),
         }
     }
 }
@@ -85,14 +157,32 @@ implem_revault_txin!(
 );
 impl UnvaultTxIn {
     /// Instanciate a TxIn referencing an unvault txout. We need the sequence to be explicitly
-    /// specified for this one, as it may spend a CSV-encumbered path.
-    pub fn new(outpoint: OutPoint, prev_txout: UnvaultTxOut, sequence: u32) -> UnvaultTxIn {
+    /// specified for this one, as it may spend a CSV-encumbered path. For the managers' path,
+    /// this is a [Sequence::Csv] of the relative timelock of the Unvault descriptor, in blocks
+    /// or time-based.
+    pub fn new(outpoint: OutPoint, prev_txout: UnvaultTxOut, sequence: Sequence) -> UnvaultTxIn {
         UnvaultTxIn {
             outpoint,
             prev_txout,
-            sequence,
+            sequence: sequence.into(),
         }
     }
+
+    /// Instanciate a TxIn referencing an unvault txout to be spent by the managers, in a
+    /// [Spend](crate::transactions::SpendTransaction). The sequence is set to the relative
+    /// timelock of the Unvault descriptor. If it has several spending tiers, this is the
+    /// shortest one: use [UnvaultTxIn::new] to spend through another tier.
+    pub fn new_spend(outpoint: OutPoint, prev_txout: UnvaultTxOut) -> UnvaultTxIn {
+        let csv = prev_txout.descriptor().csv_value();
+        UnvaultTxIn::new(outpoint, prev_txout, Sequence::Csv(csv))
+    }
+
+    /// Instanciate a TxIn referencing an unvault txout to be spent by the stakeholders, in a
+    /// [Cancel](crate::transactions::CancelTransaction) or an
+    /// [UnvaultEmergency](crate::transactions::UnvaultEmergencyTransaction). It signals for RBF.
+    pub fn new_revocation(outpoint: OutPoint, prev_txout: UnvaultTxOut) -> UnvaultTxIn {
+        UnvaultTxIn::new(outpoint, prev_txout, Sequence::Rbf)
+    }
 }
 
 implem_revault_txin!(
@@ -107,7 +197,110 @@ impl CpfpTxIn {
         CpfpTxIn {
             outpoint,
             prev_txout,
-            sequence: RBF_SEQUENCE,
+            sequence: Sequence::Rbf.into(),
+        }
+    }
+}
+
+/// An [Emergency](crate::transactions::EmergencyTransaction) txo spent by the
+/// [EmergencySweep](crate::transactions::EmergencySweepTransaction) transaction once the crisis
+/// is over.
+///
+/// Unlike the other txins, it is not derived from an xpub: the Emergency Deep Vault keys are raw
+/// public keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmergencyTxIn {
+    outpoint: OutPoint,
+    prev_txout: EmergencyTxOut,
+    descriptor: EmergencyDescriptor,
+    sequence: u32,
+}
+
+impl EmergencyTxIn {
+    /// Instanciate a TxIn referencing an Emergency txout which signals for RBF. We need the
+    /// Emergency descriptor in order to be able to spend it.
+    ///
+    /// Will error if the `prev_txout` does not pay to the `descriptor`.
+    pub fn new(
+        outpoint: OutPoint,
+        prev_txout: EmergencyTxOut,
+        descriptor: EmergencyDescriptor,
+    ) -> Result<EmergencyTxIn, TxoutCreationError> {
+        if prev_txout.script_pubkey() != &descriptor.inner().script_pubkey() {
+            return Err(TxoutCreationError::DescriptorMismatch);
+        }
+
+        Ok(EmergencyTxIn {
+            outpoint,
+            prev_txout,
+            descriptor,
+            sequence: Sequence::Rbf.into(),
+        })
+    }
+
+    /// Get a reference to the descriptor of the txout this txin refers
+    pub fn descriptor(&self) -> &EmergencyDescriptor {
+        &self.descriptor
+    }
+}
+
+impl RevaultTxIn<EmergencyTxOut> for EmergencyTxIn {
+    fn outpoint(&self) -> OutPoint {
+        self.outpoint
+    }
+
+    fn txout(&self) -> &EmergencyTxOut {
+        &self.prev_txout
+    }
+
+    fn into_txout(self) -> EmergencyTxOut {
+        self.prev_txout
+    }
+
+    fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    fn max_sat_weight(&self) -> usize {
+        self.descriptor.max_sat_weight()
+    }
+
+    /// The Emergency descriptor is not derived, this is always the index 0.
+    fn derivation_index(&self) -> DerivationIndex {
+        DerivationIndex::default()
+    }
+
+    fn unsigned_txin(&self) -> TxIn {
+        TxIn {
+            previous_output: self.outpoint,
+            sequence: self.sequence,
+            ..TxIn::default()
+        }
+    }
+
+    /// The keys don't have an origin, they are given an empty derivation path from a null
+    /// fingerprint.
+    fn into_psbtin(self) -> PsbtIn {
+        let bip32_derivation = self
+            .descriptor
+            .keys()
+            .into_iter()
+            .map(|key| {
+                (
+                    key,
+                    (
+                        bip32::Fingerprint::from(&[0, 0, 0, 0][..]),
+                        bip32::DerivationPath::from(vec![]),
+                    ),
+                )
+            })
+            .collect();
+
+        PsbtIn {
+            witness_script: Some(self.descriptor.inner().explicit_script()),
+            bip32_derivation,
+            witness_utxo: Some(self.prev_txout.into_txout()),
+            ..PsbtIn::default()
         }
     }
 }
@@ -3,14 +3,18 @@
 //! Wrappers around bitcoin's TxOut to statically check Revault transactions creation and ease
 //! their PSBT management.
 
-use crate::scripts::{
-    DerivedCpfpDescriptor, DerivedDepositDescriptor, DerivedUnvaultDescriptor, EmergencyAddress,
+use crate::{
+    error::TxoutCreationError,
+    scripts::{
+        CsvValue, DerivationIndex, DerivedCpfpDescriptor, DerivedDepositDescriptor,
+        DerivedUnvaultDescriptor, EmergencyAddress,
+    },
 };
 
 use miniscript::{
     bitcoin::{
-        util::{bip32, psbt::Output as PsbtOut},
-        Amount, PublicKey, Script, TxOut,
+        util::{address::Payload, bip32, psbt::Output as PsbtOut},
+        Address, Amount, Network, PublicKey, Script, TxOut,
     },
     DescriptorTrait,
 };
@@ -20,6 +24,47 @@ use std::{collections::BTreeMap, fmt};
 /// Map of a raw public key to the xpub used to derive it and its derivation path
 pub type Bip32Deriv = BTreeMap<PublicKey, (bip32::Fingerprint, bip32::DerivationPath)>;
 
+// Check an address is valid for the network we are operating on. The test networks share their
+// address prefixes, except for Regtest's bech32 one, so a Testnet address is valid on Signet.
+fn check_address_network(address: &Address, network: Network) -> Result<(), TxoutCreationError> {
+    let is_legacy = match address.payload {
+        Payload::PubkeyHash(_) | Payload::ScriptHash(_) => true,
+        Payload::WitnessProgram { .. } => false,
+    };
+    let is_valid = match (address.network, network) {
+        (a, b) if a == b => true,
+        (Network::Bitcoin, _) | (_, Network::Bitcoin) => false,
+        (Network::Regtest, _) | (_, Network::Regtest) => is_legacy,
+        _ => true,
+    };
+
+    if !is_valid {
+        return Err(TxoutCreationError::NetworkMismatch(
+            network,
+            address.network,
+        ));
+    }
+
+    Ok(())
+}
+
+/// The maximum size of an OP_RETURN scriptPubKey relayed by bitcoind by default.
+pub const MAX_OP_RETURN_RELAY: usize = 83;
+
+// Check a scriptPubKey is of a type bitcoind would relay by default. Bare multisig is
+// purposely not supported.
+fn is_standard_script(script_pubkey: &Script) -> bool {
+    if script_pubkey.is_op_return() {
+        return script_pubkey.len() <= MAX_OP_RETURN_RELAY;
+    }
+
+    if script_pubkey.is_witness_program() && script_pubkey.as_bytes()[0] == 0x00 {
+        return script_pubkey.is_v0_p2wpkh() || script_pubkey.is_v0_p2wsh();
+    }
+
+    script_pubkey.is_p2pkh() || script_pubkey.is_p2sh() || script_pubkey.is_witness_program()
+}
+
 /// Any output of a Revault transaction.
 pub trait RevaultTxOut: fmt::Debug + Clone + PartialEq {
     /// Get a reference to the inner txout
@@ -30,6 +75,16 @@ pub trait RevaultTxOut: fmt::Debug + Clone + PartialEq {
 
     /// Create a Psbt output for this txout
     fn psbtout(&self) -> PsbtOut;
+
+    /// Get the value of this txout
+    fn value(&self) -> Amount {
+        Amount::from_sat(self.txout().value)
+    }
+
+    /// Get a reference to the scriptPubKey of this txout
+    fn script_pubkey(&self) -> &Script {
+        &self.txout().script_pubkey
+    }
 }
 
 /// An output of a Revault transaction that we manage "internally", ie for which we have the
@@ -41,32 +96,52 @@ pub trait RevaultInternalTxOut: fmt::Debug + Clone + PartialEq + RevaultTxOut {
     /// Get the actual inner witness script ("redeem Script of the witness program")
     fn into_witness_script(self) -> Script;
 
+    /// Get the BIP143 script code to commit to when signing for an input spending this txout.
+    ///
+    /// All our internal txouts are P2WSH, so it is the witness script and *not* the scriptPubKey,
+    /// which would give a valid-looking but wrong signature hash.
+    fn script_code(&self) -> &Script {
+        self.witness_script()
+    }
+
     /// Get a reference to the map of public key to xpub source and derivation index
     fn bip32_derivation(&self) -> &Bip32Deriv;
 
     /// Get the actual map of public key to xpub source and derivation index
     fn into_bip32_derivation(self) -> Bip32Deriv;
 
+    /// Get the derivation index of the descriptor this txout was created from
+    fn derivation_index(&self) -> DerivationIndex;
+
+    /// Get the actual inner txout, witness script and map of public key to xpub source and
+    /// derivation index at once, without cloning any of them.
+    fn into_parts(self) -> (TxOut, Script, Bip32Deriv);
+
     /// Get the maximum size, in weight units, a satisfaction for this scriptPubKey would cost.
-    fn max_sat_weight(&self) -> usize {
-        miniscript::descriptor::Wsh::new(
-            miniscript::Miniscript::parse(self.witness_script())
-                .expect("The witness_script is always created from a Miniscript"),
-        )
-        .expect("The witness_script is always a P2WSH")
-        .max_satisfaction_weight()
-        .expect("It's a sane Script, derived from a Miniscript")
-    }
+    fn max_sat_weight(&self) -> usize;
 }
 
 macro_rules! implem_revault_txout {
-    ( $struct_name:ident, $doc_comment:meta ) => {
+    ( $struct_name:ident, $descriptor_name:ident, $doc_comment:meta ) => {
         #[$doc_comment]
-        #[derive(Debug, Clone, PartialEq, Default)]
+        #[derive(Debug, Clone, PartialEq)]
         pub struct $struct_name {
             txout: TxOut,
             witness_script: Script,
             bip32_derivation: Bip32Deriv,
+            descriptor: $descriptor_name,
+        }
+
+        impl $struct_name {
+            /// Get a reference to the derived descriptor this txout was created from
+            pub fn descriptor(&self) -> &$descriptor_name {
+                &self.descriptor
+            }
+
+            /// Get the address of this txout on the given network
+            pub fn address(&self, network: Network) -> Address {
+                self.descriptor.address(network)
+            }
         }
 
         impl RevaultTxOut for $struct_name {
@@ -102,12 +177,25 @@ macro_rules! implem_revault_txout {
             fn into_bip32_derivation(self) -> Bip32Deriv {
                 self.bip32_derivation
             }
+
+            fn derivation_index(&self) -> DerivationIndex {
+                self.descriptor.derivation_index()
+            }
+
+            fn max_sat_weight(&self) -> usize {
+                self.descriptor.max_sat_weight()
+            }
+
+            fn into_parts(self) -> (TxOut, Script, Bip32Deriv) {
+                (self.txout, self.witness_script, self.bip32_derivation)
+            }
         }
     };
 }
 
 implem_revault_txout!(
     DepositTxOut,
+    DerivedDepositDescriptor,
     doc = "A deposit transaction output. Used by the [Deposit](crate::transactions::DepositTransaction), \
             the [Cancel](crate::transactions::CancelTransaction), and the \
             [Spend](crate::transactions::SpendTransaction)."
@@ -124,18 +212,18 @@ impl DepositTxOut {
             bip32_derivation: script_descriptor
                 .keys()
                 .into_iter()
-                .map(|k| {
-                    (
-                        k.key,
-                        (k.origin.0, bip32::DerivationPath::from(&[k.origin.1][..])),
-                    )
-                })
+                .map(|k| (k.key, k.origin))
                 .collect(),
+            descriptor: script_descriptor.clone(),
         }
     }
 }
 
-implem_revault_txout!(UnvaultTxOut, doc = "*The* Unvault transaction output.");
+implem_revault_txout!(
+    UnvaultTxOut,
+    DerivedUnvaultDescriptor,
+    doc = "*The* Unvault transaction output."
+);
 impl UnvaultTxOut {
     /// Create a new UnvaultTxOut out of the given Unvault script descriptor
     pub fn new(value: Amount, script_descriptor: &DerivedUnvaultDescriptor) -> UnvaultTxOut {
@@ -148,15 +236,17 @@ impl UnvaultTxOut {
             bip32_derivation: script_descriptor
                 .keys()
                 .into_iter()
-                .map(|k| {
-                    (
-                        k.key,
-                        (k.origin.0, bip32::DerivationPath::from(&[k.origin.1][..])),
-                    )
-                })
+                .map(|k| (k.key, k.origin))
                 .collect(),
+            descriptor: script_descriptor.clone(),
         }
     }
+
+    /// Get the relative locktime the managers must wait for before spending this output. If the
+    /// descriptor has several spending tiers, this is the shortest one.
+    pub fn csv_value(&self) -> CsvValue {
+        self.descriptor.csv_value()
+    }
 }
 
 /// The Emergency Deep Vault, the destination of the Emergency transactions fund.
@@ -170,6 +260,41 @@ impl EmergencyTxOut {
             value: value.as_sat(),
         })
     }
+
+    /// Create a new EmergencyTxOut, checking the Emergency address is for the given network.
+    pub fn new_checked(
+        address: EmergencyAddress,
+        value: Amount,
+        network: Network,
+    ) -> Result<EmergencyTxOut, TxoutCreationError> {
+        check_address_network(address.address(), network)?;
+        Ok(EmergencyTxOut::new(address, value))
+    }
+
+    /// Create a new EmergencyTxOut for an Emergency transaction spending `spent_value`.
+    ///
+    /// Will error if `value` is below the dust threshold of the Emergency scriptPubKey or if it
+    /// would leave less than `min_fee` to the miners out of `spent_value`.
+    pub fn new_checked_value(
+        address: EmergencyAddress,
+        value: Amount,
+        spent_value: Amount,
+        min_fee: Amount,
+    ) -> Result<EmergencyTxOut, TxoutCreationError> {
+        let max_value = spent_value
+            .checked_sub(min_fee)
+            .ok_or(TxoutCreationError::InsufficientFee(min_fee))?;
+        if value > max_value {
+            return Err(TxoutCreationError::InsufficientFee(min_fee));
+        }
+
+        let txo = EmergencyTxOut::new(address, value);
+        if value < txo.script_pubkey().dust_value() {
+            return Err(TxoutCreationError::Dust);
+        }
+
+        Ok(txo)
+    }
 }
 
 impl RevaultTxOut for EmergencyTxOut {
@@ -188,6 +313,7 @@ impl RevaultTxOut for EmergencyTxOut {
 
 implem_revault_txout!(
     CpfpTxOut,
+    DerivedCpfpDescriptor,
     doc = "The output attached to the [Unvault](crate::transactions::UnvaultTransaction) \
             so that the fund managers can fee-bump it."
 );
@@ -203,13 +329,9 @@ impl CpfpTxOut {
             bip32_derivation: script_descriptor
                 .keys()
                 .into_iter()
-                .map(|k| {
-                    (
-                        k.key,
-                        (k.origin.0, bip32::DerivationPath::from(&[k.origin.1][..])),
-                    )
-                })
+                .map(|k| (k.key, k.origin))
                 .collect(),
+            descriptor: script_descriptor.clone(),
         }
     }
 }
@@ -220,9 +342,49 @@ impl CpfpTxOut {
 pub struct SpendTxOut(TxOut);
 
 impl SpendTxOut {
+    /// Create a new SpendTxOut without any check.
     pub fn new(txo: TxOut) -> Self {
         SpendTxOut(txo)
     }
+
+    /// Create a new SpendTxOut, checking it would be relayed by bitcoind.
+    ///
+    /// Will error if the scriptPubKey is not a standard template (P2PKH, P2SH, a witness
+    /// program or an OP_RETURN of at most [MAX_OP_RETURN_RELAY] bytes) or if the value is
+    /// below its dust threshold.
+    pub fn new_checked(txo: TxOut) -> Result<SpendTxOut, TxoutCreationError> {
+        if !is_standard_script(&txo.script_pubkey) {
+            return Err(TxoutCreationError::InvalidScriptPubkeyType);
+        }
+
+        if txo.value < txo.script_pubkey.dust_value().as_sat() {
+            return Err(TxoutCreationError::Dust);
+        }
+
+        Ok(SpendTxOut(txo))
+    }
+
+    /// Create a new SpendTxOut paying `amount` to `address`.
+    ///
+    /// Will error if the address is not for the given `network` or if the amount is below the
+    /// dust threshold of the address' scriptPubKey.
+    pub fn destination(
+        address: Address,
+        amount: Amount,
+        network: Network,
+    ) -> Result<SpendTxOut, TxoutCreationError> {
+        check_address_network(&address, network)?;
+
+        let script_pubkey = address.script_pubkey();
+        if amount < script_pubkey.dust_value() {
+            return Err(TxoutCreationError::Dust);
+        }
+
+        Ok(SpendTxOut(TxOut {
+            value: amount.as_sat(),
+            script_pubkey,
+        }))
+    }
 }
 
 impl RevaultTxOut for SpendTxOut {
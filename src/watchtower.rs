@@ -0,0 +1,317 @@
+//! # Revault watchtower bundle
+//!
+//! Everything a watchtower needs to watch a vault and revault (or emergency) any Unvault attempt,
+//! as a single object which can be sent over the wire.
+
+use crate::{
+    error::WatchtowerBundleError,
+    scripts::{DepositDescriptor, DerivationIndex, UnvaultDescriptor},
+    transactions::{
+        CancelTransaction, CancelTransactionsBatch, EmergencyTransaction, RevaultTransaction,
+        UnvaultEmergencyTransaction,
+    },
+};
+
+use miniscript::bitcoin::{
+    hashes::{sha256, Hash},
+    secp256k1,
+    util::bip32,
+    OutPoint,
+};
+
+#[cfg(feature = "use-serde")]
+use {
+    serde::de::{self, Deserialize, Deserializer},
+    serde::ser::{Serialize, SerializeStruct, Serializer},
+    std::{convert::TryFrom, fmt, str::FromStr},
+};
+
+use std::fmt::Display;
+
+// The descriptors are identified by the hash of their string representation rather than by their
+// checksum, which only detects typos and for which collisions can be crafted.
+fn descriptor_hash(descriptor: &impl Display) -> sha256::Hash {
+    sha256::Hash::hash(descriptor.to_string().as_bytes())
+}
+
+/// The presigned transactions a watchtower needs to guard a vault, along with the information to
+/// relate them to the vault's deposit and descriptors.
+///
+/// The transactions are expected to carry the signatures of all the stakeholders.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchtowerBundle {
+    deposit_outpoint: OutPoint,
+    derivation_index: DerivationIndex,
+    deposit_descriptor_hash: sha256::Hash,
+    unvault_descriptor_hash: sha256::Hash,
+    cancel_txs: Vec<CancelTransaction>,
+    emergency_tx: EmergencyTransaction,
+    unvault_emergency_tx: UnvaultEmergencyTransaction,
+}
+
+impl WatchtowerBundle {
+    /// Create a bundle for the vault at `deposit_outpoint` out of its presigned transactions.
+    pub fn new(
+        deposit_outpoint: OutPoint,
+        derivation_index: DerivationIndex,
+        deposit_descriptor: &DepositDescriptor,
+        unvault_descriptor: &UnvaultDescriptor,
+        cancel_batch: CancelTransactionsBatch,
+        emergency_tx: EmergencyTransaction,
+        unvault_emergency_tx: UnvaultEmergencyTransaction,
+    ) -> WatchtowerBundle {
+        WatchtowerBundle {
+            deposit_outpoint,
+            derivation_index,
+            deposit_descriptor_hash: descriptor_hash(deposit_descriptor),
+            unvault_descriptor_hash: descriptor_hash(unvault_descriptor),
+            cancel_txs: cancel_batch.all_feerates().to_vec(),
+            emergency_tx,
+            unvault_emergency_tx,
+        }
+    }
+
+    /// Get the outpoint of the deposit this bundle guards
+    pub fn deposit_outpoint(&self) -> OutPoint {
+        self.deposit_outpoint
+    }
+
+    /// Get the derivation index of the descriptors of the vault this bundle guards
+    pub fn derivation_index(&self) -> DerivationIndex {
+        self.derivation_index
+    }
+
+    /// Get the SHA256 of the string representation of the (generalistic) Deposit descriptor
+    pub fn deposit_descriptor_hash(&self) -> sha256::Hash {
+        self.deposit_descriptor_hash
+    }
+
+    /// Get the SHA256 of the string representation of the (generalistic) Unvault descriptor
+    pub fn unvault_descriptor_hash(&self) -> sha256::Hash {
+        self.unvault_descriptor_hash
+    }
+
+    /// Get the Cancel transactions, ordered by ascending feerate
+    pub fn cancel_txs(&self) -> &[CancelTransaction] {
+        &self.cancel_txs
+    }
+
+    /// Get the Emergency transaction
+    pub fn emergency_tx(&self) -> &EmergencyTransaction {
+        &self.emergency_tx
+    }
+
+    /// Get the Unvault Emergency transaction
+    pub fn unvault_emergency_tx(&self) -> &UnvaultEmergencyTransaction {
+        &self.unvault_emergency_tx
+    }
+
+    /// Get the Unvault outpoint spent by the Cancel and Unvault Emergency transactions
+    pub fn unvault_outpoint(&self) -> OutPoint {
+        self.unvault_emergency_tx.tx().input[0].previous_output
+    }
+
+    /// Check whether this bundle was created for these descriptors
+    pub fn matches_descriptors(
+        &self,
+        deposit_descriptor: &DepositDescriptor,
+        unvault_descriptor: &UnvaultDescriptor,
+    ) -> bool {
+        self.deposit_descriptor_hash == descriptor_hash(deposit_descriptor)
+            && self.unvault_descriptor_hash == descriptor_hash(unvault_descriptor)
+    }
+
+    /// Check the internal consistency of the bundle:
+    /// - the Emergency transaction spends the deposit outpoint
+    /// - the Cancel and Unvault Emergency transactions all spend the same Unvault outpoint
+    /// - both Emergency transactions pay to the same Emergency Script
+    /// - the stakeholders' keys of all the inputs were derived at the bundle's derivation index
+    /// - all the partial signatures are valid
+    pub fn verify<C: secp256k1::Verification>(
+        &self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), WatchtowerBundleError> {
+        if self.cancel_txs.is_empty() {
+            return Err(WatchtowerBundleError::MissingCancel);
+        }
+
+        let emer_outpoint = self.emergency_tx.tx().input[0].previous_output;
+        if emer_outpoint != self.deposit_outpoint {
+            return Err(WatchtowerBundleError::EmergencyOutpointMismatch(
+                emer_outpoint,
+            ));
+        }
+
+        let unvault_outpoint = self.unvault_outpoint();
+        for cancel_tx in self.cancel_txs.iter() {
+            let cancel_outpoint = cancel_tx.tx().input[0].previous_output;
+            if cancel_outpoint != unvault_outpoint {
+                return Err(WatchtowerBundleError::UnvaultOutpointMismatch(
+                    cancel_outpoint,
+                ));
+            }
+        }
+
+        if self.emergency_tx.tx().output[0].script_pubkey
+            != self.unvault_emergency_tx.tx().output[0].script_pubkey
+        {
+            return Err(WatchtowerBundleError::EmergencyScriptMismatch);
+        }
+
+        // Only the stakeholders' keys are necessarily derived at the vault's index, the
+        // Cosigning Servers' ones may be raw keys. These are all the keys of the deposit, spent
+        // by the Emergency transaction, and are part of the Unvault Script too.
+        let child_number = bip32::ChildNumber::from(self.derivation_index);
+        let deposit_keys = &self.emergency_tx.psbt().inputs[0].bip32_derivation;
+        if deposit_keys
+            .values()
+            .any(|(_, path)| path.as_ref().last() != Some(&child_number))
+        {
+            return Err(WatchtowerBundleError::DerivationIndexMismatch);
+        }
+        let unvault_psbts = self
+            .cancel_txs
+            .iter()
+            .map(|tx| tx.psbt())
+            .chain(std::iter::once(self.unvault_emergency_tx.psbt()));
+        for psbt in unvault_psbts {
+            for psbtin in psbt.inputs.iter() {
+                for (key, (_, path)) in psbtin.bip32_derivation.iter() {
+                    if deposit_keys.contains_key(key) && path.as_ref().last() != Some(&child_number)
+                    {
+                        return Err(WatchtowerBundleError::DerivationIndexMismatch);
+                    }
+                }
+            }
+        }
+
+        for cancel_tx in self.cancel_txs.iter() {
+            cancel_tx
+                .verify_signatures(secp)
+                .map_err(WatchtowerBundleError::InvalidSignature)?;
+        }
+        self.emergency_tx
+            .verify_signatures(secp)
+            .map_err(WatchtowerBundleError::InvalidSignature)?;
+        self.unvault_emergency_tx
+            .verify_signatures(secp)
+            .map_err(WatchtowerBundleError::InvalidSignature)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "use-serde")]
+const BUNDLE_FIELDS: &[&str] = &[
+    "deposit_outpoint",
+    "derivation_index",
+    "deposit_descriptor_hash",
+    "unvault_descriptor_hash",
+    "cancel_txs",
+    "emergency_tx",
+    "unvault_emergency_tx",
+];
+
+// FIXME: the windows CI build is preventing us from using the 'use-serde' feature of
+// rust-bitcoin, hence the manual implementations.
+#[cfg(feature = "use-serde")]
+impl Serialize for WatchtowerBundle {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("WatchtowerBundle", BUNDLE_FIELDS.len())?;
+        state.serialize_field("deposit_outpoint", &self.deposit_outpoint.to_string())?;
+        state.serialize_field("derivation_index", &u32::from(self.derivation_index))?;
+        state.serialize_field(
+            "deposit_descriptor_hash",
+            &self.deposit_descriptor_hash.to_string(),
+        )?;
+        state.serialize_field(
+            "unvault_descriptor_hash",
+            &self.unvault_descriptor_hash.to_string(),
+        )?;
+        state.serialize_field("cancel_txs", &self.cancel_txs)?;
+        state.serialize_field("emergency_tx", &self.emergency_tx)?;
+        state.serialize_field("unvault_emergency_tx", &self.unvault_emergency_tx)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "use-serde")]
+impl<'de> Deserialize<'de> for WatchtowerBundle {
+    fn deserialize<D>(deserializer: D) -> Result<WatchtowerBundle, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BundleVisitor;
+
+        impl<'de> de::Visitor<'de> for BundleVisitor {
+            type Value = WatchtowerBundle;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a watchtower bundle")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<WatchtowerBundle, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut deposit_outpoint = None;
+                let mut derivation_index = None;
+                let mut deposit_descriptor_hash = None;
+                let mut unvault_descriptor_hash = None;
+                let mut cancel_txs = None;
+                let mut emergency_tx = None;
+                let mut unvault_emergency_tx = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "deposit_outpoint" => {
+                            let outpoint = map.next_value::<String>()?;
+                            deposit_outpoint =
+                                Some(OutPoint::from_str(&outpoint).map_err(de::Error::custom)?);
+                        }
+                        "derivation_index" => {
+                            let index = map.next_value::<u32>()?;
+                            derivation_index =
+                                Some(DerivationIndex::try_from(index).map_err(de::Error::custom)?);
+                        }
+                        "deposit_descriptor_hash" => {
+                            let hash = map.next_value::<String>()?;
+                            deposit_descriptor_hash =
+                                Some(sha256::Hash::from_str(&hash).map_err(de::Error::custom)?);
+                        }
+                        "unvault_descriptor_hash" => {
+                            let hash = map.next_value::<String>()?;
+                            unvault_descriptor_hash =
+                                Some(sha256::Hash::from_str(&hash).map_err(de::Error::custom)?);
+                        }
+                        "cancel_txs" => cancel_txs = Some(map.next_value()?),
+                        "emergency_tx" => emergency_tx = Some(map.next_value()?),
+                        "unvault_emergency_tx" => unvault_emergency_tx = Some(map.next_value()?),
+                        _ => return Err(de::Error::unknown_field(&key, BUNDLE_FIELDS)),
+                    }
+                }
+
+                Ok(WatchtowerBundle {
+                    deposit_outpoint: deposit_outpoint
+                        .ok_or_else(|| de::Error::missing_field("deposit_outpoint"))?,
+                    derivation_index: derivation_index
+                        .ok_or_else(|| de::Error::missing_field("derivation_index"))?,
+                    deposit_descriptor_hash: deposit_descriptor_hash
+                        .ok_or_else(|| de::Error::missing_field("deposit_descriptor_hash"))?,
+                    unvault_descriptor_hash: unvault_descriptor_hash
+                        .ok_or_else(|| de::Error::missing_field("unvault_descriptor_hash"))?,
+                    cancel_txs: cancel_txs.ok_or_else(|| de::Error::missing_field("cancel_txs"))?,
+                    emergency_tx: emergency_tx
+                        .ok_or_else(|| de::Error::missing_field("emergency_tx"))?,
+                    unvault_emergency_tx: unvault_emergency_tx
+                        .ok_or_else(|| de::Error::missing_field("unvault_emergency_tx"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct("WatchtowerBundle", BUNDLE_FIELDS, BundleVisitor)
+    }
+}